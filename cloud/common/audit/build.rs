@@ -0,0 +1,3 @@
+fn main() {
+    proto_builder::build_protos();
+}
@@ -0,0 +1,272 @@
+pub mod protos;
+pub mod service;
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, File},
+    future::Future,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tonic::{Request, Response, Status};
+
+use crate::protos::audit::ListAuditEventsRequest;
+
+/// Mutating call made to a service, as written to the log file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub sequence: u64,
+    pub timestamp_unix_ms: u64,
+    pub subject: String,
+    pub rpc: String,
+    pub resource_ids: Vec<String>,
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+}
+
+impl AuditEvent {
+    /// Whether the event matches every filter set in `request`
+    fn matches(&self, request: &ListAuditEventsRequest) -> bool {
+        self.timestamp_unix_ms >= request.since_unix_ms
+            && (request.until_unix_ms == 0 || self.timestamp_unix_ms < request.until_unix_ms)
+            && self.sequence > request.after_sequence
+            && (request.subject.is_empty() || self.subject == request.subject)
+            && (request.rpc.is_empty()
+                || self.rpc == request.rpc
+                || self.rpc.ends_with(&format!("/{}", request.rpc)))
+            && (request.resource_id.is_empty()
+                || self.resource_ids.iter().any(|resource_id| {
+                    resource_id == &request.resource_id
+                        || resource_id.ends_with(&format!("/{}", request.resource_id))
+                }))
+    }
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    Io(PathBuf, io::Error),
+    /// Line of the log file isn't an event
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+    },
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Io(path, error) => {
+                write!(formatter, "Failed to access {}: {error}", path.display())
+            }
+            AuditError::Corrupt { path, line } => write!(
+                formatter,
+                "Line {line} of {} should be an audit event",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+/// Append only log of the mutating calls made to a service, kept in memory and, when opened
+/// from a file, appended to it as json lines
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    state: Mutex<State>,
+    /// Subjects allowed to list the events, every caller when not restricted
+    readers: Option<HashSet<String>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    events: Vec<AuditEvent>,
+    file: Option<(PathBuf, File)>,
+}
+
+impl AuditLog {
+    /// Opens the log stored at `path`, creating it readable only by its owner when missing,
+    /// and reloads the events already in it
+    pub fn open(path: &Path) -> Result<AuditLog, AuditError> {
+        let io_error = |error| AuditError::Io(PathBuf::from(path), error);
+
+        if let Some(directory) = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+        {
+            fs::create_dir_all(directory).map_err(io_error)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.read(true).append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(path).map_err(io_error)?;
+        let mut events = Vec::new();
+
+        for (index, line) in BufReader::new(&file).lines().enumerate() {
+            let line = line.map_err(io_error)?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            events.push(
+                serde_json::from_str(&line).map_err(|_| AuditError::Corrupt {
+                    path: PathBuf::from(path),
+                    line: index + 1,
+                })?,
+            );
+        }
+
+        Ok(AuditLog {
+            state: Mutex::new(State {
+                events,
+                file: Some((PathBuf::from(path), file)),
+            }),
+            readers: None,
+        })
+    }
+
+    /// Only lets `readers` list the events, rather than every authenticated caller
+    pub fn with_readers(mut self, readers: impl IntoIterator<Item = String>) -> AuditLog {
+        self.readers = Some(readers.into_iter().collect());
+
+        self
+    }
+
+    /// Starts recording the call `rpc` made through `request`, which is appended to the log
+    /// once the entry records it
+    pub fn begin<T>(&self, request: &Request<T>, rpc: &'static str) -> AuditEntry<'_> {
+        AuditEntry {
+            log: self,
+            timestamp_unix_ms: unix_time_ms(),
+            subject: auth::identity(request)
+                .map(|identity| identity.subject.clone())
+                .unwrap_or_default(),
+            rpc,
+            resource_ids: Vec::new(),
+        }
+    }
+
+    /// Events matching the filters of `request`, oldest first
+    pub fn events(&self, request: &ListAuditEventsRequest) -> Vec<AuditEvent> {
+        let state = self.state.lock().expect("Should acquire lock");
+        let matching = state.events.iter().filter(|event| event.matches(request));
+
+        match request.limit {
+            0 => matching.cloned().collect(),
+            limit => matching.take(limit as usize).cloned().collect(),
+        }
+    }
+
+    /// Whether the caller of `request` may list the events, which every caller may when the
+    /// service doesn't authenticate them
+    pub fn can_read<T>(&self, request: &Request<T>) -> bool {
+        match (auth::identity(request), &self.readers) {
+            (Some(identity), Some(readers)) => readers.contains(&identity.subject),
+            _ => true,
+        }
+    }
+
+    fn append(&self, mut event: AuditEvent) {
+        let mut state = self.state.lock().expect("Should acquire lock");
+        event.sequence = state.events.last().map_or(1, |last| last.sequence + 1);
+
+        if let Some((path, file)) = state.file.as_mut() {
+            let mut line = serde_json::to_vec(&event).expect("Should serialize audit event");
+            line.push(b'\n');
+
+            // The call already happened, so failing to persist its event can only be reported
+            if let Err(error) = file.write_all(&line) {
+                tracing::error!(
+                    path = %path.display(),
+                    %error,
+                    "Failed to append audit event"
+                );
+            }
+        }
+
+        state.events.push(event);
+    }
+}
+
+/// Call being recorded, appended to the log with its outcome once it ran
+#[must_use = "Entries are only appended to the log by recording the call"]
+pub struct AuditEntry<'a> {
+    log: &'a AuditLog,
+    timestamp_unix_ms: u64,
+    subject: String,
+    rpc: &'static str,
+    resource_ids: Vec<String>,
+}
+
+impl AuditEntry<'_> {
+    /// Records that the call names the resource `resource_id`, unless it is empty
+    pub fn resource(mut self, resource_id: &str) -> Self {
+        if !resource_id.is_empty() {
+            self.resource_ids.push(String::from(resource_id));
+        }
+
+        self
+    }
+
+    /// Runs the call and appends it to the log with its outcome
+    pub async fn record<T>(
+        self,
+        call: impl Future<Output = Result<Response<T>, Status>>,
+    ) -> Result<Response<T>, Status> {
+        self.record_with(call, |_| Vec::new()).await
+    }
+
+    /// Runs the call and appends it to the log with its outcome, along with the ids of the
+    /// resources its response names
+    pub async fn record_with<T>(
+        self,
+        call: impl Future<Output = Result<Response<T>, Status>>,
+        created: impl FnOnce(&T) -> Vec<String>,
+    ) -> Result<Response<T>, Status> {
+        let result = call.await;
+        let mut resource_ids = self.resource_ids;
+        let (outcome, message) = match &result {
+            Ok(response) => {
+                for resource_id in created(response.get_ref()) {
+                    if !resource_id.is_empty() && !resource_ids.contains(&resource_id) {
+                        resource_ids.push(resource_id);
+                    }
+                }
+
+                (format!("{:?}", tonic::Code::Ok), String::new())
+            }
+            Err(status) => (
+                format!("{:?}", status.code()),
+                String::from(status.message()),
+            ),
+        };
+
+        self.log.append(AuditEvent {
+            sequence: 0,
+            timestamp_unix_ms: self.timestamp_unix_ms,
+            subject: self.subject,
+            rpc: String::from(self.rpc),
+            resource_ids,
+            outcome,
+            message,
+        });
+
+        result
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Should be after the unix epoch")
+        .as_millis() as u64
+}
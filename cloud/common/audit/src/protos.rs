@@ -0,0 +1,3 @@
+pub mod audit {
+    tonic::include_proto!("audit");
+}
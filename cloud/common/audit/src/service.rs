@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use crate::{
+    protos::audit::{
+        audit_server, AuditEvent as AuditEventProto, ListAuditEventsRequest,
+        ListAuditEventsResponse,
+    },
+    AuditEvent, AuditLog,
+};
+
+/// Serves the events of an audit log to its readers
+pub struct AuditService {
+    log: Arc<AuditLog>,
+}
+
+impl AuditService {
+    pub fn new(log: Arc<AuditLog>) -> AuditService {
+        AuditService { log }
+    }
+}
+
+#[tonic::async_trait]
+impl audit_server::Audit for AuditService {
+    async fn list_audit_events(
+        &self,
+        request: Request<ListAuditEventsRequest>,
+    ) -> Result<Response<ListAuditEventsResponse>, Status> {
+        if !self.log.can_read(&request) {
+            return Err(Status::permission_denied(
+                "Caller isn't allowed to read the audit log",
+            ));
+        }
+
+        Ok(Response::new(ListAuditEventsResponse {
+            event: self
+                .log
+                .events(request.get_ref())
+                .into_iter()
+                .map(AuditEventProto::from)
+                .collect(),
+        }))
+    }
+}
+
+impl From<AuditEvent> for AuditEventProto {
+    fn from(event: AuditEvent) -> AuditEventProto {
+        AuditEventProto {
+            sequence: event.sequence,
+            timestamp_unix_ms: event.timestamp_unix_ms,
+            subject: event.subject,
+            rpc: event.rpc,
+            resource_ids: event.resource_ids,
+            outcome: event.outcome,
+            message: event.message,
+        }
+    }
+}
+
+impl From<AuditEventProto> for AuditEvent {
+    fn from(event: AuditEventProto) -> AuditEvent {
+        AuditEvent {
+            sequence: event.sequence,
+            timestamp_unix_ms: event.timestamp_unix_ms,
+            subject: event.subject,
+            rpc: event.rpc,
+            resource_ids: event.resource_ids,
+            outcome: event.outcome,
+            message: event.message,
+        }
+    }
+}
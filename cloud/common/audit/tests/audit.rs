@@ -0,0 +1,154 @@
+use std::fs;
+
+use audit::{protos::audit::ListAuditEventsRequest, AuditError, AuditLog};
+use auth::{AuthMethod, Identity};
+use tonic::{Request, Response, Status};
+
+fn request_from(subject: &str) -> Request<()> {
+    let mut request = Request::new(());
+    request.extensions_mut().insert(Identity {
+        subject: String::from(subject),
+        method: AuthMethod::Token,
+    });
+
+    request
+}
+
+#[tokio::test]
+async fn events_are_appended_and_reloaded() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("audit").join("audit.jsonl");
+    let log = AuditLog::open(&path).expect("Should open log");
+
+    log.begin(
+        &request_from("alice"),
+        "/data_center.DataCenter/StopInstance",
+    )
+    .resource("projects/default/instances/vm-1")
+    .record(async { Ok(Response::new(())) })
+    .await
+    .expect("Should stop instance");
+    log.begin(&request_from("bob"), "/data_center.DataCenter/StopInstance")
+        .resource("projects/default/instances/vm-2")
+        .record::<()>(async { Err(Status::permission_denied("Bob isn't an operator")) })
+        .await
+        .expect_err("Should refuse to stop instance");
+
+    let contents = fs::read_to_string(&path).expect("Should read log");
+    assert_eq!(contents.lines().count(), 2);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    drop(log);
+    let log = AuditLog::open(&path).expect("Should reopen log");
+    log.begin(
+        &request_from("alice"),
+        "/data_center.Projects/CreateProject",
+    )
+    .record_with(async { Ok(Response::new("web")) }, |project| {
+        vec![String::from(*project)]
+    })
+    .await
+    .expect("Should create project");
+
+    let events = log.events(&ListAuditEventsRequest::default());
+    assert_eq!(
+        events
+            .iter()
+            .map(|event| (
+                event.sequence,
+                event.subject.as_str(),
+                event.outcome.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (1, "alice", "Ok"),
+            (2, "bob", "PermissionDenied"),
+            (3, "alice", "Ok")
+        ]
+    );
+    assert_eq!(events[1].message, "Bob isn't an operator");
+    assert_eq!(events[2].resource_ids, vec!["web"]);
+
+    fs::write(&path, "not an event\n").unwrap();
+    assert!(matches!(
+        AuditLog::open(&path),
+        Err(AuditError::Corrupt { line: 1, .. })
+    ));
+}
+
+#[tokio::test]
+async fn events_are_filtered() {
+    let log = AuditLog::default();
+
+    for (subject, rpc, resource_id) in [
+        (
+            "alice",
+            "/data_center.DataCenter/StartInstance",
+            "projects/default/instances/vm-1",
+        ),
+        (
+            "bob",
+            "/data_center.DataCenter/StopInstance",
+            "projects/default/instances/vm-1",
+        ),
+        (
+            "bob",
+            "/data_center.DataCenter/StopInstance",
+            "projects/default/instances/vm-2",
+        ),
+    ] {
+        log.begin(&request_from(subject), rpc)
+            .resource(resource_id)
+            .record(async { Ok(Response::new(())) })
+            .await
+            .expect("Should record call");
+    }
+
+    let sequences = |request: ListAuditEventsRequest| {
+        log.events(&request)
+            .into_iter()
+            .map(|event| event.sequence)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        sequences(ListAuditEventsRequest {
+            resource_id: String::from("vm-1"),
+            ..Default::default()
+        }),
+        vec![1, 2]
+    );
+    assert_eq!(
+        sequences(ListAuditEventsRequest {
+            rpc: String::from("StopInstance"),
+            subject: String::from("bob"),
+            ..Default::default()
+        }),
+        vec![2, 3]
+    );
+    assert_eq!(
+        sequences(ListAuditEventsRequest {
+            after_sequence: 1,
+            limit: 1,
+            ..Default::default()
+        }),
+        vec![2]
+    );
+    assert!(sequences(ListAuditEventsRequest {
+        since_unix_ms: u64::MAX,
+        ..Default::default()
+    })
+    .is_empty());
+
+    let readers = AuditLog::default().with_readers([String::from("alice")]);
+    assert!(readers.can_read(&request_from("alice")));
+    assert!(!readers.can_read(&request_from("bob")));
+    assert!(readers.can_read(&Request::new(())));
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::AuthError;
+
+/// Prefix of generated api keys, telling them apart from signed tokens
+pub const API_KEY_PREFIX: &str = "dck_";
+/// Random bytes in a generated api key
+const API_KEY_LENGTH: usize = 32;
+
+/// Api keys accepted by a service, kept as sha256 digests so the file holding them never
+/// contains a usable key. Each line of the file is a subject followed by the hex digest of its
+/// key, and lines starting with `#` are ignored
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiKeys {
+    /// Subjects keyed by the digest of their key
+    subjects: HashMap<String, String>,
+}
+
+impl ApiKeys {
+    pub fn load(path: &Path) -> Result<ApiKeys, AuthError> {
+        let contents =
+            fs::read_to_string(path).map_err(|error| AuthError::Io(PathBuf::from(path), error))?;
+        let mut subjects = HashMap::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((subject, digest)) = line.split_once(char::is_whitespace) else {
+                return Err(AuthError::InvalidApiKeys {
+                    path: PathBuf::from(path),
+                    line: index + 1,
+                });
+            };
+            subjects.insert(String::from(digest.trim()), String::from(subject));
+        }
+
+        Ok(ApiKeys { subjects })
+    }
+
+    /// Subject the api key was issued to, if it is one of these keys
+    pub fn subject(&self, api_key: &str) -> Option<&str> {
+        self.subjects.get(&digest_hex(api_key)).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subjects.is_empty()
+    }
+}
+
+/// Generates an api key for `subject` and appends its digest to the file at `path`, returning
+/// the key, which can't be recovered from the file
+pub fn add_api_key(path: &Path, subject: &str) -> Result<String, AuthError> {
+    if subject.is_empty() || subject.contains(char::is_whitespace) {
+        return Err(AuthError::InvalidSubject(String::from(subject)));
+    }
+
+    let mut bytes = [0; API_KEY_LENGTH];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Should generate random api key");
+    let api_key = format!("{API_KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+    let io_error = |error| AuthError::Io(PathBuf::from(path), error);
+
+    if let Some(directory) = path
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+    {
+        fs::create_dir_all(directory).map_err(io_error)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{subject} {}", digest_hex(&api_key)))
+        .map_err(io_error)?;
+
+    Ok(api_key)
+}
+
+fn digest_hex(api_key: &str) -> String {
+    digest::digest(&digest::SHA256, api_key.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,310 @@
+mod api_keys;
+pub mod token;
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use tonic::{
+    metadata::{Ascii, MetadataMap, MetadataValue},
+    service::Interceptor,
+    Request, Status,
+};
+
+pub use api_keys::{add_api_key, ApiKeys, API_KEY_PREFIX};
+
+/// Metadata key bearer tokens are sent in
+pub const AUTHORIZATION_KEY: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+/// Time tokens issued for a single request are valid for, leaving room for clock skew between
+/// the services
+const SIGNED_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Caller a request was authenticated as, available to handlers through the request
+/// extensions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub subject: String,
+    pub method: AuthMethod,
+}
+
+/// How a caller proved its identity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMethod {
+    ApiKey,
+    Token,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Io(PathBuf, io::Error),
+    /// Secret file doesn't hold a base64 encoded secret
+    InvalidSecret(PathBuf),
+    InvalidApiKeys {
+        path: PathBuf,
+        line: usize,
+    },
+    /// Subject can't be written to an api keys file
+    InvalidSubject(String),
+    /// Token can't be sent as metadata
+    InvalidToken,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Io(path, error) => {
+                write!(formatter, "Failed to access {}: {error}", path.display())
+            }
+            AuthError::InvalidSecret(path) => write!(
+                formatter,
+                "{} doesn't hold a base64 encoded secret",
+                path.display()
+            ),
+            AuthError::InvalidApiKeys { path, line } => write!(
+                formatter,
+                "Line {line} of {} should be a subject followed by a key digest",
+                path.display()
+            ),
+            AuthError::InvalidSubject(subject) => write!(
+                formatter,
+                "Subject {subject:?} should be non empty and without whitespace"
+            ),
+            AuthError::InvalidToken => formatter.write_str("Token contains invalid characters"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// Reason a caller couldn't be authenticated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    MissingToken,
+    /// Authorization isn't a bearer token
+    NotBearer,
+    UnknownApiKey,
+    /// Caller sent a signed token to a service without a secret to verify it
+    TokensNotAccepted,
+    InvalidToken(token::TokenError),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::MissingToken => formatter.write_str("Missing bearer token"),
+            Rejection::NotBearer => formatter.write_str("Authorization should be a bearer token"),
+            Rejection::UnknownApiKey => formatter.write_str("Unknown api key"),
+            Rejection::TokensNotAccepted => formatter.write_str("Signed tokens aren't accepted"),
+            Rejection::InvalidToken(error) => write!(formatter, "{error}"),
+        }
+    }
+}
+
+impl From<Rejection> for Status {
+    fn from(rejection: Rejection) -> Self {
+        Status::unauthenticated(rejection.to_string())
+    }
+}
+
+/// Reads the base64 encoded secret tokens are signed with
+pub fn read_secret(path: &Path) -> Result<Vec<u8>, AuthError> {
+    let contents =
+        fs::read_to_string(path).map_err(|error| AuthError::Io(PathBuf::from(path), error))?;
+
+    URL_SAFE_NO_PAD
+        .decode(contents.trim())
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| AuthError::InvalidSecret(PathBuf::from(path)))
+}
+
+/// Validates the bearer tokens of incoming requests, either api keys or tokens signed with the
+/// secret
+#[derive(Clone, Debug, Default)]
+pub struct Authenticator {
+    secret: Option<Vec<u8>>,
+    api_keys: ApiKeys,
+}
+
+impl Authenticator {
+    pub fn new(secret: Option<Vec<u8>>, api_keys: ApiKeys) -> Authenticator {
+        Authenticator { secret, api_keys }
+    }
+
+    /// Authenticator for the secret and api keys files given, or `None` when neither was given
+    /// and requests aren't authenticated
+    pub fn load(
+        secret_file: Option<&Path>,
+        api_keys_file: Option<&Path>,
+    ) -> Result<Option<Authenticator>, AuthError> {
+        if secret_file.is_none() && api_keys_file.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Authenticator {
+            secret: secret_file.map(read_secret).transpose()?,
+            api_keys: api_keys_file
+                .map(ApiKeys::load)
+                .transpose()?
+                .unwrap_or_default(),
+        }))
+    }
+
+    /// Identity of the caller sending `metadata`
+    pub fn authenticate(&self, metadata: &MetadataMap) -> Result<Identity, Rejection> {
+        let Some(authorization) = metadata.get(AUTHORIZATION_KEY) else {
+            return Err(Rejection::MissingToken);
+        };
+        let Some(bearer) = authorization
+            .to_str()
+            .ok()
+            .and_then(|authorization| authorization.strip_prefix(BEARER_PREFIX))
+        else {
+            return Err(Rejection::NotBearer);
+        };
+
+        if bearer.starts_with(API_KEY_PREFIX) {
+            return match self.api_keys.subject(bearer) {
+                Some(subject) => Ok(Identity {
+                    subject: String::from(subject),
+                    method: AuthMethod::ApiKey,
+                }),
+                None => Err(Rejection::UnknownApiKey),
+            };
+        }
+
+        let Some(secret) = &self.secret else {
+            return Err(Rejection::TokensNotAccepted);
+        };
+        let claims = token::verify(secret, bearer).map_err(Rejection::InvalidToken)?;
+
+        Ok(Identity {
+            subject: claims.sub,
+            method: AuthMethod::Token,
+        })
+    }
+}
+
+/// Interceptor rejecting requests without a valid bearer token and attaching the identity of
+/// the caller to those with one. Every request passes through when no authenticator is given
+#[derive(Clone, Default)]
+pub struct AuthInterceptor {
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl AuthInterceptor {
+    pub fn new(authenticator: Option<Authenticator>) -> AuthInterceptor {
+        AuthInterceptor {
+            authenticator: authenticator.map(Arc::new),
+        }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(authenticator) = &self.authenticator {
+            let identity = authenticator.authenticate(request.metadata())?;
+            request.extensions_mut().insert(identity);
+        }
+
+        Ok(request)
+    }
+}
+
+/// Identity the request was authenticated as, `None` when the service doesn't authenticate
+/// requests
+pub fn identity<T>(request: &Request<T>) -> Option<&Identity> {
+    request.extensions().get::<Identity>()
+}
+
+/// Interceptor sending a bearer token with every request of a client, leaving requests that
+/// already carry one untouched
+#[derive(Clone, Debug, Default)]
+pub struct BearerToken {
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl BearerToken {
+    pub fn new(token: &str) -> Result<BearerToken, AuthError> {
+        let authorization = format!("{BEARER_PREFIX}{token}")
+            .parse()
+            .map_err(|_| AuthError::InvalidToken)?;
+
+        Ok(BearerToken {
+            authorization: Some(authorization),
+        })
+    }
+
+    /// Token sending `token` when given and no token otherwise
+    pub fn optional(token: Option<&str>) -> Result<BearerToken, AuthError> {
+        token.map_or(Ok(BearerToken::default()), BearerToken::new)
+    }
+
+    /// Token the caller sent in `metadata`, to forward a call on its behalf
+    pub fn forwarded(metadata: &MetadataMap) -> Option<BearerToken> {
+        metadata
+            .get(AUTHORIZATION_KEY)
+            .map(|authorization| BearerToken {
+                authorization: Some(authorization.clone()),
+            })
+    }
+}
+
+impl Interceptor for BearerToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(authorization) = &self.authorization {
+            if !request.metadata().contains_key(AUTHORIZATION_KEY) {
+                request
+                    .metadata_mut()
+                    .insert(AUTHORIZATION_KEY, authorization.clone());
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+/// Interceptor sending a token signed with a secret shared by services that call each other,
+/// issued afresh for every request so it never expires
+#[derive(Clone)]
+pub struct SignedToken {
+    secret: Arc<[u8]>,
+    subject: String,
+}
+
+impl SignedToken {
+    pub fn new(secret: &[u8], subject: &str) -> SignedToken {
+        SignedToken {
+            secret: Arc::from(secret),
+            subject: String::from(subject),
+        }
+    }
+}
+
+impl fmt::Debug for SignedToken {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("SignedToken")
+            .field("subject", &self.subject)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Interceptor for SignedToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = token::issue(&self.secret, &self.subject, SIGNED_TOKEN_TTL);
+        let authorization = format!("{BEARER_PREFIX}{token}")
+            .parse()
+            .expect("Signed tokens should be valid metadata");
+        request
+            .metadata_mut()
+            .insert(AUTHORIZATION_KEY, authorization);
+
+        Ok(request)
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+/// Header of every token, tokens are only ever signed with hmac sha256
+const HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+/// Bytes of a generated signing secret
+const SECRET_LENGTH: usize = 32;
+
+/// Claims of a signed token
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// Caller the token was issued to
+    pub sub: String,
+    /// Unix time in seconds the token was issued at
+    pub iat: u64,
+    /// Unix time in seconds the token expires at
+    pub exp: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenError {
+    /// Token isn't a json web token signed with hmac sha256
+    Malformed,
+    /// Token wasn't signed with the secret
+    InvalidSignature,
+    Expired,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Malformed => formatter.write_str("Token is malformed"),
+            TokenError::InvalidSignature => formatter.write_str("Token signature is invalid"),
+            TokenError::Expired => formatter.write_str("Token has expired"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Generates a random secret to sign tokens with, base64 encoded
+pub fn generate_secret() -> String {
+    let mut secret = [0; SECRET_LENGTH];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .expect("Should generate random secret");
+
+    URL_SAFE_NO_PAD.encode(secret)
+}
+
+/// Signs a token for `subject` valid for `ttl` from now
+pub fn issue(secret: &[u8], subject: &str, ttl: Duration) -> String {
+    let now = unix_time_secs();
+    let claims = Claims {
+        sub: String::from(subject),
+        iat: now,
+        exp: now + ttl.as_secs(),
+    };
+    let payload = serde_json::to_vec(&claims).expect("Should serialize claims");
+    let signed = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(HEADER),
+        URL_SAFE_NO_PAD.encode(payload)
+    );
+    let signature = hmac::sign(&key(secret), signed.as_bytes());
+
+    format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature.as_ref()))
+}
+
+/// Claims of `token` when it was signed with `secret` and hasn't expired
+pub fn verify(secret: &[u8], token: &str) -> Result<Claims, TokenError> {
+    let Some((signed, signature)) = token.rsplit_once('.') else {
+        return Err(TokenError::Malformed);
+    };
+    let Some((header, payload)) = signed.split_once('.') else {
+        return Err(TokenError::Malformed);
+    };
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| TokenError::Malformed)?;
+    hmac::verify(&key(secret), signed.as_bytes(), &signature)
+        .map_err(|_| TokenError::InvalidSignature)?;
+
+    // The header is checked even though it is signed, so tokens claiming another algorithm
+    // are never mistaken for ours
+    let header = URL_SAFE_NO_PAD
+        .decode(header)
+        .map_err(|_| TokenError::Malformed)?;
+    let header: serde_json::Value =
+        serde_json::from_slice(&header).map_err(|_| TokenError::Malformed)?;
+
+    if header.get("alg").and_then(|alg| alg.as_str()) != Some("HS256") {
+        return Err(TokenError::Malformed);
+    }
+
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| TokenError::Malformed)?;
+    let claims: Claims = serde_json::from_slice(&payload).map_err(|_| TokenError::Malformed)?;
+
+    if claims.exp <= unix_time_secs() {
+        return Err(TokenError::Expired);
+    }
+
+    Ok(claims)
+}
+
+fn key(secret: &[u8]) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, secret)
+}
+
+fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Should be after the unix epoch")
+        .as_secs()
+}
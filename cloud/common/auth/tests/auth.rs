@@ -0,0 +1,204 @@
+use std::{fs, time::Duration};
+
+use auth::{
+    add_api_key, identity, read_secret,
+    token::{self, TokenError},
+    ApiKeys, AuthInterceptor, AuthMethod, Authenticator, BearerToken, Identity, SignedToken,
+    AUTHORIZATION_KEY,
+};
+use tonic::{metadata::MetadataMap, service::Interceptor, Code, Request, Status};
+
+const SECRET: &[u8] = b"development secret";
+
+fn bearer(token: &str) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    metadata.insert(
+        AUTHORIZATION_KEY,
+        format!("Bearer {token}").parse().unwrap(),
+    );
+
+    metadata
+}
+
+#[test]
+fn tokens_round_trip() {
+    let token = token::issue(SECRET, "alice", Duration::from_secs(60));
+    let claims = token::verify(SECRET, &token).expect("Should verify token");
+
+    assert_eq!(claims.sub, "alice");
+    assert_eq!(claims.exp, claims.iat + 60);
+}
+
+#[test]
+fn tokens_signed_with_another_secret_or_changed_are_rejected() {
+    let token = token::issue(SECRET, "alice", Duration::from_secs(60));
+    let (signed, signature) = token.rsplit_once('.').unwrap();
+    let (header, _) = signed.split_once('.').unwrap();
+    let forged = token::issue(b"other secret", "mallory", Duration::from_secs(60));
+    let (forged_signed, _) = forged.rsplit_once('.').unwrap();
+    let (_, forged_payload) = forged_signed.split_once('.').unwrap();
+
+    assert_eq!(
+        token::verify(b"other secret", &token),
+        Err(TokenError::InvalidSignature)
+    );
+    assert_eq!(
+        token::verify(SECRET, &format!("{header}.{forged_payload}.{signature}")),
+        Err(TokenError::InvalidSignature)
+    );
+    assert_eq!(
+        token::verify(SECRET, "not a token"),
+        Err(TokenError::Malformed)
+    );
+}
+
+#[test]
+fn expired_tokens_are_rejected() {
+    let token = token::issue(SECRET, "alice", Duration::ZERO);
+
+    assert_eq!(token::verify(SECRET, &token), Err(TokenError::Expired));
+}
+
+#[test]
+fn api_keys_are_stored_as_digests() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("api_keys");
+
+    let alice = add_api_key(&path, "alice").expect("Should add api key");
+    let bob = add_api_key(&path, "bob").expect("Should add api key");
+    let api_keys = ApiKeys::load(&path).expect("Should load api keys");
+
+    assert_eq!(api_keys.subject(&alice), Some("alice"));
+    assert_eq!(api_keys.subject(&bob), Some("bob"));
+    assert_eq!(api_keys.subject("dck_unknown"), None);
+    assert!(!fs::read_to_string(&path).unwrap().contains(&alice));
+    assert!(add_api_key(&path, "two words").is_err());
+}
+
+#[test]
+fn secrets_are_read_from_base64() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("secret");
+    fs::write(&path, format!("{}\n", token::generate_secret())).unwrap();
+
+    assert_eq!(read_secret(&path).expect("Should read secret").len(), 32);
+
+    fs::write(&path, "not base64!").unwrap();
+    assert!(read_secret(&path).is_err());
+}
+
+#[test]
+fn callers_are_authenticated_by_token_or_api_key() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("api_keys");
+    let api_key = add_api_key(&path, "ci").expect("Should add api key");
+    let authenticator = Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::load(&path).expect("Should load api keys"),
+    );
+    let token = token::issue(SECRET, "alice", Duration::from_secs(60));
+
+    assert_eq!(
+        authenticator
+            .authenticate(&bearer(&token))
+            .expect("Should authenticate token"),
+        Identity {
+            subject: String::from("alice"),
+            method: AuthMethod::Token,
+        }
+    );
+    assert_eq!(
+        authenticator
+            .authenticate(&bearer(&api_key))
+            .expect("Should authenticate api key"),
+        Identity {
+            subject: String::from("ci"),
+            method: AuthMethod::ApiKey,
+        }
+    );
+
+    for metadata in [
+        MetadataMap::new(),
+        bearer("dck_unknown"),
+        bearer("garbage"),
+        {
+            let mut metadata = MetadataMap::new();
+            metadata.insert(AUTHORIZATION_KEY, token.parse().unwrap());
+            metadata
+        },
+    ] {
+        let status = Status::from(
+            authenticator
+                .authenticate(&metadata)
+                .expect_err("Should reject caller"),
+        );
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+}
+
+#[test]
+fn interceptors_attach_the_caller_identity() {
+    let token = token::issue(SECRET, "alice", Duration::from_secs(60));
+    let mut client = BearerToken::new(&token).expect("Should create token");
+    let mut server = AuthInterceptor::new(Some(Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::default(),
+    )));
+
+    let request = client.call(Request::new(())).expect("Should add token");
+    let request = server.call(request).expect("Should authenticate request");
+    assert_eq!(
+        identity(&request).map(|identity| identity.subject.as_str()),
+        Some("alice")
+    );
+
+    let status = server
+        .call(Request::new(()))
+        .expect_err("Should reject request without token");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let request = AuthInterceptor::default()
+        .call(Request::new(()))
+        .expect("Should let requests through without an authenticator");
+    assert_eq!(identity(&request), None);
+}
+
+#[test]
+fn forwarded_tokens_are_kept() {
+    let mut request = Request::new(());
+    *request.metadata_mut() = bearer("caller");
+    let mut service = BearerToken::new("service").expect("Should create token");
+
+    let request = service.call(request).expect("Should keep token");
+
+    assert_eq!(
+        request.metadata().get(AUTHORIZATION_KEY).unwrap(),
+        "Bearer caller"
+    );
+    assert!(BearerToken::forwarded(request.metadata()).is_some());
+    assert!(BearerToken::forwarded(&MetadataMap::new()).is_none());
+}
+
+#[test]
+fn signed_tokens_are_only_accepted_with_their_secret() {
+    let mut client = SignedToken::new(SECRET, "node-1");
+    let mut server = AuthInterceptor::new(Some(Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::default(),
+    )));
+    let mut other = AuthInterceptor::new(Some(Authenticator::new(
+        Some(b"other secret".to_vec()),
+        ApiKeys::default(),
+    )));
+
+    let request = client.call(Request::new(())).expect("Should add token");
+    let request = server.call(request).expect("Should authenticate request");
+    assert_eq!(
+        identity(&request).map(|identity| identity.subject.as_str()),
+        Some("node-1")
+    );
+
+    let request = client.call(Request::new(())).expect("Should add token");
+    let status = other.call(request).expect_err("Should reject token");
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
@@ -0,0 +1,136 @@
+use std::{fmt, process::ExitCode, str::FromStr};
+
+use serde_json::Value;
+use tonic::Code;
+
+/// Format command results are printed in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<OutputFormat, String> {
+        match format {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(format!(
+                "Unknown output {format}, expected table, json or yaml"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// Rows of text printed in aligned columns under a header
+#[derive(Clone, Debug, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|header| String::from(*header)).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+
+        for row in self.rows.iter() {
+            for (column, cell) in row.iter().enumerate() {
+                match widths.get_mut(column) {
+                    Some(width) => *width = (*width).max(cell.chars().count()),
+                    None => widths.push(cell.chars().count()),
+                }
+            }
+        }
+
+        for row in std::iter::once(&self.headers).chain(self.rows.iter()) {
+            let mut line = String::new();
+
+            for (column, cell) in row.iter().enumerate() {
+                if column > 0 {
+                    line.push_str("  ");
+                }
+
+                line.push_str(&format!("{cell:<width$}", width = widths[column]));
+            }
+
+            writeln!(formatter, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints a result to stdout, as `table` for people or as `value` for scripts
+pub fn print(format: OutputFormat, table: &Table, value: &Value) {
+    match format {
+        OutputFormat::Table => print!("{table}"),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).expect("Json values should serialize")
+        ),
+        OutputFormat::Yaml => print!(
+            "{}",
+            serde_yaml::to_string(value).expect("Json values should serialize to yaml")
+        ),
+    }
+}
+
+/// Prints one of a stream of results as it arrives, as a `line` of text for people, a json line
+/// or a yaml document
+pub fn print_record(format: OutputFormat, line: &str, value: &Value) {
+    match format {
+        OutputFormat::Table => println!("{line}"),
+        OutputFormat::Json => println!("{value}"),
+        OutputFormat::Yaml => print!(
+            "---\n{}",
+            serde_yaml::to_string(value).expect("Json values should serialize to yaml")
+        ),
+    }
+}
+
+/// Exit code reporting a call that failed with the grpc status `code`, so scripts can tell
+/// failures apart without parsing messages. Usage errors exit with 2, like clap's own
+pub fn exit_code(code: Code) -> ExitCode {
+    let exit_code = match code {
+        Code::InvalidArgument | Code::OutOfRange => 2,
+        Code::NotFound => 3,
+        Code::AlreadyExists => 4,
+        Code::PermissionDenied | Code::Unauthenticated => 5,
+        Code::ResourceExhausted => 6,
+        Code::FailedPrecondition | Code::Aborted => 7,
+        Code::DeadlineExceeded | Code::Unavailable => 8,
+        _ => 1,
+    };
+
+    ExitCode::from(exit_code)
+}
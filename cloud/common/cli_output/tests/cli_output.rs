@@ -0,0 +1,33 @@
+use std::process::ExitCode;
+
+use cli_output::{exit_code, OutputFormat, Table};
+use tonic::Code;
+
+#[test]
+fn columns_are_aligned_to_the_widest_cell() {
+    let mut table = Table::new(&["ID", "HOST"]);
+    table.push(vec![String::from("dc-long-id"), String::from("a.local")]);
+    table.push(vec![String::from("dc-b"), String::from("b.local")]);
+
+    assert_eq!(
+        table.to_string(),
+        "ID          HOST\ndc-long-id  a.local\ndc-b        b.local\n"
+    );
+}
+
+#[test]
+fn formats_parse_from_their_names() {
+    assert_eq!("json".parse(), Ok(OutputFormat::Json));
+    assert_eq!("table".parse(), Ok(OutputFormat::Table));
+    assert_eq!("yaml".parse(), Ok(OutputFormat::Yaml));
+    assert!("xml".parse::<OutputFormat>().is_err());
+}
+
+#[test]
+fn status_codes_map_to_exit_codes() {
+    assert_eq!(exit_code(Code::InvalidArgument), ExitCode::from(2));
+    assert_eq!(exit_code(Code::NotFound), ExitCode::from(3));
+    assert_eq!(exit_code(Code::Unauthenticated), ExitCode::from(5));
+    assert_eq!(exit_code(Code::Unavailable), ExitCode::from(8));
+    assert_eq!(exit_code(Code::Internal), ExitCode::from(1));
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Environment variable pointing at a config file to use instead of the default one
+pub const CONFIG_PATH_VARIABLE: &str = "DECENTRALIZED_CLOUD_CONFIG";
+/// Environment variable naming the profile to use when none is passed on the command line
+pub const PROFILE_VARIABLE: &str = "DECENTRALIZED_CLOUD_PROFILE";
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Settings for talking to a network, selected by name
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Endpoints of the resolvers of the network
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resolvers: Vec<String>,
+    /// Data center the `datacenter` cli talks to, or a resolver routing its calls to data centers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Bearer token sent with every call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Project the `datacenter` cli creates and lists resources in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Key the data center the `datacenter` cli talks to must prove it holds, as a fingerprint
+    /// or a base64 public key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_center_key: Option<String>,
+    /// Format results are printed in when no output format is passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "TlsConfig::is_empty")]
+    pub tls: TlsConfig,
+}
+
+/// Paths of the pem encoded tls material used to reach servers
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Certificate authority servers are verified against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_certificate: Option<PathBuf>,
+    /// Certificate presented to servers requiring mutual tls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<PathBuf>,
+    /// Private key of `certificate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    pub fn is_empty(&self) -> bool {
+        self == &TlsConfig::default()
+    }
+}
+
+/// Settings of a profile that can be read and written by key
+pub const PROFILE_KEYS: &[&str] = &[
+    "host",
+    "resolvers",
+    "output",
+    "token",
+    "project",
+    "data_center_key",
+    "tls.ca_certificate",
+    "tls.certificate",
+    "tls.key",
+];
+
+impl Profile {
+    /// Value of the setting `key`, with lists joined by commas
+    pub fn get(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|path| path.display().to_string());
+
+        Ok(match key {
+            "host" => self.host.clone(),
+            "resolvers" if self.resolvers.is_empty() => None,
+            "resolvers" => Some(self.resolvers.join(",")),
+            "output" => self.output.clone(),
+            "token" => self.token.clone(),
+            "project" => self.project.clone(),
+            "data_center_key" => self.data_center_key.clone(),
+            "tls.ca_certificate" => path(&self.tls.ca_certificate),
+            "tls.certificate" => path(&self.tls.certificate),
+            "tls.key" => path(&self.tls.key),
+            _ => return Err(ConfigError::UnknownKey(String::from(key))),
+        })
+    }
+
+    /// Sets `key` to `value`, with lists split on commas. An empty value removes the setting
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let value = Some(String::from(value)).filter(|value| !value.is_empty());
+
+        match key {
+            "host" => self.host = value,
+            "resolvers" => {
+                self.resolvers = value
+                    .iter()
+                    .flat_map(|value| value.split(','))
+                    .map(String::from)
+                    .collect()
+            }
+            "output" => self.output = value,
+            "token" => self.token = value,
+            "project" => self.project = value,
+            "data_center_key" => self.data_center_key = value,
+            "tls.ca_certificate" => self.tls.ca_certificate = value.map(PathBuf::from),
+            "tls.certificate" => self.tls.certificate = value.map(PathBuf::from),
+            "tls.key" => self.tls.key = value.map(PathBuf::from),
+            _ => return Err(ConfigError::UnknownKey(String::from(key))),
+        }
+
+        Ok(())
+    }
+
+    /// Every setting the profile has, in the order of `PROFILE_KEYS`
+    pub fn settings(&self) -> Vec<(&'static str, String)> {
+        PROFILE_KEYS
+            .iter()
+            .filter_map(|key| {
+                self.get(key)
+                    .expect("Should know profile keys")
+                    .map(|value| (*key, value))
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when none is selected, `default` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// Profile was selected explicitly but isn't defined
+    UnknownProfile(String),
+    /// Key doesn't name a profile setting
+    UnknownKey(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, error) => {
+                write!(formatter, "Failed to access {}: {error}", path.display())
+            }
+            ConfigError::Parse(path, error) => {
+                write!(formatter, "Invalid config file {}: {error}", path.display())
+            }
+            ConfigError::Serialize(error) => write!(formatter, "Failed to write config: {error}"),
+            ConfigError::UnknownProfile(name) => write!(formatter, "No profile named {name}"),
+            ConfigError::UnknownKey(key) => write!(
+                formatter,
+                "No setting named {key}, expected one of {}",
+                PROFILE_KEYS.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Path of the config file, taken from the environment or under the user's config directory
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = env::var_os(CONFIG_PATH_VARIABLE) {
+            return PathBuf::from(path);
+        }
+
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_default();
+
+        config_home.join("decentralized_cloud").join("config.toml")
+    }
+
+    /// Loads the config at the default path
+    pub fn load() -> Result<Config, ConfigError> {
+        Config::load_from(&Config::default_path())
+    }
+
+    /// Loads the config at `path`, which is empty when there is no file
+    pub fn load_from(path: &Path) -> Result<Config, ConfigError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(error) => return Err(ConfigError::Io(PathBuf::from(path), error)),
+        };
+
+        toml::from_str(&contents).map_err(|error| ConfigError::Parse(PathBuf::from(path), error))
+    }
+
+    /// Saves the config to the default path
+    pub fn save(&self) -> Result<(), ConfigError> {
+        self.save_to(&Config::default_path())
+    }
+
+    /// Saves the config to `path`, readable only by the user since profiles hold tokens
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        let contents = toml::to_string_pretty(self).map_err(ConfigError::Serialize)?;
+        let io_error = |error| ConfigError::Io(PathBuf::from(path), error);
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(io_error)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        options
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(io_error)
+    }
+
+    /// Name of the profile `selected`, falling back to the profile named by the environment and
+    /// then to the default profile
+    pub fn profile_name(&self, selected: Option<&str>) -> String {
+        selected
+            .map(String::from)
+            .or_else(|| env::var(PROFILE_VARIABLE).ok())
+            .or_else(|| self.default_profile.clone())
+            .unwrap_or_else(|| String::from(DEFAULT_PROFILE))
+    }
+
+    /// Profile named `selected`, falling back to the profile named by the environment and then
+    /// to the default profile. Only a profile selected by name has to exist, otherwise an empty
+    /// profile is returned
+    pub fn profile(&self, selected: Option<&str>) -> Result<Profile, ConfigError> {
+        let explicit = selected.is_some() || env::var_os(PROFILE_VARIABLE).is_some();
+        let name = self.profile_name(selected);
+
+        match self.profiles.get(&name) {
+            Some(profile) => Ok(profile.clone()),
+            None if explicit => Err(ConfigError::UnknownProfile(name)),
+            None => Ok(Profile::default()),
+        }
+    }
+}
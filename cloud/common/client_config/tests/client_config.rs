@@ -0,0 +1,124 @@
+use client_config::{Config, ConfigError, Profile};
+
+const CONFIG: &str = r#"
+default_profile = "staging"
+
+[profiles.local]
+resolvers = ["http://[::1]:50051"]
+
+[profiles.staging]
+resolvers = ["http://10.0.0.1:50051", "http://10.0.0.2:50051"]
+"#;
+
+fn write_config(contents: &str) -> (tempfile::TempDir, Config) {
+    let directory = tempfile::tempdir().expect("Should create config dir");
+    let path = directory.path().join("config.toml");
+    std::fs::write(&path, contents).expect("Should write config");
+    let config = Config::load_from(&path).expect("Should load config");
+
+    (directory, config)
+}
+
+#[test]
+fn missing_config_is_empty() {
+    let directory = tempfile::tempdir().expect("Should create config dir");
+    let config =
+        Config::load_from(&directory.path().join("config.toml")).expect("Should load config");
+
+    assert_eq!(config, Config::default());
+    assert_eq!(
+        config.profile(None).expect("Should use default"),
+        Profile::default()
+    );
+}
+
+#[test]
+fn profiles_are_selected_by_name() {
+    let (_directory, config) = write_config(CONFIG);
+    let local = config.profile(Some("local")).expect("Should find profile");
+
+    assert_eq!(local.resolvers, vec!["http://[::1]:50051"]);
+}
+
+#[test]
+fn default_profile_is_used_without_a_selection() {
+    let (_directory, config) = write_config(CONFIG);
+    let profile = config.profile(None).expect("Should use default profile");
+
+    assert_eq!(profile.resolvers.len(), 2);
+}
+
+#[test]
+fn unknown_profiles_are_rejected() {
+    let (_directory, config) = write_config(CONFIG);
+
+    assert!(matches!(
+        config.profile(Some("production")),
+        Err(ConfigError::UnknownProfile(name)) if name == "production"
+    ));
+}
+
+#[test]
+fn unknown_settings_are_rejected() {
+    let directory = tempfile::tempdir().expect("Should create config dir");
+    let path = directory.path().join("config.toml");
+    std::fs::write(
+        &path,
+        "[profiles.local]\nresolver = \"http://[::1]:50051\"\n",
+    )
+    .expect("Should write config");
+
+    assert!(matches!(
+        Config::load_from(&path),
+        Err(ConfigError::Parse(_, _))
+    ));
+}
+
+#[test]
+fn settings_are_saved_and_loaded() {
+    let directory = tempfile::tempdir().expect("Should create config dir");
+    let path = directory.path().join("nested").join("config.toml");
+    let mut config = Config::default();
+    let profile = config.profiles.entry(String::from("local")).or_default();
+    profile
+        .set("host", "localhost:50052")
+        .expect("Should set host");
+    profile
+        .set("resolvers", "http://a:50051,http://b:50051")
+        .expect("Should set resolvers");
+    profile
+        .set("tls.ca_certificate", "/etc/ca.pem")
+        .expect("Should set ca certificate");
+    profile.set("token", "secret").expect("Should set token");
+    profile.set("token", "").expect("Should remove token");
+    config.save_to(&path).expect("Should save config");
+
+    let loaded = Config::load_from(&path).expect("Should load config");
+    let profile = loaded.profile(Some("local")).expect("Should find profile");
+
+    assert_eq!(loaded, config);
+    assert_eq!(profile.resolvers, vec!["http://a:50051", "http://b:50051"]);
+    assert_eq!(profile.get("token").expect("Should get token"), None);
+    assert_eq!(
+        profile.settings(),
+        vec![
+            ("host", String::from("localhost:50052")),
+            ("resolvers", String::from("http://a:50051,http://b:50051")),
+            ("tls.ca_certificate", String::from("/etc/ca.pem")),
+        ]
+    );
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let mut profile = Profile::default();
+
+    assert!(matches!(
+        profile.set("hostname", "localhost"),
+        Err(ConfigError::UnknownKey(key)) if key == "hostname"
+    ));
+    assert!(matches!(
+        profile.get("tls"),
+        Err(ConfigError::UnknownKey(_))
+    ));
+}
@@ -0,0 +1,128 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig};
+
+/// Paths of the pem encoded tls material of a server or client. A certificate and key make
+/// servers serve tls and clients present themselves for mutual tls, while a certificate
+/// authority makes servers require client certificates it signed and clients verify servers
+/// against it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub ca_certificate: Option<PathBuf>,
+    pub certificate: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(PathBuf, io::Error),
+    /// Certificate was given without its key, or the other way around
+    Incomplete(&'static str),
+    Invalid(tonic::transport::Error),
+    /// Endpoint asked for https while no tls was configured to verify it with
+    MissingTls(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(path, error) => {
+                write!(formatter, "Failed to read {}: {error}", path.display())
+            }
+            TlsError::Incomplete(message) => formatter.write_str(message),
+            TlsError::Invalid(error) => write!(formatter, "Invalid tls configuration: {error}"),
+            TlsError::MissingTls(address) => write!(
+                formatter,
+                "{address} is reached over https but no tls certificate authority is configured"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl TlsFiles {
+    /// Whether any tls material was given
+    pub fn is_enabled(&self) -> bool {
+        self.ca_certificate.is_some() || self.certificate.is_some() || self.key.is_some()
+    }
+
+    /// Tls a server is served with, or `None` to serve plaintext when no certificate was given
+    pub fn server_config(&self) -> Result<Option<ServerTlsConfig>, TlsError> {
+        let Some(identity) = self.identity()? else {
+            return match self.ca_certificate {
+                Some(_) => Err(TlsError::Incomplete(
+                    "Servers need a certificate and key to require client certificates",
+                )),
+                None => Ok(None),
+            };
+        };
+        let mut config = ServerTlsConfig::new().identity(identity);
+
+        if let Some(ca_certificate) = &self.ca_certificate {
+            config = config.client_ca_root(Certificate::from_pem(read(ca_certificate)?));
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Tls clients connect with, or `None` to connect in plaintext when no material was given
+    pub fn client_config(&self) -> Result<Option<ClientTlsConfig>, TlsError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let mut config = ClientTlsConfig::new();
+
+        if let Some(ca_certificate) = &self.ca_certificate {
+            config = config.ca_certificate(Certificate::from_pem(read(ca_certificate)?));
+        }
+
+        if let Some(identity) = self.identity()? {
+            config = config.identity(identity);
+        }
+
+        Ok(Some(config))
+    }
+
+    fn identity(&self) -> Result<Option<Identity>, TlsError> {
+        match (&self.certificate, &self.key) {
+            (Some(certificate), Some(key)) => {
+                Ok(Some(Identity::from_pem(read(certificate)?, read(key)?)))
+            }
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(TlsError::Incomplete(
+                "Certificate was given without its key",
+            )),
+            (None, Some(_)) => Err(TlsError::Incomplete(
+                "Key was given without its certificate",
+            )),
+        }
+    }
+}
+
+/// Endpoint for `address`, reached over https with `tls` when given and over http otherwise.
+/// Addresses may carry an http scheme, which is upgraded when tls is used, while https
+/// addresses require tls
+pub fn endpoint(address: &str, tls: Option<&ClientTlsConfig>) -> Result<Endpoint, TlsError> {
+    let authority = match address.strip_prefix("https://") {
+        Some(_) if tls.is_none() => return Err(TlsError::MissingTls(String::from(address))),
+        Some(authority) => authority,
+        None => address.strip_prefix("http://").unwrap_or(address),
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let endpoint =
+        Endpoint::from_shared(format!("{scheme}://{authority}")).map_err(TlsError::Invalid)?;
+
+    match tls {
+        Some(tls) => endpoint.tls_config(tls.clone()).map_err(TlsError::Invalid),
+        None => Ok(endpoint),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|error| TlsError::Io(PathBuf::from(path), error))
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use grpc_tls::{endpoint, TlsError, TlsFiles};
+use tonic::transport::ClientTlsConfig;
+
+#[test]
+fn endpoints_use_the_scheme_matching_tls() {
+    let tls = ClientTlsConfig::new();
+
+    assert_eq!(
+        endpoint("localhost:50051", None).unwrap().uri().to_string(),
+        "http://localhost:50051/"
+    );
+    assert_eq!(
+        endpoint("http://localhost:50051", Some(&tls))
+            .unwrap()
+            .uri()
+            .to_string(),
+        "https://localhost:50051/"
+    );
+    assert!(matches!(
+        endpoint("https://localhost:50051", None),
+        Err(TlsError::MissingTls(_))
+    ));
+}
+
+#[test]
+fn tls_is_disabled_without_files() {
+    let files = TlsFiles::default();
+
+    assert!(!files.is_enabled());
+    assert!(files.server_config().unwrap().is_none());
+    assert!(files.client_config().unwrap().is_none());
+}
+
+#[test]
+fn certificates_need_their_key() {
+    let files = TlsFiles {
+        certificate: Some(PathBuf::from("server.pem")),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        files.client_config(),
+        Err(TlsError::Incomplete(_))
+    ));
+}
+
+#[test]
+fn servers_need_a_certificate_to_verify_clients() {
+    let files = TlsFiles {
+        ca_certificate: Some(PathBuf::from("ca.pem")),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        files.server_config(),
+        Err(TlsError::Incomplete(_))
+    ));
+}
+
+#[test]
+fn missing_files_are_reported() {
+    let files = TlsFiles {
+        ca_certificate: Some(PathBuf::from("/nonexistent/ca.pem")),
+        ..Default::default()
+    };
+
+    assert!(matches!(files.client_config(), Err(TlsError::Io(..))));
+}
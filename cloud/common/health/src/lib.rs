@@ -0,0 +1,127 @@
+use std::{fmt, time::Duration};
+
+use tokio::task::JoinHandle;
+pub use tonic_health::ServingStatus;
+use tonic_health::{
+    pb::health_server::{Health, HealthServer},
+    server::{health_reporter, HealthReporter},
+};
+use tonic_reflection::server::{
+    Error as ReflectionError, ServerReflection, ServerReflectionServer,
+};
+
+/// Name the health of the server as a whole is reported under
+pub const SERVER_HEALTH: &str = "";
+/// Time between readiness checks
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Check of whether a service can serve calls, run periodically while it serves
+pub trait ReadinessCheck: Send + Sync + 'static {
+    /// Reason the service can't serve calls
+    type Error: fmt::Display + Send;
+
+    fn check(&self) -> Result<(), Self::Error>;
+}
+
+/// Readiness of a server, reported through the `grpc.health.v1` service it was created with
+pub struct Readiness {
+    reporter: HealthReporter,
+    services: Vec<&'static str>,
+    checks: JoinHandle<()>,
+}
+
+impl Readiness {
+    /// Creates the health service of a server serving `services`, which are reported serving
+    /// along with the server whenever `check` passes and not serving otherwise. `check` is run
+    /// every `interval`, starting right away
+    pub fn start<C>(
+        check: C,
+        services: Vec<&'static str>,
+        interval: Duration,
+    ) -> (Readiness, HealthServer<impl Health>)
+    where
+        C: ReadinessCheck,
+    {
+        let (reporter, server) = health_reporter();
+        let checks = tokio::spawn(run_checks(
+            check,
+            reporter.clone(),
+            services.clone(),
+            interval,
+        ));
+
+        (
+            Readiness {
+                reporter,
+                services,
+                checks,
+            },
+            server,
+        )
+    }
+
+    /// Stops checking readiness and reports every service as not serving, so probes stop
+    /// routing calls to the server while it drains
+    pub async fn stop(mut self) {
+        self.checks.abort();
+        report(
+            &mut self.reporter,
+            &self.services,
+            ServingStatus::NotServing,
+        )
+        .await;
+    }
+}
+
+async fn run_checks<C>(
+    check: C,
+    mut reporter: HealthReporter,
+    services: Vec<&'static str>,
+    interval: Duration,
+) where
+    C: ReadinessCheck,
+{
+    let mut interval = tokio::time::interval(interval);
+    let mut ready = None;
+
+    loop {
+        interval.tick().await;
+        let result = check.check();
+
+        match (&result, ready) {
+            (Err(error), Some(true) | None) => tracing::warn!(%error, "Not ready to serve"),
+            (Ok(()), Some(false) | None) => tracing::info!("Ready to serve"),
+            _ => {}
+        }
+
+        let status = match result {
+            Ok(()) => ServingStatus::Serving,
+            Err(_) => ServingStatus::NotServing,
+        };
+        ready = Some(status == ServingStatus::Serving);
+        report(&mut reporter, &services, status).await;
+    }
+}
+
+async fn report(reporter: &mut HealthReporter, services: &[&'static str], status: ServingStatus) {
+    reporter.set_service_status(SERVER_HEALTH, status).await;
+
+    for service in services {
+        reporter.set_service_status(service, status).await;
+    }
+}
+
+/// Reflection service describing the services of `descriptor_sets`, along with the health
+/// service, to tools like grpcurl
+pub fn reflection(
+    descriptor_sets: &[&'static [u8]],
+) -> Result<ServerReflectionServer<impl ServerReflection>, ReflectionError> {
+    descriptor_sets
+        .iter()
+        .fold(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET),
+            |builder, descriptor_set| builder.register_encoded_file_descriptor_set(descriptor_set),
+        )
+        .build()
+}
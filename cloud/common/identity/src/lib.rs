@@ -0,0 +1,430 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{
+    engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+    signature::{self, Ed25519KeyPair, KeyPair},
+};
+
+/// Prefix of the fingerprints of public keys
+pub const FINGERPRINT_PREFIX: &str = "SHA256:";
+/// Furthest a signature's timestamp may be from the clock of the verifier
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+/// Tags separating the messages signed for different purposes, so a signature over one is
+/// never accepted as a signature over another
+const REGISTRATION_TAG: &[u8] = b"decentralized-cloud/registration/v1";
+const DEREGISTRATION_TAG: &[u8] = b"decentralized-cloud/deregistration/v1";
+const HEARTBEAT_TAG: &[u8] = b"decentralized-cloud/heartbeat/v1";
+const CHALLENGE_TAG: &[u8] = b"decentralized-cloud/challenge/v1";
+/// Bytes of a generated challenge
+const CHALLENGE_LENGTH: usize = 32;
+
+#[derive(Debug)]
+pub enum IdentityError {
+    Io(PathBuf, io::Error),
+    /// Key file doesn't hold a base64 encoded ed25519 pkcs8 key
+    InvalidKeyFile(PathBuf),
+    /// Public key isn't an ed25519 public key
+    InvalidPublicKey,
+    InvalidSignature,
+    /// Signature was made too long ago, or too far in the future, to be trusted
+    Stale {
+        signed_at_unix_ms: u64,
+        now_unix_ms: u64,
+    },
+}
+
+impl fmt::Display for IdentityError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityError::Io(path, error) => {
+                write!(formatter, "Failed to access {}: {error}", path.display())
+            }
+            IdentityError::InvalidKeyFile(path) => write!(
+                formatter,
+                "{} doesn't hold a base64 encoded ed25519 key",
+                path.display()
+            ),
+            IdentityError::InvalidPublicKey => {
+                formatter.write_str("Public key should be a 32 byte ed25519 key")
+            }
+            IdentityError::InvalidSignature => formatter.write_str("Signature is invalid"),
+            IdentityError::Stale {
+                signed_at_unix_ms,
+                now_unix_ms,
+            } => write!(
+                formatter,
+                "Signature made at {signed_at_unix_ms} is more than {}s away from {now_unix_ms}",
+                MAX_CLOCK_SKEW.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IdentityError {}
+
+/// Long lived ed25519 keypair a data center proves who it is with
+pub struct IdentityKey {
+    key_pair: Ed25519KeyPair,
+}
+
+impl fmt::Debug for IdentityKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("IdentityKey")
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+impl IdentityKey {
+    /// Generates a key that only lives as long as the process
+    pub fn generate() -> IdentityKey {
+        IdentityKey::from_pkcs8(&generate_pkcs8()).expect("Should parse generated key")
+    }
+
+    /// Reads the key stored at `path`, generating and storing one readable only by the owner
+    /// when there is none
+    pub fn load_or_generate(path: &Path) -> Result<IdentityKey, IdentityError> {
+        let io_error = |error| IdentityError::Io(PathBuf::from(path), error);
+
+        match fs::read_to_string(path) {
+            Ok(contents) => URL_SAFE_NO_PAD
+                .decode(contents.trim())
+                .ok()
+                .and_then(|pkcs8| IdentityKey::from_pkcs8(&pkcs8))
+                .ok_or_else(|| IdentityError::InvalidKeyFile(PathBuf::from(path))),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                let pkcs8 = generate_pkcs8();
+
+                if let Some(directory) = path
+                    .parent()
+                    .filter(|directory| !directory.as_os_str().is_empty())
+                {
+                    fs::create_dir_all(directory).map_err(io_error)?;
+                }
+
+                let mut options = fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+                options
+                    .open(path)
+                    .and_then(|mut file| {
+                        io::Write::write_all(&mut file, URL_SAFE_NO_PAD.encode(&pkcs8).as_bytes())
+                    })
+                    .map_err(io_error)?;
+
+                Ok(IdentityKey::from_pkcs8(&pkcs8).expect("Should parse generated key"))
+            }
+            Err(error) => Err(io_error(error)),
+        }
+    }
+
+    fn from_pkcs8(pkcs8: &[u8]) -> Option<IdentityKey> {
+        Ed25519KeyPair::from_pkcs8(pkcs8)
+            .ok()
+            .map(|key_pair| IdentityKey { key_pair })
+    }
+
+    pub fn public_key(&self) -> Vec<u8> {
+        self.key_pair.public_key().as_ref().to_vec()
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.key_pair.public_key().as_ref())
+    }
+
+    /// Signs the registration described by `claims`, whose public key should be this key's
+    pub fn sign_registration(&self, claims: &RegistrationClaims) -> Vec<u8> {
+        self.sign(&claims.message())
+    }
+
+    /// Signs the deregistration of the data center this key is bound to
+    pub fn sign_deregistration(&self, claims: &DeregistrationClaims) -> Vec<u8> {
+        self.sign(&claims.message())
+    }
+
+    /// Signs a heartbeat of the data center this key is bound to
+    pub fn sign_heartbeat(&self, claims: &HeartbeatClaims) -> Vec<u8> {
+        self.sign(&claims.message())
+    }
+
+    /// Signs a challenge a client sent to make sure it reached the data center it expected
+    pub fn sign_challenge(&self, data_center_id: &str, challenge: &[u8]) -> Vec<u8> {
+        self.sign(&challenge_message(data_center_id, challenge))
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(message).as_ref().to_vec()
+    }
+}
+
+/// What a data center claims about itself when registering with a resolver
+#[derive(Clone, Debug, Default)]
+pub struct RegistrationClaims<'a> {
+    pub data_center_id: &'a str,
+    pub host_name: &'a str,
+    pub region: &'a str,
+    pub zone: &'a str,
+    pub labels: Vec<(&'a str, &'a str)>,
+    pub services: &'a [i32],
+    pub architectures: &'a [String],
+    pub public_key: &'a [u8],
+    pub signed_at_unix_ms: u64,
+}
+
+impl<'a> RegistrationClaims<'a> {
+    /// Claims made by the fields of a registration request, taken apart since every service
+    /// generates its own copy of the request
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_parts<L>(
+        data_center_id: &'a str,
+        host_name: &'a str,
+        region: &'a str,
+        zone: &'a str,
+        labels: L,
+        services: &'a [i32],
+        architectures: &'a [String],
+        public_key: &'a [u8],
+        signed_at_unix_ms: u64,
+    ) -> RegistrationClaims<'a>
+    where
+        L: IntoIterator<Item = (&'a String, &'a String)>,
+    {
+        RegistrationClaims {
+            data_center_id,
+            host_name,
+            region,
+            zone,
+            labels: labels
+                .into_iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect(),
+            services,
+            architectures,
+            public_key,
+            signed_at_unix_ms,
+        }
+    }
+
+    /// Bytes signed for the claims, every field prefixed by its length and labels sorted so
+    /// the message doesn't depend on the order they were collected in
+    fn message(&self) -> Vec<u8> {
+        let mut labels = self.labels.clone();
+        labels.sort_unstable();
+        let mut message = Message::new(REGISTRATION_TAG);
+        message.field(self.data_center_id.as_bytes());
+        message.field(self.host_name.as_bytes());
+        message.field(self.region.as_bytes());
+        message.field(self.zone.as_bytes());
+        message.count(labels.len());
+
+        for (key, value) in labels {
+            message.field(key.as_bytes());
+            message.field(value.as_bytes());
+        }
+
+        message.count(self.services.len());
+
+        for service in self.services {
+            message.field(&service.to_be_bytes());
+        }
+
+        message.count(self.architectures.len());
+
+        for architecture in self.architectures {
+            message.field(architecture.as_bytes());
+        }
+
+        message.field(self.public_key);
+        message.field(&self.signed_at_unix_ms.to_be_bytes());
+
+        message.0
+    }
+
+    /// Checks that the claims were signed by the key they carry recently enough to not be
+    /// replayed from long ago
+    pub fn verify(&self, signature: &[u8], now_unix_ms: u64) -> Result<(), IdentityError> {
+        check_skew(self.signed_at_unix_ms, now_unix_ms)?;
+
+        verify(self.public_key, &self.message(), signature)
+    }
+}
+
+/// What a data center claims when asking a resolver to forget it
+#[derive(Clone, Debug, Default)]
+pub struct DeregistrationClaims<'a> {
+    pub data_center_id: &'a str,
+    pub signed_at_unix_ms: u64,
+}
+
+impl DeregistrationClaims<'_> {
+    fn message(&self) -> Vec<u8> {
+        let mut message = Message::new(DEREGISTRATION_TAG);
+        message.field(self.data_center_id.as_bytes());
+        message.field(&self.signed_at_unix_ms.to_be_bytes());
+
+        message.0
+    }
+
+    /// Checks that the claims were signed recently by `public_key`, the key the data center
+    /// registered with
+    pub fn verify(
+        &self,
+        public_key: &[u8],
+        signature: &[u8],
+        now_unix_ms: u64,
+    ) -> Result<(), IdentityError> {
+        check_skew(self.signed_at_unix_ms, now_unix_ms)?;
+
+        verify(public_key, &self.message(), signature)
+    }
+}
+
+/// What a data center claims when telling a resolver it is still alive
+#[derive(Clone, Debug, Default)]
+pub struct HeartbeatClaims<'a> {
+    pub data_center_id: &'a str,
+    pub signed_at_unix_ms: u64,
+}
+
+impl HeartbeatClaims<'_> {
+    fn message(&self) -> Vec<u8> {
+        let mut message = Message::new(HEARTBEAT_TAG);
+        message.field(self.data_center_id.as_bytes());
+        message.field(&self.signed_at_unix_ms.to_be_bytes());
+
+        message.0
+    }
+
+    /// Checks that the claims were signed recently by `public_key`, the key the data center
+    /// registered with
+    pub fn verify(
+        &self,
+        public_key: &[u8],
+        signature: &[u8],
+        now_unix_ms: u64,
+    ) -> Result<(), IdentityError> {
+        check_skew(self.signed_at_unix_ms, now_unix_ms)?;
+
+        verify(public_key, &self.message(), signature)
+    }
+}
+
+/// Random challenge for a data center to sign, never sent twice
+pub fn generate_challenge() -> Vec<u8> {
+    let mut challenge = vec![0; CHALLENGE_LENGTH];
+    SystemRandom::new()
+        .fill(&mut challenge)
+        .expect("Should generate random challenge");
+
+    challenge
+}
+
+/// Checks that the data center holding `public_key` signed `challenge`
+pub fn verify_challenge(
+    public_key: &[u8],
+    data_center_id: &str,
+    challenge: &[u8],
+    signature: &[u8],
+) -> Result<(), IdentityError> {
+    verify(
+        public_key,
+        &challenge_message(data_center_id, challenge),
+        signature,
+    )
+}
+
+/// Short printable digest identifying a public key, which is what gets pinned
+pub fn fingerprint(public_key: &[u8]) -> String {
+    let digest = digest::digest(&digest::SHA256, public_key);
+
+    format!("{FINGERPRINT_PREFIX}{}", STANDARD_NO_PAD.encode(digest))
+}
+
+/// Whether `pinned`, either a fingerprint or a base64 public key, names `public_key`
+pub fn matches_pin(pinned: &str, public_key: &[u8]) -> bool {
+    let pinned = pinned.trim();
+
+    if pinned.starts_with(FINGERPRINT_PREFIX) {
+        return pinned == fingerprint(public_key);
+    }
+
+    [&STANDARD_NO_PAD, &URL_SAFE_NO_PAD]
+        .iter()
+        .any(|engine| engine.decode(pinned.trim_end_matches('=')).as_deref() == Ok(public_key))
+}
+
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Should be after the unix epoch")
+        .as_millis() as u64
+}
+
+fn check_skew(signed_at_unix_ms: u64, now_unix_ms: u64) -> Result<(), IdentityError> {
+    if now_unix_ms.abs_diff(signed_at_unix_ms) > MAX_CLOCK_SKEW.as_millis() as u64 {
+        return Err(IdentityError::Stale {
+            signed_at_unix_ms,
+            now_unix_ms,
+        });
+    }
+
+    Ok(())
+}
+
+fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), IdentityError> {
+    if public_key.len() != 32 {
+        return Err(IdentityError::InvalidPublicKey);
+    }
+
+    signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(message, signature)
+        .map_err(|_| IdentityError::InvalidSignature)
+}
+
+fn challenge_message(data_center_id: &str, challenge: &[u8]) -> Vec<u8> {
+    let mut message = Message::new(CHALLENGE_TAG);
+    message.field(data_center_id.as_bytes());
+    message.field(challenge);
+
+    message.0
+}
+
+fn generate_pkcs8() -> Vec<u8> {
+    Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .expect("Should generate ed25519 key")
+        .as_ref()
+        .to_vec()
+}
+
+/// Unambiguous encoding of the fields of a signed message
+struct Message(Vec<u8>);
+
+impl Message {
+    fn new(tag: &[u8]) -> Message {
+        let mut message = Message(Vec::new());
+        message.field(tag);
+
+        message
+    }
+
+    fn field(&mut self, bytes: &[u8]) {
+        self.count(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn count(&mut self, count: usize) {
+        self.0.extend_from_slice(&(count as u64).to_be_bytes());
+    }
+}
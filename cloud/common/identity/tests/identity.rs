@@ -0,0 +1,168 @@
+use std::fs;
+
+use identity::{
+    fingerprint, matches_pin, unix_time_ms, verify_challenge, DeregistrationClaims,
+    HeartbeatClaims, IdentityError, IdentityKey, RegistrationClaims,
+};
+
+fn claims<'a>(key: &'a [u8], architectures: &'a [String]) -> RegistrationClaims<'a> {
+    RegistrationClaims {
+        data_center_id: "dc-1",
+        host_name: "[::1]:50052",
+        region: "eu",
+        labels: vec![("tier", "gold"), ("disk", "ssd")],
+        services: &[0, 1],
+        architectures,
+        public_key: key,
+        signed_at_unix_ms: unix_time_ms(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn keys_are_generated_once_and_reloaded() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("keys").join("identity.key");
+
+    let key = IdentityKey::load_or_generate(&path).expect("Should generate key");
+    let reloaded = IdentityKey::load_or_generate(&path).expect("Should reload key");
+    assert_eq!(key.public_key(), reloaded.public_key());
+    assert_eq!(key.fingerprint(), fingerprint(&reloaded.public_key()));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    fs::write(&path, "not a key").unwrap();
+    assert!(matches!(
+        IdentityKey::load_or_generate(&path),
+        Err(IdentityError::InvalidKeyFile(_))
+    ));
+}
+
+#[test]
+fn registrations_verify_only_unchanged_and_recent() {
+    let key = IdentityKey::generate();
+    let public_key = key.public_key();
+    let architectures = vec![String::from("x86_64")];
+    let claims = claims(&public_key, &architectures);
+    let signature = key.sign_registration(&claims);
+
+    claims
+        .verify(&signature, unix_time_ms())
+        .expect("Should verify signed claims");
+
+    let reordered = RegistrationClaims {
+        labels: vec![("disk", "ssd"), ("tier", "gold")],
+        ..claims.clone()
+    };
+    reordered
+        .verify(&signature, unix_time_ms())
+        .expect("Should not depend on the order of labels");
+
+    let moved = RegistrationClaims {
+        host_name: "attacker:50052",
+        ..claims.clone()
+    };
+    assert!(matches!(
+        moved.verify(&signature, unix_time_ms()),
+        Err(IdentityError::InvalidSignature)
+    ));
+
+    let other = IdentityKey::generate().public_key();
+    let impersonated = RegistrationClaims {
+        public_key: &other,
+        ..claims.clone()
+    };
+    assert!(matches!(
+        impersonated.verify(&signature, unix_time_ms()),
+        Err(IdentityError::InvalidSignature)
+    ));
+
+    assert!(matches!(
+        claims.verify(&signature, claims.signed_at_unix_ms + 3_600_000),
+        Err(IdentityError::Stale { .. })
+    ));
+}
+
+#[test]
+fn deregistrations_verify_only_with_the_bound_key() {
+    let key = IdentityKey::generate();
+    let claims = DeregistrationClaims {
+        data_center_id: "dc-1",
+        signed_at_unix_ms: unix_time_ms(),
+    };
+    let signature = key.sign_deregistration(&claims);
+
+    claims
+        .verify(&key.public_key(), &signature, unix_time_ms())
+        .expect("Should verify signed claims");
+
+    let other = DeregistrationClaims {
+        data_center_id: "dc-2",
+        ..claims.clone()
+    };
+    assert!(matches!(
+        other.verify(&key.public_key(), &signature, unix_time_ms()),
+        Err(IdentityError::InvalidSignature)
+    ));
+    assert!(matches!(
+        claims.verify(
+            &IdentityKey::generate().public_key(),
+            &signature,
+            unix_time_ms()
+        ),
+        Err(IdentityError::InvalidSignature)
+    ));
+    assert!(matches!(
+        claims.verify(
+            &key.public_key(),
+            &signature,
+            claims.signed_at_unix_ms + 3_600_000
+        ),
+        Err(IdentityError::Stale { .. })
+    ));
+}
+
+#[test]
+fn heartbeats_are_not_taken_for_deregistrations() {
+    let key = IdentityKey::generate();
+    let heartbeat = HeartbeatClaims {
+        data_center_id: "dc-1",
+        signed_at_unix_ms: unix_time_ms(),
+    };
+    let signature = key.sign_heartbeat(&heartbeat);
+
+    heartbeat
+        .verify(&key.public_key(), &signature, unix_time_ms())
+        .expect("Should verify signed heartbeat");
+
+    let deregistration = DeregistrationClaims {
+        data_center_id: heartbeat.data_center_id,
+        signed_at_unix_ms: heartbeat.signed_at_unix_ms,
+    };
+    assert!(matches!(
+        deregistration.verify(&key.public_key(), &signature, unix_time_ms()),
+        Err(IdentityError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn challenges_and_pins_name_the_key() {
+    let key = IdentityKey::generate();
+    let signature = key.sign_challenge("dc-1", b"nonce");
+
+    verify_challenge(&key.public_key(), "dc-1", b"nonce", &signature)
+        .expect("Should verify challenge");
+    assert!(verify_challenge(&key.public_key(), "dc-2", b"nonce", &signature).is_err());
+
+    assert!(matches_pin(&key.fingerprint(), &key.public_key()));
+    assert!(!matches_pin(
+        &IdentityKey::generate().fingerprint(),
+        &key.public_key()
+    ));
+}
@@ -0,0 +1,207 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, StatusCode,
+};
+pub use prometheus;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use tonic::Code;
+use tower::{Layer, Service};
+
+/// Path the metrics are served on
+pub const METRICS_PATH: &str = "/metrics";
+/// Metadata key grpc status codes are sent under, in the headers of calls failing before they
+/// answered
+const GRPC_STATUS_KEY: &str = "grpc-status";
+
+/// Metrics of a service, exposed to prometheus in its text format
+pub struct Metrics {
+    registry: Registry,
+    /// Calls handled by service, method and grpc status code
+    handled: IntCounterVec,
+    /// Time taken to answer calls by service and method
+    handling_seconds: HistogramVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    /// Creates a registry holding the metrics of the grpc calls served through `layer`
+    pub fn new() -> Metrics {
+        let registry = Registry::new();
+        let handled = IntCounterVec::new(
+            Opts::new(
+                "grpc_server_handled_total",
+                "Calls handled by the server, by service, method and status code",
+            ),
+            &["grpc_service", "grpc_method", "grpc_code"],
+        )
+        .expect("Should create handled counter");
+        let handling_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "grpc_server_handling_seconds",
+                "Time the server took to answer calls, by service and method",
+            ),
+            &["grpc_service", "grpc_method"],
+        )
+        .expect("Should create handling histogram");
+        registry
+            .register(Box::new(handled.clone()))
+            .expect("Should register handled counter");
+        registry
+            .register(Box::new(handling_seconds.clone()))
+            .expect("Should register handling histogram");
+
+        Metrics {
+            registry,
+            handled,
+            handling_seconds,
+        }
+    }
+
+    /// Registry further metrics of the service are registered in
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Every registered metric in the prometheus text format
+    pub fn encode(&self) -> String {
+        let mut encoded = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut encoded)
+            .expect("Should encode metrics");
+
+        String::from_utf8(encoded).expect("Metrics should be utf-8")
+    }
+
+    fn observe(&self, path: &str, code: Code, started: Instant) {
+        let (service, method) = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .unwrap_or(("", path));
+        self.handled
+            .with_label_values(&[service, method, &format!("{code:?}")])
+            .inc();
+        self.handling_seconds
+            .with_label_values(&[service, method])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Layer recording the calls of the grpc server it wraps in `metrics`. Calls are timed until
+/// they answer, which for streaming calls is when the stream starts, and streams failing after
+/// they started are counted with the code they started with
+#[derive(Clone)]
+pub struct MetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: Arc<Metrics>) -> MetricsLayer {
+        MetricsLayer { metrics }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> MetricsService<S> {
+        MetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, RequestBody, ResponseBody> Service<http::Request<RequestBody>> for MetricsService<S>
+where
+    S: Service<http::Request<RequestBody>, Response = http::Response<ResponseBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(context)
+    }
+
+    fn call(&mut self, request: http::Request<RequestBody>) -> Self::Future {
+        let path = String::from(request.uri().path());
+        let started = Instant::now();
+        let metrics = self.metrics.clone();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+            let code = match &response {
+                Ok(response) => response
+                    .headers()
+                    .get(GRPC_STATUS_KEY)
+                    .and_then(|code| code.to_str().ok())
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .map_or(Code::Ok, Code::from),
+                Err(_) => Code::Unknown,
+            };
+            metrics.observe(&path, code, started);
+
+            response
+        })
+    }
+}
+
+/// Serves the metrics over http on `address` until the server fails
+pub async fn serve(metrics: Arc<Metrics>, address: SocketAddr) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let metrics = metrics.clone();
+
+                async move { Ok::<_, Infallible>(respond(&metrics, &request)) }
+            }))
+        }
+    });
+
+    hyper::Server::try_bind(&address)?.serve(make_service).await
+}
+
+fn respond(metrics: &Metrics, request: &hyper::Request<Body>) -> hyper::Response<Body> {
+    let mut response = hyper::Response::new(Body::empty());
+
+    if request.uri().path() != METRICS_PATH {
+        *response.status_mut() = StatusCode::NOT_FOUND;
+    } else if request.method() != Method::GET {
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+    } else {
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(prometheus::TEXT_FORMAT),
+        );
+        *response.body_mut() = Body::from(metrics.encode());
+    }
+
+    response
+}
@@ -0,0 +1,85 @@
+use std::{convert::Infallible, net::TcpListener, sync::Arc, time::Duration};
+
+use hyper::{body, Client, StatusCode, Uri};
+use metrics::{Metrics, MetricsLayer, METRICS_PATH};
+use tower::{service_fn, Layer, ServiceExt};
+
+async fn call(layer: &MetricsLayer, path: &str, grpc_status: Option<&'static str>) {
+    let service = layer.layer(service_fn(|_: http::Request<()>| async move {
+        let mut response = http::Response::builder();
+
+        if let Some(grpc_status) = grpc_status {
+            response = response.header("grpc-status", grpc_status);
+        }
+
+        Ok::<_, Infallible>(response.body(()).unwrap())
+    }));
+
+    service
+        .oneshot(http::Request::builder().uri(path).body(()).unwrap())
+        .await
+        .expect("Should call service");
+}
+
+#[tokio::test]
+async fn calls_are_counted_by_method_and_code() {
+    let metrics = Arc::new(Metrics::new());
+    let layer = MetricsLayer::new(metrics.clone());
+
+    call(&layer, "/data_center.DataCenter/StartInstance", None).await;
+    call(&layer, "/data_center.DataCenter/StartInstance", None).await;
+    call(&layer, "/data_center.DataCenter/StopInstance", Some("5")).await;
+
+    let encoded = metrics.encode();
+    assert!(encoded.contains(
+        r#"grpc_server_handled_total{grpc_code="Ok",grpc_method="StartInstance",grpc_service="data_center.DataCenter"} 2"#
+    ));
+    assert!(encoded.contains(
+        r#"grpc_server_handled_total{grpc_code="NotFound",grpc_method="StopInstance",grpc_service="data_center.DataCenter"} 1"#
+    ));
+    assert!(encoded.contains(
+        r#"grpc_server_handling_seconds_count{grpc_method="StartInstance",grpc_service="data_center.DataCenter"} 2"#
+    ));
+}
+
+#[tokio::test]
+async fn metrics_are_served_over_http() {
+    let metrics = Arc::new(Metrics::new());
+    call(
+        &MetricsLayer::new(metrics.clone()),
+        "/resolver.DcnsResolver/ListDataCenters",
+        None,
+    )
+    .await;
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Should find free port");
+    tokio::spawn(metrics::serve(metrics, address));
+
+    let client = Client::new();
+    let uri = |path: &str| -> Uri { format!("http://{address}{path}").parse().unwrap() };
+    let mut response = None;
+
+    for _ in 0..50 {
+        match client.get(uri(METRICS_PATH)).await {
+            Ok(served) => {
+                response = Some(served);
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+
+    let response = response.expect("Should serve metrics");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body())
+        .await
+        .expect("Should read metrics");
+    assert!(String::from_utf8_lossy(&body).contains(r#"grpc_method="ListDataCenters""#));
+
+    let missing = client
+        .get(uri("/other"))
+        .await
+        .expect("Should answer other paths");
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
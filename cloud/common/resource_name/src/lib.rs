@@ -0,0 +1,187 @@
+use std::{fmt, str::FromStr};
+
+const DATA_CENTER_PREFIX: &str = "dc";
+
+/// Kinds of resources data centers hold
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Machine,
+    Instance,
+    Image,
+}
+
+impl ResourceKind {
+    /// Name of the collection resources of this kind are named under
+    pub fn collection(&self) -> &'static str {
+        match self {
+            ResourceKind::Machine => "machines",
+            ResourceKind::Instance => "instances",
+            ResourceKind::Image => "images",
+        }
+    }
+
+    fn from_collection(collection: &str) -> Option<ResourceKind> {
+        match collection {
+            "machines" => Some(ResourceKind::Machine),
+            "instances" => Some(ResourceKind::Instance),
+            "images" => Some(ResourceKind::Image),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResourceKind::Machine => "machine",
+            ResourceKind::Instance => "instance",
+            ResourceKind::Image => "image",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResourceNameError {
+    /// Name doesn't follow the dc/<data center id>/<collection>/<id> layout
+    Malformed(String),
+    /// Name refers to a collection that doesn't exist
+    UnknownCollection(String),
+    /// Segment of the name is empty or holds characters other than letters, digits, `-`, `_`
+    /// and `.`
+    InvalidSegment(String),
+    /// Name refers to a different kind of resource than expected
+    UnexpectedKind {
+        expected: ResourceKind,
+        found: ResourceKind,
+    },
+}
+
+impl fmt::Display for ResourceNameError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceNameError::Malformed(name) => write!(
+                formatter,
+                "Resource name {name} should be formatted as dc/<data center id>/<collection>/<id>"
+            ),
+            ResourceNameError::UnknownCollection(collection) => write!(
+                formatter,
+                "Unknown collection {collection}, expected machines, instances or images"
+            ),
+            ResourceNameError::InvalidSegment(segment) => write!(
+                formatter,
+                "Invalid segment {segment:?}, segments must be non empty and only hold letters, \
+                 digits, '-', '_' and '.'"
+            ),
+            ResourceNameError::UnexpectedKind { expected, found } => {
+                write!(
+                    formatter,
+                    "Expected the name of a resource of kind {expected}, found one of kind {found}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceNameError {}
+
+/// Name of a resource that encodes the data center owning it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceName {
+    data_center_id: String,
+    kind: ResourceKind,
+    id: String,
+}
+
+impl ResourceName {
+    pub fn new(
+        data_center_id: &str,
+        kind: ResourceKind,
+        id: &str,
+    ) -> Result<ResourceName, ResourceNameError> {
+        validate_segment(data_center_id)?;
+        validate_segment(id)?;
+
+        Ok(ResourceName {
+            data_center_id: String::from(data_center_id),
+            kind,
+            id: String::from(id),
+        })
+    }
+
+    /// Parses a name, checking it refers to a resource of the `expected` kind
+    pub fn parse_kind(
+        name: &str,
+        expected: ResourceKind,
+    ) -> Result<ResourceName, ResourceNameError> {
+        let name: ResourceName = name.parse()?;
+
+        if name.kind != expected {
+            return Err(ResourceNameError::UnexpectedKind {
+                expected,
+                found: name.kind,
+            });
+        }
+
+        Ok(name)
+    }
+
+    /// Id of the data center owning the resource
+    pub fn data_center_id(&self) -> &str {
+        &self.data_center_id
+    }
+
+    pub fn kind(&self) -> ResourceKind {
+        self.kind
+    }
+
+    /// Id of the resource within its data center
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl FromStr for ResourceName {
+    type Err = ResourceNameError;
+
+    fn from_str(name: &str) -> Result<ResourceName, ResourceNameError> {
+        let segments: Vec<&str> = name.split('/').collect();
+        let [DATA_CENTER_PREFIX, data_center_id, collection, id] = segments.as_slice() else {
+            return Err(ResourceNameError::Malformed(String::from(name)));
+        };
+        let Some(kind) = ResourceKind::from_collection(collection) else {
+            return Err(ResourceNameError::UnknownCollection(String::from(
+                *collection,
+            )));
+        };
+
+        ResourceName::new(data_center_id, kind, id)
+    }
+}
+
+impl fmt::Display for ResourceName {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{DATA_CENTER_PREFIX}/{}/{}/{}",
+            self.data_center_id,
+            self.kind.collection(),
+            self.id
+        )
+    }
+}
+
+/// Checks `segment` can be used as a data center or resource id within a name
+pub fn validate_segment(segment: &str) -> Result<(), ResourceNameError> {
+    let valid = !segment.is_empty()
+        && segment
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "-_.".contains(character));
+
+    if !valid {
+        return Err(ResourceNameError::InvalidSegment(String::from(segment)));
+    }
+
+    Ok(())
+}
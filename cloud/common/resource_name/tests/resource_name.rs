@@ -0,0 +1,70 @@
+use resource_name::{validate_segment, ResourceKind, ResourceName, ResourceNameError};
+
+#[test]
+fn names_round_trip() {
+    let name = ResourceName::new("dc-a", ResourceKind::Machine, "V1StGXR8_Z5jdHi6B-myT")
+        .expect("Should create name");
+
+    assert_eq!(name.to_string(), "dc/dc-a/machines/V1StGXR8_Z5jdHi6B-myT");
+    assert_eq!(name.to_string().parse::<ResourceName>(), Ok(name));
+}
+
+#[test]
+fn names_expose_their_parts() {
+    let name: ResourceName = "dc/eu-west.1/instances/abc"
+        .parse()
+        .expect("Should parse name");
+
+    assert_eq!(name.data_center_id(), "eu-west.1");
+    assert_eq!(name.kind(), ResourceKind::Instance);
+    assert_eq!(name.id(), "abc");
+}
+
+#[test]
+fn malformed_names_are_rejected() {
+    for name in [
+        "",
+        "abc",
+        "dc/dc-a/machines",
+        "dc/dc-a/machines/abc/extra",
+        "datacenter/dc-a/machines/abc",
+    ] {
+        assert_eq!(
+            name.parse::<ResourceName>(),
+            Err(ResourceNameError::Malformed(String::from(name)))
+        );
+    }
+}
+
+#[test]
+fn unknown_collections_are_rejected() {
+    assert_eq!(
+        "dc/dc-a/disks/abc".parse::<ResourceName>(),
+        Err(ResourceNameError::UnknownCollection(String::from("disks")))
+    );
+}
+
+#[test]
+fn invalid_segments_are_rejected() {
+    assert_eq!(
+        "dc//machines/abc".parse::<ResourceName>(),
+        Err(ResourceNameError::InvalidSegment(String::new()))
+    );
+    assert_eq!(
+        "dc/dc a/machines/abc".parse::<ResourceName>(),
+        Err(ResourceNameError::InvalidSegment(String::from("dc a")))
+    );
+    assert!(validate_segment("dc:a").is_err());
+    assert!(validate_segment("dc_a-1.eu").is_ok());
+}
+
+#[test]
+fn names_of_the_wrong_kind_are_rejected() {
+    assert_eq!(
+        ResourceName::parse_kind("dc/dc-a/images/abc", ResourceKind::Machine),
+        Err(ResourceNameError::UnexpectedKind {
+            expected: ResourceKind::Machine,
+            found: ResourceKind::Image,
+        })
+    );
+}
@@ -0,0 +1,338 @@
+use std::{
+    fmt,
+    future::Future,
+    io::IsTerminal,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::{TraceContextExt, TraceError, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{self, TracerProvider},
+    Resource,
+};
+use tonic::{metadata::MetadataMap, service::Interceptor, Code, Request, Status};
+use tower::{Layer, Service};
+use tracing::{field, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer as _,
+};
+
+/// Variable the log filter is read from, in the syntax of `tracing_subscriber::EnvFilter`
+pub const LOG_FILTER_VARIABLE: &str = "RUST_LOG";
+/// Metadata key grpc status codes are sent under, in the headers of calls failing before they
+/// answered
+const GRPC_STATUS_KEY: &str = "grpc-status";
+
+/// Format logs are written to standard error in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One json object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<LogFormat, String> {
+        match format {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "Unknown log format {format}, expected text or json"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(formatter, "text"),
+            LogFormat::Json => write!(formatter, "json"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TelemetryError {
+    Exporter(TraceError),
+    Subscriber(tracing_subscriber::util::TryInitError),
+}
+
+impl fmt::Display for TelemetryError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TelemetryError::Exporter(error) => {
+                write!(formatter, "Failed to create otlp exporter: {error}")
+            }
+            TelemetryError::Subscriber(error) => {
+                write!(formatter, "Failed to install log subscriber: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TelemetryError {}
+
+/// Logs and traces of a process, configured before being installed with `init`
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    service_name: &'static str,
+    log_format: LogFormat,
+    default_filter: &'static str,
+    otlp_endpoint: Option<String>,
+}
+
+impl TelemetryConfig {
+    /// Logs at info level as text, exporting no spans
+    pub fn new(service_name: &'static str) -> TelemetryConfig {
+        TelemetryConfig {
+            service_name,
+            log_format: LogFormat::Text,
+            default_filter: "info",
+            otlp_endpoint: None,
+        }
+    }
+
+    pub fn with_log_format(mut self, log_format: LogFormat) -> TelemetryConfig {
+        self.log_format = log_format;
+
+        self
+    }
+
+    /// Filters logs with `default_filter` when `RUST_LOG` isn't set
+    pub fn with_default_filter(mut self, default_filter: &'static str) -> TelemetryConfig {
+        self.default_filter = default_filter;
+
+        self
+    }
+
+    /// Exports spans over otlp to the collector at `otlp_endpoint`, e.g.
+    /// `http://localhost:4317`, when given
+    pub fn with_otlp_endpoint(mut self, otlp_endpoint: Option<String>) -> TelemetryConfig {
+        self.otlp_endpoint = otlp_endpoint;
+
+        self
+    }
+
+    /// Installs the subscriber writing logs to standard error and the propagator carrying trace
+    /// context through grpc metadata. Spans are tracked whether or not they are exported, so
+    /// the trace context of calls is always propagated
+    pub fn init(self) -> Result<Telemetry, TelemetryError> {
+        let mut provider =
+            TracerProvider::builder().with_config(trace::config().with_resource(Resource::new([
+                KeyValue::new("service.name", self.service_name),
+            ])));
+
+        if let Some(otlp_endpoint) = &self.otlp_endpoint {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(otlp_endpoint)
+                .build_span_exporter()
+                .map_err(TelemetryError::Exporter)?;
+            provider = provider.with_batch_exporter(exporter, runtime::Tokio);
+        }
+
+        let provider = provider.build();
+        let tracer = provider.tracer(self.service_name);
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        global::set_tracer_provider(provider);
+
+        let filter = EnvFilter::try_from_env(LOG_FILTER_VARIABLE)
+            .unwrap_or_else(|_| EnvFilter::new(self.default_filter));
+        let logs = match self.log_format {
+            LogFormat::Text => tracing_subscriber::fmt::layer()
+                .with_ansi(std::io::stderr().is_terminal())
+                .with_writer(std::io::stderr)
+                .boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_writer(std::io::stderr)
+                .boxed(),
+        };
+        // Spans are filtered apart from logs, so quiet logs still leave calls traced
+        let spans = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(LevelFilter::INFO);
+
+        tracing_subscriber::registry()
+            .with(logs.with_filter(filter))
+            .with(spans)
+            .try_init()
+            .map_err(TelemetryError::Subscriber)?;
+
+        Ok(Telemetry {
+            exporting: self.otlp_endpoint.is_some(),
+        })
+    }
+}
+
+/// Installed telemetry, flushing the spans not exported yet when dropped
+#[must_use = "Spans not exported yet are flushed when the telemetry is dropped"]
+pub struct Telemetry {
+    exporting: bool,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if self.exporting {
+            global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Id of the trace `span` belongs to, `None` when spans aren't tracked
+pub fn trace_id(span: &Span) -> Option<String> {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Interceptor sending the trace context of the current span with every request of a client,
+/// before passing requests on to `inner`
+#[derive(Clone, Debug, Default)]
+pub struct Traced<I> {
+    inner: I,
+}
+
+impl<I> Traced<I> {
+    pub fn new(inner: I) -> Traced<I> {
+        Traced { inner }
+    }
+}
+
+impl<I: Interceptor> Interceptor for Traced<I> {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        inject(request.metadata_mut());
+
+        self.inner.call(request)
+    }
+}
+
+/// Writes the trace context of the current span to `metadata`
+pub fn inject(metadata: &mut MetadataMap) {
+    let context = Span::current().context();
+
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MetadataInjector(metadata))
+    });
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (
+            key.parse::<tonic::metadata::AsciiMetadataKey>(),
+            value.parse(),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a http::HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Layer serving every call of the grpc server it wraps within a span, continuing the trace
+/// the caller sent, and logging the code each call answered with
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceLayer;
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = TraceService<S>;
+
+    fn layer(&self, inner: S) -> TraceService<S> {
+        TraceService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TraceService<S> {
+    inner: S,
+}
+
+impl<S, RequestBody, ResponseBody> Service<http::Request<RequestBody>> for TraceService<S>
+where
+    S: Service<http::Request<RequestBody>, Response = http::Response<ResponseBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(context)
+    }
+
+    fn call(&mut self, request: http::Request<RequestBody>) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(request.headers()))
+        });
+        let span = tracing::info_span!(
+            "rpc",
+            rpc = request.uri().path(),
+            otel.name = request.uri().path(),
+            trace_id = field::Empty,
+        );
+        span.set_parent(parent);
+
+        if let Some(trace_id) = trace_id(&span) {
+            span.record("trace_id", trace_id);
+        }
+
+        let started = Instant::now();
+        let response = span.in_scope(|| self.inner.call(request));
+
+        Box::pin(
+            async move {
+                let response = response.await;
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+
+                match &response {
+                    Ok(response) => {
+                        let code = response
+                            .headers()
+                            .get(GRPC_STATUS_KEY)
+                            .and_then(|code| code.to_str().ok())
+                            .and_then(|code| code.parse::<i32>().ok())
+                            .map_or(Code::Ok, Code::from);
+                        tracing::info!(code = ?code, elapsed_ms, "Answered call");
+                    }
+                    Err(_) => tracing::warn!(elapsed_ms, "Failed to answer call"),
+                }
+
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,66 @@
+use std::convert::Infallible;
+
+use telemetry::{LogFormat, TelemetryConfig, TraceLayer, Traced};
+use tonic::{service::Interceptor, Request};
+use tower::{service_fn, Layer, ServiceExt};
+use tracing::Span;
+
+/// Interceptor passing requests on untouched
+#[derive(Clone, Default)]
+struct PassThrough;
+
+impl Interceptor for PassThrough {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, tonic::Status> {
+        Ok(request)
+    }
+}
+
+#[test]
+fn log_formats_are_parsed() {
+    assert_eq!("json".parse(), Ok(LogFormat::Json));
+    assert_eq!("text".parse(), Ok(LogFormat::Text));
+    assert!("yaml".parse::<LogFormat>().is_err());
+}
+
+#[tokio::test]
+async fn trace_context_is_carried_from_clients_to_servers() {
+    let _telemetry = TelemetryConfig::new("telemetry_test")
+        .init()
+        .expect("Should install telemetry");
+    let span = tracing::info_span!("client");
+    let client_trace_id = telemetry::trace_id(&span).expect("Should track span");
+    let request = {
+        let _entered = span.enter();
+
+        Traced::new(PassThrough)
+            .call(Request::new(()))
+            .expect("Should intercept request")
+    };
+    let traceparent = request
+        .metadata()
+        .get("traceparent")
+        .expect("Should send trace context")
+        .to_str()
+        .unwrap();
+    assert!(traceparent.contains(&client_trace_id));
+
+    let mut http_request = http::Request::builder()
+        .uri("/data_center.DataCenter/ListInstances")
+        .body(())
+        .unwrap();
+    *http_request.headers_mut() = request.metadata().clone().into_headers();
+    let service = TraceLayer.layer(service_fn(|_: http::Request<()>| async {
+        let server_trace_id = telemetry::trace_id(&Span::current());
+
+        Ok::<_, Infallible>(http::Response::new(server_trace_id))
+    }));
+    let response = service
+        .oneshot(http_request)
+        .await
+        .expect("Should call service");
+
+    assert_eq!(response.into_body(), Some(client_trace_id));
+
+    let orphan = tracing::info_span!("orphan");
+    assert_ne!(telemetry::trace_id(&orphan), telemetry::trace_id(&span));
+}
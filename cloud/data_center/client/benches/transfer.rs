@@ -0,0 +1,91 @@
+//! Compares uploading and downloading a file over a single stream with splitting it into ranges
+//! sent over concurrent streams, against a data center on loopback
+
+use std::{fs, path::Path, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use data_center_client::sdk::{DataCenterSdk, TransferOptions};
+use data_center_service::{
+    data_center::LocalDataCenter, protos::data_center::data_center_server::DataCenterServer,
+};
+use tokio::{net::TcpListener, runtime::Runtime};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+
+const FILE_SIZE: usize = 64 * 1048576;
+const PARALLELISM: [usize; 3] = [1, 4, 8];
+
+fn start_data_center(runtime: &Runtime) -> DataCenterSdk {
+    runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Should bind listener");
+        let address = listener.local_addr().expect("Should have address");
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(DataCenterServer::new(LocalDataCenter::new(String::from(
+                    "bench",
+                ))))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .expect("Should serve data center");
+        });
+
+        DataCenterSdk::connect(&address.to_string()).expect("Should create sdk")
+    })
+}
+
+fn storage_path(directory: &Path, name: &str) -> String {
+    String::from(directory.join(name).to_str().expect("Should be utf-8"))
+}
+
+fn transfer(criterion: &mut Criterion) {
+    let runtime = Runtime::new().expect("Should create runtime");
+    let sdk = start_data_center(&runtime);
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let local_path = directory.path().join("local");
+    let stored_path = storage_path(directory.path(), "stored");
+    let downloaded_path = directory.path().join("downloaded");
+    fs::write(&local_path, vec![1; FILE_SIZE]).expect("Should write file");
+    runtime
+        .block_on(sdk.upload_file(&local_path, &stored_path, &TransferOptions::default()))
+        .expect("Should upload file");
+
+    let mut group = criterion.benchmark_group("transfer");
+    group
+        .throughput(Throughput::Bytes(FILE_SIZE as u64))
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10));
+
+    for parallelism in PARALLELISM {
+        let options = TransferOptions::default().with_parallelism(parallelism);
+
+        group.bench_with_input(
+            BenchmarkId::new("upload", parallelism),
+            &options,
+            |bencher, options| {
+                bencher.to_async(&runtime).iter(|| async {
+                    sdk.upload_file(&local_path, &stored_path, options)
+                        .await
+                        .expect("Should upload file")
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("download", parallelism),
+            &options,
+            |bencher, options| {
+                bencher.to_async(&runtime).iter(|| async {
+                    sdk.download_file(&stored_path, &downloaded_path, options)
+                        .await
+                        .expect("Should download file")
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, transfer);
+criterion_main!(benches);
@@ -1,11 +1,46 @@
 use clap::{Args, Parser, Subcommand};
+use cli_output::OutputFormat;
+
+use crate::protos::{audit::ListAuditEventsRequest, data_center::Role};
 
 #[derive(Debug, Parser)]
 #[command(name = "datacenter")]
 #[command(about = "Data center", long_about = None)]
 pub struct Cli {
-    /// Host name of the data center
-    pub host_name: String,
+    /// Host name of the data center, or of a resolver to have it route calls to data centers.
+    /// Defaults to the host of the selected profile
+    #[arg(long, global = true, env = "DATACENTER_HOST")]
+    pub host: Option<String>,
+    /// Profile of the config file to take settings from
+    #[arg(long, global = true, env = "DECENTRALIZED_CLOUD_PROFILE")]
+    pub profile: Option<String>,
+    /// Format listed and fetched resources are printed in, table, json or yaml. Defaults to the
+    /// output of the selected profile, then to table
+    #[arg(long, global = true)]
+    pub output: Option<OutputFormat>,
+    /// Most streams a file is uploaded or downloaded over at once, each carrying a range of the
+    /// file
+    #[arg(
+        long,
+        global = true,
+        env = "DATACENTER_PARALLELISM",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub parallelism: u16,
+    /// Project resources are created in and listed from. Defaults to the project of the
+    /// selected profile, then to the data center's default project and to every project the
+    /// caller can view when listing
+    #[arg(long, global = true, env = "DATACENTER_PROJECT")]
+    pub project: Option<String>,
+    /// Fingerprint or base64 public key the data center must prove it holds before any call is
+    /// made, see `datacenter identity`. Defaults to the data_center_key of the selected profile
+    #[arg(long, global = true, env = "DATACENTER_KEY")]
+    pub data_center_key: Option<String>,
+    /// Otlp collector the spans of the command are exported to over grpc, e.g.
+    /// http://localhost:4317. Calls carry the trace context to the services either way
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -18,6 +53,158 @@ pub enum Commands {
     Compute(ComputeArguments),
     Storage(StorageArguments),
     Os(OperatingSystemArguments),
+    /// Read and change the settings of the selected profile
+    Config(ConfigArguments),
+    /// Manage the projects of the data center and the roles callers hold in them
+    Project(ProjectArguments),
+    /// Check a bearer token against the host and store it in the selected profile
+    Login(LoginArguments),
+    /// Remove the bearer token from the selected profile
+    Logout,
+    /// Show the data center the host is and the key it proves it holds
+    Identity,
+    /// List the mutating calls recorded in the audit log of the data center, printed as json
+    /// lines with --output json
+    Audit(AuditArguments),
+}
+
+#[derive(Debug, Args)]
+pub struct AuditArguments {
+    /// Only list calls made at or after this time in milliseconds since the unix epoch
+    #[arg(long, default_value_t = 0)]
+    pub since: u64,
+    /// Only list calls made before this time in milliseconds since the unix epoch
+    #[arg(long)]
+    pub until: Option<u64>,
+    /// Only list calls naming this resource, by full name or bare id
+    #[arg(long)]
+    pub resource: Option<String>,
+    /// Only list calls made by this subject
+    #[arg(long)]
+    pub subject: Option<String>,
+    /// Only list calls of this rpc, e.g. StopInstance
+    #[arg(long)]
+    pub rpc: Option<String>,
+    /// Most calls to list
+    #[arg(long)]
+    pub limit: Option<u32>,
+}
+
+impl AuditArguments {
+    pub fn request(self) -> ListAuditEventsRequest {
+        ListAuditEventsRequest {
+            since_unix_ms: self.since,
+            until_unix_ms: self.until.unwrap_or_default(),
+            resource_id: self.resource.unwrap_or_default(),
+            subject: self.subject.unwrap_or_default(),
+            rpc: self.rpc.unwrap_or_default(),
+            after_sequence: 0,
+            limit: self.limit.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct LoginArguments {
+    /// Token or api key to log in with, read from stdin when not provided
+    #[arg(long, env = "DATACENTER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ProjectArguments {
+    #[command(subcommand)]
+    pub project: ProjectCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProjectCommands {
+    /// Create a project, making yourself its admin
+    Create(ProjectIdArguments),
+    /// List the projects you hold a role in
+    List,
+    /// Delete a project holding no resources
+    Delete(ProjectIdArguments),
+    /// Grant a role in a project, replacing the role the subject held in it
+    Grant(GrantArguments),
+    /// Remove the role a subject holds in a project
+    Revoke(RevokeArguments),
+    /// List who holds which role in a project
+    Bindings(ProjectIdArguments),
+    /// Show what a project uses of its quota
+    Quota(ProjectIdArguments),
+    /// Change the quota of a project, keeping the limits that aren't passed
+    SetQuota(SetQuotaArguments),
+}
+
+#[derive(Debug, Args)]
+pub struct ProjectIdArguments {
+    pub project_id: String,
+}
+
+#[derive(Debug, Args)]
+pub struct GrantArguments {
+    pub project_id: String,
+    /// Subject of the tokens or api keys the role is granted to
+    pub subject: String,
+    /// viewer, operator or admin
+    #[arg(value_parser = parse_role)]
+    pub role: Role,
+}
+
+#[derive(Debug, Args)]
+pub struct SetQuotaArguments {
+    pub project_id: String,
+    /// Most vcpus the started instances of the project may use
+    #[arg(long)]
+    pub vcpus: Option<u32>,
+    /// Most ram in mb the started instances of the project may use
+    #[arg(long)]
+    pub ram_mb: Option<u32>,
+    /// Most disk in mb the machines of the project may use
+    #[arg(long)]
+    pub disk_mb: Option<u32>,
+    /// Most instances of the project started at once
+    #[arg(long)]
+    pub instances: Option<u32>,
+    /// Most bytes of files and images the project may store
+    #[arg(long)]
+    pub storage_bytes: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct RevokeArguments {
+    pub project_id: String,
+    pub subject: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArguments {
+    #[command(subcommand)]
+    pub config: ConfigCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    /// Set a setting of the profile, creating the profile if needed. An empty value removes it
+    Set(ConfigSetArguments),
+    /// Print a setting of the profile
+    Get(ConfigGetArguments),
+    /// List the settings of every profile
+    List,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigSetArguments {
+    /// host, resolvers, output, token, project, data_center_key, tls.ca_certificate,
+    /// tls.certificate or tls.key
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigGetArguments {
+    pub key: String,
 }
 
 #[derive(Debug, Args)]
@@ -77,7 +264,7 @@ pub enum UpCommands {
 #[derive(Debug, Args)]
 pub struct UpLocalImageArguments {
     pub local_path: String,
-    pub storage_path: String
+    pub storage_path: String,
 }
 
 #[derive(Debug, Args)]
@@ -171,6 +358,17 @@ pub struct DownloadImageArguments {
     pub destination_path: String,
 }
 
+fn parse_role(role: &str) -> Result<Role, String> {
+    match role {
+        "viewer" => Ok(Role::Viewer),
+        "operator" => Ok(Role::Operator),
+        "admin" => Ok(Role::Admin),
+        _ => Err(format!(
+            "Unknown role {role}, expected viewer, operator or admin"
+        )),
+    }
+}
+
 pub fn parse_cli() -> Cli {
     Cli::parse()
 }
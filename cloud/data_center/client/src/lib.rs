@@ -1,2 +1,5 @@
-pub mod protos;
 pub mod cli;
+pub mod output;
+pub mod progress;
+pub mod protos;
+pub mod sdk;
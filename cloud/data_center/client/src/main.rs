@@ -1,469 +1,491 @@
 use std::{
-    fs::{write, File},
-    io::{Read, Seek},
-    ops::Range,
+    io::{stdin, IsTerminal},
+    process::ExitCode,
+    sync::Arc,
 };
 
 use anyhow::{Context, Result};
+use cli_output::OutputFormat;
+use client_config::Config;
 use data_center_client::{
     cli::{
-        parse_cli, Commands, ComputeArguments, ComputeCommands, CreateMachineArguments,
-        DownloadFileArguments, DownloadImageArguments, GetImageMetadataArguments,
-        InstanceArguments, InstanceCommands, MachineArguments, MachineCommands,
-        OperatingSystemArguments, OperatingSystemCommands, ProvisionInstanceArguments,
+        parse_cli, Cli, Commands, ComputeArguments, ComputeCommands, ConfigArguments,
+        ConfigCommands, ConfigGetArguments, ConfigSetArguments, CreateMachineArguments,
+        DownloadFileArguments, DownloadImageArguments, GetImageMetadataArguments, GrantArguments,
+        InstanceArguments, InstanceCommands, LoginArguments, MachineArguments, MachineCommands,
+        OperatingSystemArguments, OperatingSystemCommands, ProjectArguments, ProjectCommands,
+        ProjectIdArguments, ProvisionInstanceArguments, RevokeArguments, SetQuotaArguments,
         StartInstanceArguments, StopInstanceArguments, StorageArguments, StorageCommands,
         UpArguments, UpCommands, UpLocalImageArguments, UploadFileArguments, UploadImageArguments,
     },
-    protos::data_center::{
-        data_center_client::DataCenterClient, Chunk, CreateFileMetadataRequest,
-        CreateImageMetadataRequest, CreateMachineRequest, DownloadFileRequest, FileMetadata,
-        GetFileMetadataRequest, GetImageMetadataRequest, ListImageMetadataRequest,
-        ListInstancesRequest, ListMachinesRequest, ProvisionInstanceRequest, Resources,
-        StartInstanceRequest, StopInstanceRequest, UploadFileRequest,
+    output::{
+        print_audit_events, print_identity, print_image, print_images, print_instances,
+        print_machines, print_projects, print_quota, print_role_bindings, print_settings,
     },
+    progress::{Direction, TransferReporter},
+    protos::data_center::{Quota, Resources},
+    sdk::{DataCenterSdk, SdkError, TransferOptions},
 };
-use tokio_stream::Stream;
-use tonic::{transport::Channel, Request};
-
-const ONE_MB: usize = 1048576;
+use grpc_tls::TlsFiles;
+use telemetry::TelemetryConfig;
+use tracing::Instrument;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = parse_cli();
-    let mut client = DataCenterClient::connect(format!("http://{}", args.host_name)).await?;
+    let _telemetry = match TelemetryConfig::new("datacenter")
+        .with_default_filter("warn")
+        .with_otlp_endpoint(args.otlp_endpoint.clone())
+        .init()
+    {
+        Ok(telemetry) => telemetry,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // Every call the command makes belongs to this trace, which services log calls under
+    let span = tracing::info_span!("datacenter");
+    let Err(error) = run(args).instrument(span.clone()).await else {
+        return ExitCode::SUCCESS;
+    };
+    eprintln!("Error: {error:#}");
 
-    match args.command {
-        Commands::Instance(arguments) => handle_instance_command(arguments, &mut client).await,
-        Commands::Machine(arguments) => handle_machine_command(arguments, &mut client).await,
-        Commands::Compute(arguments) => handle_compute_command(arguments, &mut client).await,
-        Commands::Storage(arguments) => handle_storage_command(arguments, &mut client).await,
-        Commands::Os(arguments) => handle_image_command(arguments, &mut client).await,
+    if let Some(trace_id) = telemetry::trace_id(&span) {
+        eprintln!("Trace id: {trace_id}");
     }
-}
 
-async fn handle_machine_command(
-    arguments: MachineArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    match arguments.machine {
-        MachineCommands::CreateMachine(arguments) => create_machine(arguments, client).await,
-        MachineCommands::ListMachines => list_machines(client).await,
+    match error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<SdkError>())
+    {
+        Some(error) => error
+            .code()
+            .map(cli_output::exit_code)
+            .unwrap_or(ExitCode::FAILURE),
+        None => ExitCode::FAILURE,
     }
 }
 
-async fn handle_instance_command(
-    arguments: InstanceArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    match arguments.instance {
-        InstanceCommands::StopInstance(arguments) => stop_instance(arguments, client).await,
-        InstanceCommands::StartInstance(arguments) => start_instance(arguments, client).await,
-        InstanceCommands::ProvisionInstance(arguments) => {
-            provision_instance(arguments, client).await
+async fn run(args: Cli) -> Result<()> {
+    let mut config = Config::load()?;
+
+    match args.command {
+        Commands::Config(arguments) => {
+            return handle_config_command(
+                arguments,
+                &mut config,
+                args.profile.as_deref(),
+                args.output,
+            );
         }
-        InstanceCommands::ListInstances => list_instances(client).await,
+        Commands::Logout => return set_token(&mut config, args.profile.as_deref(), ""),
+        _ => {}
     }
-}
 
-async fn handle_compute_command(
-    arguments: ComputeArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    match arguments.compute {
-        ComputeCommands::Up(arguments) => handle_up(arguments, client).await,
+    let profile = config.profile(args.profile.as_deref())?;
+    let output = match args.output {
+        Some(output) => output,
+        None => profile
+            .output
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(anyhow::Error::msg)
+            .context("Invalid output in profile")?
+            .unwrap_or_default(),
+    };
+    let host = args
+        .host
+        .or(profile.host)
+        .context("No data center host, pass --host or set host in the profile")?;
+    let tls = TlsFiles {
+        ca_certificate: profile.tls.ca_certificate,
+        certificate: profile.tls.certificate,
+        key: profile.tls.key,
+    };
+    let sdk = DataCenterSdk::connect_with_tls(&host, tls.client_config()?.as_ref())?;
+    let pinned_key = args.data_center_key.or(profile.data_center_key);
+
+    if let Commands::Login(arguments) = args.command {
+        return login(
+            arguments,
+            sdk,
+            pinned_key.as_deref(),
+            &mut config,
+            args.profile.as_deref(),
+        )
+        .await;
     }
-}
 
-async fn handle_up(arguments: UpArguments, client: &mut DataCenterClient<Channel>) -> Result<()> {
-    let resources = Resources {
-        ram_mb: arguments.ram_mb,
-        disk_mb: arguments.disk_mb,
-        vcpus: arguments.vcpus,
+    let sdk = match &profile.token {
+        Some(token) => sdk.with_token(token)?,
+        None => sdk,
     };
 
-    match arguments.up {
-        UpCommands::LocalImage(arguments) => {
-            handle_up_local_image(arguments, resources, client).await
+    if let Some(pinned_key) = &pinned_key {
+        sdk.verify_key(pinned_key)
+            .await
+            .context("Host didn't prove it holds the pinned key")?;
+    }
+
+    let sdk = match args.project.or(profile.project) {
+        Some(project_id) => sdk.with_project(&project_id),
+        None => sdk,
+    };
+    let parallelism = usize::from(args.parallelism);
+
+    match args.command {
+        Commands::Instance(arguments) => handle_instance_command(arguments, &sdk, output).await,
+        Commands::Machine(arguments) => handle_machine_command(arguments, &sdk, output).await,
+        Commands::Compute(arguments) => handle_compute_command(arguments, &sdk, parallelism).await,
+        Commands::Storage(arguments) => handle_storage_command(arguments, &sdk, parallelism).await,
+        Commands::Os(arguments) => handle_image_command(arguments, &sdk, output, parallelism).await,
+        Commands::Project(arguments) => handle_project_command(arguments, &sdk, output).await,
+        Commands::Identity => {
+            print_identity(output, &sdk.prove_identity().await?);
+
+            Ok(())
+        }
+        Commands::Audit(arguments) => {
+            print_audit_events(output, &sdk.list_audit_events(arguments.request()).await?);
+
+            Ok(())
         }
+        Commands::Config(_) | Commands::Logout => {
+            unreachable!("Config commands are handled before connecting")
+        }
+        Commands::Login(_) => unreachable!("Logins are handled before using the profile token"),
     }
 }
 
-async fn handle_up_local_image(
-    arguments: UpLocalImageArguments,
-    resources: Resources,
-    client: &mut DataCenterClient<Channel>,
+async fn login(
+    arguments: LoginArguments,
+    sdk: DataCenterSdk,
+    pinned_key: Option<&str>,
+    config: &mut Config,
+    selected: Option<&str>,
 ) -> Result<()> {
-    let mut source_file = File::open(&arguments.local_path).context("Should open file")?;
-    let file_size = source_file
-        .seek(std::io::SeekFrom::End(0))
-        .context("Should seek to end")?;
-    source_file
-        .seek(std::io::SeekFrom::Start(0))
-        .context("Should seek to start")?;
-    let create_image_response = client
-        .create_image_metadata(Request::new(CreateImageMetadataRequest {
-            file_size,
-            destination_file_path: arguments.storage_path,
-        }))
-        .await?;
-    let image = create_image_response
-        .into_inner()
-        .os_image_metadata
-        .expect("Should have image metadata");
-    let file_metadata = &image.file_metadata.expect("Should have file metadata");
-    client
-        .upload_file(Request::new(stream_chunks(&file_metadata, source_file)))
-        .await?;
-    let create_machine_response = client
-        .create_machine(Request::new(CreateMachineRequest {
-            image_id: image.image_id,
-            resources: Some(resources),
-        }))
-        .await
-        .context("Failed to provision machine")?;
-    let machine = create_machine_response
-        .into_inner()
-        .machine
-        .expect("Should have machine");
-    let provision_instance_response = client
-        .provision_instance(Request::new(ProvisionInstanceRequest {
-            machine_id: machine.machine_id,
-        }))
-        .await
-        .context("Failed to start instance")?;
-    let instance = provision_instance_response
-        .into_inner()
-        .instance
-        .expect("Should have instance");
-    dbg!(instance);
+    let token = match arguments.token {
+        Some(token) => token,
+        None => {
+            if stdin().is_terminal() {
+                eprint!("Token: ");
+            }
 
-    Ok(())
-}
+            let mut token = String::new();
+            stdin()
+                .read_line(&mut token)
+                .context("Failed to read token")?;
 
-async fn create_machine(
-    arguments: CreateMachineArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    client
-        .create_machine(Request::new(CreateMachineRequest {
-            image_id: arguments.image_id,
-            resources: Some(Resources {
-                ram_mb: arguments.ram_mb,
-                disk_mb: arguments.disk_mb,
-                vcpus: arguments.vcpus,
-            }),
-        }))
-        .await
-        .context("Failed to provision machine")?;
+            String::from(token.trim())
+        }
+    };
+    anyhow::ensure!(!token.is_empty(), "No token was given");
 
-    Ok(())
-}
+    let sdk = sdk.with_token(&token)?;
 
-async fn stop_instance(
-    arguments: StopInstanceArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    client
-        .stop_instance(Request::new(StopInstanceRequest {
-            instance_id: arguments.instance_id,
-        }))
-        .await
-        .context("Failed to stop instance")?;
+    if let Some(pinned_key) = pinned_key {
+        sdk.verify_key(pinned_key)
+            .await
+            .context("Host didn't prove it holds the pinned key")?;
+    }
+
+    // Any authenticated read tells whether the host accepts the token
+    sdk.list_images().await.context("Host rejected the token")?;
+    set_token(config, selected, &token)?;
+    eprintln!(
+        "Logged in, token saved to profile {}",
+        config.profile_name(selected)
+    );
 
     Ok(())
 }
 
-async fn start_instance(
-    arguments: StartInstanceArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    client
-        .start_instance(Request::new(StartInstanceRequest {
-            instance_id: arguments.instance_id,
-        }))
-        .await
-        .context("Failed to start instance")?;
+/// Stores `token` in the selected profile, removing it when empty
+fn set_token(config: &mut Config, selected: Option<&str>, token: &str) -> Result<()> {
+    config
+        .profiles
+        .entry(config.profile_name(selected))
+        .or_default()
+        .set("token", token)?;
+    config.save()?;
 
     Ok(())
 }
 
-async fn provision_instance(
-    arguments: ProvisionInstanceArguments,
-    client: &mut DataCenterClient<Channel>,
+fn handle_config_command(
+    arguments: ConfigArguments,
+    config: &mut Config,
+    selected: Option<&str>,
+    output: Option<OutputFormat>,
 ) -> Result<()> {
-    client
-        .provision_instance(Request::new(ProvisionInstanceRequest {
-            machine_id: arguments.machine_id,
-        }))
-        .await
-        .context("Failed to start instance")?;
+    match arguments.config {
+        ConfigCommands::Set(ConfigSetArguments { key, value }) => {
+            if key == "output" && !value.is_empty() {
+                value.parse::<OutputFormat>().map_err(anyhow::Error::msg)?;
+            }
+
+            config
+                .profiles
+                .entry(config.profile_name(selected))
+                .or_default()
+                .set(&key, &value)?;
+            config.save()?;
+        }
+        ConfigCommands::Get(ConfigGetArguments { key }) => {
+            let value = config
+                .profile(selected)?
+                .get(&key)?
+                .with_context(|| format!("{key} isn't set"))?;
+            println!("{value}");
+        }
+        ConfigCommands::List => print_settings(output.unwrap_or_default(), config),
+    }
 
     Ok(())
 }
 
-async fn list_machines(client: &mut DataCenterClient<Channel>) -> Result<()> {
-    let response = client
-        .list_machines(Request::new(ListMachinesRequest {}))
-        .await
-        .context("Shoud list machines")?
-        .into_inner();
-    dbg!(response);
+async fn handle_project_command(
+    arguments: ProjectArguments,
+    sdk: &DataCenterSdk,
+    output: OutputFormat,
+) -> Result<()> {
+    match arguments.project {
+        ProjectCommands::Create(ProjectIdArguments { project_id }) => {
+            let project = sdk.create_project(&project_id).await?;
+            println!("{}", project.project_id);
+        }
+        ProjectCommands::List => print_projects(output, &sdk.list_projects().await?),
+        ProjectCommands::Delete(ProjectIdArguments { project_id }) => {
+            sdk.delete_project(&project_id).await?
+        }
+        ProjectCommands::Grant(GrantArguments {
+            project_id,
+            subject,
+            role,
+        }) => {
+            sdk.set_role_binding(&project_id, &subject, role).await?;
+        }
+        ProjectCommands::Revoke(RevokeArguments {
+            project_id,
+            subject,
+        }) => sdk.remove_role_binding(&project_id, &subject).await?,
+        ProjectCommands::Bindings(ProjectIdArguments { project_id }) => {
+            print_role_bindings(output, &sdk.list_role_bindings(&project_id).await?)
+        }
+        ProjectCommands::Quota(ProjectIdArguments { project_id }) => print_quota(
+            output,
+            &sdk.get_quota(&project_id).await?,
+            &sdk.get_usage(&project_id).await?,
+        ),
+        ProjectCommands::SetQuota(arguments) => set_quota(arguments, sdk, output).await?,
+    }
 
     Ok(())
 }
 
-async fn list_instances(client: &mut DataCenterClient<Channel>) -> Result<()> {
-    let response = client
-        .list_instances(Request::new(ListInstancesRequest {}))
-        .await
-        .context("Shoud list instances")?
-        .into_inner();
-    dbg!(response);
+async fn set_quota(
+    arguments: SetQuotaArguments,
+    sdk: &DataCenterSdk,
+    output: OutputFormat,
+) -> Result<()> {
+    let current = sdk.get_quota(&arguments.project_id).await?;
+    let quota = sdk
+        .set_quota(
+            &arguments.project_id,
+            Quota {
+                vcpus: arguments.vcpus.or(current.vcpus),
+                ram_mb: arguments.ram_mb.or(current.ram_mb),
+                disk_mb: arguments.disk_mb.or(current.disk_mb),
+                instances: arguments.instances.or(current.instances),
+                storage_bytes: arguments.storage_bytes.or(current.storage_bytes),
+            },
+        )
+        .await?;
+    print_quota(output, &quota, &sdk.get_usage(&arguments.project_id).await?);
 
     Ok(())
 }
 
-async fn handle_storage_command(
-    arguments: StorageArguments,
-    client: &mut DataCenterClient<Channel>,
+async fn handle_machine_command(
+    arguments: MachineArguments,
+    sdk: &DataCenterSdk,
+    output: OutputFormat,
 ) -> Result<()> {
-    match arguments.storage {
-        StorageCommands::UploadFile(arguments) => upload_file(arguments, client).await,
-        StorageCommands::DownloadFile(arguments) => download_file(arguments, client).await,
+    match arguments.machine {
+        MachineCommands::CreateMachine(arguments) => create_machine(arguments, sdk).await,
+        MachineCommands::ListMachines => {
+            print_machines(output, &sdk.list_machines().await?);
+
+            Ok(())
+        }
     }
 }
 
-async fn upload_file(
-    arguments: UploadFileArguments,
-    client: &mut DataCenterClient<Channel>,
+async fn handle_instance_command(
+    arguments: InstanceArguments,
+    sdk: &DataCenterSdk,
+    output: OutputFormat,
 ) -> Result<()> {
-    let mut source_file = File::open(&arguments.local_file_path).context("Should open file")?;
-    let file_size = source_file
-        .seek(std::io::SeekFrom::End(0))
-        .context("Should seek to end")?;
-    source_file
-        .seek(std::io::SeekFrom::Start(0))
-        .context("Should seek to start")?;
-    let file_metadata = client
-        .create_file_metadata(Request::new(CreateFileMetadataRequest {
-            file_path: arguments.storage_file_path,
-            file_size,
-        }))
-        .await?
-        .into_inner()
-        .metadata
-        .expect("Should contain metadata");
-
-    client
-        .upload_file(Request::new(stream_chunks(&file_metadata, source_file)))
-        .await?;
+    match arguments.instance {
+        InstanceCommands::StopInstance(StopInstanceArguments { instance_id }) => {
+            Ok(sdk.stop_instance(&instance_id).await?)
+        }
+        InstanceCommands::StartInstance(StartInstanceArguments { instance_id }) => {
+            Ok(sdk.start_instance(&instance_id).await?)
+        }
+        InstanceCommands::ProvisionInstance(ProvisionInstanceArguments { machine_id }) => {
+            let instance = sdk.provision_instance(&machine_id).await?;
+            println!("{}", instance.instance_id);
 
-    Ok(())
-}
+            Ok(())
+        }
+        InstanceCommands::ListInstances => {
+            print_instances(output, &sdk.list_instances().await?);
 
-async fn download_file(
-    arguments: DownloadFileArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    let file_metadata = client
-        .get_file_metadata(Request::new(GetFileMetadataRequest {
-            file_path: arguments.storage_path,
-        }))
-        .await?
-        .into_inner()
-        .metadata
-        .expect("Metadata should exist");
-
-    write_file(&arguments.local_path, file_metadata, client).await
+            Ok(())
+        }
+    }
 }
 
-async fn write_file(
-    local_path: &str,
-    file_metadata: FileMetadata,
-    client: &mut DataCenterClient<Channel>,
+async fn handle_compute_command(
+    arguments: ComputeArguments,
+    sdk: &DataCenterSdk,
+    parallelism: usize,
 ) -> Result<()> {
-    let mut stream = client
-        .download_file(Request::new(DownloadFileRequest {
-            source_path: String::from(&file_metadata.file_path),
-        }))
-        .await?
-        .into_inner();
-    let mut contents = vec![0; file_metadata.file_size as usize];
-
-    while let Some(message) = stream.message().await? {
-        let chunk = message
-            .chunk
-            .expect("All download requests must have a chunk");
-        contents.splice(
-            Range {
-                start: chunk.start as usize,
-                end: chunk.end as usize,
-            },
-            chunk.data,
-        );
+    match arguments.compute {
+        ComputeCommands::Up(arguments) => handle_up(arguments, sdk, parallelism).await,
     }
-
-    write(local_path, &contents).expect("Should write image");
-
-    Ok(())
 }
 
-async fn handle_image_command(
-    arguments: OperatingSystemArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    match arguments.os {
-        OperatingSystemCommands::UploadImage(arguments) => upload_image(arguments, client).await,
-        OperatingSystemCommands::DownloadImage(arguments) => {
-            download_image(arguments, client).await
-        }
-        OperatingSystemCommands::ListImageMetadata => list_image_metadata(client).await,
-        OperatingSystemCommands::GetImageMetadata(arguments) => {
-            get_image_metadata(arguments, client).await
+async fn handle_up(arguments: UpArguments, sdk: &DataCenterSdk, parallelism: usize) -> Result<()> {
+    let resources = Resources {
+        ram_mb: arguments.ram_mb,
+        disk_mb: arguments.disk_mb,
+        vcpus: arguments.vcpus,
+    };
+
+    match arguments.up {
+        UpCommands::LocalImage(arguments) => {
+            handle_up_local_image(arguments, resources, sdk, parallelism).await
         }
     }
 }
 
-async fn get_image_metadata(
-    arguments: GetImageMetadataArguments,
-    client: &mut DataCenterClient<Channel>,
+async fn handle_up_local_image(
+    arguments: UpLocalImageArguments,
+    resources: Resources,
+    sdk: &DataCenterSdk,
+    parallelism: usize,
 ) -> Result<()> {
-    let image = client
-        .get_image_metadata(Request::new(GetImageMetadataRequest {
-            image_id: arguments.image_id,
-        }))
-        .await
-        .context("Failed to get image")?
-        .into_inner()
-        .image
-        .context("No metadata for image")?;
-
-    println!("{:?}", image);
+    let instance = sdk
+        .up_local_image(
+            &arguments.local_path,
+            &arguments.storage_path,
+            resources,
+            &report_progress(Direction::Upload, parallelism),
+        )
+        .await?;
+    println!("{}", instance.instance_id);
 
     Ok(())
 }
 
-async fn upload_image(
-    arguments: UploadImageArguments,
-    client: &mut DataCenterClient<Channel>,
-) -> Result<()> {
-    let mut source_file = File::open(&arguments.source_image_path).context("Should open file")?;
-    let file_size = source_file
-        .seek(std::io::SeekFrom::End(0))
-        .context("Should seek to end")?;
-    source_file
-        .seek(std::io::SeekFrom::Start(0))
-        .context("Should seek to start")?;
-    let image = if let Some(image_id) = arguments.image_id {
-        client
-            .get_image_metadata(Request::new(GetImageMetadataRequest { image_id }))
-            .await
-            .context("Failed to get image")?
-            .into_inner()
-            .image
-            .context("No metadata for image")?
-    } else {
-        client
-            .create_image_metadata(Request::new(CreateImageMetadataRequest {
-                file_size,
-                destination_file_path: arguments.destination_image_path,
-            }))
-            .await?
-            .into_inner()
-            .os_image_metadata
-            .context("Should create image")?
-    };
-    let file_metadata = &image.file_metadata.expect("Should have file metadata");
-    client
-        .upload_file(Request::new(stream_chunks(&file_metadata, source_file)))
+async fn create_machine(arguments: CreateMachineArguments, sdk: &DataCenterSdk) -> Result<()> {
+    let machine = sdk
+        .create_machine(
+            &arguments.image_id,
+            Resources {
+                ram_mb: arguments.ram_mb,
+                disk_mb: arguments.disk_mb,
+                vcpus: arguments.vcpus,
+            },
+        )
         .await?;
+    println!("{}", machine.machine_id);
 
     Ok(())
 }
 
-struct ChunkedReader<T>
-where
-    T: Read,
-{
-    source: T,
-    write_path: String,
-    position: usize,
-}
-
-impl<T> ChunkedReader<T>
-where
-    T: Read,
-{
-    fn new(source: T, write_path: String) -> ChunkedReader<T> {
-        ChunkedReader {
-            source,
-            write_path,
-            position: 0,
+async fn handle_storage_command(
+    arguments: StorageArguments,
+    sdk: &DataCenterSdk,
+    parallelism: usize,
+) -> Result<()> {
+    match arguments.storage {
+        StorageCommands::UploadFile(UploadFileArguments {
+            local_file_path,
+            storage_file_path,
+        }) => {
+            sdk.upload_file(
+                &local_file_path,
+                &storage_file_path,
+                &report_progress(Direction::Upload, parallelism),
+            )
+            .await?;
         }
-    }
-}
-
-impl<T> Iterator for ChunkedReader<T>
-where
-    T: Read,
-{
-    type Item = UploadFileRequest;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut buffer = [0; ONE_MB];
-
-        match self.source.read(&mut buffer) {
-            Ok(0) => None,
-            Ok(count) => {
-                let current_position = self.position;
-                self.position += count;
-
-                Some(UploadFileRequest {
-                    file_path: self.write_path.clone(),
-                    chunk: Some(Chunk {
-                        start: current_position as u64,
-                        end: (current_position + count) as u64,
-                        data: buffer[..count].to_vec(),
-                    }),
-                })
-            }
-            Err(_) => None,
+        StorageCommands::DownloadFile(DownloadFileArguments {
+            storage_path,
+            local_path,
+        }) => {
+            sdk.download_file(
+                &storage_path,
+                &local_path,
+                &report_progress(Direction::Download, parallelism),
+            )
+            .await?;
         }
     }
-}
-
-fn stream_chunks(
-    file_metadata: &FileMetadata,
-    source: File,
-) -> impl Stream<Item = UploadFileRequest> {
-    let file_path = String::from(&file_metadata.file_path);
-    let reader = ChunkedReader::new(source, file_path);
 
-    tokio_stream::iter(reader)
+    Ok(())
 }
 
-async fn download_image(
-    arguments: DownloadImageArguments,
-    client: &mut DataCenterClient<Channel>,
+async fn handle_image_command(
+    arguments: OperatingSystemArguments,
+    sdk: &DataCenterSdk,
+    output: OutputFormat,
+    parallelism: usize,
 ) -> Result<()> {
-    let image = client
-        .get_image_metadata(Request::new(GetImageMetadataRequest {
-            image_id: arguments.image_id,
-        }))
-        .await?
-        .into_inner()
-        .image
-        .expect("Should have image metadata");
-    let file_metadata = image.file_metadata.expect("Should have file metadata");
-
-    write_file(&arguments.destination_path, file_metadata, client).await
-}
-
-async fn list_image_metadata(client: &mut DataCenterClient<Channel>) -> Result<()> {
-    dbg!(client
-        .list_image_metadata(Request::new(ListImageMetadataRequest {}))
-        .await?
-        .into_inner());
+    match arguments.os {
+        OperatingSystemCommands::UploadImage(UploadImageArguments {
+            image_id,
+            source_image_path,
+            destination_image_path,
+        }) => {
+            let image = sdk
+                .upload_image(
+                    &source_image_path,
+                    &destination_image_path,
+                    image_id.as_deref(),
+                    &report_progress(Direction::Upload, parallelism),
+                )
+                .await?;
+            println!("{}", image.image_id);
+        }
+        OperatingSystemCommands::DownloadImage(DownloadImageArguments {
+            image_id,
+            destination_path,
+        }) => {
+            sdk.download_image(
+                &image_id,
+                &destination_path,
+                &report_progress(Direction::Download, parallelism),
+            )
+            .await?;
+        }
+        OperatingSystemCommands::ListImageMetadata => {
+            print_images(output, &sdk.list_images().await?)
+        }
+        OperatingSystemCommands::GetImageMetadata(GetImageMetadataArguments { image_id }) => {
+            print_image(output, &sdk.get_image(&image_id).await?)
+        }
+    }
 
     Ok(())
 }
+
+fn report_progress(direction: Direction, parallelism: usize) -> TransferOptions {
+    TransferOptions::with_progress(Arc::new(TransferReporter::new(direction)))
+        .with_parallelism(parallelism)
+}
@@ -0,0 +1,339 @@
+use cli_output::{OutputFormat, Table};
+use client_config::Config;
+use serde_json::{json, Value};
+
+use crate::{
+    protos::{
+        audit::AuditEvent,
+        data_center::{
+            Instance, Machine, OsImageMetadata, Project, Quota, Resources, Role, RoleBinding, Usage,
+        },
+    },
+    sdk::ProvenIdentity,
+};
+
+const MACHINE_HEADERS: [&str; 6] = ["ID", "PROJECT", "IMAGE", "RAM MB", "DISK MB", "VCPUS"];
+const INSTANCE_HEADERS: [&str; 6] = ["ID", "PROJECT", "MACHINE", "STATE", "IP ADDRESS", "PID"];
+const IMAGE_HEADERS: [&str; 4] = ["ID", "PROJECT", "PATH", "SIZE"];
+const PROJECT_HEADERS: [&str; 1] = ["ID"];
+const ROLE_BINDING_HEADERS: [&str; 3] = ["PROJECT", "SUBJECT", "ROLE"];
+const QUOTA_HEADERS: [&str; 3] = ["RESOURCE", "USED", "LIMIT"];
+const IDENTITY_HEADERS: [&str; 2] = ["DATA CENTER", "KEY"];
+const AUDIT_HEADERS: [&str; 6] = [
+    "SEQUENCE",
+    "TIME MS",
+    "SUBJECT",
+    "RPC",
+    "RESOURCES",
+    "OUTCOME",
+];
+
+pub fn print_machines(format: OutputFormat, machines: &[Machine]) {
+    let mut table = Table::new(&MACHINE_HEADERS);
+
+    for machine in machines {
+        table.push(machine_row(machine));
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(machines.iter().map(machine_json).collect()),
+    );
+}
+
+pub fn print_instances(format: OutputFormat, instances: &[Instance]) {
+    let mut table = Table::new(&INSTANCE_HEADERS);
+
+    for instance in instances {
+        table.push(instance_row(instance));
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(instances.iter().map(instance_json).collect()),
+    );
+}
+
+pub fn print_images(format: OutputFormat, images: &[OsImageMetadata]) {
+    let mut table = Table::new(&IMAGE_HEADERS);
+
+    for image in images {
+        table.push(image_row(image));
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(images.iter().map(image_json).collect()),
+    );
+}
+
+pub fn print_image(format: OutputFormat, image: &OsImageMetadata) {
+    let mut table = Table::new(&IMAGE_HEADERS);
+    table.push(image_row(image));
+
+    cli_output::print(format, &table, &image_json(image));
+}
+
+pub fn print_projects(format: OutputFormat, projects: &[Project]) {
+    let mut table = Table::new(&PROJECT_HEADERS);
+
+    for project in projects {
+        table.push(vec![project.project_id.clone()]);
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(
+            projects
+                .iter()
+                .map(|project| json!({ "project_id": project.project_id }))
+                .collect(),
+        ),
+    );
+}
+
+pub fn print_identity(format: OutputFormat, identity: &ProvenIdentity) {
+    let mut table = Table::new(&IDENTITY_HEADERS);
+    table.push(vec![
+        identity.data_center_id.clone(),
+        identity.fingerprint(),
+    ]);
+
+    cli_output::print(
+        format,
+        &table,
+        &json!({
+            "data_center_id": identity.data_center_id,
+            "key_fingerprint": identity.fingerprint(),
+        }),
+    );
+}
+
+pub fn print_role_bindings(format: OutputFormat, bindings: &[RoleBinding]) {
+    let mut table = Table::new(&ROLE_BINDING_HEADERS);
+
+    for binding in bindings {
+        table.push(vec![
+            binding.project_id.clone(),
+            binding.subject.clone(),
+            String::from(role_name(binding.role())),
+        ]);
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(
+            bindings
+                .iter()
+                .map(|binding| {
+                    json!({
+                        "project_id": binding.project_id,
+                        "subject": binding.subject,
+                        "role": role_name(binding.role()),
+                    })
+                })
+                .collect(),
+        ),
+    );
+}
+
+/// Prints what a project uses of each resource next to the limit of its quota
+pub fn print_quota(format: OutputFormat, quota: &Quota, usage: &Usage) {
+    let resources = [
+        ("vcpus", u64::from(usage.vcpus), quota.vcpus.map(u64::from)),
+        (
+            "ram_mb",
+            u64::from(usage.ram_mb),
+            quota.ram_mb.map(u64::from),
+        ),
+        (
+            "disk_mb",
+            u64::from(usage.disk_mb),
+            quota.disk_mb.map(u64::from),
+        ),
+        (
+            "instances",
+            u64::from(usage.instances),
+            quota.instances.map(u64::from),
+        ),
+        ("storage_bytes", usage.storage_bytes, quota.storage_bytes),
+    ];
+    let mut table = Table::new(&QUOTA_HEADERS);
+
+    for (resource, used, limit) in resources {
+        table.push(vec![
+            String::from(resource),
+            used.to_string(),
+            limit.map_or_else(|| String::from("unlimited"), |limit| limit.to_string()),
+        ]);
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(
+            resources
+                .iter()
+                .map(|(resource, used, limit)| {
+                    json!({ "resource": resource, "used": used, "limit": limit })
+                })
+                .collect(),
+        ),
+    );
+}
+
+/// Prints audit events as a table, or one per line so they can be exported as json lines
+pub fn print_audit_events(format: OutputFormat, events: &[AuditEvent]) {
+    if format != OutputFormat::Table {
+        for event in events {
+            cli_output::print_record(format, "", &audit_event_json(event));
+        }
+
+        return;
+    }
+
+    let mut table = Table::new(&AUDIT_HEADERS);
+
+    for event in events {
+        table.push(vec![
+            event.sequence.to_string(),
+            event.timestamp_unix_ms.to_string(),
+            event.subject.clone(),
+            event.rpc.clone(),
+            event.resource_ids.join(","),
+            event.outcome.clone(),
+        ]);
+    }
+
+    print!("{table}");
+}
+
+/// Name roles are written with on the command line
+pub fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Viewer => "viewer",
+        Role::Operator => "operator",
+        Role::Admin => "admin",
+    }
+}
+
+fn machine_row(machine: &Machine) -> Vec<String> {
+    let resources = machine.resources.clone().unwrap_or_default();
+
+    vec![
+        machine.machine_id.clone(),
+        machine.project_id.clone(),
+        machine
+            .image_metadata
+            .as_ref()
+            .map(|image| image.image_id.clone())
+            .unwrap_or_default(),
+        resources.ram_mb.to_string(),
+        resources.disk_mb.to_string(),
+        resources.vcpus.to_string(),
+    ]
+}
+
+fn instance_row(instance: &Instance) -> Vec<String> {
+    vec![
+        instance.instance_id.clone(),
+        instance.project_id.clone(),
+        instance
+            .machine
+            .as_ref()
+            .map(|machine| machine.machine_id.clone())
+            .unwrap_or_default(),
+        String::from(instance.state().as_str_name()),
+        instance.ip_address.clone(),
+        instance.process_id.clone(),
+    ]
+}
+
+fn image_row(image: &OsImageMetadata) -> Vec<String> {
+    let file_metadata = image.file_metadata.clone().unwrap_or_default();
+
+    vec![
+        image.image_id.clone(),
+        image.project_id.clone(),
+        file_metadata.file_path,
+        file_metadata.file_size.to_string(),
+    ]
+}
+
+fn machine_json(machine: &Machine) -> Value {
+    json!({
+        "machine_id": machine.machine_id,
+        "project_id": machine.project_id,
+        "image": machine.image_metadata.as_ref().map(image_json),
+        "resources": machine.resources.as_ref().map(resources_json),
+    })
+}
+
+fn instance_json(instance: &Instance) -> Value {
+    json!({
+        "instance_id": instance.instance_id,
+        "project_id": instance.project_id,
+        "state": instance.state().as_str_name(),
+        "ip_address": instance.ip_address,
+        "process_id": instance.process_id,
+        "machine": instance.machine.as_ref().map(machine_json),
+    })
+}
+
+fn image_json(image: &OsImageMetadata) -> Value {
+    let file_metadata = image.file_metadata.clone().unwrap_or_default();
+
+    json!({
+        "image_id": image.image_id,
+        "project_id": image.project_id,
+        "file_path": file_metadata.file_path,
+        "file_size": file_metadata.file_size,
+    })
+}
+
+fn audit_event_json(event: &AuditEvent) -> Value {
+    json!({
+        "sequence": event.sequence,
+        "timestamp_unix_ms": event.timestamp_unix_ms,
+        "subject": event.subject,
+        "rpc": event.rpc,
+        "resource_ids": event.resource_ids,
+        "outcome": event.outcome,
+        "message": event.message,
+    })
+}
+
+fn resources_json(resources: &Resources) -> Value {
+    json!({
+        "ram_mb": resources.ram_mb,
+        "disk_mb": resources.disk_mb,
+        "vcpus": resources.vcpus,
+    })
+}
+
+const SETTING_HEADERS: [&str; 3] = ["PROFILE", "KEY", "VALUE"];
+
+/// Prints the settings of every profile, hiding tokens
+pub fn print_settings(format: OutputFormat, config: &Config) {
+    let mut table = Table::new(&SETTING_HEADERS);
+    let mut settings = Vec::new();
+
+    for (name, profile) in &config.profiles {
+        for (key, value) in profile.settings() {
+            let value = if key == "token" {
+                String::from("<redacted>")
+            } else {
+                value
+            };
+            table.push(vec![name.clone(), String::from(key), value.clone()]);
+            settings.push(json!({ "profile": name, "key": key, "value": value }));
+        }
+    }
+
+    cli_output::print(format, &table, &Value::Array(settings));
+}
@@ -0,0 +1,176 @@
+use std::{
+    io::{stderr, IsTerminal},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+use crate::sdk::Progress;
+
+/// How often progress is printed when stderr isn't a terminal
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+const BAR_TEMPLATE: &str =
+    "{msg} [{bar:30}] {bytes}/{total_bytes} {binary_bytes_per_sec} eta {eta}";
+
+/// Direction data is transferred in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+impl Direction {
+    fn ongoing(self) -> &'static str {
+        match self {
+            Direction::Upload => "Uploading",
+            Direction::Download => "Downloading",
+        }
+    }
+
+    fn done(self) -> &'static str {
+        match self {
+            Direction::Upload => "Uploaded",
+            Direction::Download => "Downloaded",
+        }
+    }
+}
+
+/// Reports the progress of a transfer on stderr, as a progress bar on terminals and as a line
+/// printed every few seconds otherwise, followed by a summary once the transfer finishes
+pub struct TransferReporter {
+    direction: Direction,
+    bar: Option<ProgressBar>,
+    state: Mutex<TransferState>,
+}
+
+struct TransferState {
+    total_bytes: u64,
+    transferred: u64,
+    started: Instant,
+    last_report: Instant,
+}
+
+impl TransferReporter {
+    pub fn new(direction: Direction) -> TransferReporter {
+        let bar = stderr().is_terminal().then(|| {
+            let bar = ProgressBar::new(0).with_message(direction.ongoing());
+            bar.set_style(
+                ProgressStyle::with_template(BAR_TEMPLATE)
+                    .expect("Should parse template")
+                    .progress_chars("=> "),
+            );
+
+            bar
+        });
+
+        TransferReporter {
+            direction,
+            bar,
+            state: Mutex::new(TransferState {
+                total_bytes: 0,
+                transferred: 0,
+                started: Instant::now(),
+                last_report: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl Progress for TransferReporter {
+    fn started(&self, total_bytes: u64) {
+        let now = Instant::now();
+        *self.state.lock().expect("Should acquire lock") = TransferState {
+            total_bytes,
+            transferred: 0,
+            started: now,
+            last_report: now,
+        };
+
+        if let Some(bar) = &self.bar {
+            bar.reset();
+            bar.set_length(total_bytes);
+        }
+    }
+
+    fn advanced(&self, bytes: u64) {
+        let mut state = self.state.lock().expect("Should acquire lock");
+        state.transferred += bytes;
+
+        match &self.bar {
+            Some(bar) => bar.set_position(state.transferred),
+            None if state.last_report.elapsed() >= REPORT_INTERVAL => {
+                state.last_report = Instant::now();
+                eprintln!(
+                    "{}",
+                    progress_line(
+                        self.direction,
+                        state.transferred,
+                        state.total_bytes,
+                        state.started.elapsed()
+                    )
+                );
+            }
+            None => {}
+        }
+    }
+
+    fn finished(&self) {
+        let state = self.state.lock().expect("Should acquire lock");
+
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+
+        eprintln!(
+            "{}",
+            summary(self.direction, state.transferred, state.started.elapsed())
+        );
+    }
+}
+
+/// Line describing a transfer in progress, like
+/// `Uploading 12.00 MiB of 48.00 MiB (25%) at 6.00 MiB/s, 6s left`
+pub fn progress_line(
+    direction: Direction,
+    transferred: u64,
+    total_bytes: u64,
+    elapsed: Duration,
+) -> String {
+    let rate = rate(transferred, elapsed);
+    let percent = (transferred * 100).checked_div(total_bytes).unwrap_or(100);
+    let remaining = match rate {
+        0 => String::from("unknown time"),
+        rate => format!(
+            "{}s",
+            total_bytes.saturating_sub(transferred).div_ceil(rate)
+        ),
+    };
+
+    format!(
+        "{} {} of {} ({percent}%) at {}/s, {remaining} left",
+        direction.ongoing(),
+        HumanBytes(transferred),
+        HumanBytes(total_bytes),
+        HumanBytes(rate)
+    )
+}
+
+/// Summary of a finished transfer, like `Uploaded 48.00 MiB in 8.0s (6.00 MiB/s)`
+pub fn summary(direction: Direction, transferred: u64, elapsed: Duration) -> String {
+    format!(
+        "{} {} in {:.1}s ({}/s)",
+        direction.done(),
+        HumanBytes(transferred),
+        elapsed.as_secs_f64(),
+        HumanBytes(rate(transferred, elapsed))
+    )
+}
+
+/// Average bytes per second
+fn rate(transferred: u64, elapsed: Duration) -> u64 {
+    match elapsed.as_secs_f64() {
+        seconds if seconds > 0.0 => (transferred as f64 / seconds) as u64,
+        _ => transferred,
+    }
+}
@@ -1,3 +1,7 @@
 pub mod data_center {
     tonic::include_proto!("data_center");
 }
+
+pub mod audit {
+    tonic::include_proto!("audit");
+}
@@ -0,0 +1,29 @@
+use tonic::Request;
+
+use super::{DataCenterSdk, SdkError};
+use crate::protos::audit::{audit_client::AuditClient, AuditEvent, ListAuditEventsRequest};
+
+impl DataCenterSdk {
+    /// Events of the audit log of the data center matching the filters of `request`, oldest
+    /// first
+    pub async fn list_audit_events(
+        &self,
+        request: ListAuditEventsRequest,
+    ) -> Result<Vec<AuditEvent>, SdkError> {
+        self.retry(|_| {
+            let request = request.clone();
+
+            async move {
+                Ok(
+                    AuditClient::with_interceptor(self.channel.clone(), self.token.clone())
+                        .list_audit_events(Request::new(request))
+                        .await
+                        .map_err(SdkError::status("list audit events"))?
+                        .into_inner()
+                        .event,
+                )
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,119 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use grpc_tls::TlsError;
+use tonic::{Code, Status};
+
+/// Failure of a call made through the sdk
+#[derive(Debug)]
+pub enum SdkError {
+    /// Endpoint isn't a valid uri
+    InvalidEndpoint(String),
+    /// Tls the endpoint is reached over is missing or can't be used
+    Tls(TlsError),
+    /// Token contains characters that can't be sent as metadata
+    InvalidToken,
+    /// Call failed with a grpc status, after any retries it was allowed
+    Status {
+        operation: &'static str,
+        status: Box<Status>,
+    },
+    /// Resource the call refers to doesn't exist
+    NotFound { kind: &'static str, id: String },
+    /// Local file couldn't be read or written
+    Io { path: PathBuf, error: io::Error },
+    /// Response was missing a field the data center always sets
+    MissingField(&'static str),
+    /// Download ended before every byte of the file arrived
+    Incomplete { expected: u64, received: u64 },
+    /// Data center didn't sign the challenge with the key it answered with
+    InvalidProof(identity::IdentityError),
+    /// Data center holds another key than the pinned one
+    KeyMismatch {
+        data_center_id: String,
+        fingerprint: String,
+    },
+}
+
+impl SdkError {
+    /// Grpc status code best describing the failure, if it came from a call
+    pub fn code(&self) -> Option<Code> {
+        match self {
+            SdkError::Status { status, .. } if is_unreachable(status) => Some(Code::Unavailable),
+            SdkError::Status { status, .. } => Some(status.code()),
+            SdkError::NotFound { .. } => Some(Code::NotFound),
+            SdkError::Incomplete { .. } => Some(Code::Unavailable),
+            SdkError::InvalidProof(_) | SdkError::KeyMismatch { .. } => Some(Code::Unauthenticated),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn status(operation: &'static str) -> impl FnOnce(Status) -> SdkError {
+        move |status| SdkError::Status {
+            operation,
+            status: Box::new(status),
+        }
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> SdkError {
+        let path = path.into();
+
+        move |error| SdkError::Io { path, error }
+    }
+}
+
+impl fmt::Display for SdkError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SdkError::InvalidEndpoint(endpoint) => {
+                write!(formatter, "Invalid data center endpoint {endpoint}")
+            }
+            SdkError::Tls(error) => write!(formatter, "{error}"),
+            SdkError::InvalidToken => formatter.write_str("Token contains invalid characters"),
+            SdkError::Status { operation, status } => write!(
+                formatter,
+                "Failed to {operation}: {:?}: {}",
+                status.code(),
+                status.message()
+            ),
+            SdkError::NotFound { kind, id } => write!(formatter, "No {kind} {id}"),
+            SdkError::Io { path, .. } => write!(formatter, "Failed to access {}", path.display()),
+            SdkError::MissingField(field) => write!(formatter, "Response is missing {field}"),
+            SdkError::Incomplete { expected, received } => write!(
+                formatter,
+                "Transfer ended after {received} of {expected} bytes"
+            ),
+            SdkError::InvalidProof(error) => {
+                write!(
+                    formatter,
+                    "Data center failed to prove its identity: {error}"
+                )
+            }
+            SdkError::KeyMismatch {
+                data_center_id,
+                fingerprint,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} holds key {fingerprint}, not the pinned key"
+            ),
+        }
+    }
+}
+
+impl Error for SdkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SdkError::Io { error, .. } => Some(error),
+            SdkError::InvalidProof(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the data center couldn't be reached, either answering unavailable or failing before
+/// a connection was made
+pub(crate) fn is_unreachable(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+        || status
+            .source()
+            .is_some_and(|source| source.is::<tonic::transport::Error>())
+}
@@ -0,0 +1,66 @@
+use tonic::Request;
+
+use super::{DataCenterSdk, SdkError};
+use crate::protos::data_center::ProveIdentityRequest;
+
+/// Data center that proved it holds the private half of `public_key`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvenIdentity {
+    pub data_center_id: String,
+    pub public_key: Vec<u8>,
+}
+
+impl ProvenIdentity {
+    pub fn fingerprint(&self) -> String {
+        identity::fingerprint(&self.public_key)
+    }
+}
+
+impl DataCenterSdk {
+    /// Has the data center sign a fresh challenge with its key, returning the key it proved
+    /// it holds
+    pub async fn prove_identity(&self) -> Result<ProvenIdentity, SdkError> {
+        let challenge = identity::generate_challenge();
+        let proof = self
+            .retry(|mut client| {
+                let challenge = challenge.clone();
+
+                async move {
+                    client
+                        .prove_identity(Request::new(ProveIdentityRequest { challenge }))
+                        .await
+                        .map_err(SdkError::status("prove identity"))
+                }
+            })
+            .await?
+            .into_inner();
+
+        identity::verify_challenge(
+            &proof.public_key,
+            &proof.data_center_id,
+            &challenge,
+            &proof.signature,
+        )
+        .map_err(SdkError::InvalidProof)?;
+
+        Ok(ProvenIdentity {
+            data_center_id: proof.data_center_id,
+            public_key: proof.public_key,
+        })
+    }
+
+    /// Checks that the data center holds the `pinned` key, given as a fingerprint or a base64
+    /// public key
+    pub async fn verify_key(&self, pinned: &str) -> Result<ProvenIdentity, SdkError> {
+        let proven = self.prove_identity().await?;
+
+        if !identity::matches_pin(pinned, &proven.public_key) {
+            return Err(SdkError::KeyMismatch {
+                fingerprint: proven.fingerprint(),
+                data_center_id: proven.data_center_id,
+            });
+        }
+
+        Ok(proven)
+    }
+}
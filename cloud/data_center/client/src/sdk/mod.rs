@@ -0,0 +1,601 @@
+mod audit;
+mod error;
+mod identity;
+mod projects;
+mod transfer;
+
+use std::{
+    fs::File,
+    future::Future,
+    io::SeekFrom,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use auth::BearerToken;
+use futures::future::try_join_all;
+use grpc_tls::TlsError;
+use telemetry::Traced;
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+    Request,
+};
+
+use error::is_unreachable;
+pub use error::SdkError;
+pub use identity::ProvenIdentity;
+pub use transfer::{split_ranges, NoProgress, Progress, TransferOptions};
+
+use crate::protos::data_center::{
+    data_center_client::DataCenterClient, CreateFileMetadataRequest, CreateImageMetadataRequest,
+    CreateMachineRequest, DownloadFileRequest, FileMetadata, GetFileMetadataRequest,
+    GetImageMetadataRequest, Instance, ListImageMetadataRequest, ListInstancesRequest,
+    ListMachinesRequest, Machine, OsImageMetadata, ProvisionInstanceRequest, Resources,
+    StartInstanceRequest, StopInstanceRequest,
+};
+
+/// How calls that failed because the data center was unreachable are retried
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Number of times a call is made before giving up
+    pub attempts: u32,
+    /// Delay before the first retry, doubled after every retry
+    pub initial_backoff: Duration,
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Typed client for a data center, or for a resolver routing calls to data centers.
+/// Reads and transfers are retried while the data center is unreachable, calls creating
+/// resources are made once so they are never duplicated
+#[derive(Clone)]
+pub struct DataCenterSdk {
+    channel: Channel,
+    endpoint: Option<Endpoint>,
+    retry_policy: RetryPolicy,
+    token: Traced<BearerToken>,
+    /// Project resources are created in and listed from, the data center's default project and
+    /// every project the caller can view when empty
+    project_id: String,
+}
+
+/// Generated client sending the bearer token of the sdk and the trace context with every call
+pub type Client = DataCenterClient<InterceptedService<Channel, Traced<BearerToken>>>;
+
+impl DataCenterSdk {
+    /// Creates an sdk for the data center at `host`, connecting on the first call. Hosts
+    /// without a scheme are reached over http
+    pub fn connect(host: &str) -> Result<DataCenterSdk, SdkError> {
+        Self::connect_with_tls(host, None)
+    }
+
+    /// Creates an sdk for the data center at `host` reached over `tls` when given, presenting
+    /// the client certificate of `tls` to data centers requiring mutual tls
+    pub fn connect_with_tls(
+        host: &str,
+        tls: Option<&ClientTlsConfig>,
+    ) -> Result<DataCenterSdk, SdkError> {
+        let endpoint = grpc_tls::endpoint(host, tls).map_err(|error| match error {
+            TlsError::Invalid(_) => SdkError::InvalidEndpoint(String::from(host)),
+            error => SdkError::Tls(error),
+        })?;
+
+        Ok(DataCenterSdk {
+            endpoint: Some(endpoint.clone()),
+            ..DataCenterSdk::new(endpoint.connect_lazy())
+        })
+    }
+
+    /// Creates an sdk making every call over `channel`
+    pub fn new(channel: Channel) -> DataCenterSdk {
+        DataCenterSdk {
+            channel,
+            endpoint: None,
+            retry_policy: RetryPolicy::default(),
+            token: Traced::default(),
+            project_id: String::new(),
+        }
+    }
+
+    /// Sends `token` as a bearer token with every call
+    pub fn with_token(mut self, token: &str) -> Result<DataCenterSdk, SdkError> {
+        self.token = Traced::new(BearerToken::new(token).map_err(|_| SdkError::InvalidToken)?);
+
+        Ok(self)
+    }
+
+    /// Creates resources in `project_id` and only lists the resources of that project
+    pub fn with_project(mut self, project_id: &str) -> DataCenterSdk {
+        self.project_id = String::from(project_id);
+
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> DataCenterSdk {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Generated client, for calls the sdk doesn't wrap
+    pub fn client(&self) -> Client {
+        DataCenterClient::with_interceptor(self.channel.clone(), self.token.clone())
+    }
+
+    pub async fn list_machines(&self) -> Result<Vec<Machine>, SdkError> {
+        self.retry(|mut client| async move {
+            Ok(client
+                .list_machines(Request::new(ListMachinesRequest {
+                    project_id: self.project_id.clone(),
+                }))
+                .await
+                .map_err(SdkError::status("list machines"))?
+                .into_inner()
+                .machine)
+        })
+        .await
+    }
+
+    pub async fn list_instances(&self) -> Result<Vec<Instance>, SdkError> {
+        self.retry(|mut client| async move {
+            Ok(client
+                .list_instances(Request::new(ListInstancesRequest {
+                    project_id: self.project_id.clone(),
+                }))
+                .await
+                .map_err(SdkError::status("list instances"))?
+                .into_inner()
+                .instance)
+        })
+        .await
+    }
+
+    pub async fn list_images(&self) -> Result<Vec<OsImageMetadata>, SdkError> {
+        self.retry(|mut client| async move {
+            Ok(client
+                .list_image_metadata(Request::new(ListImageMetadataRequest {
+                    project_id: self.project_id.clone(),
+                }))
+                .await
+                .map_err(SdkError::status("list images"))?
+                .into_inner()
+                .metadata)
+        })
+        .await
+    }
+
+    pub async fn get_image(&self, image_id: &str) -> Result<OsImageMetadata, SdkError> {
+        self.retry(|mut client| async move {
+            client
+                .get_image_metadata(Request::new(GetImageMetadataRequest {
+                    image_id: String::from(image_id),
+                }))
+                .await
+                .map_err(SdkError::status("get image"))?
+                .into_inner()
+                .image
+                .ok_or_else(|| SdkError::NotFound {
+                    kind: "image",
+                    id: String::from(image_id),
+                })
+        })
+        .await
+    }
+
+    pub async fn get_file_metadata(&self, storage_path: &str) -> Result<FileMetadata, SdkError> {
+        self.retry(|mut client| async move {
+            client
+                .get_file_metadata(Request::new(GetFileMetadataRequest {
+                    file_path: String::from(storage_path),
+                }))
+                .await
+                .map_err(SdkError::status("get file metadata"))?
+                .into_inner()
+                .metadata
+                .ok_or_else(|| SdkError::NotFound {
+                    kind: "file",
+                    id: String::from(storage_path),
+                })
+        })
+        .await
+    }
+
+    pub async fn create_machine(
+        &self,
+        image_id: &str,
+        resources: Resources,
+    ) -> Result<Machine, SdkError> {
+        self.client()
+            .create_machine(Request::new(CreateMachineRequest {
+                image_id: String::from(image_id),
+                resources: Some(resources),
+                project_id: self.project_id.clone(),
+            }))
+            .await
+            .map_err(SdkError::status("create machine"))?
+            .into_inner()
+            .machine
+            .ok_or(SdkError::MissingField("machine"))
+    }
+
+    pub async fn provision_instance(&self, machine_id: &str) -> Result<Instance, SdkError> {
+        self.client()
+            .provision_instance(Request::new(ProvisionInstanceRequest {
+                machine_id: String::from(machine_id),
+            }))
+            .await
+            .map_err(SdkError::status("provision instance"))?
+            .into_inner()
+            .instance
+            .ok_or(SdkError::MissingField("instance"))
+    }
+
+    pub async fn start_instance(&self, instance_id: &str) -> Result<(), SdkError> {
+        self.retry(|mut client| async move {
+            client
+                .start_instance(Request::new(StartInstanceRequest {
+                    instance_id: String::from(instance_id),
+                }))
+                .await
+                .map_err(SdkError::status("start instance"))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn stop_instance(&self, instance_id: &str) -> Result<(), SdkError> {
+        self.retry(|mut client| async move {
+            client
+                .stop_instance(Request::new(StopInstanceRequest {
+                    instance_id: String::from(instance_id),
+                }))
+                .await
+                .map_err(SdkError::status("stop instance"))?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Uploads the file at `local_path` to `storage_path` on the data center
+    pub async fn upload_file(
+        &self,
+        local_path: impl AsRef<Path>,
+        storage_path: &str,
+        options: &TransferOptions,
+    ) -> Result<FileMetadata, SdkError> {
+        let local_path = local_path.as_ref();
+        let file_size = file_size(local_path)?;
+        let file_metadata = self
+            .retry(|mut client| async move {
+                client
+                    .create_file_metadata(Request::new(CreateFileMetadataRequest {
+                        file_path: String::from(storage_path),
+                        file_size,
+                        project_id: self.project_id.clone(),
+                    }))
+                    .await
+                    .map_err(SdkError::status("create file metadata"))?
+                    .into_inner()
+                    .metadata
+                    .ok_or(SdkError::MissingField("metadata"))
+            })
+            .await?;
+        self.send_file(local_path, &file_metadata, options).await?;
+
+        Ok(file_metadata)
+    }
+
+    /// Downloads the file stored at `storage_path` on the data center to `local_path`
+    pub async fn download_file(
+        &self,
+        storage_path: &str,
+        local_path: impl AsRef<Path>,
+        options: &TransferOptions,
+    ) -> Result<FileMetadata, SdkError> {
+        let file_metadata = self.get_file_metadata(storage_path).await?;
+        self.receive_file(&file_metadata, local_path.as_ref(), options)
+            .await?;
+
+        Ok(file_metadata)
+    }
+
+    /// Uploads the image at `local_path`, storing it at `storage_path` on the data center. The
+    /// contents of an existing image are replaced when `image_id` is given
+    pub async fn upload_image(
+        &self,
+        local_path: impl AsRef<Path>,
+        storage_path: &str,
+        image_id: Option<&str>,
+        options: &TransferOptions,
+    ) -> Result<OsImageMetadata, SdkError> {
+        let local_path = local_path.as_ref();
+        let file_size = file_size(local_path)?;
+        let image = match image_id {
+            Some(image_id) => self.get_image(image_id).await?,
+            None => self
+                .client()
+                .create_image_metadata(Request::new(CreateImageMetadataRequest {
+                    file_size,
+                    destination_file_path: String::from(storage_path),
+                    project_id: self.project_id.clone(),
+                }))
+                .await
+                .map_err(SdkError::status("create image"))?
+                .into_inner()
+                .os_image_metadata
+                .ok_or(SdkError::MissingField("os_image_metadata"))?,
+        };
+        let file_metadata = image
+            .file_metadata
+            .as_ref()
+            .ok_or(SdkError::MissingField("file_metadata"))?;
+        self.send_file(local_path, file_metadata, options).await?;
+
+        Ok(image)
+    }
+
+    /// Downloads the image `image_id` to `local_path`
+    pub async fn download_image(
+        &self,
+        image_id: &str,
+        local_path: impl AsRef<Path>,
+        options: &TransferOptions,
+    ) -> Result<OsImageMetadata, SdkError> {
+        let image = self.get_image(image_id).await?;
+        let file_metadata = image
+            .file_metadata
+            .as_ref()
+            .ok_or(SdkError::MissingField("file_metadata"))?;
+        self.receive_file(file_metadata, local_path.as_ref(), options)
+            .await?;
+
+        Ok(image)
+    }
+
+    /// Uploads the image at `local_path` and starts an instance of a machine booting it
+    pub async fn up_local_image(
+        &self,
+        local_path: impl AsRef<Path>,
+        storage_path: &str,
+        resources: Resources,
+        options: &TransferOptions,
+    ) -> Result<Instance, SdkError> {
+        let image = self
+            .upload_image(local_path, storage_path, None, options)
+            .await?;
+        let machine = self.create_machine(&image.image_id, resources).await?;
+
+        self.provision_instance(&machine.machine_id).await
+    }
+
+    /// Uploads the file at `local_path` as ranges sent over concurrent streams
+    async fn send_file(
+        &self,
+        local_path: &Path,
+        file_metadata: &FileMetadata,
+        options: &TransferOptions,
+    ) -> Result<(), SdkError> {
+        let ranges = transfer::split_ranges(file_metadata.file_size, options.parallelism);
+
+        self.retry(|client| {
+            let ranges = ranges.clone();
+
+            async move {
+                options.progress.started(file_metadata.file_size);
+                try_join_all(ranges.into_iter().enumerate().map(|(index, range)| {
+                    let mut client = self.stream_client(&client, index);
+
+                    async move {
+                        let source = File::open(local_path).map_err(SdkError::io(local_path))?;
+                        let mut chunks = transfer::stream_chunks(
+                            &file_metadata.file_path,
+                            source,
+                            range,
+                            options.progress.clone(),
+                        )
+                        .map_err(SdkError::io(local_path))?;
+                        // Chunks are sent until one fails to be read, then held open so the
+                        // upload is dropped, aborting it, rather than ended, which would store
+                        // a truncated file
+                        let (sender, sent) = mpsc::channel(1);
+                        let (failed, read_failed) = oneshot::channel();
+                        tokio::spawn(async move {
+                            while let Some(chunk) = chunks.next().await {
+                                match chunk {
+                                    Ok(chunk) => {
+                                        if sender.send(chunk).await.is_err() {
+                                            return;
+                                        }
+                                    }
+                                    Err(error) => {
+                                        let _ = failed.send(error);
+                                        sender.closed().await;
+
+                                        return;
+                                    }
+                                }
+                            }
+                        });
+
+                        let upload = client.upload_file(Request::new(ReceiverStream::new(sent)));
+
+                        tokio::select! {
+                            biased;
+                            Ok(error) = read_failed => Err(SdkError::io(local_path)(error)),
+                            response = upload => response.map_err(SdkError::status("upload file")),
+                        }
+                    }
+                }))
+                .await?;
+                options.progress.finished();
+
+                Ok(())
+            }
+        })
+        .await
+    }
+
+    /// Downloads the file to `local_path` as ranges received over concurrent streams
+    async fn receive_file(
+        &self,
+        file_metadata: &FileMetadata,
+        local_path: &Path,
+        options: &TransferOptions,
+    ) -> Result<(), SdkError> {
+        let ranges = transfer::split_ranges(file_metadata.file_size, options.parallelism);
+
+        self.retry(|client| {
+            let ranges = ranges.clone();
+
+            async move {
+                tokio::fs::File::create(local_path)
+                    .await
+                    .map_err(SdkError::io(local_path))?
+                    .set_len(file_metadata.file_size)
+                    .await
+                    .map_err(SdkError::io(local_path))?;
+                options.progress.started(file_metadata.file_size);
+                let received: u64 =
+                    try_join_all(ranges.into_iter().enumerate().map(|(index, range)| {
+                        let client = self.stream_client(&client, index);
+
+                        receive_range(client, file_metadata, range, local_path, options)
+                    }))
+                    .await?
+                    .into_iter()
+                    .sum();
+
+                if received < file_metadata.file_size {
+                    return Err(SdkError::Incomplete {
+                        expected: file_metadata.file_size,
+                        received,
+                    });
+                }
+
+                options.progress.finished();
+
+                Ok(())
+            }
+        })
+        .await
+    }
+
+    /// Client for the `index`th concurrent stream of a transfer. Every stream after the first
+    /// gets a connection of its own when the sdk knows its endpoint, so streams are sent and
+    /// received in parallel rather than taking turns on a single connection
+    fn stream_client(&self, client: &Client, index: usize) -> Client {
+        match &self.endpoint {
+            Some(endpoint) if index > 0 => {
+                DataCenterClient::with_interceptor(endpoint.connect_lazy(), self.token.clone())
+            }
+            _ => client.clone(),
+        }
+    }
+
+    /// Makes `call` until it succeeds, fails for a reason other than the data center being
+    /// unreachable, or the retry policy runs out of attempts
+    async fn retry<T, F, R>(&self, call: F) -> Result<T, SdkError>
+    where
+        F: Fn(Client) -> R,
+        R: Future<Output = Result<T, SdkError>>,
+    {
+        let mut backoff = self.retry_policy.initial_backoff;
+        let mut attempt = 1;
+
+        loop {
+            match call(self.client()).await {
+                Err(error) if is_transient(&error) && attempt < self.retry_policy.attempts => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Downloads `range` of the file into the same range of `local_path`, returning the number of
+/// bytes received
+async fn receive_range(
+    mut client: Client,
+    file_metadata: &FileMetadata,
+    range: Range<u64>,
+    local_path: &Path,
+    options: &TransferOptions,
+) -> Result<u64, SdkError> {
+    let mut stream = client
+        .download_file(Request::new(DownloadFileRequest {
+            source_path: file_metadata.file_path.clone(),
+            start: range.start,
+            end: range.end,
+        }))
+        .await
+        .map_err(SdkError::status("download file"))?
+        .into_inner();
+    let mut destination = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(local_path)
+        .await
+        .map_err(SdkError::io(local_path))?;
+    let mut received = 0;
+
+    while let Some(message) = stream
+        .message()
+        .await
+        .map_err(SdkError::status("download file"))?
+    {
+        let chunk = message.chunk.ok_or(SdkError::MissingField("chunk"))?;
+        destination
+            .seek(SeekFrom::Start(chunk.start))
+            .await
+            .map_err(SdkError::io(local_path))?;
+        destination
+            .write_all(&chunk.data)
+            .await
+            .map_err(SdkError::io(local_path))?;
+        received += chunk.data.len() as u64;
+        options.progress.advanced(chunk.data.len() as u64);
+    }
+
+    destination
+        .flush()
+        .await
+        .map_err(SdkError::io(local_path))?;
+
+    Ok(received)
+}
+
+/// Whether the call might succeed if made again
+fn is_transient(error: &SdkError) -> bool {
+    match error {
+        SdkError::Status { status, .. } => is_unreachable(status),
+        SdkError::Incomplete { .. } => true,
+        _ => false,
+    }
+}
+
+fn file_size(path: &Path) -> Result<u64, SdkError> {
+    Ok(std::fs::metadata(path)
+        .map_err(SdkError::io(PathBuf::from(path)))?
+        .len())
+}
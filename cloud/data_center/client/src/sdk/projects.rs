@@ -0,0 +1,162 @@
+use auth::BearerToken;
+use telemetry::Traced;
+use tonic::{service::interceptor::InterceptedService, transport::Channel, Request};
+
+use super::{DataCenterSdk, SdkError};
+use crate::protos::data_center::{
+    projects_client::ProjectsClient, CreateProjectRequest, DeleteProjectRequest, GetQuotaRequest,
+    GetUsageRequest, ListProjectsRequest, ListRoleBindingsRequest, Project, Quota,
+    RemoveRoleBindingRequest, Role, RoleBinding, SetQuotaRequest, SetRoleBindingRequest, Usage,
+};
+
+impl DataCenterSdk {
+    /// Creates a project, making the caller its admin
+    pub async fn create_project(&self, project_id: &str) -> Result<Project, SdkError> {
+        self.projects_client()
+            .create_project(Request::new(CreateProjectRequest {
+                project_id: String::from(project_id),
+            }))
+            .await
+            .map_err(SdkError::status("create project"))?
+            .into_inner()
+            .project
+            .ok_or(SdkError::MissingField("project"))
+    }
+
+    /// Projects the caller holds a role in
+    pub async fn list_projects(&self) -> Result<Vec<Project>, SdkError> {
+        self.retry(|_| async move {
+            Ok(self
+                .projects_client()
+                .list_projects(Request::new(ListProjectsRequest {}))
+                .await
+                .map_err(SdkError::status("list projects"))?
+                .into_inner()
+                .project)
+        })
+        .await
+    }
+
+    /// Deletes a project holding no resources
+    pub async fn delete_project(&self, project_id: &str) -> Result<(), SdkError> {
+        self.projects_client()
+            .delete_project(Request::new(DeleteProjectRequest {
+                project_id: String::from(project_id),
+            }))
+            .await
+            .map_err(SdkError::status("delete project"))?;
+
+        Ok(())
+    }
+
+    /// Grants `role` in the project to `subject`, replacing any role it held there
+    pub async fn set_role_binding(
+        &self,
+        project_id: &str,
+        subject: &str,
+        role: Role,
+    ) -> Result<RoleBinding, SdkError> {
+        self.projects_client()
+            .set_role_binding(Request::new(SetRoleBindingRequest {
+                binding: Some(RoleBinding {
+                    project_id: String::from(project_id),
+                    subject: String::from(subject),
+                    role: role as i32,
+                }),
+            }))
+            .await
+            .map_err(SdkError::status("set role binding"))?
+            .into_inner()
+            .binding
+            .ok_or(SdkError::MissingField("binding"))
+    }
+
+    /// Removes any role `subject` held in the project
+    pub async fn remove_role_binding(
+        &self,
+        project_id: &str,
+        subject: &str,
+    ) -> Result<(), SdkError> {
+        self.projects_client()
+            .remove_role_binding(Request::new(RemoveRoleBindingRequest {
+                project_id: String::from(project_id),
+                subject: String::from(subject),
+            }))
+            .await
+            .map_err(SdkError::status("remove role binding"))?;
+
+        Ok(())
+    }
+
+    pub async fn list_role_bindings(&self, project_id: &str) -> Result<Vec<RoleBinding>, SdkError> {
+        self.retry(|_| async move {
+            Ok(self
+                .projects_client()
+                .list_role_bindings(Request::new(ListRoleBindingsRequest {
+                    project_id: String::from(project_id),
+                }))
+                .await
+                .map_err(SdkError::status("list role bindings"))?
+                .into_inner()
+                .binding)
+        })
+        .await
+    }
+
+    /// Quota of the project, the data center's default quota unless one was set
+    pub async fn get_quota(&self, project_id: &str) -> Result<Quota, SdkError> {
+        self.retry(|_| async move {
+            self.projects_client()
+                .get_quota(Request::new(GetQuotaRequest {
+                    project_id: String::from(project_id),
+                }))
+                .await
+                .map_err(SdkError::status("get quota"))?
+                .into_inner()
+                .quota
+                .ok_or(SdkError::MissingField("quota"))
+        })
+        .await
+    }
+
+    /// Replaces the quota of the project, which only admins of the data center may do
+    pub async fn set_quota(&self, project_id: &str, quota: Quota) -> Result<Quota, SdkError> {
+        self.retry(|_| {
+            let quota = quota.clone();
+
+            async move {
+                self.projects_client()
+                    .set_quota(Request::new(SetQuotaRequest {
+                        project_id: String::from(project_id),
+                        quota: Some(quota),
+                    }))
+                    .await
+                    .map_err(SdkError::status("set quota"))?
+                    .into_inner()
+                    .quota
+                    .ok_or(SdkError::MissingField("quota"))
+            }
+        })
+        .await
+    }
+
+    /// What the project uses of each resource limited by its quota
+    pub async fn get_usage(&self, project_id: &str) -> Result<Usage, SdkError> {
+        self.retry(|_| async move {
+            self.projects_client()
+                .get_usage(Request::new(GetUsageRequest {
+                    project_id: String::from(project_id),
+                }))
+                .await
+                .map_err(SdkError::status("get usage"))?
+                .into_inner()
+                .usage
+                .ok_or(SdkError::MissingField("usage"))
+        })
+        .await
+    }
+
+    fn projects_client(&self) -> ProjectsClient<InterceptedService<Channel, Traced<BearerToken>>> {
+        ProjectsClient::with_interceptor(self.channel.clone(), self.token.clone())
+    }
+}
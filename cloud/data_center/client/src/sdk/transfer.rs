@@ -0,0 +1,168 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+    sync::Arc,
+};
+
+use tokio_stream::Stream;
+
+use crate::protos::data_center::{Chunk, UploadFileRequest};
+
+pub(crate) const ONE_MB: usize = 1048576;
+/// Smallest range a file is split into, smaller files are sent over a single stream
+const MIN_RANGE_SIZE: u64 = 8 * ONE_MB as u64;
+
+/// Hooks told how a transfer is progressing. A transfer that is retried starts over, calling
+/// `started` again
+pub trait Progress: Send + Sync {
+    /// Transfer of `total_bytes` started
+    fn started(&self, _total_bytes: u64) {}
+
+    /// Another `bytes` were transferred
+    fn advanced(&self, _bytes: u64) {}
+
+    /// Every byte was transferred
+    fn finished(&self) {}
+}
+
+/// Progress that is ignored
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// Options for transferring files
+#[derive(Clone)]
+pub struct TransferOptions {
+    pub progress: Arc<dyn Progress>,
+    /// Most streams a file is transferred over at once, each carrying a range of the file
+    pub parallelism: usize,
+}
+
+impl Default for TransferOptions {
+    fn default() -> TransferOptions {
+        TransferOptions {
+            progress: Arc::new(NoProgress),
+            parallelism: 4,
+        }
+    }
+}
+
+impl TransferOptions {
+    pub fn with_progress(progress: Arc<dyn Progress>) -> TransferOptions {
+        TransferOptions {
+            progress,
+            ..TransferOptions::default()
+        }
+    }
+
+    pub fn with_parallelism(mut self, parallelism: usize) -> TransferOptions {
+        self.parallelism = parallelism;
+
+        self
+    }
+}
+
+/// Splits a file of `file_size` bytes into at most `parallelism` ranges of whole chunks, each
+/// at least `MIN_RANGE_SIZE` bytes
+pub fn split_ranges(file_size: u64, parallelism: usize) -> Vec<Range<u64>> {
+    let chunk_size = ONE_MB as u64;
+    let range_size = file_size
+        .div_ceil(parallelism.max(1) as u64)
+        .div_ceil(chunk_size)
+        .saturating_mul(chunk_size)
+        .max(MIN_RANGE_SIZE);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < file_size {
+        let end = (start + range_size).min(file_size);
+        ranges.push(start..end);
+        start = end;
+    }
+
+    if ranges.is_empty() {
+        ranges.push(0..0);
+    }
+
+    ranges
+}
+
+/// Reads a file as the upload requests writing it to `write_path`, ending with the error of a
+/// failed read
+struct ChunkedReader<T>
+where
+    T: Read,
+{
+    source: T,
+    write_path: String,
+    position: u64,
+    progress: Arc<dyn Progress>,
+}
+
+impl<T> ChunkedReader<T>
+where
+    T: Read,
+{
+    fn new(
+        source: T,
+        write_path: String,
+        position: u64,
+        progress: Arc<dyn Progress>,
+    ) -> ChunkedReader<T> {
+        ChunkedReader {
+            source,
+            write_path,
+            position,
+            progress,
+        }
+    }
+}
+
+impl<T> Iterator for ChunkedReader<T>
+where
+    T: Read,
+{
+    type Item = io::Result<UploadFileRequest>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buffer = vec![0; ONE_MB];
+
+        match self.source.read(&mut buffer) {
+            Ok(0) => None,
+            Ok(count) => {
+                let current_position = self.position;
+                self.position += count as u64;
+                buffer.truncate(count);
+                self.progress.advanced(count as u64);
+
+                Some(Ok(UploadFileRequest {
+                    file_path: self.write_path.clone(),
+                    chunk: Some(Chunk {
+                        start: current_position,
+                        end: current_position + count as u64,
+                        data: buffer,
+                    }),
+                }))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+/// Upload requests writing the `range` of `source` to the same range of `write_path`
+pub(crate) fn stream_chunks(
+    write_path: &str,
+    mut source: File,
+    range: Range<u64>,
+    progress: Arc<dyn Progress>,
+) -> io::Result<impl Stream<Item = io::Result<UploadFileRequest>>> {
+    source.seek(SeekFrom::Start(range.start))?;
+
+    Ok(tokio_stream::iter(ChunkedReader::new(
+        source.take(range.end - range.start),
+        String::from(write_path),
+        range.start,
+        progress,
+    )))
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use data_center_client::progress::{progress_line, summary, Direction};
+
+#[test]
+fn progress_lines_show_throughput_and_time_left() {
+    let line = progress_line(
+        Direction::Upload,
+        12 * 1048576,
+        48 * 1048576,
+        Duration::from_secs(2),
+    );
+
+    assert_eq!(
+        line,
+        "Uploading 12.00 MiB of 48.00 MiB (25%) at 6.00 MiB/s, 6s left"
+    );
+}
+
+#[test]
+fn progress_lines_handle_transfers_that_have_not_moved() {
+    let line = progress_line(Direction::Download, 0, 1048576, Duration::from_secs(1));
+
+    assert_eq!(
+        line,
+        "Downloading 0 B of 1.00 MiB (0%) at 0 B/s, unknown time left"
+    );
+}
+
+#[test]
+fn summaries_show_the_average_rate() {
+    let line = summary(Direction::Download, 48 * 1048576, Duration::from_secs(8));
+
+    assert_eq!(line, "Downloaded 48.00 MiB in 8.0s (6.00 MiB/s)");
+}
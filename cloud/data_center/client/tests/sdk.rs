@@ -0,0 +1,363 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use data_center_client::{
+    protos::data_center::{CreateMachineRequest, DownloadFileRequest},
+    sdk::{split_ranges, DataCenterSdk, Progress, SdkError, TransferOptions},
+};
+use data_center_service::{
+    data_center::{LocalDataCenter, FILES_DIRECTORY},
+    protos::data_center::data_center_server::DataCenterServer,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Code, Request};
+
+async fn serve(listener: TcpListener, storage_root: PathBuf) {
+    Server::builder()
+        .add_service(DataCenterServer::new(
+            LocalDataCenter::new(String::from("dc-1")).with_storage_root(storage_root),
+        ))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .expect("Should serve data center");
+}
+
+async fn start_data_center(storage_root: &Path) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    tokio::spawn(serve(listener, storage_root.to_path_buf()));
+
+    address
+}
+
+#[derive(Default)]
+struct CountingProgress {
+    total: AtomicU64,
+    transferred: AtomicU64,
+    finished: AtomicU64,
+}
+
+impl Progress for CountingProgress {
+    fn started(&self, total_bytes: u64) {
+        self.total.store(total_bytes, Ordering::SeqCst);
+        self.transferred.store(0, Ordering::SeqCst);
+    }
+
+    fn advanced(&self, bytes: u64) {
+        self.transferred.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn finished(&self) {
+        self.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn files_round_trip_with_progress() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("local");
+    let downloaded_path = directory.path().join("downloaded");
+    let contents: Vec<u8> = (0..3 * 1048576 + 17).map(|index| index as u8).collect();
+    fs::write(&local_path, &contents).expect("Should write file");
+    let progress = Arc::new(CountingProgress::default());
+    let options = TransferOptions::with_progress(progress.clone());
+
+    let metadata = sdk
+        .upload_file(&local_path, "stored", &options)
+        .await
+        .expect("Should upload file");
+    assert_eq!(metadata.file_size, contents.len() as u64);
+    assert_eq!(
+        progress.transferred.load(Ordering::SeqCst),
+        metadata.file_size
+    );
+
+    sdk.download_file("stored", &downloaded_path, &options)
+        .await
+        .expect("Should download file");
+
+    assert_eq!(fs::read(&downloaded_path).unwrap(), contents);
+    assert_eq!(progress.total.load(Ordering::SeqCst), metadata.file_size);
+    assert_eq!(
+        progress.transferred.load(Ordering::SeqCst),
+        metadata.file_size
+    );
+    assert_eq!(progress.finished.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn large_files_round_trip_over_several_streams() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("local");
+    let storage_path = directory.path().join(FILES_DIRECTORY).join("stored");
+    let downloaded_path = directory.path().join("downloaded");
+    let contents: Vec<u8> = (0..20 * 1048576 + 5)
+        .map(|index: u32| (index % 251) as u8)
+        .collect();
+    fs::write(&local_path, &contents).expect("Should write file");
+    fs::create_dir_all(directory.path().join(FILES_DIRECTORY)).expect("Should create files");
+    fs::write(&storage_path, vec![7; 30 * 1048576]).expect("Should write stale file");
+    let progress = Arc::new(CountingProgress::default());
+    let options = TransferOptions::with_progress(progress.clone()).with_parallelism(4);
+
+    sdk.upload_file(&local_path, "stored", &options)
+        .await
+        .expect("Should upload file");
+    assert_eq!(fs::read(&storage_path).unwrap(), contents);
+    assert_eq!(
+        progress.transferred.load(Ordering::SeqCst),
+        contents.len() as u64
+    );
+
+    sdk.download_file("stored", &downloaded_path, &options)
+        .await
+        .expect("Should download file");
+
+    assert_eq!(fs::read(&downloaded_path).unwrap(), contents);
+    assert_eq!(
+        progress.transferred.load(Ordering::SeqCst),
+        contents.len() as u64
+    );
+}
+
+#[test]
+fn files_are_split_into_ranges_of_whole_chunks() {
+    let mb = 1048576;
+
+    assert_eq!(split_ranges(0, 4), vec![0..0]);
+    assert_eq!(split_ranges(3 * mb, 4), vec![0..3 * mb]);
+    assert_eq!(split_ranges(64 * mb, 1), vec![0..64 * mb]);
+    assert_eq!(
+        split_ranges(20 * mb + 5, 4),
+        vec![0..8 * mb, 8 * mb..16 * mb, 16 * mb..20 * mb + 5]
+    );
+    assert_eq!(
+        split_ranges(100 * mb, 4),
+        vec![
+            0..25 * mb,
+            25 * mb..50 * mb,
+            50 * mb..75 * mb,
+            75 * mb..100 * mb
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ranges_outside_of_files_are_rejected() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("local");
+    fs::write(&local_path, b"contents").expect("Should write file");
+    sdk.upload_file(&local_path, "stored", &TransferOptions::default())
+        .await
+        .expect("Should upload file");
+
+    let status = sdk
+        .client()
+        .download_file(Request::new(DownloadFileRequest {
+            source_path: String::from("stored"),
+            start: 4,
+            end: 9,
+        }))
+        .await
+        .expect_err("Should reject range");
+
+    assert_eq!(status.code(), Code::OutOfRange);
+}
+
+#[tokio::test]
+async fn files_that_cannot_be_read_fail_their_download() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("local");
+    fs::write(&local_path, vec![7; 10_000]).expect("Should write file");
+    sdk.upload_file(&local_path, "stored", &TransferOptions::default())
+        .await
+        .expect("Should upload file");
+    fs::write(
+        directory.path().join(FILES_DIRECTORY).join("stored"),
+        [7; 100],
+    )
+    .expect("Should shrink stored file");
+
+    let error = sdk
+        .download_file(
+            "stored",
+            directory.path().join("downloaded"),
+            &TransferOptions::default(),
+        )
+        .await
+        .expect_err("Should fail to download");
+
+    assert_eq!(error.code(), Some(Code::Internal));
+}
+
+#[tokio::test]
+async fn machines_without_resources_are_invalid() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+
+    let status = sdk
+        .client()
+        .create_machine(Request::new(CreateMachineRequest::default()))
+        .await
+        .expect_err("Should reject machine");
+
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn images_are_uploaded_and_fetched() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("image.qcow2");
+    let storage_path = directory.path().join(FILES_DIRECTORY).join("stored.qcow2");
+    fs::write(&local_path, b"image").expect("Should write image");
+
+    let image = sdk
+        .upload_image(
+            &local_path,
+            "stored.qcow2",
+            None,
+            &TransferOptions::default(),
+        )
+        .await
+        .expect("Should upload image");
+    let fetched = sdk
+        .get_image(&image.image_id)
+        .await
+        .expect("Should get image");
+
+    assert_eq!(fetched, image);
+    assert_eq!(sdk.list_images().await.unwrap(), vec![image]);
+    assert_eq!(fs::read(&storage_path).unwrap(), b"image");
+}
+
+#[tokio::test]
+async fn files_are_only_stored_within_the_files_directory() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(&directory.path().join("storage")).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+    let local_path = directory.path().join("local");
+    fs::write(&local_path, b"contents").expect("Should write file");
+
+    for storage_path in [
+        "",
+        "/tmp/stored",
+        "../stored",
+        "nested/../../stored",
+        "../instances/stored",
+    ] {
+        let error = sdk
+            .upload_file(&local_path, storage_path, &TransferOptions::default())
+            .await
+            .expect_err("Should refuse path");
+        assert_eq!(error.code(), Some(Code::InvalidArgument), "{storage_path}");
+    }
+
+    sdk.upload_file(&local_path, "nested/stored", &TransferOptions::default())
+        .await
+        .expect("Should upload file");
+    assert_eq!(
+        fs::read(
+            directory
+                .path()
+                .join("storage")
+                .join(FILES_DIRECTORY)
+                .join("nested")
+                .join("stored")
+        )
+        .unwrap(),
+        b"contents"
+    );
+    assert!(!directory.path().join("stored").exists());
+}
+
+#[tokio::test]
+async fn missing_resources_are_not_found() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+
+    let error = sdk
+        .get_image("missing")
+        .await
+        .expect_err("Should not find image");
+
+    assert!(matches!(error, SdkError::NotFound { kind: "image", .. }));
+    assert_eq!(error.code(), Some(Code::NotFound));
+}
+
+#[tokio::test]
+async fn local_files_that_cannot_be_read_are_reported() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let address = start_data_center(directory.path()).await;
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+
+    let error = sdk
+        .upload_file("/nonexistent/file", "stored", &TransferOptions::default())
+        .await
+        .expect_err("Should fail to read file");
+
+    assert!(matches!(error, SdkError::Io { .. }));
+    assert_eq!(error.code(), None);
+}
+
+#[tokio::test]
+async fn calls_are_retried_until_the_data_center_is_reachable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    drop(listener);
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        serve(
+            TcpListener::bind(address)
+                .await
+                .expect("Should bind listener"),
+            PathBuf::from("."),
+        )
+        .await;
+    });
+
+    assert!(sdk
+        .list_machines()
+        .await
+        .expect("Should list machines")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn unreachable_data_centers_are_unavailable() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    drop(listener);
+    let sdk = DataCenterSdk::connect(&address.to_string()).expect("Should create sdk");
+
+    let error = sdk
+        .list_instances()
+        .await
+        .expect_err("Should fail to reach data center");
+
+    assert_eq!(error.code(), Some(Code::Unavailable));
+}
@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use grpc_tls::TlsFiles;
+use telemetry::LogFormat;
+
+use crate::{
+    data_center::DEFAULT_CAPACITY,
+    instances::ShutdownPolicy,
+    protos::data_center::{Quota, Resources, ServiceType},
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "data_center_service")]
+#[command(about = "Data center service", long_about = None)]
+pub struct Cli {
+    /// Address to serve the data center on
+    #[arg(long, env = "DATA_CENTER_ADDRESS", default_value = "[::1]:50052")]
+    pub address: String,
+    /// Host name other services reach this data center at, defaults to the address
+    #[arg(long, env = "DATA_CENTER_HOST_NAME")]
+    pub host_name: Option<String>,
+    /// Stable id of the data center. When not provided, the id generated by the first start is
+    /// kept in the storage root and reused. Names of the resources the data center holds start
+    /// with dc/<id>/
+    #[arg(long, env = "DATA_CENTER_ID", value_parser = parse_data_center_id)]
+    pub id: Option<String>,
+    /// Region the data center is located in
+    #[arg(long, env = "DATA_CENTER_REGION", default_value = "")]
+    pub region: String,
+    /// Zone within the region the data center is located in
+    #[arg(long, env = "DATA_CENTER_ZONE", default_value = "")]
+    pub zone: String,
+    /// Label describing the data center as key=value, may be repeated
+    #[arg(long = "label", value_parser = parse_label)]
+    pub labels: Vec<(String, String)>,
+    /// Services the data center offers, defaults to every service
+    #[arg(long = "service", value_parser = parse_service_type)]
+    pub services: Vec<ServiceType>,
+    /// Cpu architectures the data center runs machines on, defaults to the host architecture
+    #[arg(long = "architecture")]
+    pub architectures: Vec<String>,
+    /// Endpoint of a resolver to register with, e.g. http://[::1]:50051, may be repeated to
+    /// fail over between the resolvers of a cluster
+    #[arg(
+        long = "resolver",
+        env = "DATA_CENTER_RESOLVERS",
+        value_delimiter = ','
+    )]
+    pub resolvers: Vec<String>,
+    /// Seconds between heartbeats sent to the resolver
+    #[arg(
+        long,
+        env = "DATA_CENTER_HEARTBEAT_INTERVAL_SECS",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub heartbeat_interval_secs: u64,
+    /// Endpoint of another data center to join the gossip membership through, e.g.
+    /// http://[::1]:50053, may be repeated
+    #[arg(long = "seed", env = "DATA_CENTER_SEEDS", value_delimiter = ',')]
+    pub seeds: Vec<String>,
+    /// Milliseconds between probes of the other members of the gossip membership
+    #[arg(long, env = "DATA_CENTER_GOSSIP_INTERVAL_MS", default_value_t = 1000)]
+    pub gossip_interval_ms: u64,
+    /// Pem certificate to serve tls with and to present to resolvers and other data centers
+    #[arg(long, env = "DATA_CENTER_TLS_CERTIFICATE", requires = "tls_key")]
+    pub tls_certificate: Option<PathBuf>,
+    /// Pem private key of the tls certificate
+    #[arg(long, env = "DATA_CENTER_TLS_KEY", requires = "tls_certificate")]
+    pub tls_key: Option<PathBuf>,
+    /// Pem certificate authority resolvers and other data centers are verified against, and
+    /// that clients must present a certificate signed by
+    #[arg(long, env = "DATA_CENTER_TLS_CA_CERTIFICATE")]
+    pub tls_ca_certificate: Option<PathBuf>,
+    /// File holding the base64 secret bearer tokens of callers are signed with, callers aren't
+    /// authenticated when neither this nor an api keys file is provided
+    #[arg(long, env = "DATA_CENTER_AUTH_SECRET_FILE")]
+    pub auth_secret_file: Option<PathBuf>,
+    /// File holding the digests of the api keys callers may use as bearer tokens
+    #[arg(long, env = "DATA_CENTER_API_KEYS_FILE")]
+    pub api_keys_file: Option<PathBuf>,
+    /// Subject holding the admin role in every project, may be repeated
+    #[arg(long = "admin", env = "DATA_CENTER_ADMINS", value_delimiter = ',')]
+    pub admins: Vec<String>,
+    /// Vcpus the data center offers the started instances of machines
+    #[arg(long, env = "DATA_CENTER_CAPACITY_VCPUS", default_value_t = DEFAULT_CAPACITY.vcpus)]
+    pub capacity_vcpus: u32,
+    /// Ram in mb the data center offers the started instances of machines
+    #[arg(long, env = "DATA_CENTER_CAPACITY_RAM_MB", default_value_t = DEFAULT_CAPACITY.ram_mb)]
+    pub capacity_ram_mb: u32,
+    /// Disk in mb the data center offers machines
+    #[arg(
+        long,
+        env = "DATA_CENTER_CAPACITY_DISK_MB",
+        default_value_t = DEFAULT_CAPACITY.disk_mb
+    )]
+    pub capacity_disk_mb: u32,
+    /// Most vcpus the started instances of a project may use, unless the project was given a
+    /// quota of its own. Unlimited when not provided
+    #[arg(long, env = "DATA_CENTER_QUOTA_VCPUS")]
+    pub quota_vcpus: Option<u32>,
+    /// Most ram in mb the started instances of a project may use
+    #[arg(long, env = "DATA_CENTER_QUOTA_RAM_MB")]
+    pub quota_ram_mb: Option<u32>,
+    /// Most disk in mb the machines of a project may use
+    #[arg(long, env = "DATA_CENTER_QUOTA_DISK_MB")]
+    pub quota_disk_mb: Option<u32>,
+    /// Most instances of a project that may be started at once
+    #[arg(long, env = "DATA_CENTER_QUOTA_INSTANCES")]
+    pub quota_instances: Option<u32>,
+    /// Most bytes of files and images a project may store
+    #[arg(long, env = "DATA_CENTER_QUOTA_STORAGE_BYTES")]
+    pub quota_storage_bytes: Option<u64>,
+    /// File holding the key the data center signs its registrations with and proves who it is
+    /// to clients, generated when missing. A key only living as long as the process is used
+    /// when not provided, which resolvers refuse while they still hold the previous key
+    #[arg(long, env = "DATA_CENTER_IDENTITY_KEY_FILE")]
+    pub identity_key_file: Option<PathBuf>,
+    /// File the audit log of the mutating calls made to the data center is appended to as json
+    /// lines, kept in memory when not provided. Only admins of the data center may read it
+    #[arg(long, env = "DATA_CENTER_AUDIT_LOG_FILE")]
+    pub audit_log_file: Option<PathBuf>,
+    /// Directory files are stored under, along with the processes of running instances, which
+    /// must be writable for the data center to report itself ready
+    #[arg(long, env = "DATA_CENTER_STORAGE_ROOT", default_value = ".")]
+    pub storage_root: PathBuf,
+    /// What happens to running instances on shutdown, stop to power them down or detach to
+    /// leave them running for the next data center started on the storage root to reattach to
+    #[arg(long, env = "DATA_CENTER_ON_SHUTDOWN", default_value_t = ShutdownPolicy::Stop)]
+    pub on_shutdown: ShutdownPolicy,
+    /// Seconds instances are given to power down when stopped before their hypervisor is killed
+    #[arg(long, env = "DATA_CENTER_STOP_TIMEOUT_SECS", default_value_t = 30)]
+    pub stop_timeout_secs: u64,
+    /// Seconds calls in flight are given to finish on shutdown before they are dropped
+    #[arg(long, env = "DATA_CENTER_DRAIN_TIMEOUT_SECS", default_value_t = 30)]
+    pub drain_timeout_secs: u64,
+    /// Address to serve prometheus metrics on over http at /metrics, not served when not
+    /// provided
+    #[arg(long, env = "DATA_CENTER_METRICS_ADDRESS")]
+    pub metrics_address: Option<String>,
+    /// Format logs are written to standard error in, text or json. Which logs are written is
+    /// filtered with RUST_LOG, info by default
+    #[arg(long, env = "DATA_CENTER_LOG_FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Otlp collector spans are exported to over grpc, e.g. http://localhost:4317, spans aren't
+    /// exported when not provided
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    /// Bearer token the data center presents to resolvers
+    #[arg(long, env = "DATA_CENTER_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+fn parse_label(label: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = label.split_once('=') else {
+        return Err(format!("Label {label} should be formatted as key=value"));
+    };
+
+    Ok((String::from(key), String::from(value)))
+}
+
+fn parse_data_center_id(id: &str) -> Result<String, String> {
+    resource_name::validate_segment(id).map_err(|error| error.to_string())?;
+
+    Ok(String::from(id))
+}
+
+fn parse_service_type(service: &str) -> Result<ServiceType, String> {
+    ServiceType::from_str_name(service).ok_or_else(|| {
+        format!("Unknown service {service}, expected Storage, Compute or OperatingSystemImages")
+    })
+}
+
+impl Cli {
+    /// Resources the data center offers machines
+    pub fn capacity(&self) -> Resources {
+        Resources {
+            ram_mb: self.capacity_ram_mb,
+            disk_mb: self.capacity_disk_mb,
+            vcpus: self.capacity_vcpus,
+        }
+    }
+
+    /// Quota of the projects that weren't given one of their own
+    pub fn default_quota(&self) -> Quota {
+        Quota {
+            vcpus: self.quota_vcpus,
+            ram_mb: self.quota_ram_mb,
+            disk_mb: self.quota_disk_mb,
+            instances: self.quota_instances,
+            storage_bytes: self.quota_storage_bytes,
+        }
+    }
+
+    pub fn tls_files(&self) -> TlsFiles {
+        TlsFiles {
+            ca_certificate: self.tls_ca_certificate.clone(),
+            certificate: self.tls_certificate.clone(),
+            key: self.tls_key.clone(),
+        }
+    }
+}
+
+pub fn parse_cli() -> Cli {
+    Cli::parse()
+}
@@ -0,0 +1,1291 @@
+use core::panic;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use audit::AuditLog;
+use auth::Identity;
+use identity::IdentityKey;
+use metrics::prometheus::{IntCounterVec, Opts};
+use nanoid::nanoid;
+use resource_name::{ResourceKind, ResourceName, ResourceNameError};
+use tokio::{
+    io::{AsyncSeekExt, AsyncWriteExt},
+    task::JoinSet,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{
+    instances::{InstanceProcess, InstanceStore, ShutdownPolicy, INSTANCES_DIRECTORY},
+    projects::{self, ProjectStore},
+    protos::data_center::{
+        data_center_server::DataCenter, CheckResourceRequest, CheckResourceResponse, Chunk,
+        CreateFileMetadataRequest, CreateFileMetadataResponse, CreateImageMetadataRequest,
+        CreateImageMetadataResponse, CreateMachineRequest, CreateMachineResponse,
+        DownloadFileRequest, DownloadFileResponse, FileMetadata, GetFileMetadataRequest,
+        GetFileMetadataResponse, GetImageMetadataRequest, GetImageMetadataResponse, Instance,
+        InstanceState, ListImageMetadataRequest, ListImageMetadataResponse, ListInstancesRequest,
+        ListInstancesResponse, ListMachinesRequest, ListMachinesResponse, Machine, OsImageMetadata,
+        ProveIdentityRequest, ProveIdentityResponse, ProvisionInstanceRequest,
+        ProvisionInstanceResponse, Quota, Resources, Role, StartInstanceRequest,
+        StartInstanceResponse, StopInstanceRequest, StopInstanceResponse, UploadFileRequest,
+        UploadFileResponse, Usage,
+    },
+    quotas::{self, Quotas},
+};
+
+/// Binary instances are started with
+pub const HYPERVISOR: &str = "qemu-system-x86_64";
+/// Time instances are given to power down when stopped before their hypervisor is killed
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+/// File of the storage root the id generated for a data center started without one is kept in
+pub const ID_FILE: &str = "data_center_id";
+/// Directory of the storage root files are stored in, apart from what the data center keeps
+/// for itself
+pub const FILES_DIRECTORY: &str = "files";
+/// Resources a data center offers machines unless given a capacity of its own
+pub const DEFAULT_CAPACITY: Resources = Resources {
+    ram_mb: 512,
+    disk_mb: 512,
+    vcpus: 1,
+};
+
+/// Id kept in `storage_root` by an earlier data center started without an id, or a new one
+/// kept there for the next, so a data center keeps its id and its resource names across
+/// restarts
+pub fn load_or_generate_id(storage_root: &Path) -> io::Result<String> {
+    let path = storage_root.join(ID_FILE);
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => {
+            let data_center_id = contents.trim();
+            resource_name::validate_segment(data_center_id)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+            Ok(String::from(data_center_id))
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let data_center_id = nanoid!();
+            fs::create_dir_all(storage_root)?;
+            let staged_path = path.with_extension("tmp");
+            fs::write(&staged_path, &data_center_id)?;
+            fs::rename(staged_path, path)?;
+
+            Ok(data_center_id)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Data center running its machines as processes on the local host
+pub struct LocalDataCenter {
+    data_center_id: String,
+    machines_by_id: Mutex<HashMap<String, Machine>>,
+    instances_by_instance_id: Mutex<HashMap<String, Instance>>,
+    processes_by_instance_id: Mutex<HashMap<String, InstanceProcess>>,
+    images_by_id: Mutex<HashMap<String, OsImageMetadata>>,
+    files_by_path: Mutex<HashMap<String, FileMetadata>>,
+    projects: ProjectStore,
+    quotas: Quotas,
+    /// Resources offered to machines, before what they reserve is taken out
+    capacity: Resources,
+    identity_key: Option<Arc<IdentityKey>>,
+    audit: Arc<AuditLog>,
+    transferred_bytes: IntCounterVec,
+    /// Directory relative file paths are stored under
+    storage_root: PathBuf,
+    /// Processes of running instances, under the storage root
+    instance_store: InstanceStore,
+    stop_timeout: Duration,
+}
+
+/// Data center is graph of services (want either distributed or local)
+#[tonic::async_trait]
+impl DataCenter for LocalDataCenter {
+    type DownloadFileStream = ReceiverStream<Result<DownloadFileResponse, Status>>;
+
+    async fn get_image_metadata(
+        &self,
+        request: Request<GetImageMetadataRequest>,
+    ) -> Result<Response<GetImageMetadataResponse>, Status> {
+        let caller = auth::identity(&request).cloned();
+        let request = request.into_inner();
+        let image_id = self
+            .name(ResourceKind::Image, &request.image_id)
+            .map_err(invalid_name)?;
+        let image = self
+            .images_by_id
+            .lock()
+            .expect("Should acquire lock")
+            .get(&image_id)
+            .cloned();
+
+        if let Some(image) = &image {
+            self.projects
+                .authorize(caller.as_ref(), &image.project_id, Role::Viewer)?;
+        }
+
+        Ok(Response::new(GetImageMetadataResponse { image }))
+    }
+
+    async fn create_image_metadata(
+        &self,
+        request: Request<CreateImageMetadataRequest>,
+    ) -> Result<Response<CreateImageMetadataResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/CreateImageMetadata")
+            .resource(projects::project_id(&request.get_ref().project_id));
+
+        entry
+            .record_with(
+                async move {
+                    let caller = auth::identity(&request).cloned();
+                    let image_id = self.new_name(ResourceKind::Image);
+                    let request = request.into_inner();
+                    let project_id = projects::project_id(&request.project_id);
+                    check_file_path(&request.destination_file_path)?;
+                    let file_metadata = self.store_file_metadata(
+                        caller.as_ref(),
+                        project_id,
+                        request.destination_file_path,
+                        request.file_size,
+                    )?;
+                    let image = OsImageMetadata {
+                        image_id: image_id.clone(),
+                        file_metadata: Some(file_metadata),
+                        project_id: String::from(project_id),
+                    };
+                    self.images_by_id
+                        .lock()
+                        .expect("Should acquire lock")
+                        .insert(image_id, image.clone());
+
+                    Ok(Response::new(CreateImageMetadataResponse {
+                        os_image_metadata: Some(image),
+                    }))
+                },
+                |response| {
+                    response
+                        .os_image_metadata
+                        .iter()
+                        .map(|image| image.image_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn check_resource(
+        &self,
+        _request: Request<CheckResourceRequest>,
+    ) -> Result<Response<CheckResourceResponse>, Status> {
+        Ok(Response::new(self.resources()))
+    }
+
+    async fn create_machine(
+        &self,
+        request: Request<CreateMachineRequest>,
+    ) -> Result<Response<CreateMachineResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/CreateMachine")
+            .resource(projects::project_id(&request.get_ref().project_id))
+            .resource(&self.audited_name(ResourceKind::Image, &request.get_ref().image_id));
+
+        entry
+            .record_with(
+                async move {
+                    let caller = auth::identity(&request).cloned();
+                    let request = request.into_inner();
+                    let project_id = projects::project_id(&request.project_id);
+                    self.projects
+                        .authorize(caller.as_ref(), project_id, Role::Operator)?;
+                    let resources = request.resources.ok_or_else(|| {
+                        Status::invalid_argument("Machines must request resources")
+                    })?;
+                    let image_id = self
+                        .name(ResourceKind::Image, &request.image_id)
+                        .map_err(invalid_name)?;
+                    let image = self
+                        .images_by_id
+                        .lock()
+                        .expect("Should acquire lock")
+                        .get(&image_id)
+                        .cloned()
+                        .ok_or_else(|| Status::not_found(format!("No image {image_id}")))?;
+                    self.projects
+                        .authorize(caller.as_ref(), &image.project_id, Role::Viewer)?;
+                    let mut machines = self.machines_by_id.lock().expect("Should acquire lock");
+                    quotas::check(
+                        project_id,
+                        &self.quotas.quota(project_id),
+                        &machine_usage(machines.values(), Some(project_id)),
+                        &Usage {
+                            disk_mb: resources.disk_mb,
+                            ..Default::default()
+                        },
+                    )?;
+                    let machine_id = self.new_name(ResourceKind::Machine);
+                    let machine = Machine {
+                        machine_id: machine_id.clone(),
+                        resources: Some(resources),
+                        image_metadata: Some(image),
+                        project_id: String::from(project_id),
+                    };
+                    machines.insert(machine_id, machine.clone());
+
+                    Ok(Response::new(CreateMachineResponse {
+                        machine: Some(machine),
+                    }))
+                },
+                |response| {
+                    response
+                        .machine
+                        .iter()
+                        .map(|machine| machine.machine_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn start_instance(
+        &self,
+        request: Request<StartInstanceRequest>,
+    ) -> Result<Response<StartInstanceResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/StartInstance")
+            .resource(&self.audited_name(ResourceKind::Instance, &request.get_ref().instance_id));
+
+        entry
+            .record(async move {
+                let caller = auth::identity(&request).cloned();
+                let request = request.into_inner();
+                let instance_id = self
+                    .name(ResourceKind::Instance, &request.instance_id)
+                    .map_err(invalid_name)?;
+                let mut instances = self
+                    .instances_by_instance_id
+                    .lock()
+                    .expect("Should acquire lock");
+                let mut instance = instances
+                    .get(&instance_id)
+                    .cloned()
+                    .ok_or_else(|| Status::not_found(format!("No instance {instance_id}")))?;
+                self.projects
+                    .authorize(caller.as_ref(), &instance.project_id, Role::Operator)?;
+
+                // Starting a started instance leaves it running as it is, rather than starting
+                // a second hypervisor on its disk
+                if instance.state() == InstanceState::Started {
+                    return Ok(Response::new(StartInstanceResponse {
+                        instance: Some(instance),
+                    }));
+                }
+
+                let machine = instance.machine.clone().expect("Machine should exist");
+                quotas::check(
+                    &instance.project_id,
+                    &self.quotas.quota(&instance.project_id),
+                    &instance_usage(instances.values(), Some(&instance.project_id)),
+                    &started_usage(&machine),
+                )?;
+                let process = self
+                    .start_instance_process(&instance_id, &machine)
+                    .map_err(failed_to_start)?;
+                instance.set_state(InstanceState::Started);
+                instance.process_id = process.process_id().to_string();
+                self.track(&instance, process);
+                instances.insert(instance.instance_id.clone(), instance.clone());
+
+                Ok(Response::new(StartInstanceResponse {
+                    instance: Some(instance),
+                }))
+            })
+            .await
+    }
+
+    async fn create_file_metadata(
+        &self,
+        request: Request<CreateFileMetadataRequest>,
+    ) -> Result<Response<CreateFileMetadataResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/CreateFileMetadata")
+            .resource(projects::project_id(&request.get_ref().project_id))
+            .resource(&request.get_ref().file_path);
+
+        entry
+            .record(async move {
+                let caller = auth::identity(&request).cloned();
+                let request = request.into_inner();
+                check_file_path(&request.file_path)?;
+                let file_metadata = self.store_file_metadata(
+                    caller.as_ref(),
+                    projects::project_id(&request.project_id),
+                    request.file_path,
+                    request.file_size,
+                )?;
+
+                Ok(Response::new(CreateFileMetadataResponse {
+                    metadata: Some(file_metadata),
+                }))
+            })
+            .await
+    }
+
+    async fn download_file(
+        &self,
+        request: Request<DownloadFileRequest>,
+    ) -> Result<Response<Self::DownloadFileStream>, Status> {
+        let caller = auth::identity(&request).cloned();
+        let request = request.into_inner();
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        let file_metadata = self
+            .file_metadata(&request.source_path)
+            .ok_or_else(|| file_not_found(&request.source_path))?;
+        self.projects
+            .authorize(caller.as_ref(), &file_metadata.project_id, Role::Viewer)?;
+        let end = match request.end {
+            0 => file_metadata.file_size,
+            end => end,
+        };
+
+        if request.start > end || end > file_metadata.file_size {
+            return Err(Status::out_of_range(format!(
+                "Range {}..{end} is outside of {}, which is {} bytes",
+                request.start, file_metadata.file_path, file_metadata.file_size
+            )));
+        }
+
+        let mut file = File::open(self.stored_path(&file_metadata.file_path)?)?;
+        file.seek(SeekFrom::Start(request.start))?;
+
+        let downloaded = self.transferred_bytes.with_label_values(&["download"]);
+        let file_path = file_metadata.file_path;
+
+        // Failures end the stream with an error rather than only ending it, so the client
+        // doesn't take a partial file for the whole range
+        tokio::spawn(async move {
+            let mut chunk = [0; 4096];
+            let mut reader = BufReader::new(file.take(end - request.start));
+            let mut start = request.start;
+
+            while start < end {
+                let bytes_read = match reader.read(&mut chunk) {
+                    Ok(0) => {
+                        let _ = sender
+                            .send(Err(Status::internal(format!(
+                                "{file_path} ended at {start}, before {end}"
+                            ))))
+                            .await;
+                        break;
+                    }
+                    Ok(bytes_read) => bytes_read,
+                    Err(error) => {
+                        let _ = sender
+                            .send(Err(Status::internal(format!(
+                                "Failed to read {file_path}: {error}"
+                            ))))
+                            .await;
+                        break;
+                    }
+                };
+
+                let sent = sender
+                    .send(Ok(DownloadFileResponse {
+                        chunk: Some(Chunk {
+                            start,
+                            end: start + bytes_read as u64,
+                            data: chunk[..bytes_read].to_vec(),
+                        }),
+                    }))
+                    .await;
+
+                if sent.is_err() {
+                    break;
+                }
+
+                downloaded.inc_by(bytes_read as u64);
+                start += bytes_read as u64;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    /// Writes each chunk at its offset in the file, so a file can be uploaded as several ranges
+    /// over concurrent uploads
+    async fn upload_file(
+        &self,
+        request: Request<Streaming<UploadFileRequest>>,
+    ) -> Result<Response<UploadFileResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/UploadFile");
+        let caller = auth::identity(&request).cloned();
+        let mut stream = request.into_inner();
+        let first = match stream.message().await {
+            Ok(Some(first)) => first,
+            Ok(None) => {
+                return entry
+                    .record(async {
+                        Err(Status::invalid_argument(
+                            "Uploads must contain at least one chunk",
+                        ))
+                    })
+                    .await
+            }
+            Err(status) => return entry.record(async { Err(status) }).await,
+        };
+
+        entry
+            .resource(&first.file_path)
+            .record(self.write_upload(caller, first, stream))
+            .await
+    }
+
+    async fn get_file_metadata(
+        &self,
+        request: Request<GetFileMetadataRequest>,
+    ) -> Result<Response<GetFileMetadataResponse>, Status> {
+        let caller = auth::identity(&request).cloned();
+        let metadata = self.file_metadata(&request.into_inner().file_path);
+
+        if let Some(metadata) = &metadata {
+            self.projects
+                .authorize(caller.as_ref(), &metadata.project_id, Role::Viewer)?;
+        }
+
+        Ok(Response::new(GetFileMetadataResponse { metadata }))
+    }
+
+    async fn list_image_metadata(
+        &self,
+        request: Request<ListImageMetadataRequest>,
+    ) -> Result<Response<ListImageMetadataResponse>, Status> {
+        let visible = self.visible(&request, &request.get_ref().project_id);
+        let metadata = self
+            .images_by_id
+            .lock()
+            .expect("Should acquire lock")
+            .values()
+            .filter(|image| visible(&image.project_id))
+            .cloned()
+            .collect();
+
+        Ok(Response::new(ListImageMetadataResponse { metadata }))
+    }
+
+    async fn provision_instance(
+        &self,
+        request: Request<ProvisionInstanceRequest>,
+    ) -> Result<Response<ProvisionInstanceResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/ProvisionInstance")
+            .resource(&self.audited_name(ResourceKind::Machine, &request.get_ref().machine_id));
+
+        entry
+            .record_with(
+                async move {
+                    let caller = auth::identity(&request).cloned();
+                    let request = request.into_inner();
+                    let machine_id = self
+                        .name(ResourceKind::Machine, &request.machine_id)
+                        .map_err(invalid_name)?;
+                    let machine = self
+                        .machines_by_id
+                        .lock()
+                        .expect("Should acquire lock")
+                        .get(&machine_id)
+                        .cloned()
+                        .ok_or_else(|| Status::not_found(format!("No machine {machine_id}")))?;
+                    self.projects.authorize(
+                        caller.as_ref(),
+                        &machine.project_id,
+                        Role::Operator,
+                    )?;
+                    let mut instances = self
+                        .instances_by_instance_id
+                        .lock()
+                        .expect("Should acquire lock");
+                    quotas::check(
+                        &machine.project_id,
+                        &self.quotas.quota(&machine.project_id),
+                        &instance_usage(instances.values(), Some(&machine.project_id)),
+                        &started_usage(&machine),
+                    )?;
+                    let instance_id = self.new_name(ResourceKind::Instance);
+                    let process = self
+                        .start_instance_process(&instance_id, &machine)
+                        .map_err(failed_to_start)?;
+                    let instance = Instance {
+                        process_id: process.process_id().to_string(),
+                        instance_id,
+                        ip_address: String::from("192.168.0.1"),
+                        project_id: machine.project_id.clone(),
+                        machine: Some(machine),
+                        state: InstanceState::Started as i32,
+                    };
+                    self.track(&instance, process);
+                    instances.insert(String::from(&instance.instance_id), instance.clone());
+
+                    Ok(Response::new(ProvisionInstanceResponse {
+                        instance: Some(instance),
+                    }))
+                },
+                |response| {
+                    response
+                        .instance
+                        .iter()
+                        .map(|instance| instance.instance_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn stop_instance(
+        &self,
+        request: Request<StopInstanceRequest>,
+    ) -> Result<Response<StopInstanceResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/data_center.DataCenter/StopInstance")
+            .resource(&self.audited_name(ResourceKind::Instance, &request.get_ref().instance_id));
+
+        entry
+            .record(async move {
+                let caller = auth::identity(&request).cloned();
+                let request = request.into_inner();
+                let instance_id = self
+                    .name(ResourceKind::Instance, &request.instance_id)
+                    .map_err(invalid_name)?;
+                {
+                    let instances = self
+                        .instances_by_instance_id
+                        .lock()
+                        .expect("Should acquire lock");
+                    let instance = instances
+                        .get(&instance_id)
+                        .ok_or_else(|| Status::not_found(format!("No instance {instance_id}")))?;
+                    self.projects.authorize(
+                        caller.as_ref(),
+                        &instance.project_id,
+                        Role::Operator,
+                    )?;
+                }
+                let process = self
+                    .processes_by_instance_id
+                    .lock()
+                    .expect("Should acquire lock")
+                    .remove(&instance_id);
+
+                // The instance holds its share of the quota until its hypervisor exited
+                if let Some(process) = process {
+                    process.stop(self.stop_timeout).await;
+                }
+
+                if let Some(instance) = self
+                    .instances_by_instance_id
+                    .lock()
+                    .expect("Should acquire lock")
+                    .get_mut(&instance_id)
+                {
+                    instance.set_state(InstanceState::Stopped);
+                }
+
+                Ok(Response::new(StopInstanceResponse {}))
+            })
+            .await
+    }
+
+    async fn list_machines(
+        &self,
+        request: Request<ListMachinesRequest>,
+    ) -> Result<Response<ListMachinesResponse>, Status> {
+        let visible = self.visible(&request, &request.get_ref().project_id);
+
+        Ok(Response::new(ListMachinesResponse {
+            machine: self
+                .machines_by_id
+                .lock()
+                .expect("Should acquire lock")
+                .values()
+                .filter(|machine| visible(&machine.project_id))
+                .cloned()
+                .collect(),
+        }))
+    }
+
+    async fn list_instances(
+        &self,
+        request: Request<ListInstancesRequest>,
+    ) -> Result<Response<ListInstancesResponse>, Status> {
+        let visible = self.visible(&request, &request.get_ref().project_id);
+
+        Ok(Response::new(ListInstancesResponse {
+            instance: self
+                .instances_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .values()
+                .filter(|instance| visible(&instance.project_id))
+                .cloned()
+                .collect(),
+        }))
+    }
+
+    async fn prove_identity(
+        &self,
+        request: Request<ProveIdentityRequest>,
+    ) -> Result<Response<ProveIdentityResponse>, Status> {
+        let Some(identity_key) = &self.identity_key else {
+            return Err(Status::unimplemented("Data center has no identity key"));
+        };
+
+        Ok(Response::new(ProveIdentityResponse {
+            data_center_id: self.data_center_id.clone(),
+            public_key: identity_key.public_key(),
+            signature: identity_key
+                .sign_challenge(&self.data_center_id, &request.get_ref().challenge),
+        }))
+    }
+}
+
+impl LocalDataCenter {
+    /// Creates a data center naming its resources under `data_center_id`, without admins or
+    /// quotas
+    pub fn new(data_center_id: String) -> LocalDataCenter {
+        LocalDataCenter {
+            data_center_id,
+            machines_by_id: Mutex::default(),
+            instances_by_instance_id: Mutex::default(),
+            processes_by_instance_id: Mutex::default(),
+            images_by_id: Mutex::default(),
+            files_by_path: Mutex::default(),
+            projects: ProjectStore::new([]),
+            quotas: Quotas::default(),
+            capacity: DEFAULT_CAPACITY,
+            identity_key: None,
+            audit: Arc::default(),
+            transferred_bytes: IntCounterVec::new(
+                Opts::new(
+                    "data_center_transferred_bytes_total",
+                    "Bytes of files uploaded to and downloaded from the data center",
+                ),
+                &["direction"],
+            )
+            .expect("Should create transferred bytes counter"),
+            storage_root: PathBuf::from("."),
+            instance_store: InstanceStore::new(PathBuf::from(".").join(INSTANCES_DIRECTORY)),
+            stop_timeout: DEFAULT_STOP_TIMEOUT,
+        }
+    }
+
+    /// Makes `admins` admins of every project
+    pub fn with_admins(mut self, admins: impl IntoIterator<Item = String>) -> LocalDataCenter {
+        self.projects = ProjectStore::new(admins);
+
+        self
+    }
+
+    /// Limits the projects that aren't given a quota of their own to `quota`
+    pub fn with_default_quota(mut self, quota: Quota) -> LocalDataCenter {
+        self.quotas = Quotas::new(quota);
+
+        self
+    }
+
+    /// Offers machines `capacity` rather than the default capacity
+    pub fn with_capacity(mut self, capacity: Resources) -> LocalDataCenter {
+        self.capacity = capacity;
+
+        self
+    }
+
+    /// Proves to clients that they reached this data center by signing their challenges with
+    /// `identity_key`
+    pub fn with_identity_key(mut self, identity_key: Arc<IdentityKey>) -> LocalDataCenter {
+        self.identity_key = Some(identity_key);
+
+        self
+    }
+
+    /// Records the mutating calls made to the data center in `audit`
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> LocalDataCenter {
+        self.audit = audit;
+
+        self
+    }
+
+    pub fn projects(&self) -> &ProjectStore {
+        &self.projects
+    }
+
+    pub fn quotas(&self) -> &Quotas {
+        &self.quotas
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Stores files under `storage_root` rather than the working directory, along with the
+    /// processes of running instances
+    pub fn with_storage_root(mut self, storage_root: PathBuf) -> LocalDataCenter {
+        self.instance_store = InstanceStore::new(storage_root.join(INSTANCES_DIRECTORY));
+        self.storage_root = storage_root;
+
+        self
+    }
+
+    /// Gives instances `stop_timeout` to power down when stopped before killing them
+    pub fn with_stop_timeout(mut self, stop_timeout: Duration) -> LocalDataCenter {
+        self.stop_timeout = stop_timeout;
+
+        self
+    }
+
+    pub fn storage_root(&self) -> &Path {
+        &self.storage_root
+    }
+
+    /// Reattaches to the instances an earlier data center left running on the same storage
+    /// root, returning how many it reattached to. Fails without touching any instance when some
+    /// are named after another data center id, so they aren't left running untracked
+    pub async fn reattach_instances(&self) -> io::Result<usize> {
+        let running = self.instance_store.reattach().await?;
+        let mut foreign: Vec<String> = running
+            .iter()
+            .map(|(instance, _)| {
+                ResourceName::parse_kind(&instance.instance_id, ResourceKind::Instance)
+                    .map(|name| name.data_center_id().to_string())
+                    .unwrap_or_else(|_| instance.instance_id.clone())
+            })
+            .filter(|data_center_id| *data_center_id != self.data_center_id)
+            .collect();
+
+        if !foreign.is_empty() {
+            foreign.sort_unstable();
+            foreign.dedup();
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Instances of data center {} are still running on storage root {}, start \
+                     the data center with that id to reattach to them",
+                    foreign.join(", "),
+                    self.storage_root.display()
+                ),
+            ));
+        }
+
+        let mut reattached = 0;
+
+        for (mut instance, process) in running {
+            tracing::info!(
+                instance_id = %instance.instance_id,
+                process_id = process.process_id(),
+                "Reattached to instance"
+            );
+
+            if let Some(machine) = &instance.machine {
+                self.machines_by_id
+                    .lock()
+                    .expect("Should acquire lock")
+                    .entry(machine.machine_id.clone())
+                    .or_insert_with(|| machine.clone());
+            }
+
+            instance.set_state(InstanceState::Started);
+            instance.process_id = process.process_id().to_string();
+            self.processes_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .insert(instance.instance_id.clone(), process);
+            self.instances_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .insert(instance.instance_id.clone(), instance);
+            reattached += 1;
+        }
+
+        Ok(reattached)
+    }
+
+    /// Stops or detaches every running instance according to `policy`, once the data center
+    /// stopped serving
+    pub async fn shutdown_instances(&self, policy: ShutdownPolicy) {
+        let processes: Vec<(String, InstanceProcess)> = self
+            .processes_by_instance_id
+            .lock()
+            .expect("Should acquire lock")
+            .drain()
+            .collect();
+
+        match policy {
+            ShutdownPolicy::Detach => {
+                for (instance_id, process) in processes {
+                    tracing::info!(
+                        %instance_id,
+                        process_id = process.process_id(),
+                        "Leaving instance running"
+                    );
+                    process.detach();
+                }
+            }
+            ShutdownPolicy::Stop => {
+                let mut stopping = JoinSet::new();
+
+                for (instance_id, process) in processes {
+                    let stop_timeout = self.stop_timeout;
+                    stopping.spawn(async move {
+                        process.stop(stop_timeout).await;
+
+                        instance_id
+                    });
+                }
+
+                while let Some(stopped) = stopping.join_next().await {
+                    let Ok(instance_id) = stopped else {
+                        continue;
+                    };
+                    tracing::info!(%instance_id, "Stopped instance");
+
+                    if let Some(instance) = self
+                        .instances_by_instance_id
+                        .lock()
+                        .expect("Should acquire lock")
+                        .get_mut(&instance_id)
+                    {
+                        instance.set_state(InstanceState::Stopped);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resources the data center has left for new machines out of its capacity, every machine
+    /// reserving its disk and every started instance the ram and vcpus of its machine
+    pub fn resources(&self) -> CheckResourceResponse {
+        let machines = machine_usage(
+            self.machines_by_id
+                .lock()
+                .expect("Should acquire lock")
+                .values(),
+            None,
+        );
+        let instances = instance_usage(
+            self.instances_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .values(),
+            None,
+        );
+        let reserved = quotas::add(&machines, &instances);
+
+        CheckResourceResponse {
+            available_resources: Some(Resources {
+                ram_mb: self.capacity.ram_mb.saturating_sub(reserved.ram_mb),
+                disk_mb: self.capacity.disk_mb.saturating_sub(reserved.disk_mb),
+                vcpus: self.capacity.vcpus.saturating_sub(reserved.vcpus),
+            }),
+            total_resources: Some(self.capacity.clone()),
+        }
+    }
+
+    /// Number of instances in each state
+    pub fn instance_counts(&self) -> HashMap<InstanceState, usize> {
+        let mut counts = HashMap::new();
+
+        for instance in self
+            .instances_by_instance_id
+            .lock()
+            .expect("Should acquire lock")
+            .values()
+        {
+            *counts.entry(instance.state()).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Bytes of files uploaded to and downloaded from the data center, by direction
+    pub fn transferred_bytes(&self) -> &IntCounterVec {
+        &self.transferred_bytes
+    }
+
+    /// What the project uses of each resource limited by quotas
+    pub fn usage(&self, project_id: &str) -> Usage {
+        let machines = machine_usage(
+            self.machines_by_id
+                .lock()
+                .expect("Should acquire lock")
+                .values(),
+            Some(project_id),
+        );
+        let instances = instance_usage(
+            self.instances_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .values(),
+            Some(project_id),
+        );
+        let storage = storage_usage(
+            self.files_by_path
+                .lock()
+                .expect("Should acquire lock")
+                .values(),
+            project_id,
+        );
+
+        quotas::add(&quotas::add(&machines, &instances), &storage)
+    }
+
+    /// Whether any machine, instance, image or file belongs to the project
+    pub fn holds_resources(&self, project_id: &str) -> bool {
+        self.machines_by_id
+            .lock()
+            .expect("Should acquire lock")
+            .values()
+            .any(|machine| machine.project_id == project_id)
+            || self
+                .instances_by_instance_id
+                .lock()
+                .expect("Should acquire lock")
+                .values()
+                .any(|instance| instance.project_id == project_id)
+            || self
+                .images_by_id
+                .lock()
+                .expect("Should acquire lock")
+                .values()
+                .any(|image| image.project_id == project_id)
+            || self
+                .files_by_path
+                .lock()
+                .expect("Should acquire lock")
+                .values()
+                .any(|file| file.project_id == project_id)
+    }
+
+    /// Filter of the resources a list request returns, those of the requested project or of
+    /// every project the caller can view when none is requested
+    fn visible<T>(&self, request: &Request<T>, project_id: &str) -> impl Fn(&str) -> bool + '_ {
+        let caller = auth::identity(request).cloned();
+        let requested = String::from(project_id);
+
+        move |project_id| {
+            (requested.is_empty() || requested == project_id)
+                && self.projects.can_view(caller.as_ref(), project_id)
+        }
+    }
+
+    /// Records the metadata of a file of the project, which replaces the file stored at the
+    /// same path only when the caller can operate on the project of both
+    fn store_file_metadata(
+        &self,
+        caller: Option<&Identity>,
+        project_id: &str,
+        file_path: String,
+        file_size: u64,
+    ) -> Result<FileMetadata, projects::ProjectError> {
+        self.projects
+            .authorize(caller, project_id, Role::Operator)?;
+        let mut files_by_path = self.files_by_path.lock().expect("Should lock file");
+
+        if let Some(existing) = files_by_path.get(&file_path) {
+            self.projects
+                .authorize(caller, &existing.project_id, Role::Operator)?;
+        }
+
+        // A file replaced within the project gives back its share of the quota
+        quotas::check(
+            project_id,
+            &self.quotas.quota(project_id),
+            &storage_usage(
+                files_by_path
+                    .values()
+                    .filter(|file| file.file_path != file_path),
+                project_id,
+            ),
+            &Usage {
+                storage_bytes: file_size,
+                ..Default::default()
+            },
+        )?;
+
+        let file_metadata = FileMetadata {
+            file_path,
+            file_size,
+            version: 0,
+            project_id: String::from(project_id),
+        };
+        files_by_path.insert(file_metadata.file_path.clone(), file_metadata.clone());
+
+        Ok(file_metadata)
+    }
+
+    /// Name of a new resource held by this data center
+    fn new_name(&self, kind: ResourceKind) -> String {
+        ResourceName::new(&self.data_center_id, kind, &nanoid!())
+            .expect("Generated ids should be valid")
+            .to_string()
+    }
+
+    /// Full name of the resource `id` refers to, which is either a resource name or the bare id
+    /// of a resource held by this data center
+    fn name(&self, kind: ResourceKind, id: &str) -> Result<String, ResourceNameError> {
+        let name = if id.contains('/') {
+            ResourceName::parse_kind(id, kind)
+        } else {
+            ResourceName::new(&self.data_center_id, kind, id)
+        };
+
+        name.map(|name| name.to_string())
+    }
+
+    /// Writes each chunk of an upload starting with `first` at its offset in the file
+    async fn write_upload(
+        &self,
+        caller: Option<Identity>,
+        first: UploadFileRequest,
+        mut stream: Streaming<UploadFileRequest>,
+    ) -> Result<Response<UploadFileResponse>, Status> {
+        let file_metadata = self
+            .file_metadata(&first.file_path)
+            .ok_or_else(|| file_not_found(&first.file_path))?;
+        self.projects
+            .authorize(caller.as_ref(), &file_metadata.project_id, Role::Operator)?;
+        let path = self.stored_path(&file_metadata.file_path)?;
+
+        if let Some(directory) = path.parent() {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        file.set_len(file_metadata.file_size).await?;
+        let mut bytes_written = 0;
+        let mut message = Some(first);
+
+        while let Some(request) = message {
+            let Some(chunk) = request.chunk else {
+                return Err(Status::invalid_argument(
+                    "All upload requests must have a chunk",
+                ));
+            };
+
+            if chunk.end > file_metadata.file_size
+                || chunk.end.checked_sub(chunk.start) != Some(chunk.data.len() as u64)
+            {
+                return Err(Status::out_of_range(format!(
+                    "Chunk {}..{} of {} bytes doesn't fit {}, which is {} bytes",
+                    chunk.start,
+                    chunk.end,
+                    chunk.data.len(),
+                    file_metadata.file_path,
+                    file_metadata.file_size
+                )));
+            }
+
+            file.seek(SeekFrom::Start(chunk.start)).await?;
+            file.write_all(&chunk.data).await?;
+            bytes_written += chunk.data.len() as u64;
+            self.transferred_bytes
+                .with_label_values(&["upload"])
+                .inc_by(chunk.data.len() as u64);
+            message = stream.message().await?;
+        }
+
+        file.flush().await?;
+
+        Ok(Response::new(UploadFileResponse { bytes_written }))
+    }
+
+    /// Name the audit log records for the resource `id` refers to, which is `id` as sent when
+    /// it isn't a valid name
+    fn audited_name(&self, kind: ResourceKind, id: &str) -> String {
+        self.name(kind, id).unwrap_or_else(|_| String::from(id))
+    }
+
+    /// Where the file created with `file_path` is stored, which is always within the files
+    /// directory of the storage root
+    fn stored_path(&self, file_path: &str) -> io::Result<PathBuf> {
+        check_file_path(file_path)?;
+
+        Ok(self.storage_root.join(FILES_DIRECTORY).join(file_path))
+    }
+
+    fn file_metadata(&self, file_path: &str) -> Option<FileMetadata> {
+        self.files_by_path
+            .lock()
+            .expect("Should acquire lock")
+            .get(file_path)
+            .cloned()
+    }
+
+    fn start_instance_process(
+        &self,
+        instance_id: &str,
+        machine: &Machine,
+    ) -> io::Result<InstanceProcess> {
+        let Some(image_metadata) = &machine.image_metadata else {
+            panic!("Should have image metadata");
+        };
+        let Some(file_metadata) = &image_metadata.file_metadata else {
+            panic!("Should have file metadata")
+        };
+        let mut command = Command::new(HYPERVISOR);
+        command
+            .arg("-accel")
+            .arg("hvf")
+            .arg("-cpu")
+            .arg("host,-rdtscp")
+            .arg("-smp")
+            .arg("2")
+            .arg("-m")
+            .arg(format!(
+                "{}G",
+                machine
+                    .resources
+                    .as_ref()
+                    .expect("Should have resources")
+                    .ram_mb
+                    / 1024
+            ))
+            .arg("-device")
+            .arg("usb-tablet")
+            .arg("-nographic")
+            .arg("-usb")
+            .arg("-device")
+            .arg("virtio-net,netdev=vmnic")
+            .arg("-netdev")
+            .arg("user,id=vmnic,hostfwd=tcp::9001-:22")
+            .arg("-drive")
+            .arg(format!(
+                "file={},if=virtio",
+                self.stored_path(&file_metadata.file_path)?.display()
+            ));
+
+        self.instance_store.spawn(instance_id, command)
+    }
+
+    /// Tracks the process of the started `instance`, recording the instance so a restarted
+    /// data center can reattach to it
+    fn track(&self, instance: &Instance, process: InstanceProcess) {
+        if let Err(error) = process.record(instance) {
+            tracing::warn!(
+                instance_id = %instance.instance_id,
+                %error,
+                "Failed to record instance, it won't be reattached after a restart"
+            );
+        }
+
+        self.processes_by_instance_id
+            .lock()
+            .expect("Should acquire lock")
+            .insert(instance.instance_id.clone(), process);
+    }
+}
+
+/// Usage of the machines of the project, or of every project when `None`, which hold their
+/// disk whether or not they run
+fn machine_usage<'a>(
+    machines: impl Iterator<Item = &'a Machine>,
+    project_id: Option<&str>,
+) -> Usage {
+    Usage {
+        disk_mb: machines
+            .filter(|machine| project_id.is_none_or(|project_id| machine.project_id == project_id))
+            .filter_map(|machine| machine.resources.as_ref())
+            .fold(0, |disk_mb, resources| {
+                disk_mb.saturating_add(resources.disk_mb)
+            }),
+        ..Default::default()
+    }
+}
+
+/// Usage of the started instances of the project, or of every project when `None`
+fn instance_usage<'a>(
+    instances: impl Iterator<Item = &'a Instance>,
+    project_id: Option<&str>,
+) -> Usage {
+    instances
+        .filter(|instance| {
+            project_id.is_none_or(|project_id| instance.project_id == project_id)
+                && instance.state() == InstanceState::Started
+        })
+        .filter_map(|instance| instance.machine.as_ref())
+        .fold(Usage::default(), |usage, machine| {
+            quotas::add(&usage, &started_usage(machine))
+        })
+}
+
+/// Usage added by starting an instance of the machine
+fn started_usage(machine: &Machine) -> Usage {
+    let resources = machine.resources.clone().unwrap_or_default();
+
+    Usage {
+        vcpus: resources.vcpus,
+        ram_mb: resources.ram_mb,
+        instances: 1,
+        ..Default::default()
+    }
+}
+
+/// Usage of the files and images stored for the project
+fn storage_usage<'a>(files: impl Iterator<Item = &'a FileMetadata>, project_id: &str) -> Usage {
+    Usage {
+        storage_bytes: files
+            .filter(|file| file.project_id == project_id)
+            .fold(0, |bytes, file| bytes.saturating_add(file.file_size)),
+        ..Default::default()
+    }
+}
+
+/// Checks that `file_path` is relative and only names directories below it, so files can't be
+/// stored outside of the files directory
+fn check_file_path(file_path: &str) -> io::Result<()> {
+    let path = Path::new(file_path);
+
+    if file_path.is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("File path {file_path} should be relative and not leave its directory"),
+        ));
+    }
+
+    Ok(())
+}
+
+fn file_not_found(file_path: &str) -> Status {
+    Status::not_found(format!("No file stored at {file_path}"))
+}
+
+fn failed_to_start(error: io::Error) -> Status {
+    Status::internal(format!("Failed to start instance: {error}"))
+}
+
+fn invalid_name(error: ResourceNameError) -> Status {
+    Status::invalid_argument(error.to_string())
+}
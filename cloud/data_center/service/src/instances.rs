@@ -0,0 +1,289 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+    str::FromStr,
+    time::Duration,
+};
+
+use nanoid::nanoid;
+use prost::Message;
+use tokio::process::{Child, Command};
+
+use crate::{protos::data_center::Instance, qmp::Qmp};
+
+/// Directory of the storage root the processes of running instances are tracked in
+pub const INSTANCES_DIRECTORY: &str = "instances";
+/// Record of the instance a process runs, read back when reattaching to it
+const INSTANCE_FILE: &str = "instance.pb";
+/// File the hypervisor writes its process id to
+const PID_FILE: &str = "hypervisor.pid";
+/// Socket the hypervisor serves the qemu machine protocol on
+const QMP_SOCKET: &str = "qmp.sock";
+/// File the hypervisor writes its output to, so it outlives the data center it was started by
+const LOG_FILE: &str = "hypervisor.log";
+/// Time between checks of whether a reattached hypervisor exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What the data center does with the instances still running when it shuts down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Powers instances down, killing those that don't power down in time
+    #[default]
+    Stop,
+    /// Leaves instances running for the next data center started on the storage root to
+    /// reattach to
+    Detach,
+}
+
+impl FromStr for ShutdownPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<ShutdownPolicy, String> {
+        match policy {
+            "stop" => Ok(ShutdownPolicy::Stop),
+            "detach" => Ok(ShutdownPolicy::Detach),
+            _ => Err(format!(
+                "Unknown shutdown policy {policy}, expected stop or detach"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ShutdownPolicy {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownPolicy::Stop => write!(formatter, "stop"),
+            ShutdownPolicy::Detach => write!(formatter, "detach"),
+        }
+    }
+}
+
+/// Directory tracking the hypervisor processes of running instances, each in a directory of
+/// its own holding its pidfile, qmp socket, output and the record of its instance
+#[derive(Clone, Debug)]
+pub struct InstanceStore {
+    directory: PathBuf,
+}
+
+impl InstanceStore {
+    pub fn new(directory: PathBuf) -> InstanceStore {
+        InstanceStore { directory }
+    }
+
+    /// Starts the hypervisor `command` for the instance `instance_id`, tracked by a pidfile
+    /// and qmp socket. The process runs in a process group of its own, so signals sent to the
+    /// data center don't reach it, and isn't killed when dropped
+    pub fn spawn(
+        &self,
+        instance_id: &str,
+        mut command: process::Command,
+    ) -> io::Result<InstanceProcess> {
+        let name = instance_id.rsplit('/').next().unwrap_or(instance_id);
+        let directory = self.directory.join(format!("{name}-{}", nanoid!(8)));
+        fs::create_dir_all(&directory)?;
+        let log = File::create(directory.join(LOG_FILE))?;
+        command
+            .arg("-pidfile")
+            .arg(directory.join(PID_FILE))
+            .arg("-qmp")
+            .arg(format!(
+                "unix:{},server=on,wait=off",
+                directory.join(QMP_SOCKET).display()
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(log)
+            .process_group(0);
+        let child = Command::from(command)
+            .kill_on_drop(false)
+            .spawn()
+            .inspect_err(|_| {
+                let _ = fs::remove_dir_all(&directory);
+            })?;
+        let process_id = child.id().expect("Process should have a pid while running");
+
+        Ok(InstanceProcess {
+            process_id,
+            directory,
+            child: Some(child),
+        })
+    }
+
+    /// Instances whose hypervisor, left running by an earlier data center, still answers on its
+    /// qmp socket, along with their processes. Directories of hypervisors that exited are
+    /// removed
+    pub async fn reattach(&self) -> io::Result<Vec<(Instance, InstanceProcess)>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut reattached = Vec::new();
+
+        for entry in entries {
+            let directory = entry?.path();
+
+            if !directory.is_dir() {
+                continue;
+            }
+
+            let instance = match read_instance(&directory) {
+                Ok(instance) => instance,
+                Err(error) => {
+                    tracing::warn!(
+                        directory = %directory.display(),
+                        %error,
+                        "Failed to read instance record, leaving it in place"
+                    );
+                    continue;
+                }
+            };
+
+            if Qmp::connect(&directory.join(QMP_SOCKET)).await.is_err() {
+                tracing::info!(
+                    instance_id = %instance.instance_id,
+                    "Instance exited while the data center was down"
+                );
+                fs::remove_dir_all(&directory)?;
+                continue;
+            }
+
+            let process_id = fs::read_to_string(directory.join(PID_FILE))
+                .ok()
+                .and_then(|process_id| process_id.trim().parse().ok())
+                .or_else(|| instance.process_id.parse().ok())
+                .unwrap_or_default();
+            reattached.push((
+                instance,
+                InstanceProcess {
+                    process_id,
+                    directory,
+                    child: None,
+                },
+            ));
+        }
+
+        Ok(reattached)
+    }
+}
+
+fn read_instance(directory: &Path) -> io::Result<Instance> {
+    let contents = fs::read(directory.join(INSTANCE_FILE))?;
+
+    Instance::decode(contents.as_slice())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Hypervisor process an instance runs in, started by this data center or left running by an
+/// earlier one and reattached to
+#[derive(Debug)]
+pub struct InstanceProcess {
+    process_id: u32,
+    directory: PathBuf,
+    /// Process when it was started by this data center, reattached processes are only reached
+    /// through their qmp socket
+    child: Option<Child>,
+}
+
+impl InstanceProcess {
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Socket the hypervisor serves the qemu machine protocol on
+    pub fn qmp_socket(&self) -> PathBuf {
+        self.directory.join(QMP_SOCKET)
+    }
+
+    /// Records `instance` next to its process, replacing its previous record, so a restarted
+    /// data center can reattach to it
+    pub fn record(&self, instance: &Instance) -> io::Result<()> {
+        let path = self.directory.join(INSTANCE_FILE);
+        let staged_path = path.with_extension("tmp");
+        let mut staged = File::create(&staged_path)?;
+        staged.write_all(&instance.encode_to_vec())?;
+        staged.sync_all()?;
+
+        fs::rename(staged_path, path)
+    }
+
+    /// Leaves the process running once the data center exits, for the next one to reattach to.
+    /// Dropping the process does the same, this only makes it explicit
+    pub fn detach(self) {}
+
+    /// Asks the guest to power down, killing the hypervisor when it hasn't exited within
+    /// `timeout`, and removes the directory tracking it
+    pub async fn stop(mut self, timeout: Duration) {
+        let powerdown = match Qmp::connect(&self.qmp_socket()).await {
+            Ok(mut qmp) => qmp.execute("system_powerdown").await,
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = powerdown {
+            tracing::warn!(
+                process_id = self.process_id,
+                %error,
+                "Failed to ask instance to power down"
+            );
+        }
+
+        if tokio::time::timeout(timeout, self.wait_for_exit())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                process_id = self.process_id,
+                timeout_secs = timeout.as_secs(),
+                "Instance didn't power down in time, killing it"
+            );
+            self.kill().await;
+        }
+
+        if let Err(error) = fs::remove_dir_all(&self.directory) {
+            tracing::warn!(
+                directory = %self.directory.display(),
+                %error,
+                "Failed to remove directory of stopped instance"
+            );
+        }
+    }
+
+    async fn wait_for_exit(&mut self) {
+        match &mut self.child {
+            Some(child) => {
+                let _ = child.wait().await;
+            }
+            None => {
+                while Qmp::connect(&self.qmp_socket()).await.is_ok() {
+                    tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn kill(&mut self) {
+        let killed = match &mut self.child {
+            Some(child) => child.kill().await,
+            None => match Qmp::connect(&self.qmp_socket()).await {
+                // The hypervisor may exit before answering
+                Ok(mut qmp) => match qmp.execute("quit").await {
+                    Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+                    result => result,
+                },
+                Err(error) => Err(error),
+            },
+        };
+
+        if let Err(error) = killed {
+            tracing::error!(
+                process_id = self.process_id,
+                %error,
+                "Failed to kill instance"
+            );
+        }
+    }
+}
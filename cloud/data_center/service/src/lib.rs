@@ -1 +1,11 @@
+pub mod cli;
+pub mod data_center;
+pub mod instances;
+pub mod membership;
+pub mod metrics;
+pub mod projects;
 pub mod protos;
+pub mod qmp;
+pub mod quotas;
+pub mod readiness;
+pub mod registration;
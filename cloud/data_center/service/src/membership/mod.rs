@@ -0,0 +1,704 @@
+pub mod service;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use auth::BearerToken;
+use rand::seq::SliceRandom;
+use telemetry::Traced;
+use tokio::task::{JoinHandle, JoinSet};
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig},
+    Request,
+};
+
+use crate::protos::{
+    data_center::{data_center_server::DataCenter, CheckResourceRequest},
+    membership::{
+        membership_client::MembershipClient, IndirectPingRequest, IndirectPingResponse,
+        JoinRequest, JoinResponse, Member, MemberState, PingRequest, PingResponse,
+    },
+    resolver,
+};
+
+/// Client of another member sending the member's token and the trace context with every call
+type MemberClient = MembershipClient<InterceptedService<Channel, Traced<BearerToken>>>;
+
+/// Most membership updates piggybacked on a single message
+const MAX_PIGGYBACKED_UPDATES: usize = 16;
+/// Multiplied by the log of the group size to give the number of times an update is gossiped
+const RETRANSMIT_MULTIPLIER: usize = 3;
+
+pub struct MembershipConfig {
+    /// Data center this member runs
+    pub local: resolver::DataCenter,
+    /// Endpoints of members to join the group through
+    pub seeds: Vec<String>,
+    /// Interval between probes of members
+    pub protocol_period: Duration,
+    /// Time a probed member has to acknowledge a ping
+    pub ping_timeout: Duration,
+    /// Number of members asked to probe a member that failed to acknowledge a ping
+    pub indirect_checks: usize,
+    /// Time a member stays suspected before it is declared dead
+    pub suspicion_timeout: Duration,
+    /// Time dead and departed members are remembered so stale gossip can't bring them back
+    pub dead_member_ttl: Duration,
+    /// Tls members are dialed with, members are dialed in plaintext when not provided
+    pub tls: Option<ClientTlsConfig>,
+    /// Token presented to the other members, which authenticate gossip like any other call
+    pub token: BearerToken,
+}
+
+impl MembershipConfig {
+    pub fn new(local: resolver::DataCenter, seeds: Vec<String>) -> MembershipConfig {
+        MembershipConfig {
+            local,
+            seeds,
+            protocol_period: Duration::from_secs(1),
+            ping_timeout: Duration::from_millis(500),
+            indirect_checks: 3,
+            suspicion_timeout: Duration::from_secs(5),
+            dead_member_ttl: Duration::from_secs(60),
+            tls: None,
+            token: BearerToken::default(),
+        }
+    }
+}
+
+struct MemberEntry {
+    member: Member,
+    /// When the member last changed state
+    changed_at: Instant,
+}
+
+/// Membership update waiting to be piggybacked on messages to other members
+struct Gossip {
+    member: Member,
+    transmissions: usize,
+}
+
+struct MembershipState {
+    local: Member,
+    members: BTreeMap<String, MemberEntry>,
+    gossip: Vec<Gossip>,
+    /// Members left to probe in the current round
+    probe_order: Vec<String>,
+}
+
+impl MembershipState {
+    /// Merges an update into the membership, returning whether it changed anything. Updates
+    /// with a higher incarnation win, and within an incarnation the more severe state wins
+    fn apply(&mut self, mut update: Member) -> bool {
+        let Some(update_id) = member_id(&update).map(String::from) else {
+            return false;
+        };
+
+        if update_id == member_id(&self.local).unwrap_or_default() {
+            // Refute suspicion of this member by outliving the incarnation it was suspected in
+            if update.state() != MemberState::Alive
+                && self.local.state() == MemberState::Alive
+                && update.incarnation >= self.local.incarnation
+            {
+                self.local.incarnation = update.incarnation + 1;
+                self.enqueue_local();
+            }
+
+            return false;
+        }
+
+        let Some(entry) = self.members.get_mut(&update_id) else {
+            if !is_live(&update) {
+                return false;
+            }
+
+            self.members.insert(
+                update_id,
+                MemberEntry {
+                    member: update.clone(),
+                    changed_at: Instant::now(),
+                },
+            );
+            self.enqueue(update);
+
+            return true;
+        };
+        let last_seen = last_seen_unix_ms(&entry.member).max(last_seen_unix_ms(&update));
+        let overrides = update.incarnation > entry.member.incarnation
+            || (update.incarnation == entry.member.incarnation
+                && severity(update.state()) > severity(entry.member.state()));
+
+        if !overrides {
+            set_last_seen_unix_ms(&mut entry.member, last_seen);
+
+            return false;
+        }
+
+        set_last_seen_unix_ms(&mut update, last_seen);
+
+        if update.state() != entry.member.state() {
+            entry.changed_at = Instant::now();
+        }
+
+        entry.member = update.clone();
+        self.enqueue(update);
+
+        true
+    }
+
+    fn apply_all(&mut self, updates: Vec<Member>) {
+        for update in updates {
+            self.apply(update);
+        }
+    }
+
+    /// Records that the member was heard from directly
+    fn heard_from(&mut self, id: &str) {
+        if let Some(entry) = self.members.get_mut(id) {
+            set_last_seen_unix_ms(&mut entry.member, unix_time_ms());
+        }
+    }
+
+    fn enqueue(&mut self, member: Member) {
+        let id = member_id(&member).map(String::from);
+        self.gossip
+            .retain(|gossip| member_id(&gossip.member).map(String::from) != id);
+        self.gossip.push(Gossip {
+            member,
+            transmissions: self.retransmit_limit(),
+        });
+    }
+
+    fn enqueue_local(&mut self) {
+        self.enqueue(self.local.clone());
+    }
+
+    /// Number of times an update is gossiped, growing with the log of the group size so it
+    /// reaches every member with high probability
+    fn retransmit_limit(&self) -> usize {
+        let group_size = self.members.len() + 1;
+
+        RETRANSMIT_MULTIPLIER * (usize::BITS - group_size.leading_zeros()) as usize
+    }
+
+    /// Takes the updates to piggyback on the next message, preferring the least gossiped ones
+    fn piggyback(&mut self) -> Vec<Member> {
+        self.gossip
+            .sort_by_key(|gossip| std::cmp::Reverse(gossip.transmissions));
+        let updates = self
+            .gossip
+            .iter_mut()
+            .take(MAX_PIGGYBACKED_UPDATES)
+            .map(|gossip| {
+                gossip.transmissions -= 1;
+                gossip.member.clone()
+            })
+            .collect();
+        self.gossip.retain(|gossip| gossip.transmissions > 0);
+
+        updates
+    }
+
+    /// Picks the next member to probe, going through the live members in a random order so each
+    /// is probed once per round
+    fn next_probe_target(&mut self) -> Option<Member> {
+        if self.probe_order.is_empty() {
+            self.probe_order = self
+                .members
+                .iter()
+                .filter(|(_, entry)| is_live(&entry.member))
+                .map(|(id, _)| id.clone())
+                .collect();
+            self.probe_order.shuffle(&mut rand::thread_rng());
+        }
+
+        while let Some(id) = self.probe_order.pop() {
+            match self.members.get(&id) {
+                Some(entry) if is_live(&entry.member) => return Some(entry.member.clone()),
+                _ => continue,
+            }
+        }
+
+        None
+    }
+
+    /// Picks up to `count` alive members other than `excluded` at random
+    fn random_alive_members(&self, count: usize, excluded: &str) -> Vec<Member> {
+        let mut members: Vec<Member> = self
+            .members
+            .iter()
+            .filter(|(id, entry)| *id != excluded && entry.member.state() == MemberState::Alive)
+            .map(|(_, entry)| entry.member.clone())
+            .collect();
+        members.shuffle(&mut rand::thread_rng());
+        members.truncate(count);
+
+        members
+    }
+
+    fn suspect(&mut self, id: &str) {
+        let Some(entry) = self.members.get(id) else {
+            return;
+        };
+
+        if entry.member.state() == MemberState::Alive {
+            let mut member = entry.member.clone();
+            member.set_state(MemberState::Suspect);
+            self.apply(member);
+        }
+    }
+
+    /// Declares members suspected for longer than `suspicion_timeout` dead and forgets members
+    /// that have been dead or gone for longer than `dead_member_ttl`
+    fn expire(&mut self, suspicion_timeout: Duration, dead_member_ttl: Duration) {
+        let expired: Vec<Member> = self
+            .members
+            .values()
+            .filter(|entry| {
+                entry.member.state() == MemberState::Suspect
+                    && entry.changed_at.elapsed() >= suspicion_timeout
+            })
+            .map(|entry| {
+                let mut member = entry.member.clone();
+                member.set_state(MemberState::Dead);
+                member
+            })
+            .collect();
+        self.apply_all(expired);
+        self.members.retain(|_, entry| {
+            is_live(&entry.member) || entry.changed_at.elapsed() < dead_member_ttl
+        });
+    }
+
+    fn members(&self) -> Vec<Member> {
+        let mut local = self.local.clone();
+        set_last_seen_unix_ms(&mut local, unix_time_ms());
+
+        std::iter::once(local)
+            .chain(self.members.values().map(|entry| entry.member.clone()))
+            .collect()
+    }
+
+    fn has_live_members(&self) -> bool {
+        self.members.values().any(|entry| is_live(&entry.member))
+    }
+}
+
+/// Member of the gossip group data centers use to discover each other and exchange their
+/// liveness and capacity without depending on a resolver
+pub struct Membership {
+    config: MembershipConfig,
+    state: Mutex<MembershipState>,
+    clients: Mutex<HashMap<String, MemberClient>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Membership {
+    /// Joins the group through the configured seeds and starts probing its members. Seeds that
+    /// can't be reached are retried for as long as no other member is known
+    pub async fn start<T>(config: MembershipConfig, data_center: Arc<T>) -> Arc<Membership>
+    where
+        T: DataCenter,
+    {
+        let local = Member {
+            data_center: Some(config.local.clone()),
+            state: MemberState::Alive as i32,
+            incarnation: 0,
+        };
+        let membership = Arc::new(Membership {
+            config,
+            state: Mutex::new(MembershipState {
+                local,
+                members: BTreeMap::new(),
+                gossip: Vec::new(),
+                probe_order: Vec::new(),
+            }),
+            clients: Mutex::new(HashMap::new()),
+            tasks: Mutex::new(Vec::new()),
+        });
+        membership.refresh_resources(data_center.as_ref()).await;
+
+        if !membership.config.seeds.is_empty() && !membership.join().await {
+            tracing::warn!("Failed to join the membership group through any seed, retrying");
+        }
+
+        let task = tokio::spawn(membership.clone().run(data_center));
+        membership
+            .tasks
+            .lock()
+            .expect("Should acquire lock")
+            .push(task);
+
+        membership
+    }
+
+    /// Every member known to this member, including itself
+    pub fn members(&self) -> Vec<Member> {
+        self.state.lock().expect("Should acquire lock").members()
+    }
+
+    /// Stops probing and tells the group this member is leaving
+    pub async fn leave(&self) {
+        self.stop();
+        let targets = {
+            let mut state = self.state.lock().expect("Should acquire lock");
+            state.local.set_state(MemberState::Left);
+            state.local.incarnation += 1;
+            state.enqueue_local();
+
+            state.random_alive_members(self.config.indirect_checks, "")
+        };
+        let mut pings = JoinSet::new();
+
+        for target in targets {
+            let membership = self.ping_request();
+            let client = self.client(&target);
+            let timeout = self.config.ping_timeout;
+
+            pings.spawn(async move {
+                if let Some(mut client) = client {
+                    let _ = client.ping(with_timeout(membership, timeout)).await;
+                }
+            });
+        }
+
+        while pings.join_next().await.is_some() {}
+    }
+
+    /// Stops probing without telling the group, which will eventually declare this member dead
+    pub fn stop(&self) {
+        for task in self.tasks.lock().expect("Should acquire lock").drain(..) {
+            task.abort();
+        }
+    }
+
+    pub fn handle_ping(&self, request: PingRequest) -> PingResponse {
+        let mut state = self.state.lock().expect("Should acquire lock");
+        self.merge_sender(&mut state, request.sender);
+        state.apply_all(request.updates);
+
+        PingResponse {
+            updates: state.piggyback(),
+        }
+    }
+
+    pub async fn handle_indirect_ping(&self, request: IndirectPingRequest) -> IndirectPingResponse {
+        {
+            let mut state = self.state.lock().expect("Should acquire lock");
+            self.merge_sender(&mut state, request.sender);
+            state.apply_all(request.updates);
+        }
+
+        let target = Member {
+            data_center: Some(resolver::DataCenter {
+                host_name: request.target_host_name,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let acknowledged = self.ping(&target).await;
+
+        IndirectPingResponse {
+            acknowledged,
+            updates: self.state.lock().expect("Should acquire lock").piggyback(),
+        }
+    }
+
+    pub fn handle_join(&self, request: JoinRequest) -> JoinResponse {
+        let mut state = self.state.lock().expect("Should acquire lock");
+        self.merge_sender(&mut state, request.member);
+
+        JoinResponse {
+            members: state.members(),
+        }
+    }
+
+    fn merge_sender(&self, state: &mut MembershipState, sender: Option<Member>) {
+        let Some(mut sender) = sender else {
+            return;
+        };
+        set_last_seen_unix_ms(&mut sender, unix_time_ms());
+        state.apply(sender);
+    }
+
+    async fn run<T>(self: Arc<Membership>, data_center: Arc<T>)
+    where
+        T: DataCenter,
+    {
+        let mut interval = tokio::time::interval(self.config.protocol_period);
+
+        loop {
+            interval.tick().await;
+            self.refresh_resources(data_center.as_ref()).await;
+
+            let has_live_members = self
+                .state
+                .lock()
+                .expect("Should acquire lock")
+                .has_live_members();
+
+            if !has_live_members {
+                self.join().await;
+            }
+
+            self.probe().await;
+            self.state
+                .lock()
+                .expect("Should acquire lock")
+                .expire(self.config.suspicion_timeout, self.config.dead_member_ttl);
+        }
+    }
+
+    /// Joins the group through the first seed that answers, returning whether any did
+    async fn join(&self) -> bool {
+        for seed in self.config.seeds.iter() {
+            let Some(mut client) = self.client_for(seed) else {
+                continue;
+            };
+            let request = JoinRequest {
+                member: Some(
+                    self.state
+                        .lock()
+                        .expect("Should acquire lock")
+                        .local
+                        .clone(),
+                ),
+            };
+
+            if let Ok(response) = client
+                .join(with_timeout(request, self.config.ping_timeout))
+                .await
+            {
+                self.state
+                    .lock()
+                    .expect("Should acquire lock")
+                    .apply_all(response.into_inner().members);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Probes the next member, first directly and then through other members before
+    /// suspecting it
+    async fn probe(&self) {
+        let Some(target) = self
+            .state
+            .lock()
+            .expect("Should acquire lock")
+            .next_probe_target()
+        else {
+            return;
+        };
+
+        if self.ping(&target).await {
+            return;
+        }
+
+        let target_id = member_id(&target).unwrap_or_default().to_string();
+        let helpers = self
+            .state
+            .lock()
+            .expect("Should acquire lock")
+            .random_alive_members(self.config.indirect_checks, &target_id);
+        let mut indirect_pings = JoinSet::new();
+
+        for helper in helpers {
+            let Some(mut client) = self.client(&helper) else {
+                continue;
+            };
+            let request = {
+                let mut state = self.state.lock().expect("Should acquire lock");
+
+                IndirectPingRequest {
+                    target_host_name: host_name(&target).to_string(),
+                    sender: Some(state.local.clone()),
+                    updates: state.piggyback(),
+                }
+            };
+            // The helper's own ping needs its full timeout on top of the time to reach it
+            let timeout = self.config.ping_timeout * 2;
+
+            indirect_pings
+                .spawn(async move { client.indirect_ping(with_timeout(request, timeout)).await });
+        }
+
+        let mut acknowledged = false;
+
+        while let Some(result) = indirect_pings.join_next().await {
+            if let Ok(Ok(response)) = result {
+                let response = response.into_inner();
+                acknowledged |= response.acknowledged;
+                self.state
+                    .lock()
+                    .expect("Should acquire lock")
+                    .apply_all(response.updates);
+            }
+        }
+
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        if acknowledged {
+            state.heard_from(&target_id);
+        } else {
+            state.suspect(&target_id);
+        }
+    }
+
+    /// Pings `target`, returning whether it acknowledged the ping in time
+    async fn ping(&self, target: &Member) -> bool {
+        let Some(mut client) = self.client(target) else {
+            return false;
+        };
+        let request = self.ping_request();
+
+        match client
+            .ping(with_timeout(request, self.config.ping_timeout))
+            .await
+        {
+            Ok(response) => {
+                let mut state = self.state.lock().expect("Should acquire lock");
+                state.apply_all(response.into_inner().updates);
+
+                if let Some(id) = member_id(target) {
+                    state.heard_from(id);
+                }
+
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn ping_request(&self) -> PingRequest {
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        PingRequest {
+            sender: Some(state.local.clone()),
+            updates: state.piggyback(),
+        }
+    }
+
+    /// Checks the data center's resources, gossiping them under a new incarnation when they
+    /// changed
+    async fn refresh_resources<T>(&self, data_center: &T)
+    where
+        T: DataCenter,
+    {
+        let resources = match data_center
+            .check_resource(Request::new(CheckResourceRequest {}))
+            .await
+        {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                tracing::warn!(
+                    message = status.message(),
+                    "Failed to check resources for gossip"
+                );
+                return;
+            }
+        };
+        let mut state = self.state.lock().expect("Should acquire lock");
+        let Some(local) = state.local.data_center.as_mut() else {
+            return;
+        };
+
+        if local.available_resources == resources.available_resources
+            && local.capacity == resources.total_resources
+        {
+            return;
+        }
+
+        local.available_resources = resources.available_resources;
+        local.capacity = resources.total_resources;
+        state.local.incarnation += 1;
+        state.enqueue_local();
+    }
+
+    fn client(&self, member: &Member) -> Option<MemberClient> {
+        self.client_for(host_name(member))
+    }
+
+    fn client_for(&self, host_name: &str) -> Option<MemberClient> {
+        let endpoint = grpc_tls::endpoint(host_name, self.config.tls.as_ref()).ok()?;
+        let key = endpoint.uri().to_string();
+        let mut clients = self.clients.lock().expect("Should acquire lock");
+
+        if let Some(client) = clients.get(&key) {
+            return Some(client.clone());
+        }
+
+        let channel = endpoint
+            .connect_timeout(self.config.ping_timeout)
+            .connect_lazy();
+        let client =
+            MembershipClient::with_interceptor(channel, Traced::new(self.config.token.clone()));
+        clients.insert(key, client.clone());
+
+        Some(client)
+    }
+}
+
+fn with_timeout<T>(message: T, timeout: Duration) -> Request<T> {
+    let mut request = Request::new(message);
+    request.set_timeout(timeout);
+
+    request
+}
+
+fn member_id(member: &Member) -> Option<&str> {
+    member
+        .data_center
+        .as_ref()
+        .map(|data_center| data_center.data_center_id.as_str())
+        .filter(|id| !id.is_empty())
+}
+
+fn host_name(member: &Member) -> &str {
+    member
+        .data_center
+        .as_ref()
+        .map(|data_center| data_center.host_name.as_str())
+        .unwrap_or_default()
+}
+
+fn last_seen_unix_ms(member: &Member) -> u64 {
+    member
+        .data_center
+        .as_ref()
+        .map(|data_center| data_center.last_seen_unix_ms)
+        .unwrap_or_default()
+}
+
+fn set_last_seen_unix_ms(member: &mut Member, last_seen_unix_ms: u64) {
+    if let Some(data_center) = member.data_center.as_mut() {
+        data_center.last_seen_unix_ms = last_seen_unix_ms;
+    }
+}
+
+fn is_live(member: &Member) -> bool {
+    matches!(member.state(), MemberState::Alive | MemberState::Suspect)
+}
+
+/// Order in which states override each other within an incarnation
+fn severity(state: MemberState) -> u8 {
+    match state {
+        MemberState::Alive => 0,
+        MemberState::Suspect => 1,
+        MemberState::Dead => 2,
+        MemberState::Left => 3,
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use crate::{
+    membership::Membership,
+    protos::membership::{
+        membership_server, IndirectPingRequest, IndirectPingResponse, JoinRequest, JoinResponse,
+        ListMembersRequest, ListMembersResponse, PingRequest, PingResponse,
+    },
+};
+
+/// Serves the membership protocol to the other members of the group
+pub struct MembershipService {
+    membership: Arc<Membership>,
+}
+
+impl MembershipService {
+    pub fn new(membership: Arc<Membership>) -> MembershipService {
+        MembershipService { membership }
+    }
+}
+
+#[tonic::async_trait]
+impl membership_server::Membership for MembershipService {
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        Ok(Response::new(
+            self.membership.handle_ping(request.into_inner()),
+        ))
+    }
+
+    async fn indirect_ping(
+        &self,
+        request: Request<IndirectPingRequest>,
+    ) -> Result<Response<IndirectPingResponse>, Status> {
+        Ok(Response::new(
+            self.membership
+                .handle_indirect_ping(request.into_inner())
+                .await,
+        ))
+    }
+
+    async fn join(&self, request: Request<JoinRequest>) -> Result<Response<JoinResponse>, Status> {
+        Ok(Response::new(
+            self.membership.handle_join(request.into_inner()),
+        ))
+    }
+
+    async fn list_members(
+        &self,
+        _request: Request<ListMembersRequest>,
+    ) -> Result<Response<ListMembersResponse>, Status> {
+        Ok(Response::new(ListMembersResponse {
+            members: self.membership.members(),
+        }))
+    }
+}
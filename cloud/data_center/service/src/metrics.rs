@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use metrics::prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    IntGaugeVec, Opts,
+};
+
+use crate::{
+    data_center::LocalDataCenter,
+    protos::data_center::{InstanceState, Resources},
+};
+
+/// Collects the state of a data center when metrics are scraped: its instances by state, the
+/// capacity it has used and has available, and the bytes transferred to and from it
+pub struct DataCenterCollector {
+    data_center: Arc<LocalDataCenter>,
+    instances: IntGaugeVec,
+    capacity: IntGaugeVec,
+}
+
+impl DataCenterCollector {
+    pub fn new(data_center: Arc<LocalDataCenter>) -> DataCenterCollector {
+        DataCenterCollector {
+            data_center,
+            instances: IntGaugeVec::new(
+                Opts::new(
+                    "data_center_instances",
+                    "Instances of the data center by state",
+                ),
+                &["state"],
+            )
+            .expect("Should create instances gauge"),
+            capacity: IntGaugeVec::new(
+                Opts::new(
+                    "data_center_capacity",
+                    "Resources of the data center used by and available for machines",
+                ),
+                &["resource", "state"],
+            )
+            .expect("Should create capacity gauge"),
+        }
+    }
+}
+
+impl Collector for DataCenterCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.instances
+            .desc()
+            .into_iter()
+            .chain(self.capacity.desc())
+            .chain(self.data_center.transferred_bytes().desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let counts = self.data_center.instance_counts();
+
+        for state in [InstanceState::Stopped, InstanceState::Started] {
+            self.instances
+                .with_label_values(&[state.as_str_name()])
+                .set(counts.get(&state).copied().unwrap_or_default() as i64);
+        }
+
+        let resources = self.data_center.resources();
+        let available = resources.available_resources.unwrap_or_default();
+        let total = resources.total_resources.unwrap_or_default();
+        let amounts = |resources: &Resources| {
+            [
+                ("ram_mb", resources.ram_mb),
+                ("disk_mb", resources.disk_mb),
+                ("vcpus", resources.vcpus),
+            ]
+        };
+
+        // Used is what machines and started instances hold out of the capacity
+        for ((resource, available), (_, total)) in
+            amounts(&available).into_iter().zip(amounts(&total))
+        {
+            self.capacity
+                .with_label_values(&[resource, "available"])
+                .set(i64::from(available));
+            self.capacity
+                .with_label_values(&[resource, "used"])
+                .set(i64::from(total.saturating_sub(available)));
+        }
+
+        // Touching both directions reports them before any file was transferred
+        for direction in ["upload", "download"] {
+            self.data_center
+                .transferred_bytes()
+                .with_label_values(&[direction]);
+        }
+
+        self.instances
+            .collect()
+            .into_iter()
+            .chain(self.capacity.collect())
+            .chain(self.data_center.transferred_bytes().collect())
+            .collect()
+    }
+}
@@ -0,0 +1,314 @@
+pub mod service;
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Mutex,
+};
+
+use auth::Identity;
+use resource_name::ResourceNameError;
+use tonic::Status;
+
+use crate::{
+    protos::data_center::{Project, Role, RoleBinding},
+    quotas::QuotaExceeded,
+};
+
+/// Project resources are created in when a request doesn't name one
+pub const DEFAULT_PROJECT: &str = "default";
+
+/// Projects of a data center and the roles callers hold in them. Callers that weren't
+/// authenticated, because the data center doesn't authenticate anyone, may do anything
+pub struct ProjectStore {
+    /// Role of each subject bound in a project, by project id
+    bindings_by_project_id: Mutex<BTreeMap<String, BTreeMap<String, Role>>>,
+    /// Subjects holding the admin role in every project
+    admins: HashSet<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProjectError {
+    UnknownProject(String),
+    ProjectExists(String),
+    InvalidProjectId(ResourceNameError),
+    /// Caller doesn't hold `role` or a greater one in the project
+    Denied {
+        subject: String,
+        project_id: String,
+        role: Role,
+    },
+    /// Project still holds resources
+    NotEmpty(String),
+    /// Default project can't be deleted
+    DefaultProject,
+    EmptySubject,
+    /// Caller isn't an admin of the whole data center
+    NotDataCenterAdmin(String),
+    QuotaExceeded(QuotaExceeded),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectError::UnknownProject(project_id) => {
+                write!(formatter, "No project {project_id}")
+            }
+            ProjectError::ProjectExists(project_id) => {
+                write!(formatter, "Project {project_id} already exists")
+            }
+            ProjectError::InvalidProjectId(error) => {
+                write!(formatter, "Invalid project id: {error}")
+            }
+            ProjectError::Denied {
+                subject,
+                project_id,
+                role,
+            } => write!(
+                formatter,
+                "{subject} needs the {} role in project {project_id}",
+                role_name(*role)
+            ),
+            ProjectError::NotEmpty(project_id) => {
+                write!(formatter, "Project {project_id} still holds resources")
+            }
+            ProjectError::DefaultProject => {
+                write!(formatter, "The {DEFAULT_PROJECT} project can't be deleted")
+            }
+            ProjectError::EmptySubject => formatter.write_str("Role bindings need a subject"),
+            ProjectError::NotDataCenterAdmin(subject) => {
+                write!(formatter, "{subject} isn't an admin of the data center")
+            }
+            ProjectError::QuotaExceeded(error) => write!(formatter, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<QuotaExceeded> for ProjectError {
+    fn from(error: QuotaExceeded) -> Self {
+        ProjectError::QuotaExceeded(error)
+    }
+}
+
+impl From<ProjectError> for Status {
+    fn from(error: ProjectError) -> Self {
+        let message = error.to_string();
+
+        match error {
+            ProjectError::UnknownProject(_) => Status::not_found(message),
+            ProjectError::ProjectExists(_) => Status::already_exists(message),
+            ProjectError::InvalidProjectId(_) | ProjectError::EmptySubject => {
+                Status::invalid_argument(message)
+            }
+            ProjectError::Denied { .. } | ProjectError::NotDataCenterAdmin(_) => {
+                Status::permission_denied(message)
+            }
+            ProjectError::QuotaExceeded(_) => Status::resource_exhausted(message),
+            ProjectError::NotEmpty(_) | ProjectError::DefaultProject => {
+                Status::failed_precondition(message)
+            }
+        }
+    }
+}
+
+/// Lowercase name of the role, as written by the cli
+pub fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Viewer => "viewer",
+        Role::Operator => "operator",
+        Role::Admin => "admin",
+    }
+}
+
+/// Project a request is made in, the default project when it names none
+pub fn project_id(requested: &str) -> &str {
+    match requested {
+        "" => DEFAULT_PROJECT,
+        requested => requested,
+    }
+}
+
+impl ProjectStore {
+    /// Creates a store holding only the default project, where `admins` are admins of every
+    /// project
+    pub fn new(admins: impl IntoIterator<Item = String>) -> ProjectStore {
+        ProjectStore {
+            bindings_by_project_id: Mutex::new(BTreeMap::from([(
+                String::from(DEFAULT_PROJECT),
+                BTreeMap::new(),
+            )])),
+            admins: admins.into_iter().collect(),
+        }
+    }
+
+    /// Role `subject` holds in the project, if the project exists and binds the subject
+    pub fn role(&self, subject: &str, project_id: &str) -> Option<Role> {
+        let bindings = self
+            .bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock");
+        let bound = bindings.get(project_id)?.get(subject).copied();
+
+        match self.admins.contains(subject) {
+            true => Some(Role::Admin),
+            false => bound,
+        }
+    }
+
+    /// Checks the project exists and the caller holds `role` or a greater one in it
+    pub fn authorize(
+        &self,
+        caller: Option<&Identity>,
+        project_id: &str,
+        role: Role,
+    ) -> Result<(), ProjectError> {
+        if !self.exists(project_id) {
+            return Err(ProjectError::UnknownProject(String::from(project_id)));
+        }
+
+        let Some(caller) = caller else {
+            return Ok(());
+        };
+
+        match self.role(&caller.subject, project_id) {
+            Some(held) if held >= role => Ok(()),
+            _ => Err(ProjectError::Denied {
+                subject: caller.subject.clone(),
+                project_id: String::from(project_id),
+                role,
+            }),
+        }
+    }
+
+    /// Checks the caller is an admin of the whole data center
+    pub fn authorize_data_center_admin(
+        &self,
+        caller: Option<&Identity>,
+    ) -> Result<(), ProjectError> {
+        match caller {
+            Some(caller) if !self.admins.contains(&caller.subject) => {
+                Err(ProjectError::NotDataCenterAdmin(caller.subject.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the caller may read the resources of the project
+    pub fn can_view(&self, caller: Option<&Identity>, project_id: &str) -> bool {
+        self.authorize(caller, project_id, Role::Viewer).is_ok()
+    }
+
+    pub fn exists(&self, project_id: &str) -> bool {
+        self.bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .contains_key(project_id)
+    }
+
+    /// Creates the project, binding the caller as its admin
+    pub fn create(
+        &self,
+        caller: Option<&Identity>,
+        project_id: &str,
+    ) -> Result<Project, ProjectError> {
+        resource_name::validate_segment(project_id).map_err(ProjectError::InvalidProjectId)?;
+        let mut bindings = self
+            .bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock");
+
+        if bindings.contains_key(project_id) {
+            return Err(ProjectError::ProjectExists(String::from(project_id)));
+        }
+
+        bindings.insert(
+            String::from(project_id),
+            caller
+                .map(|caller| (caller.subject.clone(), Role::Admin))
+                .into_iter()
+                .collect(),
+        );
+
+        Ok(Project {
+            project_id: String::from(project_id),
+        })
+    }
+
+    /// Deletes the project, which the caller must have checked holds no resources
+    pub fn delete(&self, project_id: &str) -> Result<(), ProjectError> {
+        if project_id == DEFAULT_PROJECT {
+            return Err(ProjectError::DefaultProject);
+        }
+
+        self.bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .remove(project_id)
+            .map(|_| ())
+            .ok_or_else(|| ProjectError::UnknownProject(String::from(project_id)))
+    }
+
+    /// Projects the caller may view, sorted by id
+    pub fn projects(&self, caller: Option<&Identity>) -> Vec<Project> {
+        let project_ids: Vec<String> = self
+            .bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .keys()
+            .cloned()
+            .collect();
+
+        project_ids
+            .into_iter()
+            .filter(|project_id| self.can_view(caller, project_id))
+            .map(|project_id| Project { project_id })
+            .collect()
+    }
+
+    /// Binds the subject to the role, replacing any role it held in the project
+    pub fn bind(&self, binding: &RoleBinding) -> Result<(), ProjectError> {
+        if binding.subject.is_empty() {
+            return Err(ProjectError::EmptySubject);
+        }
+
+        self.bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .get_mut(&binding.project_id)
+            .ok_or_else(|| ProjectError::UnknownProject(binding.project_id.clone()))?
+            .insert(binding.subject.clone(), binding.role());
+
+        Ok(())
+    }
+
+    /// Removes any role the subject held in the project
+    pub fn unbind(&self, project_id: &str, subject: &str) -> Result<(), ProjectError> {
+        self.bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .get_mut(project_id)
+            .ok_or_else(|| ProjectError::UnknownProject(String::from(project_id)))?
+            .remove(subject);
+
+        Ok(())
+    }
+
+    /// Role bindings of the project, sorted by subject
+    pub fn bindings(&self, project_id: &str) -> Result<Vec<RoleBinding>, ProjectError> {
+        Ok(self
+            .bindings_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .get(project_id)
+            .ok_or_else(|| ProjectError::UnknownProject(String::from(project_id)))?
+            .iter()
+            .map(|(subject, role)| RoleBinding {
+                project_id: String::from(project_id),
+                subject: subject.clone(),
+                role: *role as i32,
+            })
+            .collect())
+    }
+}
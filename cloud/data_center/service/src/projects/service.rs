@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use crate::{
+    data_center::LocalDataCenter,
+    projects::ProjectError,
+    protos::data_center::{
+        projects_server, CreateProjectRequest, CreateProjectResponse, DeleteProjectRequest,
+        DeleteProjectResponse, GetQuotaRequest, GetQuotaResponse, GetUsageRequest,
+        GetUsageResponse, ListProjectsRequest, ListProjectsResponse, ListRoleBindingsRequest,
+        ListRoleBindingsResponse, RemoveRoleBindingRequest, RemoveRoleBindingResponse, Role,
+        SetQuotaRequest, SetQuotaResponse, SetRoleBindingRequest, SetRoleBindingResponse,
+    },
+};
+
+/// Serves the management of the projects of a data center, of their role bindings and quotas
+pub struct ProjectService {
+    data_center: Arc<LocalDataCenter>,
+}
+
+impl ProjectService {
+    pub fn new(data_center: Arc<LocalDataCenter>) -> ProjectService {
+        ProjectService { data_center }
+    }
+}
+
+#[tonic::async_trait]
+impl projects_server::Projects for ProjectService {
+    async fn create_project(
+        &self,
+        request: Request<CreateProjectRequest>,
+    ) -> Result<Response<CreateProjectResponse>, Status> {
+        let entry = self
+            .data_center
+            .audit()
+            .begin(&request, "/data_center.Projects/CreateProject")
+            .resource(&request.get_ref().project_id);
+
+        entry
+            .record(async move {
+                let project = self
+                    .data_center
+                    .projects()
+                    .create(auth::identity(&request), &request.get_ref().project_id)?;
+
+                Ok(Response::new(CreateProjectResponse {
+                    project: Some(project),
+                }))
+            })
+            .await
+    }
+
+    async fn list_projects(
+        &self,
+        request: Request<ListProjectsRequest>,
+    ) -> Result<Response<ListProjectsResponse>, Status> {
+        Ok(Response::new(ListProjectsResponse {
+            project: self
+                .data_center
+                .projects()
+                .projects(auth::identity(&request)),
+        }))
+    }
+
+    async fn delete_project(
+        &self,
+        request: Request<DeleteProjectRequest>,
+    ) -> Result<Response<DeleteProjectResponse>, Status> {
+        let entry = self
+            .data_center
+            .audit()
+            .begin(&request, "/data_center.Projects/DeleteProject")
+            .resource(&request.get_ref().project_id);
+
+        entry
+            .record(async move {
+                let project_id = &request.get_ref().project_id;
+                let projects = self.data_center.projects();
+                projects.authorize(auth::identity(&request), project_id, Role::Admin)?;
+
+                if self.data_center.holds_resources(project_id) {
+                    return Err(ProjectError::NotEmpty(project_id.clone()).into());
+                }
+
+                projects.delete(project_id)?;
+                self.data_center.quotas().remove(project_id);
+
+                Ok(Response::new(DeleteProjectResponse {}))
+            })
+            .await
+    }
+
+    async fn set_role_binding(
+        &self,
+        request: Request<SetRoleBindingRequest>,
+    ) -> Result<Response<SetRoleBindingResponse>, Status> {
+        let entry = self
+            .data_center
+            .audit()
+            .begin(&request, "/data_center.Projects/SetRoleBinding")
+            .resource(
+                request
+                    .get_ref()
+                    .binding
+                    .as_ref()
+                    .map_or("", |binding| &binding.project_id),
+            );
+
+        entry
+            .record(async move {
+                let Some(binding) = &request.get_ref().binding else {
+                    return Err(Status::invalid_argument("Missing role binding"));
+                };
+                let projects = self.data_center.projects();
+                projects.authorize(auth::identity(&request), &binding.project_id, Role::Admin)?;
+                projects.bind(binding)?;
+
+                Ok(Response::new(SetRoleBindingResponse {
+                    binding: Some(binding.clone()),
+                }))
+            })
+            .await
+    }
+
+    async fn remove_role_binding(
+        &self,
+        request: Request<RemoveRoleBindingRequest>,
+    ) -> Result<Response<RemoveRoleBindingResponse>, Status> {
+        let entry = self
+            .data_center
+            .audit()
+            .begin(&request, "/data_center.Projects/RemoveRoleBinding")
+            .resource(&request.get_ref().project_id);
+
+        entry
+            .record(async move {
+                let RemoveRoleBindingRequest {
+                    project_id,
+                    subject,
+                } = request.get_ref();
+                let projects = self.data_center.projects();
+                projects.authorize(auth::identity(&request), project_id, Role::Admin)?;
+                projects.unbind(project_id, subject)?;
+
+                Ok(Response::new(RemoveRoleBindingResponse {}))
+            })
+            .await
+    }
+
+    async fn list_role_bindings(
+        &self,
+        request: Request<ListRoleBindingsRequest>,
+    ) -> Result<Response<ListRoleBindingsResponse>, Status> {
+        let project_id = &request.get_ref().project_id;
+        let projects = self.data_center.projects();
+        projects.authorize(auth::identity(&request), project_id, Role::Viewer)?;
+
+        Ok(Response::new(ListRoleBindingsResponse {
+            binding: projects.bindings(project_id)?,
+        }))
+    }
+
+    async fn get_quota(
+        &self,
+        request: Request<GetQuotaRequest>,
+    ) -> Result<Response<GetQuotaResponse>, Status> {
+        let project_id = &request.get_ref().project_id;
+        self.data_center.projects().authorize(
+            auth::identity(&request),
+            project_id,
+            Role::Viewer,
+        )?;
+
+        Ok(Response::new(GetQuotaResponse {
+            quota: Some(self.data_center.quotas().quota(project_id)),
+        }))
+    }
+
+    /// Only admins of the data center set quotas, so projects can't raise their own
+    async fn set_quota(
+        &self,
+        request: Request<SetQuotaRequest>,
+    ) -> Result<Response<SetQuotaResponse>, Status> {
+        let entry = self
+            .data_center
+            .audit()
+            .begin(&request, "/data_center.Projects/SetQuota")
+            .resource(&request.get_ref().project_id);
+
+        entry
+            .record(async move {
+                let SetQuotaRequest { project_id, quota } = request.get_ref();
+                let projects = self.data_center.projects();
+                projects.authorize_data_center_admin(auth::identity(&request))?;
+
+                if !projects.exists(project_id) {
+                    return Err(ProjectError::UnknownProject(project_id.clone()).into());
+                }
+
+                let quota = quota.clone().unwrap_or_default();
+                self.data_center.quotas().set(project_id, quota.clone());
+
+                Ok(Response::new(SetQuotaResponse { quota: Some(quota) }))
+            })
+            .await
+    }
+
+    async fn get_usage(
+        &self,
+        request: Request<GetUsageRequest>,
+    ) -> Result<Response<GetUsageResponse>, Status> {
+        let project_id = &request.get_ref().project_id;
+        self.data_center.projects().authorize(
+            auth::identity(&request),
+            project_id,
+            Role::Viewer,
+        )?;
+
+        Ok(Response::new(GetUsageResponse {
+            usage: Some(self.data_center.usage(project_id)),
+        }))
+    }
+}
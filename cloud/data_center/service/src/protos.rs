@@ -1,3 +1,15 @@
+/// Encoded descriptors of every proto, to serve reflection with
+pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("protos_descriptor");
+
 pub mod data_center {
     tonic::include_proto!("data_center");
 }
+
+#[allow(clippy::large_enum_variant)]
+pub mod resolver {
+    tonic::include_proto!("resolver");
+}
+
+pub mod membership {
+    tonic::include_proto!("membership");
+}
@@ -0,0 +1,96 @@
+use std::{io, path::Path, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+};
+
+/// Time given to the hypervisor to greet and answer each command
+const QMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client of the qemu machine protocol a hypervisor serves on a unix socket
+pub struct Qmp {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Qmp {
+    /// Connects to the socket at `path`, waiting for the greeting of the hypervisor and leaving
+    /// capabilities negotiation mode so commands can be run
+    pub async fn connect(path: &Path) -> io::Result<Qmp> {
+        let stream = tokio::time::timeout(QMP_TIMEOUT, UnixStream::connect(path))
+            .await
+            .map_err(|_| timed_out("connect"))??;
+        let (reader, writer) = stream.into_split();
+        let mut qmp = Qmp {
+            reader: BufReader::new(reader),
+            writer,
+        };
+        let greeting = qmp.read_message().await?;
+
+        if greeting.get("QMP").is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected qmp greeting, got {greeting}"),
+            ));
+        }
+
+        qmp.execute("qmp_capabilities").await?;
+
+        Ok(qmp)
+    }
+
+    /// Runs `command`, returning once the hypervisor answered it. Events sent meanwhile are
+    /// skipped
+    pub async fn execute(&mut self, command: &str) -> io::Result<()> {
+        let mut line = json!({ "execute": command }).to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+
+        loop {
+            let message = self.read_message().await?;
+
+            if message.get("return").is_some() {
+                return Ok(());
+            }
+
+            if let Some(error) = message.get("error") {
+                return Err(io::Error::other(format!(
+                    "Hypervisor failed to run {command}: {}",
+                    error
+                        .get("desc")
+                        .and_then(Value::as_str)
+                        .unwrap_or("no description")
+                )));
+            }
+        }
+    }
+
+    async fn read_message(&mut self) -> io::Result<Value> {
+        let mut line = String::new();
+        let read = tokio::time::timeout(QMP_TIMEOUT, self.reader.read_line(&mut line))
+            .await
+            .map_err(|_| timed_out("answer"))??;
+
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Hypervisor closed the qmp socket",
+            ));
+        }
+
+        serde_json::from_str(&line)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+fn timed_out(action: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Hypervisor didn't {action} over qmp in time"),
+    )
+}
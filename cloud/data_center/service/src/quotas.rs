@@ -0,0 +1,169 @@
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use tonic::Status;
+
+use crate::protos::data_center::{Quota, Usage};
+
+/// Resource a quota limits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limited {
+    Vcpus,
+    RamMb,
+    DiskMb,
+    Instances,
+    StorageBytes,
+}
+
+impl Limited {
+    pub const ALL: [Limited; 5] = [
+        Limited::Vcpus,
+        Limited::RamMb,
+        Limited::DiskMb,
+        Limited::Instances,
+        Limited::StorageBytes,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limited::Vcpus => "vcpus",
+            Limited::RamMb => "ram_mb",
+            Limited::DiskMb => "disk_mb",
+            Limited::Instances => "instances",
+            Limited::StorageBytes => "storage_bytes",
+        }
+    }
+
+    /// Most of the resource the quota allows, if it limits it
+    pub fn limit(&self, quota: &Quota) -> Option<u64> {
+        match self {
+            Limited::Vcpus => quota.vcpus.map(u64::from),
+            Limited::RamMb => quota.ram_mb.map(u64::from),
+            Limited::DiskMb => quota.disk_mb.map(u64::from),
+            Limited::Instances => quota.instances.map(u64::from),
+            Limited::StorageBytes => quota.storage_bytes,
+        }
+    }
+
+    pub fn used(&self, usage: &Usage) -> u64 {
+        match self {
+            Limited::Vcpus => u64::from(usage.vcpus),
+            Limited::RamMb => u64::from(usage.ram_mb),
+            Limited::DiskMb => u64::from(usage.disk_mb),
+            Limited::Instances => u64::from(usage.instances),
+            Limited::StorageBytes => usage.storage_bytes,
+        }
+    }
+}
+
+/// Request that would take a project over its quota
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub project_id: String,
+    pub resource: Limited,
+    pub limit: u64,
+    pub used: u64,
+    pub requested: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "Quota of {} {} in project {} exceeded, {} used and {} requested",
+            self.limit,
+            self.resource.name(),
+            self.project_id,
+            self.used,
+            self.requested
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl From<QuotaExceeded> for Status {
+    fn from(error: QuotaExceeded) -> Self {
+        Status::resource_exhausted(error.to_string())
+    }
+}
+
+/// Checks the project stays within its quota when `requested` is added to what it uses
+pub fn check(
+    project_id: &str,
+    quota: &Quota,
+    usage: &Usage,
+    requested: &Usage,
+) -> Result<(), QuotaExceeded> {
+    for resource in Limited::ALL {
+        let used = resource.used(usage);
+        let requested = resource.used(requested);
+
+        match resource.limit(quota) {
+            Some(limit) if requested > 0 && used.saturating_add(requested) > limit => {
+                return Err(QuotaExceeded {
+                    project_id: String::from(project_id),
+                    resource,
+                    limit,
+                    used,
+                    requested,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds up two usages
+pub fn add(left: &Usage, right: &Usage) -> Usage {
+    Usage {
+        vcpus: left.vcpus.saturating_add(right.vcpus),
+        ram_mb: left.ram_mb.saturating_add(right.ram_mb),
+        disk_mb: left.disk_mb.saturating_add(right.disk_mb),
+        instances: left.instances.saturating_add(right.instances),
+        storage_bytes: left.storage_bytes.saturating_add(right.storage_bytes),
+    }
+}
+
+/// Quotas of the projects of a data center
+#[derive(Default)]
+pub struct Quotas {
+    /// Quota of the projects that weren't given one of their own
+    default_quota: Quota,
+    quotas_by_project_id: Mutex<HashMap<String, Quota>>,
+}
+
+impl Quotas {
+    pub fn new(default_quota: Quota) -> Quotas {
+        Quotas {
+            default_quota,
+            quotas_by_project_id: Mutex::default(),
+        }
+    }
+
+    /// Quota of the project, the default quota unless it was given one
+    pub fn quota(&self, project_id: &str) -> Quota {
+        self.quotas_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .get(project_id)
+            .cloned()
+            .unwrap_or_else(|| self.default_quota.clone())
+    }
+
+    pub fn set(&self, project_id: &str, quota: Quota) {
+        self.quotas_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .insert(String::from(project_id), quota);
+    }
+
+    /// Forgets the quota of a deleted project
+    pub fn remove(&self, project_id: &str) {
+        self.quotas_by_project_id
+            .lock()
+            .expect("Should acquire lock")
+            .remove(project_id);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    env, fmt,
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use health::ReadinessCheck;
+
+use crate::registration::Reachability;
+
+/// File written to and removed from the storage root to check it is writable
+const STORAGE_PROBE_FILE: &str = ".readiness_probe";
+
+/// Reason the data center can't serve calls
+#[derive(Debug)]
+pub enum Unready {
+    StorageNotWritable(PathBuf, io::Error),
+    HypervisorMissing(&'static str),
+    ResolversUnreachable,
+}
+
+impl fmt::Display for Unready {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unready::StorageNotWritable(path, error) => write!(
+                formatter,
+                "Storage root {} should be writable: {error}",
+                path.display()
+            ),
+            Unready::HypervisorMissing(hypervisor) => {
+                write!(formatter, "Hypervisor {hypervisor} should be on the path")
+            }
+            Unready::ResolversUnreachable => {
+                write!(formatter, "Resolvers should accept heartbeats")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Unready {}
+
+/// Checks the data center can store files, start instances when it offers compute and reach
+/// the resolvers it registered with
+#[derive(Clone, Debug)]
+pub struct DataCenterReadiness {
+    storage_root: PathBuf,
+    hypervisor: Option<&'static str>,
+    resolvers: Option<Reachability>,
+}
+
+impl DataCenterReadiness {
+    /// Only checks files can be stored under `storage_root`
+    pub fn new(storage_root: PathBuf) -> DataCenterReadiness {
+        DataCenterReadiness {
+            storage_root,
+            hypervisor: None,
+            resolvers: None,
+        }
+    }
+
+    /// Checks the binary `hypervisor` instances are started with is on the path
+    pub fn with_hypervisor(mut self, hypervisor: &'static str) -> DataCenterReadiness {
+        self.hypervisor = Some(hypervisor);
+
+        self
+    }
+
+    /// Checks the resolvers accepted the last heartbeat of the data center
+    pub fn with_resolvers(mut self, resolvers: Reachability) -> DataCenterReadiness {
+        self.resolvers = Some(resolvers);
+
+        self
+    }
+}
+
+impl ReadinessCheck for DataCenterReadiness {
+    type Error = Unready;
+
+    fn check(&self) -> Result<(), Unready> {
+        let probe = self.storage_root.join(STORAGE_PROBE_FILE);
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&probe)
+            .and_then(|_| fs::remove_file(&probe))
+            .map_err(|error| Unready::StorageNotWritable(self.storage_root.clone(), error))?;
+
+        if let Some(hypervisor) = self.hypervisor {
+            if !on_path(hypervisor) {
+                return Err(Unready::HypervisorMissing(hypervisor));
+            }
+        }
+
+        match &self.resolvers {
+            Some(resolvers) if !resolvers.is_reachable() => Err(Unready::ResolversUnreachable),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether an executable named `binary` is in one of the directories of `PATH`
+fn on_path(binary: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|directory| is_executable(&directory.join(binary)))
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
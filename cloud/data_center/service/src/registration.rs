@@ -0,0 +1,377 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use auth::BearerToken;
+use identity::{DeregistrationClaims, HeartbeatClaims, IdentityKey, RegistrationClaims};
+use telemetry::Traced;
+use tokio::task::JoinHandle;
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig},
+    Code, Request, Response, Status,
+};
+
+use crate::protos::{
+    data_center::{data_center_server::DataCenter, CheckResourceRequest, CheckResourceResponse},
+    resolver::{
+        dcns_resolver_client::DcnsResolverClient, DeregisterDataCenterRequest, HeartbeatRequest,
+        RegisterDataCenterRequest,
+    },
+};
+
+/// Time before registering again when no resolver could be reached, doubled after every attempt
+const INITIAL_REGISTRATION_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest time between attempts to register
+const MAX_REGISTRATION_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Registration of a data center with a resolver, kept alive through periodic heartbeats
+pub struct Registration {
+    resolvers: Resolvers,
+    data_center_id: String,
+    identity_key: Arc<IdentityKey>,
+    heartbeats: Option<JoinHandle<()>>,
+    reachability: Reachability,
+}
+
+/// Whether the resolvers accepted the last heartbeat of the data center
+#[derive(Clone, Debug)]
+pub struct Reachability(Arc<AtomicBool>);
+
+impl Reachability {
+    pub fn is_reachable(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, reachable: bool) {
+        self.0.store(reachable, Ordering::Relaxed);
+    }
+}
+
+impl Registration {
+    /// Registers the data center described by `request` with one of the `resolvers` and starts
+    /// sending heartbeats carrying the data center's available resources every `interval`.
+    /// When no resolver can be reached the data center keeps registering in the background,
+    /// backing off between attempts, while resolvers refusing the registration fail it. Every
+    /// registration is signed with `identity_key`. Resolvers are dialed with `tls` when
+    /// provided and sent `token` with every call
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start<T>(
+        resolvers: Vec<String>,
+        request: RegisterDataCenterRequest,
+        interval: Duration,
+        data_center: Arc<T>,
+        identity_key: Arc<IdentityKey>,
+        tls: Option<ClientTlsConfig>,
+        token: BearerToken,
+    ) -> Result<Registration>
+    where
+        T: DataCenter,
+    {
+        anyhow::ensure!(
+            !request.data_center_id.is_empty(),
+            "Data centers should register with an id"
+        );
+        let mut resolvers = Resolvers::new(resolvers, tls.as_ref(), token)?;
+        let resources = check_resource(data_center.as_ref()).await?;
+        let registered = match register(&mut resolvers, request.clone(), resources, &identity_key)
+            .await
+        {
+            Ok(()) => true,
+            Err(error) if unavailable(&error) => {
+                tracing::warn!("No resolver reachable, registering in the background: {error:#}");
+                false
+            }
+            Err(error) => return Err(error),
+        };
+        let reachability = Reachability(Arc::new(AtomicBool::new(registered)));
+        let data_center_id = request.data_center_id.clone();
+        let heartbeats = tokio::spawn(send_heartbeats(
+            resolvers.clone(),
+            request,
+            registered,
+            interval,
+            data_center,
+            identity_key.clone(),
+            reachability.clone(),
+        ));
+
+        Ok(Registration {
+            resolvers,
+            data_center_id,
+            identity_key,
+            heartbeats: Some(heartbeats),
+            reachability,
+        })
+    }
+
+    /// Whether the resolvers can be reached, updated with every heartbeat and false until the
+    /// data center first registered
+    pub fn reachability(&self) -> Reachability {
+        self.reachability.clone()
+    }
+
+    /// Stops sending heartbeats and removes the data center from the resolver, signing the
+    /// deregistration with the key the data center registered with
+    pub async fn stop(mut self) -> Result<()> {
+        if let Some(heartbeats) = self.heartbeats.take() {
+            heartbeats.abort();
+        }
+
+        let claims = DeregistrationClaims {
+            data_center_id: &self.data_center_id,
+            signed_at_unix_ms: identity::unix_time_ms(),
+        };
+        let request = DeregisterDataCenterRequest {
+            data_center_id: self.data_center_id.clone(),
+            signature: self.identity_key.sign_deregistration(&claims),
+            signed_at_unix_ms: claims.signed_at_unix_ms,
+        };
+        self.resolvers
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.deregister_data_center(Request::new(request)).await }
+            })
+            .await
+            .context("Should deregister data center")?;
+
+        Ok(())
+    }
+}
+
+type ResolverClient = DcnsResolverClient<InterceptedService<Channel, Traced<BearerToken>>>;
+
+/// Resolvers of the network, failing over to the next resolver whenever one is unavailable
+#[derive(Clone)]
+struct Resolvers {
+    clients: Vec<ResolverClient>,
+    current: usize,
+}
+
+impl Resolvers {
+    fn new(
+        endpoints: Vec<String>,
+        tls: Option<&ClientTlsConfig>,
+        token: BearerToken,
+    ) -> Result<Resolvers> {
+        let mut clients = Vec::new();
+
+        for endpoint in endpoints {
+            let channel = grpc_tls::endpoint(&endpoint, tls)
+                .with_context(|| format!("Invalid resolver endpoint {endpoint}"))?
+                .connect_lazy();
+            clients.push(DcnsResolverClient::with_interceptor(
+                channel,
+                Traced::new(token.clone()),
+            ));
+        }
+
+        anyhow::ensure!(!clients.is_empty(), "At least one resolver is required");
+
+        Ok(Resolvers {
+            clients,
+            current: 0,
+        })
+    }
+
+    async fn call<T, F, R>(&mut self, call: F) -> Result<Response<T>, Status>
+    where
+        F: Fn(ResolverClient) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut attempts = self.clients.len();
+
+        loop {
+            match call(self.clients[self.current].clone()).await {
+                Err(status) if status.code() == Code::Unavailable && attempts > 1 => {
+                    attempts -= 1;
+                    self.current = (self.current + 1) % self.clients.len();
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Sends heartbeats signed with `identity_key` every `interval`, first registering the data
+/// center when it isn't `registered` yet
+async fn send_heartbeats<T>(
+    mut resolvers: Resolvers,
+    request: RegisterDataCenterRequest,
+    registered: bool,
+    interval: Duration,
+    data_center: Arc<T>,
+    identity_key: Arc<IdentityKey>,
+    reachability: Reachability,
+) where
+    T: DataCenter,
+{
+    if !registered {
+        register_with_backoff(
+            &mut resolvers,
+            &request,
+            data_center.as_ref(),
+            &identity_key,
+        )
+        .await;
+        reachability.set(true);
+    }
+
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let resources = match check_resource(data_center.as_ref()).await {
+            Ok(resources) => resources,
+            Err(error) => {
+                tracing::warn!("Failed to check resources for heartbeat: {error:#}");
+                continue;
+            }
+        };
+        let claims = HeartbeatClaims {
+            data_center_id: &request.data_center_id,
+            signed_at_unix_ms: identity::unix_time_ms(),
+        };
+        let heartbeat = HeartbeatRequest {
+            data_center_id: request.data_center_id.clone(),
+            available_resources: resources.available_resources.clone(),
+            signature: identity_key.sign_heartbeat(&claims),
+            signed_at_unix_ms: claims.signed_at_unix_ms,
+        };
+        let result = resolvers
+            .call(|mut client| {
+                let heartbeat = heartbeat.clone();
+                async move { client.heartbeat(Request::new(heartbeat)).await }
+            })
+            .await;
+
+        match result {
+            Ok(_) => reachability.set(true),
+            Err(status) if status.code() == Code::NotFound => {
+                let result =
+                    register(&mut resolvers, request.clone(), resources, &identity_key).await;
+                reachability.set(result.is_ok());
+
+                if let Err(error) = result {
+                    tracing::warn!("Failed to re-register with resolver: {error:#}");
+                }
+            }
+            Err(status) => {
+                reachability.set(false);
+                tracing::warn!(
+                    code = ?status.code(),
+                    message = status.message(),
+                    "Failed to send heartbeat to resolver"
+                );
+            }
+        }
+    }
+}
+
+/// Registers the data center, retrying with backoff until one of the resolvers accepts it
+async fn register_with_backoff<T>(
+    resolvers: &mut Resolvers,
+    request: &RegisterDataCenterRequest,
+    data_center: &T,
+    identity_key: &IdentityKey,
+) where
+    T: DataCenter,
+{
+    let mut backoff = INITIAL_REGISTRATION_BACKOFF;
+
+    loop {
+        tokio::time::sleep(backoff).await;
+        let result = match check_resource(data_center).await {
+            Ok(resources) => register(resolvers, request.clone(), resources, identity_key).await,
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(()) => {
+                tracing::info!("Registered with resolver");
+                return;
+            }
+            Err(error) => {
+                tracing::warn!(
+                    backoff_secs = backoff.as_secs(),
+                    "Failed to register with resolver: {error:#}"
+                );
+                backoff = (backoff * 2).min(MAX_REGISTRATION_BACKOFF);
+            }
+        }
+    }
+}
+
+/// Whether registering failed because no resolver could be reached
+fn unavailable(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<Status>()
+        .is_some_and(|status| status.code() == Code::Unavailable)
+}
+
+/// Registers the data center under the id of `request`
+async fn register(
+    resolvers: &mut Resolvers,
+    request: RegisterDataCenterRequest,
+    resources: CheckResourceResponse,
+    identity_key: &IdentityKey,
+) -> Result<()> {
+    let request = sign(
+        RegisterDataCenterRequest {
+            available_resources: resources.available_resources,
+            capacity: resources.total_resources,
+            ..request
+        },
+        identity_key,
+    );
+    resolvers
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.register_data_center(Request::new(request)).await }
+        })
+        .await
+        .context("Should register data center")?;
+
+    Ok(())
+}
+
+/// Signs the registration as of now, so resolvers can tell it wasn't replayed from long ago
+fn sign(
+    mut request: RegisterDataCenterRequest,
+    identity_key: &IdentityKey,
+) -> RegisterDataCenterRequest {
+    request.public_key = identity_key.public_key();
+    request.signed_at_unix_ms = identity::unix_time_ms();
+    let capabilities = request.capabilities.as_ref();
+    request.signature = identity_key.sign_registration(&RegistrationClaims::from_parts(
+        &request.data_center_id,
+        &request.host_name,
+        &request.region,
+        &request.zone,
+        &request.labels,
+        capabilities.map_or(&[], |capabilities| &capabilities.services),
+        capabilities.map_or(&[], |capabilities| &capabilities.architectures),
+        &request.public_key,
+        request.signed_at_unix_ms,
+    ));
+
+    request
+}
+
+async fn check_resource<T>(data_center: &T) -> Result<CheckResourceResponse>
+where
+    T: DataCenter,
+{
+    Ok(data_center
+        .check_resource(Request::new(CheckResourceRequest {}))
+        .await
+        .context("Should check resources")?
+        .into_inner())
+}
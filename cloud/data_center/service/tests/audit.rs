@@ -0,0 +1,166 @@
+use std::{sync::Arc, time::Duration};
+
+use audit::{
+    protos::audit::{
+        audit_client::AuditClient, audit_server::AuditServer, AuditEvent, ListAuditEventsRequest,
+    },
+    service::AuditService,
+    AuditLog,
+};
+use auth::{token, ApiKeys, AuthInterceptor, Authenticator, BearerToken};
+use data_center_service::{
+    data_center::LocalDataCenter,
+    projects::service::ProjectService,
+    protos::data_center::{
+        data_center_client::DataCenterClient, data_center_server::DataCenterServer,
+        projects_client::ProjectsClient, projects_server::ProjectsServer,
+        CreateImageMetadataRequest, CreateProjectRequest, StopInstanceRequest,
+    },
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, Server},
+    Code,
+};
+
+const SECRET: &[u8] = b"data center secret";
+
+/// Data center authenticating its callers, where `root` is an admin of every project
+async fn start_data_center(audit: Arc<AuditLog>) -> Channel {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    let data_center = Arc::new(
+        LocalDataCenter::new(String::from("dc-1"))
+            .with_admins([String::from("root")])
+            .with_audit_log(audit.clone()),
+    );
+    let auth = AuthInterceptor::new(Some(Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::default(),
+    )));
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ProjectsServer::with_interceptor(
+                ProjectService::new(data_center.clone()),
+                auth.clone(),
+            ))
+            .add_service(InterceptedService::new(
+                DataCenterServer::from_arc(data_center),
+                auth.clone(),
+            ))
+            .add_service(AuditServer::with_interceptor(
+                AuditService::new(audit),
+                auth,
+            ))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+
+    Channel::from_shared(format!("http://{address}"))
+        .unwrap()
+        .connect()
+        .await
+        .expect("Should connect")
+}
+
+fn token(subject: &str) -> BearerToken {
+    BearerToken::new(&token::issue(SECRET, subject, Duration::from_secs(60)))
+        .expect("Should build token")
+}
+
+async fn list(
+    channel: &Channel,
+    subject: &str,
+    request: ListAuditEventsRequest,
+) -> Result<Vec<AuditEvent>, Code> {
+    AuditClient::with_interceptor(channel.clone(), token(subject))
+        .list_audit_events(request)
+        .await
+        .map(|response| response.into_inner().event)
+        .map_err(|status| status.code())
+}
+
+#[tokio::test]
+async fn mutating_calls_are_audited_with_their_caller_and_outcome() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let path = directory.path().join("audit.jsonl");
+    let audit = AuditLog::open(&path)
+        .expect("Should open audit log")
+        .with_readers([String::from("root")]);
+    let channel = start_data_center(Arc::new(audit)).await;
+    let mut root = DataCenterClient::with_interceptor(channel.clone(), token("root"));
+    let mut alice = DataCenterClient::with_interceptor(channel.clone(), token("alice"));
+
+    ProjectsClient::with_interceptor(channel.clone(), token("root"))
+        .create_project(CreateProjectRequest {
+            project_id: String::from("web"),
+        })
+        .await
+        .expect("Should create project");
+    let image_id = root
+        .create_image_metadata(CreateImageMetadataRequest {
+            file_size: 16,
+            destination_file_path: String::from("audited.qcow2"),
+            project_id: String::from("web"),
+        })
+        .await
+        .expect("Should create image")
+        .into_inner()
+        .os_image_metadata
+        .unwrap()
+        .image_id;
+    let status = alice
+        .stop_instance(StopInstanceRequest {
+            instance_id: String::from("missing"),
+        })
+        .await
+        .expect_err("Should not stop a missing instance");
+    assert_eq!(status.code(), Code::NotFound);
+
+    let events = list(&channel, "root", ListAuditEventsRequest::default())
+        .await
+        .expect("Should list events");
+    assert_eq!(
+        events
+            .iter()
+            .map(|event| (
+                event.subject.as_str(),
+                event.rpc.as_str(),
+                event.outcome.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            ("root", "/data_center.Projects/CreateProject", "Ok"),
+            ("root", "/data_center.DataCenter/CreateImageMetadata", "Ok"),
+            ("alice", "/data_center.DataCenter/StopInstance", "NotFound"),
+        ]
+    );
+    assert_eq!(events[1].resource_ids, vec![String::from("web"), image_id]);
+    assert_eq!(events[2].resource_ids, vec!["dc/dc-1/instances/missing"]);
+
+    let by_resource = list(
+        &channel,
+        "root",
+        ListAuditEventsRequest {
+            resource_id: String::from("missing"),
+            ..Default::default()
+        },
+    )
+    .await
+    .expect("Should list events by resource");
+    assert_eq!(by_resource, vec![events[2].clone()]);
+
+    assert_eq!(
+        list(&channel, "alice", ListAuditEventsRequest::default()).await,
+        Err(Code::PermissionDenied)
+    );
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap().lines().count(),
+        events.len()
+    );
+}
@@ -0,0 +1,23 @@
+use data_center_service::data_center::{load_or_generate_id, ID_FILE};
+
+#[test]
+fn generated_id_is_kept_in_the_storage_root() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let storage_root = directory.path().join("storage");
+
+    let data_center_id = load_or_generate_id(&storage_root).expect("Should generate id");
+
+    assert_eq!(
+        load_or_generate_id(&storage_root).expect("Should load id"),
+        data_center_id
+    );
+    assert!(storage_root.join(ID_FILE).exists());
+}
+
+#[test]
+fn invalid_kept_ids_are_rejected() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    std::fs::write(directory.path().join(ID_FILE), "dc/1").expect("Should write id");
+
+    assert!(load_or_generate_id(directory.path()).is_err());
+}
@@ -0,0 +1,166 @@
+use std::{net::SocketAddr, time::Duration};
+
+use data_center_service::{
+    data_center::LocalDataCenter,
+    protos::{data_center::data_center_server::DataCenterServer, FILE_DESCRIPTOR_SET},
+    readiness::DataCenterReadiness,
+};
+use health::{Readiness, SERVER_HEALTH};
+use tokio::{net::TcpListener, sync::oneshot};
+use tokio_stream::{wrappers::TcpListenerStream, StreamExt};
+use tonic::{
+    server::NamedService,
+    transport::{Channel, Server},
+};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use tonic_reflection::pb::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+
+const DATA_CENTER: &str = DataCenterServer::<LocalDataCenter>::NAME;
+
+/// Serves the health and reflection services of a data center storing files under
+/// `storage_root` until the returned sender is dropped
+async fn serve(storage_root: std::path::PathBuf) -> (SocketAddr, Readiness, oneshot::Sender<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    let (readiness, health_service) = Readiness::start(
+        DataCenterReadiness::new(storage_root),
+        vec![DATA_CENTER],
+        Duration::from_millis(50),
+    );
+    let reflection = health::reflection(&[FILE_DESCRIPTOR_SET]).expect("Should build reflection");
+    let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(health_service)
+            .add_service(reflection)
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                let _ = shutdown_signal.await;
+            })
+            .await
+            .expect("Should serve health");
+    });
+
+    (address, readiness, shutdown)
+}
+
+async fn connect(address: SocketAddr) -> Channel {
+    Channel::from_shared(format!("http://{address}"))
+        .expect("Should parse endpoint")
+        .connect()
+        .await
+        .expect("Should connect")
+}
+
+async fn wait_for_status(
+    client: &mut HealthClient<Channel>,
+    service: &str,
+    expected: ServingStatus,
+) {
+    for _ in 0..100 {
+        let status = client
+            .check(HealthCheckRequest {
+                service: String::from(service),
+            })
+            .await
+            .map(|response| response.into_inner().status());
+
+        if status.as_ref().is_ok_and(|status| *status == expected) {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    panic!("{service:?} should become {expected:?}");
+}
+
+#[tokio::test]
+async fn health_follows_readiness_of_storage_root() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let storage_root = directory.path().join("storage");
+    std::fs::create_dir(&storage_root).expect("Should create storage root");
+    let (address, readiness, _shutdown) = serve(storage_root.clone()).await;
+    let mut client = HealthClient::new(connect(address).await);
+
+    wait_for_status(&mut client, SERVER_HEALTH, ServingStatus::Serving).await;
+    wait_for_status(&mut client, DATA_CENTER, ServingStatus::Serving).await;
+
+    std::fs::remove_dir(&storage_root).expect("Should remove storage root");
+    wait_for_status(&mut client, SERVER_HEALTH, ServingStatus::NotServing).await;
+    wait_for_status(&mut client, DATA_CENTER, ServingStatus::NotServing).await;
+
+    std::fs::create_dir(&storage_root).expect("Should recreate storage root");
+    wait_for_status(&mut client, DATA_CENTER, ServingStatus::Serving).await;
+
+    readiness.stop().await;
+    wait_for_status(&mut client, DATA_CENTER, ServingStatus::NotServing).await;
+
+    let unknown = client
+        .check(HealthCheckRequest {
+            service: String::from("data_center.Unknown"),
+        })
+        .await
+        .expect_err("Should not know service");
+    assert_eq!(unknown.code(), tonic::Code::NotFound);
+}
+
+#[test]
+fn missing_hypervisor_is_unready() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let readiness = DataCenterReadiness::new(directory.path().to_path_buf())
+        .with_hypervisor("hypervisor-that-is-not-installed");
+
+    let error = health::ReadinessCheck::check(&readiness).expect_err("Should be unready");
+    assert!(error
+        .to_string()
+        .contains("hypervisor-that-is-not-installed"));
+}
+
+#[tokio::test]
+async fn reflection_lists_services() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let (address, _readiness, _shutdown) = serve(directory.path().to_path_buf()).await;
+    let mut client = ServerReflectionClient::new(connect(address).await);
+
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = client
+        .server_reflection_info(tokio_stream::once(request))
+        .await
+        .expect("Should open reflection stream")
+        .into_inner();
+    let response = responses
+        .next()
+        .await
+        .expect("Should answer")
+        .expect("Should list services");
+
+    let Some(MessageResponse::ListServicesResponse(list)) = response.message_response else {
+        panic!("Should answer with services");
+    };
+    let services: Vec<String> = list
+        .service
+        .into_iter()
+        .map(|service| service.name)
+        .collect();
+
+    for expected in [
+        "data_center.DataCenter",
+        "data_center.Projects",
+        "grpc.health.v1.Health",
+    ] {
+        assert!(
+            services.iter().any(|service| service == expected),
+            "{expected} should be listed in {services:?}"
+        );
+    }
+}
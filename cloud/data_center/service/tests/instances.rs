@@ -0,0 +1,171 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use data_center_service::{
+    data_center::LocalDataCenter,
+    instances::{InstanceProcess, InstanceStore, ShutdownPolicy, INSTANCES_DIRECTORY},
+    protos::data_center::{Instance, InstanceState},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixListener,
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+/// Stands in for a hypervisor that doesn't take its arguments, sleeping until it is killed
+fn fake_hypervisor(seconds: u32) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(format!("sleep {seconds}"));
+
+    command
+}
+
+fn instance(instance_id: &str, process: &InstanceProcess) -> Instance {
+    Instance {
+        instance_id: String::from(instance_id),
+        process_id: process.process_id().to_string(),
+        project_id: String::from("default"),
+        state: InstanceState::Started as i32,
+        ..Default::default()
+    }
+}
+
+/// Serves the qemu machine protocol at `path`, answering every command and sending the
+/// commands it ran on the returned channel. Stops serving once told to power down
+fn serve_qmp(path: PathBuf) -> (mpsc::UnboundedReceiver<String>, JoinHandle<()>) {
+    let listener = UnixListener::bind(&path).expect("Should bind qmp socket");
+    let (commands, received) = mpsc::unbounded_channel();
+    let server = tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.expect("Should accept");
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer
+                .write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\n")
+                .await
+                .expect("Should greet");
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let message: serde_json::Value =
+                    serde_json::from_str(&line).expect("Should send json");
+                let command = String::from(message["execute"].as_str().expect("Should execute"));
+                writer
+                    .write_all(b"{\"event\": \"SHUTDOWN\"}\n{\"return\": {}}\n")
+                    .await
+                    .expect("Should answer");
+                let _ = commands.send(command.clone());
+
+                if command == "system_powerdown" {
+                    return;
+                }
+            }
+        }
+    });
+
+    (received, server)
+}
+
+fn directories(path: &Path) -> usize {
+    std::fs::read_dir(path)
+        .map(|entries| entries.count())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn running_instances_are_reattached_and_powered_down() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let store = InstanceStore::new(directory.path().join("instances"));
+
+    let running = store
+        .spawn("dc/dc-1/instances/vm-1", fake_hypervisor(5))
+        .expect("Should start instance");
+    let (mut commands, server) = serve_qmp(running.qmp_socket());
+    running
+        .record(&instance("dc/dc-1/instances/vm-1", &running))
+        .expect("Should record instance");
+    let process_id = running.process_id();
+    running.detach();
+
+    let exited = store
+        .spawn("dc/dc-1/instances/vm-2", fake_hypervisor(0))
+        .expect("Should start instance");
+    exited
+        .record(&instance("dc/dc-1/instances/vm-2", &exited))
+        .expect("Should record instance");
+    exited.detach();
+    assert_eq!(directories(&directory.path().join("instances")), 2);
+
+    let mut reattached = store.reattach().await.expect("Should reattach");
+    assert_eq!(reattached.len(), 1);
+    let (instance, process) = reattached.remove(0);
+    assert_eq!(instance.instance_id, "dc/dc-1/instances/vm-1");
+    assert_eq!(process.process_id(), process_id);
+    assert_eq!(directories(&directory.path().join("instances")), 1);
+
+    process.stop(Duration::from_secs(5)).await;
+    server.await.expect("Should stop serving");
+    let mut ran = Vec::new();
+
+    while let Ok(command) = commands.try_recv() {
+        ran.push(command);
+    }
+
+    assert!(ran.contains(&String::from("system_powerdown")));
+    assert_eq!(directories(&directory.path().join("instances")), 0);
+    assert!(store.reattach().await.expect("Should reattach").is_empty());
+}
+
+#[tokio::test]
+async fn instances_that_dont_power_down_are_killed() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let store = InstanceStore::new(directory.path().join("instances"));
+    let process = store
+        .spawn("dc/dc-1/instances/vm-1", fake_hypervisor(30))
+        .expect("Should start instance");
+
+    let started = Instant::now();
+    process.stop(Duration::from_millis(200)).await;
+
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(directories(&directory.path().join("instances")), 0);
+}
+
+#[tokio::test]
+async fn instances_of_another_data_center_id_are_not_orphaned() {
+    let directory = tempfile::tempdir().expect("Should create directory");
+    let store = InstanceStore::new(directory.path().join(INSTANCES_DIRECTORY));
+    let running = store
+        .spawn("dc/dc-1/instances/vm-1", fake_hypervisor(5))
+        .expect("Should start instance");
+    let (_commands, server) = serve_qmp(running.qmp_socket());
+    running
+        .record(&instance("dc/dc-1/instances/vm-1", &running))
+        .expect("Should record instance");
+    running.detach();
+
+    let error = LocalDataCenter::new(String::from("dc-2"))
+        .with_storage_root(directory.path().to_path_buf())
+        .reattach_instances()
+        .await
+        .expect_err("Should refuse to start under another id");
+    assert!(error.to_string().contains("dc-1"), "{error}");
+    assert_eq!(directories(&directory.path().join(INSTANCES_DIRECTORY)), 1);
+
+    let data_center = LocalDataCenter::new(String::from("dc-1"))
+        .with_storage_root(directory.path().to_path_buf())
+        .with_stop_timeout(Duration::from_secs(5));
+    assert_eq!(
+        data_center
+            .reattach_instances()
+            .await
+            .expect("Should reattach"),
+        1
+    );
+    data_center.shutdown_instances(ShutdownPolicy::Stop).await;
+    server.await.expect("Should stop serving");
+    assert_eq!(directories(&directory.path().join(INSTANCES_DIRECTORY)), 0);
+}
@@ -0,0 +1,265 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use auth::{token, ApiKeys, AuthInterceptor, Authenticator, BearerToken};
+use data_center_service::{
+    data_center::LocalDataCenter,
+    membership::{service::MembershipService, Membership, MembershipConfig},
+    protos::{
+        data_center::data_center_server::DataCenterServer,
+        membership::{membership_server::MembershipServer, MemberState},
+        resolver::DataCenter,
+    },
+};
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+
+const SECRET: &[u8] = b"development secret";
+
+struct RunningMember {
+    address: SocketAddr,
+    membership: Arc<Membership>,
+    shutdown: oneshot::Sender<()>,
+    server: JoinHandle<()>,
+}
+
+impl RunningMember {
+    /// Member requiring tokens signed with the secret and presenting one of its own
+    async fn start(id: &str, seeds: Vec<String>) -> RunningMember {
+        let token = token::issue(SECRET, id, Duration::from_secs(3600));
+
+        RunningMember::start_with_token(
+            id,
+            seeds,
+            BearerToken::new(&token).expect("Should create token"),
+        )
+        .await
+    }
+
+    async fn start_with_token(id: &str, seeds: Vec<String>, token: BearerToken) -> RunningMember {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Should bind listener");
+        let address = listener.local_addr().expect("Should have address");
+        let data_center = Arc::new(LocalDataCenter::new(String::from(id)));
+        let mut config = MembershipConfig::new(
+            DataCenter {
+                data_center_id: String::from(id),
+                host_name: address.to_string(),
+                ..Default::default()
+            },
+            seeds,
+        );
+        config.protocol_period = Duration::from_millis(50);
+        config.ping_timeout = Duration::from_millis(100);
+        config.suspicion_timeout = Duration::from_millis(500);
+        config.token = token;
+        let membership = Membership::start(config, data_center.clone()).await;
+        let service = MembershipService::new(membership.clone());
+        let (shutdown, shutdown_signal) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            Server::builder()
+                .add_service(DataCenterServer::from_arc(data_center))
+                .add_service(MembershipServer::with_interceptor(
+                    service,
+                    AuthInterceptor::new(Some(Authenticator::new(
+                        Some(SECRET.to_vec()),
+                        ApiKeys::default(),
+                    ))),
+                ))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = shutdown_signal.await;
+                })
+                .await
+                .expect("Should serve data center");
+        });
+
+        RunningMember {
+            address,
+            membership,
+            shutdown,
+            server,
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// States of the members this member knows about, sorted by id
+    fn view(&self) -> Vec<(String, MemberState)> {
+        let mut view: Vec<(String, MemberState)> = self
+            .membership
+            .members()
+            .into_iter()
+            .map(|member| {
+                let state = member.state();
+                let id = member
+                    .data_center
+                    .map(|data_center| data_center.data_center_id)
+                    .unwrap_or_default();
+
+                (id, state)
+            })
+            .collect();
+        view.sort();
+
+        view
+    }
+
+    /// Stops the member without telling the group, as if its host crashed
+    fn crash(self) {
+        self.membership.stop();
+        let _ = self.shutdown.send(());
+        drop(self.server);
+    }
+}
+
+async fn start_group(size: usize) -> Vec<RunningMember> {
+    let seed = RunningMember::start("dc-0", Vec::new()).await;
+    let seeds = vec![seed.endpoint()];
+    let mut members = vec![seed];
+
+    for index in 1..size {
+        members.push(RunningMember::start(&format!("dc-{index}"), seeds.clone()).await);
+    }
+
+    members
+}
+
+async fn wait_for_view(members: &[RunningMember], expected: &[(&str, MemberState)]) {
+    let expected: Vec<(String, MemberState)> = expected
+        .iter()
+        .map(|(id, state)| (String::from(*id), *state))
+        .collect();
+
+    for _ in 0..100 {
+        if members.iter().all(|member| member.view() == expected) {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let views: Vec<_> = members.iter().map(RunningMember::view).collect();
+    panic!("Members did not converge on {expected:?}, views were {views:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn members_discover_each_other_through_a_seed() {
+    let members = start_group(4).await;
+    wait_for_view(
+        &members,
+        &[
+            ("dc-0", MemberState::Alive),
+            ("dc-1", MemberState::Alive),
+            ("dc-2", MemberState::Alive),
+            ("dc-3", MemberState::Alive),
+        ],
+    )
+    .await;
+
+    for member in members[0].membership.members() {
+        let data_center = member.data_center.expect("Should describe data center");
+        assert!(data_center.available_resources.is_some());
+        assert!(data_center.capacity.is_some());
+    }
+
+    for member in members {
+        member.crash();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crashed_member_is_declared_dead() {
+    let mut members = start_group(3).await;
+    wait_for_view(
+        &members,
+        &[
+            ("dc-0", MemberState::Alive),
+            ("dc-1", MemberState::Alive),
+            ("dc-2", MemberState::Alive),
+        ],
+    )
+    .await;
+
+    members.pop().expect("Should have member").crash();
+    wait_for_view(
+        &members,
+        &[
+            ("dc-0", MemberState::Alive),
+            ("dc-1", MemberState::Alive),
+            ("dc-2", MemberState::Dead),
+        ],
+    )
+    .await;
+
+    for member in members {
+        member.crash();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn departed_member_is_marked_left() {
+    let mut members = start_group(3).await;
+    wait_for_view(
+        &members,
+        &[
+            ("dc-0", MemberState::Alive),
+            ("dc-1", MemberState::Alive),
+            ("dc-2", MemberState::Alive),
+        ],
+    )
+    .await;
+
+    let departed = members.pop().expect("Should have member");
+    departed.membership.leave().await;
+    departed.crash();
+    wait_for_view(
+        &members,
+        &[
+            ("dc-0", MemberState::Alive),
+            ("dc-1", MemberState::Alive),
+            ("dc-2", MemberState::Left),
+        ],
+    )
+    .await;
+
+    for member in members {
+        member.crash();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn members_without_a_token_are_not_let_in() {
+    let members = start_group(2).await;
+    wait_for_view(
+        &members,
+        &[("dc-0", MemberState::Alive), ("dc-1", MemberState::Alive)],
+    )
+    .await;
+
+    let outsider = RunningMember::start_with_token(
+        "dc-x",
+        vec![members[0].endpoint()],
+        BearerToken::default(),
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(
+        outsider.view(),
+        vec![(String::from("dc-x"), MemberState::Alive)]
+    );
+    wait_for_view(
+        &members,
+        &[("dc-0", MemberState::Alive), ("dc-1", MemberState::Alive)],
+    )
+    .await;
+
+    outsider.crash();
+
+    for member in members {
+        member.crash();
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use data_center_service::{
+    data_center::LocalDataCenter,
+    metrics::DataCenterCollector,
+    protos::data_center::{
+        data_center_server::DataCenter, CreateImageMetadataRequest, CreateMachineRequest, Resources,
+    },
+};
+use metrics::Metrics;
+use tonic::Request;
+
+/// Value of the capacity gauge of `resource` in `state` in the encoded metrics
+fn capacity(metrics: &Metrics, resource: &str, state: &str) -> String {
+    let series = format!("data_center_capacity{{resource=\"{resource}\",state=\"{state}\"}} ");
+
+    metrics
+        .encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&series).map(String::from))
+        .expect("Should report capacity")
+}
+
+#[tokio::test]
+async fn creating_machines_uses_capacity() {
+    let data_center = Arc::new(LocalDataCenter::new(String::from("dc-1")).with_capacity(
+        Resources {
+            ram_mb: 4096,
+            disk_mb: 10240,
+            vcpus: 4,
+        },
+    ));
+    let metrics = Metrics::new();
+    metrics
+        .registry()
+        .register(Box::new(DataCenterCollector::new(data_center.clone())))
+        .expect("Should register collector");
+    assert_eq!(capacity(&metrics, "disk_mb", "used"), "0");
+    assert_eq!(capacity(&metrics, "disk_mb", "available"), "10240");
+
+    let image = data_center
+        .create_image_metadata(Request::new(CreateImageMetadataRequest {
+            destination_file_path: String::from("image.qcow2"),
+            file_size: 16,
+            ..Default::default()
+        }))
+        .await
+        .expect("Should create image")
+        .into_inner()
+        .os_image_metadata
+        .expect("Should return image");
+    data_center
+        .create_machine(Request::new(CreateMachineRequest {
+            image_id: image.image_id,
+            resources: Some(Resources {
+                ram_mb: 1024,
+                disk_mb: 4096,
+                vcpus: 2,
+            }),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should create machine");
+
+    assert_eq!(capacity(&metrics, "disk_mb", "used"), "4096");
+    assert_eq!(capacity(&metrics, "disk_mb", "available"), "6144");
+    assert_eq!(capacity(&metrics, "ram_mb", "used"), "0");
+}
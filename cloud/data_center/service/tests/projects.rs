@@ -0,0 +1,404 @@
+use std::{sync::Arc, time::Duration};
+
+use auth::{token, ApiKeys, AuthInterceptor, Authenticator, BearerToken};
+use data_center_service::{
+    data_center::LocalDataCenter,
+    projects::service::ProjectService,
+    protos::data_center::{
+        data_center_client::DataCenterClient, data_center_server::DataCenterServer,
+        projects_client::ProjectsClient, projects_server::ProjectsServer,
+        CreateFileMetadataRequest, CreateImageMetadataRequest, CreateMachineRequest,
+        CreateProjectRequest, DeleteProjectRequest, GetImageMetadataRequest, GetUsageRequest,
+        ListImageMetadataRequest, ListProjectsRequest, ListRoleBindingsRequest, Project,
+        ProvisionInstanceRequest, Quota, Resources, Role, RoleBinding, SetQuotaRequest,
+        SetRoleBindingRequest, Usage,
+    },
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, Server},
+    Code,
+};
+
+const SECRET: &[u8] = b"data center secret";
+
+type Client = DataCenterClient<InterceptedService<Channel, BearerToken>>;
+type Projects = ProjectsClient<InterceptedService<Channel, BearerToken>>;
+
+/// Data center authenticating its callers, where `root` is an admin of every project
+async fn start_data_center() -> Channel {
+    start_data_center_with_quota(Quota::default()).await
+}
+
+async fn start_data_center_with_quota(default_quota: Quota) -> Channel {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    let data_center = Arc::new(
+        LocalDataCenter::new(String::from("dc-1"))
+            .with_admins([String::from("root")])
+            .with_default_quota(default_quota),
+    );
+    let auth = AuthInterceptor::new(Some(Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::default(),
+    )));
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ProjectsServer::with_interceptor(
+                ProjectService::new(data_center.clone()),
+                auth.clone(),
+            ))
+            .add_service(InterceptedService::new(
+                DataCenterServer::from_arc(data_center),
+                auth,
+            ))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+
+    Channel::from_shared(format!("http://{address}"))
+        .unwrap()
+        .connect()
+        .await
+        .expect("Should connect")
+}
+
+fn clients(channel: &Channel, subject: &str) -> (Client, Projects) {
+    let token = BearerToken::new(&token::issue(SECRET, subject, Duration::from_secs(60)))
+        .expect("Should build token");
+
+    (
+        DataCenterClient::with_interceptor(channel.clone(), token.clone()),
+        ProjectsClient::with_interceptor(channel.clone(), token),
+    )
+}
+
+async fn create_image(client: &mut Client, project_id: &str) -> Result<String, Code> {
+    client
+        .create_image_metadata(CreateImageMetadataRequest {
+            file_size: 16,
+            destination_file_path: format!("{project_id}.qcow2"),
+            project_id: String::from(project_id),
+        })
+        .await
+        .map(|response| response.into_inner().os_image_metadata.unwrap().image_id)
+        .map_err(|status| status.code())
+}
+
+async fn list_images(client: &mut Client, project_id: &str) -> Vec<String> {
+    client
+        .list_image_metadata(ListImageMetadataRequest {
+            project_id: String::from(project_id),
+        })
+        .await
+        .expect("Should list images")
+        .into_inner()
+        .metadata
+        .into_iter()
+        .map(|image| image.image_id)
+        .collect()
+}
+
+async fn create_machine(client: &mut Client, image_id: &str, project_id: &str) -> Code {
+    create_machine_status(client, image_id, project_id)
+        .await
+        .map_or_else(|status| status.code(), |_| Code::Ok)
+}
+
+async fn create_machine_status(
+    client: &mut Client,
+    image_id: &str,
+    project_id: &str,
+) -> Result<String, tonic::Status> {
+    client
+        .create_machine(CreateMachineRequest {
+            resources: Some(Resources {
+                ram_mb: 1024,
+                disk_mb: 16,
+                vcpus: 1,
+            }),
+            image_id: String::from(image_id),
+            project_id: String::from(project_id),
+        })
+        .await
+        .map(|response| response.into_inner().machine.unwrap().machine_id)
+}
+
+async fn grant(projects: &mut Projects, project_id: &str, subject: &str, role: Role) -> Code {
+    match projects
+        .set_role_binding(SetRoleBindingRequest {
+            binding: Some(RoleBinding {
+                project_id: String::from(project_id),
+                subject: String::from(subject),
+                role: role as i32,
+            }),
+        })
+        .await
+    {
+        Ok(_) => Code::Ok,
+        Err(status) => status.code(),
+    }
+}
+
+async fn list_projects(projects: &mut Projects) -> Vec<String> {
+    projects
+        .list_projects(ListProjectsRequest {})
+        .await
+        .expect("Should list projects")
+        .into_inner()
+        .project
+        .into_iter()
+        .map(|project| project.project_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn roles_limit_what_callers_see_and_change() {
+    let channel = start_data_center().await;
+    let (mut alice, mut alice_projects) = clients(&channel, "alice");
+    let (mut bob, mut bob_projects) = clients(&channel, "bob");
+    let (_, mut root_projects) = clients(&channel, "root");
+
+    let project = alice_projects
+        .create_project(CreateProjectRequest {
+            project_id: String::from("web"),
+        })
+        .await
+        .expect("Should create project")
+        .into_inner()
+        .project;
+    assert_eq!(
+        project,
+        Some(Project {
+            project_id: String::from("web")
+        })
+    );
+    let image_id = create_image(&mut alice, "web")
+        .await
+        .expect("Should create image as admin of the project");
+    assert_eq!(
+        create_image(&mut alice, "").await,
+        Err(Code::PermissionDenied)
+    );
+
+    // Outsiders neither see nor touch the project
+    assert!(list_images(&mut bob, "").await.is_empty());
+    let status = bob
+        .get_image_metadata(GetImageMetadataRequest {
+            image_id: image_id.clone(),
+        })
+        .await
+        .expect_err("Should hide image from outsiders");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(
+        grant(&mut bob_projects, "web", "bob", Role::Admin).await,
+        Code::PermissionDenied
+    );
+
+    // Viewers read but don't change
+    assert_eq!(
+        grant(&mut alice_projects, "web", "bob", Role::Viewer).await,
+        Code::Ok
+    );
+    assert_eq!(list_images(&mut bob, "").await, vec![image_id.clone()]);
+    assert!(list_images(&mut bob, "default").await.is_empty());
+    assert_eq!(
+        create_machine(&mut bob, &image_id, "web").await,
+        Code::PermissionDenied
+    );
+
+    // Operators change
+    assert_eq!(
+        grant(&mut alice_projects, "web", "bob", Role::Operator).await,
+        Code::Ok
+    );
+    assert_eq!(create_machine(&mut bob, &image_id, "web").await, Code::Ok);
+    assert_eq!(list_projects(&mut bob_projects).await, vec!["web"]);
+    assert_eq!(
+        bob_projects
+            .list_role_bindings(ListRoleBindingsRequest {
+                project_id: String::from("web"),
+            })
+            .await
+            .expect("Should list role bindings")
+            .into_inner()
+            .binding
+            .into_iter()
+            .map(|binding| (binding.role(), binding.subject))
+            .collect::<Vec<_>>(),
+        vec![
+            (Role::Admin, String::from("alice")),
+            (Role::Operator, String::from("bob"))
+        ]
+    );
+
+    // Data center admins hold every project
+    assert_eq!(
+        list_projects(&mut root_projects).await,
+        vec!["default", "web"]
+    );
+}
+
+#[tokio::test]
+async fn only_admins_delete_projects_holding_no_resources() {
+    let channel = start_data_center().await;
+    let (mut alice, mut alice_projects) = clients(&channel, "alice");
+    let (_, mut bob_projects) = clients(&channel, "bob");
+    let (_, mut root_projects) = clients(&channel, "root");
+
+    for project_id in ["web", "scratch"] {
+        alice_projects
+            .create_project(CreateProjectRequest {
+                project_id: String::from(project_id),
+            })
+            .await
+            .expect("Should create project");
+    }
+    create_image(&mut alice, "web")
+        .await
+        .expect("Should create image");
+
+    let delete = |projects: &mut Projects, project_id: &str| {
+        let mut projects = projects.clone();
+        let project_id = String::from(project_id);
+
+        async move {
+            match projects
+                .delete_project(DeleteProjectRequest { project_id })
+                .await
+            {
+                Ok(_) => Code::Ok,
+                Err(status) => status.code(),
+            }
+        }
+    };
+
+    assert_eq!(
+        delete(&mut alice_projects, "web").await,
+        Code::FailedPrecondition
+    );
+    assert_eq!(
+        delete(&mut bob_projects, "scratch").await,
+        Code::PermissionDenied
+    );
+    assert_eq!(
+        delete(&mut root_projects, "default").await,
+        Code::FailedPrecondition
+    );
+    assert_eq!(delete(&mut alice_projects, "scratch").await, Code::Ok);
+    assert_eq!(list_projects(&mut alice_projects).await, vec!["web"]);
+}
+
+#[tokio::test]
+async fn quotas_reject_what_would_take_projects_over_them() {
+    let channel = start_data_center_with_quota(Quota {
+        disk_mb: Some(40),
+        instances: Some(0),
+        storage_bytes: Some(64),
+        ..Default::default()
+    })
+    .await;
+    let (mut alice, mut alice_projects) = clients(&channel, "alice");
+    let (_, mut root_projects) = clients(&channel, "root");
+    alice_projects
+        .create_project(CreateProjectRequest {
+            project_id: String::from("web"),
+        })
+        .await
+        .expect("Should create project");
+    let image_id = create_image(&mut alice, "web")
+        .await
+        .expect("Should create image within quota");
+
+    let create_file = |client: &mut Client, file_size: u64| {
+        let mut client = client.clone();
+
+        async move {
+            client
+                .create_file_metadata(CreateFileMetadataRequest {
+                    file_path: String::from("quota-data"),
+                    file_size,
+                    project_id: String::from("web"),
+                })
+                .await
+        }
+    };
+    let status = create_file(&mut alice, 64)
+        .await
+        .expect_err("Should reject file over the storage quota");
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(
+        status.message(),
+        "Quota of 64 storage_bytes in project web exceeded, 16 used and 64 requested"
+    );
+    create_file(&mut alice, 48)
+        .await
+        .expect("Should store file filling the quota");
+    // Replacing a file only counts its new size
+    create_file(&mut alice, 40)
+        .await
+        .expect("Should replace file within the quota");
+
+    let machine_id = create_machine_status(&mut alice, &image_id, "web")
+        .await
+        .expect("Should create machine within the disk quota");
+    create_machine_status(&mut alice, &image_id, "web")
+        .await
+        .expect("Should create machine filling the disk quota");
+    let status = create_machine_status(&mut alice, &image_id, "web")
+        .await
+        .expect_err("Should reject machine over the disk quota");
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("disk_mb"));
+
+    let status = alice
+        .provision_instance(ProvisionInstanceRequest { machine_id })
+        .await
+        .expect_err("Should reject instance over the instance quota");
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("instances"));
+
+    // Projects can't raise their own quota
+    let quota = Quota {
+        disk_mb: Some(64),
+        ..Default::default()
+    };
+    let status = alice_projects
+        .set_quota(SetQuotaRequest {
+            project_id: String::from("web"),
+            quota: Some(quota.clone()),
+        })
+        .await
+        .expect_err("Should only let data center admins set quotas");
+    assert_eq!(status.code(), Code::PermissionDenied);
+    root_projects
+        .set_quota(SetQuotaRequest {
+            project_id: String::from("web"),
+            quota: Some(quota),
+        })
+        .await
+        .expect("Should set quota as data center admin");
+    assert_eq!(create_machine(&mut alice, &image_id, "web").await, Code::Ok);
+
+    let usage = alice_projects
+        .get_usage(GetUsageRequest {
+            project_id: String::from("web"),
+        })
+        .await
+        .expect("Should get usage")
+        .into_inner()
+        .usage;
+    assert_eq!(
+        usage,
+        Some(Usage {
+            disk_mb: 48,
+            storage_bytes: 56,
+            ..Default::default()
+        })
+    );
+}
@@ -0,0 +1,65 @@
+use data_center_service::{
+    data_center::LocalDataCenter,
+    protos::data_center::{
+        data_center_server::DataCenter, CheckResourceRequest, CreateImageMetadataRequest,
+        CreateMachineRequest, Resources,
+    },
+};
+use tonic::Request;
+
+#[tokio::test]
+async fn machines_take_their_disk_out_of_the_capacity() {
+    let capacity = Resources {
+        ram_mb: 4096,
+        disk_mb: 10240,
+        vcpus: 4,
+    };
+    let data_center = LocalDataCenter::new(String::from("dc-1")).with_capacity(capacity.clone());
+    let image = data_center
+        .create_image_metadata(Request::new(CreateImageMetadataRequest {
+            destination_file_path: String::from("image.qcow2"),
+            file_size: 16,
+            ..Default::default()
+        }))
+        .await
+        .expect("Should create image")
+        .into_inner()
+        .os_image_metadata
+        .expect("Should return image");
+
+    let check = || async {
+        data_center
+            .check_resource(Request::new(CheckResourceRequest {}))
+            .await
+            .expect("Should check resources")
+            .into_inner()
+    };
+    let resources = check().await;
+    assert_eq!(resources.total_resources, Some(capacity.clone()));
+    assert_eq!(resources.available_resources, Some(capacity.clone()));
+
+    data_center
+        .create_machine(Request::new(CreateMachineRequest {
+            image_id: image.image_id,
+            resources: Some(Resources {
+                ram_mb: 1024,
+                disk_mb: 4096,
+                vcpus: 2,
+            }),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should create machine");
+
+    // Ram and vcpus are only held once an instance of the machine is started
+    let resources = check().await;
+    assert_eq!(resources.total_resources, Some(capacity));
+    assert_eq!(
+        resources.available_resources,
+        Some(Resources {
+            ram_mb: 4096,
+            disk_mb: 6144,
+            vcpus: 4,
+        })
+    );
+}
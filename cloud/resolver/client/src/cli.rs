@@ -1,22 +1,173 @@
 use clap::{Args, Parser, Subcommand};
+use cli_output::OutputFormat;
+
+use crate::protos::{audit::ListAuditEventsRequest, resolver::PlacementStrategy};
 
 #[derive(Debug, Parser)]
 #[command(name = "dcns")]
 #[command(about = "Data center network cli", long_about = None)]
 pub struct Cli {
+    /// Endpoint of a resolver in the cluster, may be repeated to fail over between resolvers.
+    /// Defaults to the resolvers of the selected profile, then to http://[::1]:50051
+    #[arg(
+        long = "resolver",
+        global = true,
+        env = "DCNS_RESOLVERS",
+        value_delimiter = ','
+    )]
+    pub resolvers: Vec<String>,
+    /// Profile of the config file to take settings from
+    #[arg(long, global = true, env = "DECENTRALIZED_CLOUD_PROFILE")]
+    pub profile: Option<String>,
+    /// Format results are printed in, table, json or yaml
+    #[arg(long, global = true, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+    /// Otlp collector the spans of the command are exported to over grpc, e.g.
+    /// http://localhost:4317. Calls carry the trace context to the services either way
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
+    /// List the data centers on the network
     List,
-    Register(RegisterDataCenterCommand)
+    /// Register a data center with the resolver
+    Register(RegisterDataCenterCommand),
+    /// Remove a data center from the resolver
+    Deregister(DataCenterIdCommand),
+    /// Show a single data center
+    Get(DataCenterIdCommand),
+    /// Print changes to the data centers on the network as they happen
+    Watch(WatchCommand),
+    /// Choose the data center a machine should be created in
+    Place(PlaceMachineCommand),
+    /// Check that a data center holds the key it registered with, and that the key is the
+    /// expected one
+    Verify(VerifyCommand),
+    /// List the registrations and proxied mutating calls recorded in the audit log of the
+    /// resolver, printed as json lines with --output json
+    Audit(AuditCommand),
 }
 
 #[derive(Debug, Args)]
 pub struct RegisterDataCenterCommand {
-    pub host_name: String
+    pub host_name: String,
+    /// Id of the data center, assigned by the resolver when not provided
+    #[arg(long)]
+    pub id: Option<String>,
+    /// Region the data center is located in
+    #[arg(long, default_value = "")]
+    pub region: String,
+    /// Zone within the region the data center is located in
+    #[arg(long, default_value = "")]
+    pub zone: String,
+    /// Label describing the data center as key=value, may be repeated
+    #[arg(long = "label", value_parser = parse_label)]
+    pub labels: Vec<(String, String)>,
+}
+
+#[derive(Debug, Args)]
+pub struct DataCenterIdCommand {
+    pub data_center_id: String,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyCommand {
+    pub data_center_id: String,
+    /// Fingerprint or base64 public key the data center must be registered with
+    #[arg(long)]
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AuditCommand {
+    /// Only list calls made at or after this time in milliseconds since the unix epoch
+    #[arg(long, default_value_t = 0)]
+    pub since: u64,
+    /// Only list calls made before this time in milliseconds since the unix epoch
+    #[arg(long)]
+    pub until: Option<u64>,
+    /// Only list calls naming this resource, by full name or bare id
+    #[arg(long)]
+    pub resource: Option<String>,
+    /// Only list calls made by this subject
+    #[arg(long)]
+    pub subject: Option<String>,
+    /// Only list calls of this rpc, e.g. RegisterDataCenter
+    #[arg(long)]
+    pub rpc: Option<String>,
+    /// Most calls to list
+    #[arg(long)]
+    pub limit: Option<u32>,
+}
+
+impl AuditCommand {
+    pub fn request(self) -> ListAuditEventsRequest {
+        ListAuditEventsRequest {
+            since_unix_ms: self.since,
+            until_unix_ms: self.until.unwrap_or_default(),
+            resource_id: self.resource.unwrap_or_default(),
+            subject: self.subject.unwrap_or_default(),
+            rpc: self.rpc.unwrap_or_default(),
+            after_sequence: 0,
+            limit: self.limit.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct WatchCommand {
+    /// Revision to resume watching after, starting with every data center when zero
+    #[arg(long, default_value_t = 0)]
+    pub from_revision: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct PlaceMachineCommand {
+    /// Ram the machine requires in mb
+    #[arg(long)]
+    pub ram_mb: u32,
+    /// Disk the machine requires in mb
+    #[arg(long)]
+    pub disk_mb: u32,
+    /// Virtual cpus the machine requires
+    #[arg(long, default_value_t = 1)]
+    pub vcpus: u32,
+    /// Os image the machine runs, which the data center must hold
+    #[arg(long, default_value = "")]
+    pub image_id: String,
+    /// Region the data center must be located in
+    #[arg(long, default_value = "")]
+    pub region: String,
+    /// Zone the data center must be located in
+    #[arg(long, default_value = "")]
+    pub zone: String,
+    /// Label the data center must have as key=value, may be repeated
+    #[arg(long = "label", value_parser = parse_label)]
+    pub labels: Vec<(String, String)>,
+    /// Id of a data center the machine must not be placed in, may be repeated
+    #[arg(long)]
+    pub anti_affinity: Vec<String>,
+    /// Strategy used to choose between data centers, BinPack, Spread or Random
+    #[arg(long, default_value = "BinPack", value_parser = parse_strategy)]
+    pub strategy: PlacementStrategy,
+}
+
+fn parse_label(label: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = label.split_once('=') else {
+        return Err(format!("Label {label} should be formatted as key=value"));
+    };
+
+    Ok((String::from(key), String::from(value)))
+}
+
+fn parse_strategy(strategy: &str) -> Result<PlacementStrategy, String> {
+    PlacementStrategy::from_str_name(strategy)
+        .ok_or_else(|| format!("Unknown strategy {strategy}, expected BinPack, Spread or Random"))
 }
 
 pub fn parse_cli() -> Cli {
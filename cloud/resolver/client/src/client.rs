@@ -0,0 +1,170 @@
+use std::{future::Future, time::Duration};
+
+use auth::BearerToken;
+use telemetry::Traced;
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig},
+    Code, Response, Status,
+};
+
+use crate::protos::{
+    audit::audit_client::AuditClient as GeneratedAuditClient,
+    data_center::data_center_client::DataCenterClient,
+    resolver::dcns_resolver_client::DcnsResolverClient,
+};
+
+pub const DEFAULT_RESOLVER: &str = "http://[::1]:50051";
+/// Number of passes made over every endpoint before a call gives up
+const ATTEMPTS: usize = 3;
+/// Delay between passes, giving a cluster time to elect a new leader
+pub(crate) const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Generated client sending the bearer token of the resolver client and the trace context with
+/// every call
+pub type Client = DcnsResolverClient<InterceptedService<Channel, Traced<BearerToken>>>;
+/// Generated client of the data center proxy of a resolver, which forwards calls to data
+/// centers
+pub type ProxyClient = DataCenterClient<InterceptedService<Channel, Traced<BearerToken>>>;
+/// Generated client of the audit log of a resolver
+pub type AuditClient = GeneratedAuditClient<InterceptedService<Channel, Traced<BearerToken>>>;
+
+/// Client for a resolver cluster that fails over between endpoints when one is unreachable
+pub struct ResolverClient {
+    endpoints: Vec<String>,
+    current: usize,
+    channel: Option<Channel>,
+    tls: Option<ClientTlsConfig>,
+    token: Traced<BearerToken>,
+}
+
+impl ResolverClient {
+    pub fn new(endpoints: Vec<String>) -> ResolverClient {
+        ResolverClient {
+            endpoints,
+            current: 0,
+            channel: None,
+            tls: None,
+            token: Traced::default(),
+        }
+    }
+
+    /// Connects to the endpoints over `tls`
+    pub fn with_tls(mut self, tls: ClientTlsConfig) -> ResolverClient {
+        self.tls = Some(tls);
+
+        self
+    }
+
+    /// Sends `token` as a bearer token with every call
+    pub fn with_token(mut self, token: BearerToken) -> ResolverClient {
+        self.token = Traced::new(token);
+
+        self
+    }
+
+    /// Runs `call` against the current endpoint, moving on to the next endpoint whenever the
+    /// current one is unavailable
+    pub async fn call<T, F, R>(&mut self, call: F) -> Result<T, Status>
+    where
+        F: Fn(Client) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
+    {
+        self.call_with(DcnsResolverClient::with_interceptor, call)
+            .await
+    }
+
+    /// Runs `call` against the data center proxy of the current endpoint, failing over like
+    /// `call`
+    pub async fn call_proxy<T, F, R>(&mut self, call: F) -> Result<T, Status>
+    where
+        F: Fn(ProxyClient) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
+    {
+        self.call_with(DataCenterClient::with_interceptor, call)
+            .await
+    }
+
+    /// Runs `call` against the audit log of the current endpoint, failing over like `call`
+    pub async fn call_audit<T, F, R>(&mut self, call: F) -> Result<T, Status>
+    where
+        F: Fn(AuditClient) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
+    {
+        self.call_with(GeneratedAuditClient::with_interceptor, call)
+            .await
+    }
+
+    async fn call_with<C, T, F, R>(
+        &mut self,
+        client: fn(Channel, Traced<BearerToken>) -> C,
+        call: F,
+    ) -> Result<T, Status>
+    where
+        F: Fn(C) -> R,
+        R: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut last_status = Status::unavailable("No resolver endpoints configured");
+
+        for attempt in 0..ATTEMPTS * self.endpoints.len() {
+            if attempt > 0 && attempt % self.endpoints.len() == 0 {
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+
+            let channel = match self.connect().await {
+                Ok(channel) => channel,
+                Err(status) => {
+                    last_status = status;
+                    self.fail_over();
+                    continue;
+                }
+            };
+
+            match call(client(channel, self.token.clone())).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) if is_unreachable(&status) => {
+                    last_status = status;
+                    self.fail_over();
+                }
+                Err(status) => return Err(status),
+            }
+        }
+
+        Err(last_status)
+    }
+
+    async fn connect(&mut self) -> Result<Channel, Status> {
+        if let Some(channel) = &self.channel {
+            return Ok(channel.clone());
+        }
+
+        let endpoint = self.endpoints[self.current].clone();
+        let unavailable = |error: &dyn std::fmt::Display| {
+            Status::unavailable(format!("Failed to connect to {endpoint}: {error}"))
+        };
+        let channel = grpc_tls::endpoint(&endpoint, self.tls.as_ref())
+            .map_err(|error| unavailable(&error))?
+            .connect()
+            .await
+            .map_err(|error| unavailable(&error))?;
+        self.channel = Some(channel.clone());
+
+        Ok(channel)
+    }
+
+    fn fail_over(&mut self) {
+        self.channel = None;
+        self.current = (self.current + 1) % self.endpoints.len();
+    }
+}
+
+/// Whether the endpoint failed to answer, either by reporting itself unavailable or through a
+/// transport failure, which tonic reports as an unknown or internal status caused by the
+/// transport error
+pub(crate) fn is_unreachable(status: &Status) -> bool {
+    match status.code() {
+        Code::Unavailable => true,
+        _ => std::error::Error::source(status)
+            .is_some_and(|source| source.is::<tonic::transport::Error>()),
+    }
+}
@@ -1,2 +1,6 @@
 pub mod cli;
+pub mod client;
+pub mod output;
 pub mod protos;
+pub mod verify;
+pub mod watch;
@@ -1,17 +1,106 @@
+use std::process::ExitCode;
+
+use auth::BearerToken;
+use cli_output::OutputFormat;
+use client_config::Config;
+use grpc_tls::TlsFiles;
 use resolver_client::{
-    cli::{parse_cli, Commands, RegisterDataCenterCommand},
-    protos::resolver::{
-        dcns_resolver_client::DcnsResolverClient, ListDataCentersRequest, RegisterDataCenterRequest,
+    cli::{
+        parse_cli, AuditCommand, Cli, Commands, DataCenterIdCommand, PlaceMachineCommand,
+        RegisterDataCenterCommand, VerifyCommand, WatchCommand,
+    },
+    client::{ResolverClient, DEFAULT_RESOLVER},
+    output::{
+        print_audit_events, print_data_center, print_data_centers, print_events, print_verified,
     },
+    protos::{
+        data_center::Resources,
+        resolver::{
+            DeregisterDataCenterRequest, GetDataCenterRequest, ListDataCentersRequest,
+            PlaceMachineRequest, PlacementConstraints, RegisterDataCenterRequest,
+        },
+    },
+    verify::{verify_data_center, VerifyError},
+    watch::NetworkView,
 };
+use telemetry::TelemetryConfig;
+use tonic::Status;
+use tracing::Instrument;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
     let args = parse_cli();
+    let _telemetry = match TelemetryConfig::new("dcns")
+        .with_default_filter("warn")
+        .with_otlp_endpoint(args.otlp_endpoint.clone())
+        .init()
+    {
+        Ok(telemetry) => telemetry,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // Every call the command makes belongs to this trace, which services log calls under
+    let span = tracing::info_span!("dcns");
+    let Err(error) = run(args).instrument(span.clone()).await else {
+        return ExitCode::SUCCESS;
+    };
+    let exit_code = match error.downcast_ref::<Status>() {
+        Some(status) => {
+            eprintln!("{:?}: {}", status.code(), status.message());
+
+            cli_output::exit_code(status.code())
+        }
+        None => {
+            eprintln!("{error}");
+
+            ExitCode::FAILURE
+        }
+    };
+
+    if let Some(trace_id) = telemetry::trace_id(&span) {
+        eprintln!("Trace id: {trace_id}");
+    }
+
+    exit_code
+}
+
+async fn run(args: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = Config::load()?.profile(args.profile.as_deref())?;
+    let resolvers = if !args.resolvers.is_empty() {
+        args.resolvers
+    } else if !profile.resolvers.is_empty() {
+        profile.resolvers
+    } else {
+        vec![String::from(DEFAULT_RESOLVER)]
+    };
+    let tls = TlsFiles {
+        ca_certificate: profile.tls.ca_certificate,
+        certificate: profile.tls.certificate,
+        key: profile.tls.key,
+    };
+    let mut client = ResolverClient::new(resolvers);
+
+    if let Some(tls) = tls.client_config()? {
+        client = client.with_tls(tls);
+    }
+
+    if let Some(token) = &profile.token {
+        client = client.with_token(BearerToken::new(token)?);
+    }
+
+    let output = args.output;
 
     match args.command {
-        Commands::Register(args) => register_data_center(args).await?,
-        Commands::List => list_data_centers().await?,
+        Commands::Register(args) => register_data_center(args, &mut client, output).await?,
+        Commands::Deregister(args) => deregister_data_center(args, &mut client).await?,
+        Commands::Get(args) => get_data_center(args, &mut client, output).await?,
+        Commands::List => list_data_centers(&mut client, output).await?,
+        Commands::Watch(args) => watch_data_centers(args, &mut client, output).await?,
+        Commands::Place(args) => place_machine(args, &mut client, output).await?,
+        Commands::Verify(args) => verify(args, &mut client, output).await?,
+        Commands::Audit(args) => list_audit_events(args, &mut client, output).await?,
     }
 
     Ok(())
@@ -19,22 +108,160 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 async fn register_data_center(
     command: RegisterDataCenterCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = DcnsResolverClient::connect("http://[::1]:50051").await?;
-    let request = tonic::Request::new(RegisterDataCenterRequest {
-        host_name: String::from(command.host_name),
-    });
-    let response = client.register_data_center(request).await?;
-    dbg!(response);
+    let request = RegisterDataCenterRequest {
+        host_name: command.host_name,
+        data_center_id: command.id.unwrap_or_default(),
+        region: command.region,
+        zone: command.zone,
+        labels: command.labels.into_iter().collect(),
+        ..Default::default()
+    };
+    let response = client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.register_data_center(request).await }
+        })
+        .await?;
+
+    if let Some(data_center) = response.data_center {
+        print_data_center(output, &data_center);
+    }
 
     Ok(())
 }
 
-async fn list_data_centers() -> Result<(), Box<dyn std::error::Error>> {
-    let mut client = DcnsResolverClient::connect("http://[::1]:50051").await?;
-    let request = tonic::Request::new(ListDataCentersRequest {});
-    let response = client.list_data_centers(request).await?;
-    dbg!(response);
+async fn deregister_data_center(
+    command: DataCenterIdCommand,
+    client: &mut ResolverClient,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = DeregisterDataCenterRequest {
+        data_center_id: command.data_center_id,
+        ..Default::default()
+    };
+    client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.deregister_data_center(request).await }
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn get_data_center(
+    command: DataCenterIdCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = GetDataCenterRequest {
+        data_center_id: command.data_center_id,
+    };
+    let response = client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.get_data_center(request).await }
+        })
+        .await?;
+
+    if let Some(data_center) = response.data_center {
+        print_data_center(output, &data_center);
+    }
+
+    Ok(())
+}
+
+async fn list_data_centers(
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = client
+        .call(|mut client| async move { client.list_data_centers(ListDataCentersRequest {}).await })
+        .await?;
+    print_data_centers(output, &response.data_center);
+
+    Ok(())
+}
+
+async fn watch_data_centers(
+    command: WatchCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let status = NetworkView::starting_at(command.from_revision)
+        .follow(client, |view, events| {
+            print_events(output, view.revision(), events)
+        })
+        .await;
+
+    Err(status.into())
+}
+
+async fn place_machine(
+    command: PlaceMachineCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = PlaceMachineRequest {
+        resources: Some(Resources {
+            ram_mb: command.ram_mb,
+            disk_mb: command.disk_mb,
+            vcpus: command.vcpus,
+        }),
+        image_id: command.image_id,
+        constraints: Some(PlacementConstraints {
+            region: command.region,
+            zone: command.zone,
+            labels: command.labels.into_iter().collect(),
+            anti_affinity: command.anti_affinity,
+        }),
+        strategy: command.strategy as i32,
+    };
+    let response = client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.place_machine(request).await }
+        })
+        .await?;
+
+    if let Some(data_center) = response.data_center {
+        print_data_center(output, &data_center);
+    }
+
+    Ok(())
+}
+
+async fn verify(
+    command: VerifyCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match verify_data_center(client, &command.data_center_id, command.key.as_deref()).await {
+        Ok(data_center) => {
+            print_verified(output, &data_center);
+
+            Ok(())
+        }
+        Err(VerifyError::Status(status)) => Err(status.into()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn list_audit_events(
+    command: AuditCommand,
+    client: &mut ResolverClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = command.request();
+    let response = client
+        .call_audit(|mut client| {
+            let request = request.clone();
+            async move { client.list_audit_events(request).await }
+        })
+        .await?;
+    print_audit_events(output, &response.event);
 
     Ok(())
 }
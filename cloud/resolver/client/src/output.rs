@@ -0,0 +1,191 @@
+use cli_output::{OutputFormat, Table};
+use serde_json::{json, Value};
+
+use crate::protos::{
+    audit::AuditEvent,
+    data_center::Resources,
+    resolver::{DataCenter, DataCenterEvent},
+};
+
+const DATA_CENTER_HEADERS: [&str; 8] = [
+    "ID",
+    "HOST",
+    "HEALTH",
+    "REGION",
+    "ZONE",
+    "AVAILABLE",
+    "CAPACITY",
+    "KEY",
+];
+
+const AUDIT_HEADERS: [&str; 6] = [
+    "SEQUENCE",
+    "TIME MS",
+    "SUBJECT",
+    "RPC",
+    "RESOURCES",
+    "OUTCOME",
+];
+
+pub fn print_data_centers(format: OutputFormat, data_centers: &[DataCenter]) {
+    let mut table = Table::new(&DATA_CENTER_HEADERS);
+
+    for data_center in data_centers {
+        table.push(data_center_row(data_center));
+    }
+
+    cli_output::print(
+        format,
+        &table,
+        &Value::Array(data_centers.iter().map(data_center_json).collect()),
+    );
+}
+
+pub fn print_data_center(format: OutputFormat, data_center: &DataCenter) {
+    let mut table = Table::new(&DATA_CENTER_HEADERS);
+    table.push(data_center_row(data_center));
+
+    cli_output::print(format, &table, &data_center_json(data_center));
+}
+
+/// Prints a data center that proved it holds the key it registered with
+pub fn print_verified(format: OutputFormat, data_center: &DataCenter) {
+    let fingerprint = identity::fingerprint(&data_center.public_key);
+    let line = format!(
+        "Data center {} at {} holds key {fingerprint}",
+        data_center.data_center_id, data_center.host_name
+    );
+    let value = json!({
+        "data_center_id": data_center.data_center_id,
+        "host_name": data_center.host_name,
+        "key_fingerprint": fingerprint,
+        "verified": true,
+    });
+
+    cli_output::print_record(format, &line, &value);
+}
+
+/// Prints the events of a watch response, one line per event so they can be followed as they
+/// arrive
+pub fn print_events(format: OutputFormat, revision: u64, events: &[DataCenterEvent]) {
+    for event in events {
+        let Some(data_center) = &event.data_center else {
+            continue;
+        };
+
+        let line = format!(
+            "{revision:<8}  {:<7}  {}  {}  {}",
+            event.change().as_str_name(),
+            data_center.data_center_id,
+            data_center.host_name,
+            data_center.health().as_str_name()
+        );
+        let value = json!({
+            "revision": revision,
+            "change": event.change().as_str_name(),
+            "data_center": data_center_json(data_center),
+        });
+
+        cli_output::print_record(format, &line, &value);
+    }
+}
+
+/// Prints audit events as a table, or one per line so they can be exported as json lines
+pub fn print_audit_events(format: OutputFormat, events: &[AuditEvent]) {
+    if format != OutputFormat::Table {
+        for event in events {
+            cli_output::print_record(format, "", &audit_event_json(event));
+        }
+
+        return;
+    }
+
+    let mut table = Table::new(&AUDIT_HEADERS);
+
+    for event in events {
+        table.push(vec![
+            event.sequence.to_string(),
+            event.timestamp_unix_ms.to_string(),
+            event.subject.clone(),
+            event.rpc.clone(),
+            event.resource_ids.join(","),
+            event.outcome.clone(),
+        ]);
+    }
+
+    print!("{table}");
+}
+
+fn data_center_row(data_center: &DataCenter) -> Vec<String> {
+    vec![
+        data_center.data_center_id.clone(),
+        data_center.host_name.clone(),
+        String::from(data_center.health().as_str_name()),
+        data_center.region.clone(),
+        data_center.zone.clone(),
+        resources_cell(data_center.available_resources.as_ref()),
+        resources_cell(data_center.capacity.as_ref()),
+        key_fingerprint(data_center).unwrap_or_else(|| String::from("-")),
+    ]
+}
+
+/// Fingerprint of the key the data center registered with, if it signed its registration
+fn key_fingerprint(data_center: &DataCenter) -> Option<String> {
+    (!data_center.public_key.is_empty()).then(|| identity::fingerprint(&data_center.public_key))
+}
+
+/// Resources as ram/disk/vcpus
+fn resources_cell(resources: Option<&Resources>) -> String {
+    match resources {
+        Some(resources) => format!(
+            "{}mb/{}mb/{}",
+            resources.ram_mb, resources.disk_mb, resources.vcpus
+        ),
+        None => String::from("-"),
+    }
+}
+
+fn data_center_json(data_center: &DataCenter) -> Value {
+    let capabilities = data_center.capabilities.clone().unwrap_or_default();
+
+    json!({
+        "data_center_id": data_center.data_center_id,
+        "host_name": data_center.host_name,
+        "health": data_center.health().as_str_name(),
+        "region": data_center.region,
+        "zone": data_center.zone,
+        "labels": data_center.labels,
+        "services": capabilities
+            .services()
+            .map(|service| service.as_str_name())
+            .collect::<Vec<_>>(),
+        "architectures": capabilities.architectures,
+        "available_resources": resources_json(data_center.available_resources.as_ref()),
+        "capacity": resources_json(data_center.capacity.as_ref()),
+        "last_seen_unix_ms": data_center.last_seen_unix_ms,
+        "key_fingerprint": key_fingerprint(data_center),
+    })
+}
+
+fn audit_event_json(event: &AuditEvent) -> Value {
+    json!({
+        "sequence": event.sequence,
+        "timestamp_unix_ms": event.timestamp_unix_ms,
+        "subject": event.subject,
+        "rpc": event.rpc,
+        "resource_ids": event.resource_ids,
+        "outcome": event.outcome,
+        "message": event.message,
+    })
+}
+
+fn resources_json(resources: Option<&Resources>) -> Value {
+    match resources {
+        Some(resources) => json!({
+            "ram_mb": resources.ram_mb,
+            "disk_mb": resources.disk_mb,
+            "vcpus": resources.vcpus,
+        }),
+        None => Value::Null,
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::large_enum_variant)]
 pub mod resolver {
     tonic::include_proto!("resolver");
 }
@@ -6,4 +7,6 @@ pub mod data_center {
     tonic::include_proto!("data_center");
 }
 
-
+pub mod audit {
+    tonic::include_proto!("audit");
+}
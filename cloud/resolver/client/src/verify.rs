@@ -0,0 +1,132 @@
+use std::fmt;
+
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    Request, Status,
+};
+
+use crate::{
+    client::ResolverClient,
+    protos::{
+        data_center::ProveIdentityRequest,
+        resolver::{DataCenter, GetDataCenterRequest},
+    },
+};
+
+/// Metadata key naming the data center the resolver forwards a call to
+const DATA_CENTER_ID_KEY: &str = "x-data-center-id";
+
+#[derive(Debug)]
+pub enum VerifyError {
+    Status(Status),
+    /// Data center registered without signing its registration
+    Unsigned(String),
+    /// Data center is registered with another key than the pinned one
+    NotPinned {
+        data_center_id: String,
+        fingerprint: String,
+    },
+    /// Data center answering for the id doesn't hold the registered key
+    InvalidProof {
+        data_center_id: String,
+        error: identity::IdentityError,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Status(status) => write!(formatter, "{}", status.message()),
+            VerifyError::Unsigned(data_center_id) => write!(
+                formatter,
+                "Data center {data_center_id} registered without a key"
+            ),
+            VerifyError::NotPinned {
+                data_center_id,
+                fingerprint,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} is registered with key {fingerprint}, not the pinned key"
+            ),
+            VerifyError::InvalidProof {
+                data_center_id,
+                error,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} failed to prove it holds its registered key: {error}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<Status> for VerifyError {
+    fn from(status: Status) -> Self {
+        VerifyError::Status(status)
+    }
+}
+
+/// Checks that the data center registered as `data_center_id` signs challenges with the key it
+/// registered with, and that the key is the `pinned` one when given, either as a fingerprint or
+/// as a base64 public key
+pub async fn verify_data_center(
+    client: &mut ResolverClient,
+    data_center_id: &str,
+    pinned: Option<&str>,
+) -> Result<DataCenter, VerifyError> {
+    let request = GetDataCenterRequest {
+        data_center_id: String::from(data_center_id),
+    };
+    let data_center = client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.get_data_center(request).await }
+        })
+        .await?
+        .data_center
+        .ok_or_else(|| Status::not_found(format!("No data center {data_center_id}")))?;
+
+    if data_center.public_key.is_empty() {
+        return Err(VerifyError::Unsigned(String::from(data_center_id)));
+    }
+
+    if let Some(pinned) = pinned {
+        if !identity::matches_pin(pinned, &data_center.public_key) {
+            return Err(VerifyError::NotPinned {
+                data_center_id: String::from(data_center_id),
+                fingerprint: identity::fingerprint(&data_center.public_key),
+            });
+        }
+    }
+
+    let challenge = identity::generate_challenge();
+    let routed_to: MetadataValue<Ascii> = data_center_id.parse().map_err(|_| {
+        Status::invalid_argument(format!("Invalid data center id {data_center_id}"))
+    })?;
+    let proof = client
+        .call_proxy(|mut client| {
+            let mut request = Request::new(ProveIdentityRequest {
+                challenge: challenge.clone(),
+            });
+            request
+                .metadata_mut()
+                .insert(DATA_CENTER_ID_KEY, routed_to.clone());
+
+            async move { client.prove_identity(request).await }
+        })
+        .await?;
+
+    identity::verify_challenge(
+        &data_center.public_key,
+        data_center_id,
+        &challenge,
+        &proof.signature,
+    )
+    .map_err(|error| VerifyError::InvalidProof {
+        data_center_id: String::from(data_center_id),
+        error,
+    })?;
+
+    Ok(data_center)
+}
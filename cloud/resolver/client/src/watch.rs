@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use tonic::{Code, Status};
+
+use crate::{
+    client::{is_unreachable, ResolverClient, RETRY_DELAY},
+    protos::resolver::{
+        DataCenter, DataCenterChange, DataCenterEvent, WatchDataCentersRequest,
+        WatchDataCentersResponse,
+    },
+};
+
+/// Live view of the data centers on the network, kept up to date by watching a resolver
+#[derive(Clone, Debug, Default)]
+pub struct NetworkView {
+    data_centers_by_id: BTreeMap<String, DataCenter>,
+    revision: u64,
+}
+
+impl NetworkView {
+    /// Empty view that only follows the changes made after `revision`
+    pub fn starting_at(revision: u64) -> NetworkView {
+        NetworkView {
+            revision,
+            ..Default::default()
+        }
+    }
+
+    pub fn data_centers(&self) -> Vec<DataCenter> {
+        self.data_centers_by_id.values().cloned().collect()
+    }
+
+    pub fn get(&self, data_center_id: &str) -> Option<&DataCenter> {
+        self.data_centers_by_id.get(data_center_id)
+    }
+
+    /// Revision of the registry the view reflects
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn apply(&mut self, response: WatchDataCentersResponse) {
+        if response.reset {
+            self.data_centers_by_id.clear();
+        }
+
+        for event in response.events {
+            let change = event.change();
+            let Some(data_center) = event.data_center else {
+                continue;
+            };
+
+            if change == DataCenterChange::Removed {
+                self.data_centers_by_id.remove(&data_center.data_center_id);
+            } else {
+                self.data_centers_by_id
+                    .insert(data_center.data_center_id.clone(), data_center);
+            }
+        }
+
+        self.revision = response.revision;
+    }
+
+    /// Keeps the view up to date, calling `on_change` with the events of every response
+    /// applied. Watching resumes from the last revision seen whenever the stream breaks, and
+    /// starts over when the resolver no longer retains that revision. Returns the status that
+    /// ended watching once no resolver can be reached or the resolver refuses the watch
+    pub async fn follow<F>(&mut self, client: &mut ResolverClient, mut on_change: F) -> Status
+    where
+        F: FnMut(&NetworkView, &[DataCenterEvent]),
+    {
+        loop {
+            let request = WatchDataCentersRequest {
+                start_revision: self.revision,
+            };
+            let result = client
+                .call(|mut client| {
+                    let request = request.clone();
+                    async move { client.watch_data_centers(request).await }
+                })
+                .await;
+            let mut stream = match result {
+                Ok(stream) => stream,
+                Err(status) if status.code() == Code::OutOfRange => {
+                    self.revision = 0;
+                    continue;
+                }
+                Err(status) => return status,
+            };
+
+            loop {
+                match stream.message().await {
+                    Ok(Some(response)) => {
+                        let events = response.events.clone();
+                        self.apply(response);
+                        on_change(self, &events);
+                    }
+                    Ok(None) => break,
+                    Err(status) if status.code() == Code::OutOfRange => {
+                        self.revision = 0;
+                        break;
+                    }
+                    Err(status) if status.code() == Code::Aborted || is_unreachable(&status) => {
+                        tokio::time::sleep(RETRY_DELAY).await;
+                        break;
+                    }
+                    Err(status) => return status,
+                }
+            }
+        }
+    }
+}
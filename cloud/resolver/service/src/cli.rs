@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use grpc_tls::TlsFiles;
+use telemetry::LogFormat;
+
+#[derive(Debug, Parser)]
+#[command(name = "resolver_service")]
+#[command(about = "Data center network resolver", long_about = None)]
+pub struct Cli {
+    /// Address to serve the resolver on
+    #[arg(long, env = "DCNS_ADDRESS", default_value = "[::1]:50051")]
+    pub address: String,
+    /// Seconds without a heartbeat before a data center is marked unhealthy
+    #[arg(
+        long,
+        env = "DCNS_HEARTBEAT_TIMEOUT_SECS",
+        default_value_t = 15,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub heartbeat_timeout_secs: u64,
+    /// Seconds without a heartbeat before a data center is evicted
+    #[arg(
+        long,
+        env = "DCNS_EVICTION_TTL_SECS",
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub eviction_ttl_secs: u64,
+    /// Directory to persist the registry in, the registry is kept in memory when not provided
+    #[arg(long, env = "DCNS_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// Id of this resolver within its cluster, runs as a standalone resolver when not provided
+    #[arg(long, env = "DCNS_NODE_ID", requires = "peer_secret_file")]
+    pub node_id: Option<String>,
+    /// Other resolver in the cluster as node_id=endpoint, may be repeated
+    #[arg(long = "peer", value_parser = parse_peer)]
+    pub peers: Vec<(String, String)>,
+    /// File holding the base64 secret shared by the resolvers of the cluster, which only accept
+    /// raft calls signed with it. Should differ from the secret of callers' tokens
+    #[arg(long, env = "DCNS_PEER_SECRET_FILE")]
+    pub peer_secret_file: Option<PathBuf>,
+    /// Endpoint of a data center to list data centers from the gossip membership of instead of
+    /// the registry, may be repeated to fail over between data centers
+    #[arg(long = "membership", env = "DCNS_MEMBERSHIP", value_delimiter = ',')]
+    pub membership: Vec<String>,
+    /// Pem certificate to serve tls with and to present to peers and data centers
+    #[arg(long, env = "DCNS_TLS_CERTIFICATE", requires = "tls_key")]
+    pub tls_certificate: Option<PathBuf>,
+    /// Pem private key of the tls certificate
+    #[arg(long, env = "DCNS_TLS_KEY", requires = "tls_certificate")]
+    pub tls_key: Option<PathBuf>,
+    /// Pem certificate authority peers and data centers are verified against, and that clients
+    /// must present a certificate signed by
+    #[arg(long, env = "DCNS_TLS_CA_CERTIFICATE")]
+    pub tls_ca_certificate: Option<PathBuf>,
+    /// File holding the base64 secret bearer tokens of callers are signed with, callers aren't
+    /// authenticated when neither this nor an api keys file is provided
+    #[arg(long, env = "DCNS_AUTH_SECRET_FILE")]
+    pub auth_secret_file: Option<PathBuf>,
+    /// File holding the digests of the api keys callers may use as bearer tokens
+    #[arg(long, env = "DCNS_API_KEYS_FILE")]
+    pub api_keys_file: Option<PathBuf>,
+    /// Refuse data centers registering without signing their registration with their key,
+    /// rather than only verifying the registrations that are signed
+    #[arg(long, env = "DCNS_REQUIRE_SIGNED_REGISTRATIONS")]
+    pub require_signed_registrations: bool,
+    /// File the audit log of the registrations and proxied mutating calls served by this
+    /// resolver is appended to as json lines, kept in memory when not provided
+    #[arg(long, env = "DCNS_AUDIT_LOG_FILE")]
+    pub audit_log_file: Option<PathBuf>,
+    /// Subject allowed to read the audit log, may be repeated. Every authenticated caller may
+    /// read it when not provided
+    #[arg(
+        long = "audit-reader",
+        env = "DCNS_AUDIT_READERS",
+        value_delimiter = ','
+    )]
+    pub audit_readers: Vec<String>,
+    /// Address to serve prometheus metrics on over http at /metrics, not served when not
+    /// provided
+    #[arg(long, env = "DCNS_METRICS_ADDRESS")]
+    pub metrics_address: Option<String>,
+    /// Format logs are written to standard error in, text or json. Which logs are written is
+    /// filtered with RUST_LOG, info by default
+    #[arg(long, env = "DCNS_LOG_FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Otlp collector spans are exported to over grpc, e.g. http://localhost:4317, spans aren't
+    /// exported when not provided
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    /// Bearer token the resolver presents to data centers when checking their resources or
+    /// forwarding calls of callers that sent none
+    #[arg(long, env = "DCNS_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+impl Cli {
+    pub fn tls_files(&self) -> TlsFiles {
+        TlsFiles {
+            ca_certificate: self.tls_ca_certificate.clone(),
+            certificate: self.tls_certificate.clone(),
+            key: self.tls_key.clone(),
+        }
+    }
+}
+
+fn parse_peer(peer: &str) -> Result<(String, String), String> {
+    let Some((node_id, endpoint)) = peer.split_once('=') else {
+        return Err(format!(
+            "Peer {peer} should be formatted as node_id=endpoint"
+        ));
+    };
+
+    Ok((String::from(node_id), String::from(endpoint)))
+}
+
+pub fn parse_cli() -> Cli {
+    Cli::parse()
+}
@@ -0,0 +1,47 @@
+use auth::BearerToken;
+use grpc_tls::TlsError;
+use telemetry::Traced;
+use tonic::{
+    metadata::MetadataMap,
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+};
+
+use crate::protos::data_center::data_center_client;
+
+/// Client of a data center sending a bearer token and the trace context with every call
+pub type DataCenterClient =
+    data_center_client::DataCenterClient<InterceptedService<Channel, Traced<BearerToken>>>;
+
+/// How the resolver reaches data centers, both to check their resources and to forward calls
+#[derive(Clone, Debug, Default)]
+pub struct Dialer {
+    /// Tls data centers are dialed with, data centers are dialed in plaintext when not provided
+    pub tls: Option<ClientTlsConfig>,
+    /// Token the resolver presents on its own behalf
+    pub token: BearerToken,
+}
+
+impl Dialer {
+    /// Endpoint of the data center reached at `host_name`
+    pub fn endpoint(&self, host_name: &str) -> Result<Endpoint, TlsError> {
+        grpc_tls::endpoint(host_name, self.tls.as_ref())
+    }
+
+    /// Client calling a data center over `channel` on behalf of the caller that sent
+    /// `metadata`, carrying only the caller's token so anonymous callers stay anonymous
+    pub fn client(&self, channel: Channel, metadata: &MetadataMap) -> DataCenterClient {
+        let token = BearerToken::forwarded(metadata).unwrap_or_default();
+
+        data_center_client::DataCenterClient::with_interceptor(channel, Traced::new(token))
+    }
+
+    /// Client calling a data center over `channel` on the resolver's own behalf, for the calls
+    /// the resolver makes itself such as checking resources to schedule machines
+    pub fn resolver_client(&self, channel: Channel) -> DataCenterClient {
+        data_center_client::DataCenterClient::with_interceptor(
+            channel,
+            Traced::new(self.token.clone()),
+        )
+    }
+}
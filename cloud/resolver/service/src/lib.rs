@@ -1 +1,12 @@
+pub mod cli;
+pub mod dialer;
+pub mod membership;
+pub mod metrics;
 pub mod protos;
+pub mod proxy;
+pub mod raft;
+pub mod readiness;
+pub mod registry;
+pub mod resolver;
+pub mod scheduler;
+pub mod store;
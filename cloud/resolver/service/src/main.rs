@@ -1,60 +1,172 @@
-use resolver_service::protos::resolver::{
-    dcns_resolver_server::{DcnsResolver, DcnsResolverServer},
-    DataCenter, ListDataCentersRequest, ListDataCentersResponse, RegisterDataCenterRequest,
-    RegisterDataCenterResponse,
+use audit::{protos::audit::audit_server::AuditServer, service::AuditService, AuditLog};
+use auth::{AuthInterceptor, Authenticator, BearerToken};
+use health::Readiness;
+use metrics::{Metrics, MetricsLayer};
+use resolver_service::{
+    cli::parse_cli,
+    dialer::Dialer,
+    membership::MembershipView,
+    metrics::ResolverCollector,
+    protos::{
+        data_center::data_center_server::DataCenterServer,
+        resolver::{dcns_resolver_server::DcnsResolverServer, raft_peer_server::RaftPeerServer},
+        FILE_DESCRIPTOR_SET,
+    },
+    proxy::DataCenterProxy,
+    raft::{
+        service::{peer_server, RaftPeerService},
+        RaftConfig, RaftNode,
+    },
+    readiness::ResolverReadiness,
+    registry::{unix_time_ms, Registry},
+    resolver::LocalDcnsResolver,
 };
-use std::sync::Mutex;
-use tonic::{transport::Server, Request, Response, Status};
+use std::{error::Error, sync::Arc, time::Duration};
+use telemetry::{TelemetryConfig, TraceLayer};
+use tonic::{server::NamedService, transport::Server};
 
-#[derive(Default)]
-struct LocalDcnsResolver {
-    data_centers: Mutex<Vec<DataCenter>>,
-}
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_cli();
+    let _telemetry = TelemetryConfig::new("resolver_service")
+        .with_log_format(args.log_format)
+        .with_otlp_endpoint(args.otlp_endpoint.clone())
+        .init()?;
+    let addr = args.address.parse()?;
+    let heartbeat_timeout = Duration::from_secs(args.heartbeat_timeout_secs);
+    let eviction_ttl = Duration::from_secs(args.eviction_ttl_secs);
+    let tls = args.tls_files();
+    let dialer = Dialer {
+        tls: tls.client_config()?,
+        token: BearerToken::optional(args.token.as_deref())?,
+    };
+    let auth = AuthInterceptor::new(Authenticator::load(
+        args.auth_secret_file.as_deref(),
+        args.api_keys_file.as_deref(),
+    )?);
+    let mut audit = match &args.audit_log_file {
+        Some(path) => AuditLog::open(path)?,
+        None => AuditLog::default(),
+    };
 
-#[tonic::async_trait]
-impl DcnsResolver for LocalDcnsResolver {
-    async fn list_data_centers(
-        &self,
-        _request: Request<ListDataCentersRequest>,
-    ) -> Result<Response<ListDataCentersResponse>, Status> {
-        Ok(Response::new(ListDataCentersResponse {
-            data_center: self
-                .data_centers
-                .lock()
-                .expect("Should fetch lock")
-                .to_vec(),
-        }))
+    if !args.audit_readers.is_empty() {
+        audit = audit.with_readers(args.audit_readers);
     }
 
-    async fn register_data_center(
-        &self,
-        request: Request<RegisterDataCenterRequest>,
-    ) -> Result<Response<RegisterDataCenterResponse>, Status> {
-        let request = request.into_inner();
-        self.data_centers
-            .lock()
-            .expect("Should fetch lock")
-            .push(DataCenter {
-                host_name: String::from(&request.host_name),
-            });
-
-        Ok(Response::new(RegisterDataCenterResponse {
-            data_center: Some(DataCenter {
-                host_name: String::from(&request.host_name),
-            }),
-        }))
+    let audit = Arc::new(audit);
+    let metrics = Arc::new(Metrics::new());
+    let mut server = Server::builder();
+
+    if let Some(server_tls) = tls.server_config()? {
+        server = server.tls_config(server_tls)?;
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = "[::1]:50051".parse()?;
-    let dcns_resolver = LocalDcnsResolver::default();
+    // Only the registry differs between a standalone resolver and a node of a cluster
+    let (dcns_resolver, node) = match args.node_id {
+        None => {
+            let mut registry = match &args.data_dir {
+                Some(data_dir) => Registry::open(data_dir, unix_time_ms())?,
+                None => Registry::default(),
+            };
+
+            if args.require_signed_registrations {
+                registry = registry.with_signed_registrations_required();
+            }
+
+            (LocalDcnsResolver::new(registry), None)
+        }
+        Some(node_id) => {
+            tracing::info!(%node_id, "Starting raft node");
+            let peer_secret_file = args
+                .peer_secret_file
+                .ok_or("Resolvers running in a cluster need a peer secret file")?;
+            let mut config = RaftConfig::new(
+                node_id,
+                args.peers.into_iter().collect(),
+                auth::read_secret(&peer_secret_file)?,
+            );
+            config.data_dir = args.data_dir;
+            config.tls = dialer.tls.clone();
+            config.signed_registrations_required = args.require_signed_registrations;
+            let node = RaftNode::start(config)?;
+
+            (LocalDcnsResolver::replicated(node.clone()), Some(node))
+        }
+    };
+    let mut dcns_resolver = dcns_resolver
+        .with_dialer(dialer.clone())
+        .with_audit_log(audit.clone());
+
+    if !args.membership.is_empty() {
+        dcns_resolver =
+            dcns_resolver.with_membership(MembershipView::new(args.membership, &dialer)?);
+    }
+
+    dcns_resolver.spawn_sweeper(heartbeat_timeout, eviction_ttl);
+    serve_metrics(&metrics, &dcns_resolver, args.metrics_address.as_deref())?;
+    let mut services = vec![
+        DataCenterServer::<DataCenterProxy>::NAME,
+        DcnsResolverServer::<LocalDcnsResolver>::NAME,
+        AuditServer::<AuditService>::NAME,
+    ];
 
-    Server::builder()
-        .add_service(DcnsResolverServer::new(dcns_resolver))
+    if node.is_some() {
+        services.push(RaftPeerServer::<RaftPeerService>::NAME);
+    }
+
+    let (_readiness, health_service) = Readiness::start(
+        ResolverReadiness::new(dcns_resolver.clone()),
+        services,
+        health::CHECK_INTERVAL,
+    );
+
+    tracing::info!(%addr, "Serving resolver");
+
+    server
+        .layer(TraceLayer)
+        .layer(MetricsLayer::new(metrics))
+        .add_service(DataCenterServer::with_interceptor(
+            DataCenterProxy::new(dcns_resolver.clone()),
+            auth.clone(),
+        ))
+        .add_service(DcnsResolverServer::with_interceptor(
+            dcns_resolver,
+            auth.clone(),
+        ))
+        .add_service(AuditServer::with_interceptor(
+            AuditService::new(audit),
+            auth,
+        ))
+        .add_optional_service(node.map(peer_server))
+        .add_service(health_service)
+        .add_service(health::reflection(&[FILE_DESCRIPTOR_SET])?)
         .serve(addr)
         .await?;
 
     Ok(())
 }
+
+/// Registers the registry of `resolver` with `metrics` and serves them on `address` when
+/// provided
+fn serve_metrics(
+    metrics: &Arc<Metrics>,
+    resolver: &LocalDcnsResolver,
+    address: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    metrics
+        .registry()
+        .register(Box::new(ResolverCollector::new(resolver.clone())))?;
+
+    if let Some(address) = address {
+        let address = address.parse()?;
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            if let Err(error) = metrics::serve(metrics, address).await {
+                tracing::error!(%error, "Failed to serve metrics");
+            }
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use auth::BearerToken;
+use grpc_tls::TlsError;
+use telemetry::Traced;
+use tonic::{service::interceptor::InterceptedService, transport::Channel, Request, Status};
+
+use crate::{
+    dialer::Dialer,
+    protos::{
+        membership::{membership_client::MembershipClient, ListMembersRequest, MemberState},
+        resolver::{DataCenter, DataCenterHealth},
+    },
+};
+
+/// View of the data centers taken from the gossip membership they maintain among themselves,
+/// read from whichever of the given data centers answers first
+#[derive(Clone)]
+pub struct MembershipView {
+    clients: Vec<MembershipClient<InterceptedService<Channel, Traced<BearerToken>>>>,
+}
+
+impl MembershipView {
+    /// View read from the data centers at `endpoints`, reached through `dialer` on the
+    /// resolver's behalf
+    pub fn new(endpoints: Vec<String>, dialer: &Dialer) -> Result<MembershipView, TlsError> {
+        let clients = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                dialer.endpoint(&endpoint).map(|endpoint| {
+                    MembershipClient::with_interceptor(
+                        endpoint.connect_lazy(),
+                        Traced::new(dialer.token.clone()),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(MembershipView { clients })
+    }
+
+    /// Data centers the membership considers alive or suspects of having failed, the latter
+    /// reported as unhealthy
+    pub async fn data_centers(&self) -> Result<Vec<DataCenter>, Status> {
+        let mut last_status = Status::unavailable("No membership endpoints configured");
+
+        for client in self.clients.iter() {
+            let members = match client
+                .clone()
+                .list_members(Request::new(ListMembersRequest {}))
+                .await
+            {
+                Ok(response) => response.into_inner().members,
+                Err(status) => {
+                    last_status = status;
+                    continue;
+                }
+            };
+            let mut data_centers: Vec<DataCenter> = members
+                .into_iter()
+                .filter_map(|member| {
+                    let health = match member.state() {
+                        MemberState::Alive => DataCenterHealth::Healthy,
+                        MemberState::Suspect => DataCenterHealth::Unhealthy,
+                        MemberState::Dead | MemberState::Left => return None,
+                    };
+                    let mut data_center = member.data_center?;
+                    data_center.set_health(health);
+
+                    Some(data_center)
+                })
+                .collect();
+            data_centers.sort_by(|left, right| left.data_center_id.cmp(&right.data_center_id));
+
+            return Ok(data_centers);
+        }
+
+        Err(Status::unavailable(format!(
+            "No membership endpoint answered: {}",
+            last_status.message()
+        )))
+    }
+}
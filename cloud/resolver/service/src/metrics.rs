@@ -0,0 +1,53 @@
+use metrics::prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    IntGaugeVec, Opts,
+};
+
+use crate::{protos::resolver::DataCenterHealth, resolver::LocalDcnsResolver};
+
+/// Collects the data centers held in the registry of a resolver by health when metrics are
+/// scraped
+pub struct ResolverCollector {
+    resolver: LocalDcnsResolver,
+    data_centers: IntGaugeVec,
+}
+
+impl ResolverCollector {
+    pub fn new(resolver: LocalDcnsResolver) -> ResolverCollector {
+        ResolverCollector {
+            resolver,
+            data_centers: IntGaugeVec::new(
+                Opts::new(
+                    "dcns_registered_data_centers",
+                    "Data centers held in the registry by health",
+                ),
+                &["health"],
+            )
+            .expect("Should create data centers gauge"),
+        }
+    }
+}
+
+impl Collector for ResolverCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.data_centers.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let registered = self.resolver.registered();
+
+        for health in [DataCenterHealth::Healthy, DataCenterHealth::Unhealthy] {
+            let count = registered
+                .iter()
+                .filter(|data_center| data_center.health() == health)
+                .count();
+
+            self.data_centers
+                .with_label_values(&[health.as_str_name()])
+                .set(count as i64);
+        }
+
+        self.data_centers.collect()
+    }
+}
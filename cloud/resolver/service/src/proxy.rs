@@ -0,0 +1,878 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    sync::Mutex,
+    time::Duration,
+};
+
+use grpc_tls::TlsError;
+use resource_name::{ResourceKind, ResourceName};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinSet,
+};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tonic::{metadata::MetadataMap, transport::Channel, Request, Response, Status, Streaming};
+use tracing::Instrument;
+
+use crate::{
+    dialer::DataCenterClient,
+    protos::{
+        data_center::{
+            data_center_server::DataCenter, CheckResourceRequest, CheckResourceResponse,
+            CreateFileMetadataRequest, CreateFileMetadataResponse, CreateImageMetadataRequest,
+            CreateImageMetadataResponse, CreateMachineRequest, CreateMachineResponse,
+            DownloadFileRequest, DownloadFileResponse, GetFileMetadataRequest,
+            GetFileMetadataResponse, GetImageMetadataRequest, GetImageMetadataResponse,
+            ListImageMetadataRequest, ListImageMetadataResponse, ListInstancesRequest,
+            ListInstancesResponse, ListMachinesRequest, ListMachinesResponse, ProveIdentityRequest,
+            ProveIdentityResponse, ProvisionInstanceRequest, ProvisionInstanceResponse, Resources,
+            ServiceType, StartInstanceRequest, StartInstanceResponse, StopInstanceRequest,
+            StopInstanceResponse, UploadFileRequest, UploadFileResponse,
+        },
+        resolver,
+    },
+    resolver::LocalDcnsResolver,
+    scheduler::{self, Spread},
+};
+
+/// Metadata key callers set to send a call to a specific data center instead of letting the
+/// proxy choose one
+pub const DATA_CENTER_ID_KEY: &str = "x-data-center-id";
+const ONE_MB: u64 = 1048576;
+/// Time a data center has to report its live resources when placing files
+const PLACEMENT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of resource owners remembered, the oldest are forgotten first and found again by
+/// asking every data center
+const OWNERS_CAPACITY: usize = 65536;
+
+/// What calls are routed by, either resources named after the data center holding them or
+/// files, which are only known by their path
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Routed {
+    Resource(ResourceKind),
+    File,
+}
+
+impl fmt::Display for Routed {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Routed::Resource(kind) => write!(formatter, "{kind}"),
+            Routed::File => formatter.write_str("file"),
+        }
+    }
+}
+
+/// Data center service that forwards every call to the data center owning the resources it
+/// touches, or to one chosen by placement for new resources, so clients only need to know the
+/// resolver
+pub struct DataCenterProxy {
+    resolver: LocalDcnsResolver,
+    /// Data center ids of the resources seen through the proxy
+    owners: Mutex<Owners>,
+    channels: Mutex<HashMap<String, Channel>>,
+}
+
+/// Owners of resources, forgetting the oldest once it holds [`OWNERS_CAPACITY`] of them
+#[derive(Default)]
+struct Owners {
+    by_resource: HashMap<(Routed, String), String>,
+    /// Resources in the order they were first recorded
+    recorded: VecDeque<(Routed, String)>,
+}
+
+impl Owners {
+    fn insert(&mut self, resource: (Routed, String), data_center_id: String) {
+        if self
+            .by_resource
+            .insert(resource.clone(), data_center_id)
+            .is_some()
+        {
+            return;
+        }
+
+        self.recorded.push_back(resource);
+
+        if self.recorded.len() > OWNERS_CAPACITY {
+            if let Some(oldest) = self.recorded.pop_front() {
+                self.by_resource.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl DataCenterProxy {
+    pub fn new(resolver: LocalDcnsResolver) -> DataCenterProxy {
+        DataCenterProxy {
+            resolver,
+            owners: Mutex::default(),
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn data_center(&self, data_center_id: &str) -> Result<resolver::DataCenter, Status> {
+        self.resolver
+            .data_centers()
+            .await?
+            .into_iter()
+            .find(|data_center| data_center.data_center_id == data_center_id)
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No data center registered with id {data_center_id}"
+                ))
+            })
+    }
+
+    /// Client of the data center calling it on behalf of the caller that sent `metadata`
+    fn client(
+        &self,
+        data_center: &resolver::DataCenter,
+        metadata: &MetadataMap,
+    ) -> Result<DataCenterClient, TlsError> {
+        let dialer = self.resolver.dialer();
+        let mut channels = self.channels.lock().expect("Should acquire lock");
+        let channel = match channels.get(&data_center.host_name) {
+            Some(channel) => channel.clone(),
+            None => {
+                let channel = dialer.endpoint(&data_center.host_name)?.connect_lazy();
+                channels.insert(data_center.host_name.clone(), channel.clone());
+
+                channel
+            }
+        };
+
+        Ok(dialer.client(channel, metadata))
+    }
+
+    /// Data center the caller asked for through the request metadata
+    async fn requested(
+        &self,
+        metadata: &MetadataMap,
+    ) -> Result<Option<resolver::DataCenter>, Status> {
+        let Some(data_center_id) = metadata.get(DATA_CENTER_ID_KEY) else {
+            return Ok(None);
+        };
+        let data_center_id = data_center_id.to_str().map_err(|_| {
+            Status::invalid_argument(format!("{DATA_CENTER_ID_KEY} must be a valid string"))
+        })?;
+
+        self.data_center(data_center_id).await.map(Some)
+    }
+
+    /// Client of the data center named by the resource name, of the one the caller asked for
+    /// or of the one owning the resource. Ids holding a `/` are names, and must name a resource
+    /// of `kind` as data centers expect
+    async fn route(
+        &self,
+        metadata: &MetadataMap,
+        kind: Routed,
+        id: &str,
+    ) -> Result<(String, DataCenterClient), Status> {
+        let data_center = match kind {
+            Routed::Resource(kind) if id.contains('/') => {
+                let name = ResourceName::parse_kind(id, kind)
+                    .map_err(|error| Status::invalid_argument(error.to_string()))?;
+
+                self.data_center(name.data_center_id()).await?
+            }
+            _ => match self.requested(metadata).await? {
+                Some(data_center) => data_center,
+                None => self.owner(metadata, kind, id).await?,
+            },
+        };
+
+        Ok((
+            data_center.data_center_id.clone(),
+            self.client(&data_center, metadata)
+                .map_err(|error| invalid_host(&data_center, error))?,
+        ))
+    }
+
+    /// Client of the data center the caller asked for, or of the healthy data center offering
+    /// `service` with the most room left for `disk_mb` of data
+    async fn place(
+        &self,
+        metadata: &MetadataMap,
+        service: ServiceType,
+        disk_mb: u32,
+    ) -> Result<(String, DataCenterClient), Status> {
+        if let Some(data_center) = self.requested(metadata).await? {
+            return Ok((
+                data_center.data_center_id.clone(),
+                self.client(&data_center, metadata)
+                    .map_err(|error| invalid_host(&data_center, error))?,
+            ));
+        }
+
+        let eligible: Vec<resolver::DataCenter> = self
+            .resolver
+            .data_centers()
+            .await?
+            .into_iter()
+            .filter(|data_center| {
+                data_center.health() == resolver::DataCenterHealth::Healthy
+                    && scheduler::offers(data_center, service)
+            })
+            .collect();
+        let candidates =
+            scheduler::check_candidates(eligible, "", PLACEMENT_TIMEOUT, self.resolver.dialer())
+                .await;
+        let resources = Resources {
+            disk_mb,
+            ..Default::default()
+        };
+        let Some(candidate) = scheduler::place(&candidates, &resources, &Spread) else {
+            return Err(Status::resource_exhausted(format!(
+                "No data center offering {} has room for {disk_mb} mb",
+                service.as_str_name()
+            )));
+        };
+
+        Ok((
+            candidate.data_center.data_center_id.clone(),
+            self.client(&candidate.data_center, metadata)
+                .map_err(|error| invalid_host(&candidate.data_center, error))?,
+        ))
+    }
+
+    /// Data center owning the resource, asking every data center on behalf of the caller that
+    /// sent `metadata` when it isn't known yet
+    async fn owner(
+        &self,
+        metadata: &MetadataMap,
+        kind: Routed,
+        id: &str,
+    ) -> Result<resolver::DataCenter, Status> {
+        if self.owner_id(kind, id).is_none() {
+            self.discover(metadata, kind, id).await?;
+        }
+
+        let Some(data_center_id) = self.owner_id(kind, id) else {
+            return Err(Status::not_found(format!(
+                "No data center holds {kind} {id}"
+            )));
+        };
+
+        self.data_center(&data_center_id).await
+    }
+
+    fn owner_id(&self, kind: Routed, id: &str) -> Option<String> {
+        self.owners
+            .lock()
+            .expect("Should acquire lock")
+            .by_resource
+            .get(&(kind, String::from(id)))
+            .cloned()
+    }
+
+    /// Remembers the owner of a resource whose name doesn't already tell it
+    fn record(&self, kind: Routed, id: &str, data_center_id: &str) {
+        if id.is_empty() || matches!(kind, Routed::Resource(_)) && id.contains('/') {
+            return;
+        }
+
+        self.owners
+            .lock()
+            .expect("Should acquire lock")
+            .insert((kind, String::from(id)), String::from(data_center_id));
+    }
+
+    /// Asks every data center for resources of `kind`, recording the owners of those found
+    async fn discover(&self, metadata: &MetadataMap, kind: Routed, id: &str) -> Result<(), Status> {
+        match kind {
+            Routed::Resource(ResourceKind::Machine) => {
+                self.list_machines(with_metadata(ListMachinesRequest::default(), metadata))
+                    .await?;
+            }
+            Routed::Resource(ResourceKind::Instance) => {
+                self.list_instances(with_metadata(ListInstancesRequest::default(), metadata))
+                    .await?;
+            }
+            Routed::Resource(ResourceKind::Image) => {
+                let request = GetImageMetadataRequest {
+                    image_id: String::from(id),
+                };
+
+                for (data_center_id, response) in self
+                    .fan_out(metadata, move |mut client| {
+                        let request = request.clone();
+                        async move { client.get_image_metadata(request).await }
+                    })
+                    .await?
+                {
+                    if let Some(image) = response.image {
+                        self.record(kind, &image.image_id, &data_center_id);
+                    }
+                }
+            }
+            Routed::File => {
+                let request = GetFileMetadataRequest {
+                    file_path: String::from(id),
+                };
+
+                for (data_center_id, response) in self
+                    .fan_out(metadata, move |mut client| {
+                        let request = request.clone();
+                        async move { client.get_file_metadata(request).await }
+                    })
+                    .await?
+                {
+                    if let Some(metadata) = response.metadata {
+                        self.record(kind, &metadata.file_path, &data_center_id);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `call` against the data center the caller asked for, or against every data center,
+    /// returning the responses of those that answered along with their ids
+    async fn fan_out<T, F, R>(
+        &self,
+        metadata: &MetadataMap,
+        call: F,
+    ) -> Result<Vec<(String, T)>, Status>
+    where
+        T: Send + 'static,
+        F: Fn(DataCenterClient) -> R,
+        R: Future<Output = Result<Response<T>, Status>> + Send + 'static,
+    {
+        let data_centers = match self.requested(metadata).await? {
+            Some(data_center) => vec![data_center],
+            None => self.resolver.data_centers().await?,
+        };
+        let mut calls = JoinSet::new();
+
+        for data_center in data_centers {
+            let response = call(
+                self.client(&data_center, metadata)
+                    .map_err(|error| invalid_host(&data_center, error))?,
+            );
+            calls.spawn(
+                async move { (data_center.data_center_id, response.await) }.in_current_span(),
+            );
+        }
+
+        let mut responses = Vec::new();
+
+        while let Some(result) = calls.join_next().await {
+            match result {
+                Ok((data_center_id, Ok(response))) => {
+                    responses.push((data_center_id, response.into_inner()))
+                }
+                Ok((data_center_id, Err(status))) => tracing::warn!(
+                    data_center_id,
+                    code = ?status.code(),
+                    message = status.message(),
+                    "Data center failed to answer"
+                ),
+                Err(error) => tracing::warn!(%error, "Failed to call data center"),
+            }
+        }
+
+        responses.sort_by(|left, right| left.0.cmp(&right.0));
+
+        Ok(responses)
+    }
+}
+
+fn with_metadata<T>(message: T, metadata: &MetadataMap) -> Request<T> {
+    let mut request = Request::new(message);
+    *request.metadata_mut() = metadata.clone();
+
+    request
+}
+
+fn invalid_host(data_center: &resolver::DataCenter, error: TlsError) -> Status {
+    Status::internal(format!(
+        "Data center {} has an invalid host name: {error}",
+        data_center.data_center_id
+    ))
+}
+
+fn sum_resources(left: Option<Resources>, right: Option<Resources>) -> Option<Resources> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(Resources {
+            ram_mb: left.ram_mb + right.ram_mb,
+            disk_mb: left.disk_mb + right.disk_mb,
+            vcpus: left.vcpus + right.vcpus,
+        }),
+        (left, right) => left.or(right),
+    }
+}
+
+fn disk_mb(file_size: u64) -> u32 {
+    file_size.div_ceil(ONE_MB) as u32
+}
+
+#[tonic::async_trait]
+impl DataCenter for DataCenterProxy {
+    type DownloadFileStream = Streaming<DownloadFileResponse>;
+
+    async fn check_resource(
+        &self,
+        request: Request<CheckResourceRequest>,
+    ) -> Result<Response<CheckResourceResponse>, Status> {
+        let responses = self
+            .fan_out(request.metadata(), |mut client| async move {
+                client.check_resource(CheckResourceRequest {}).await
+            })
+            .await?;
+
+        Ok(Response::new(responses.into_iter().fold(
+            CheckResourceResponse::default(),
+            |total, (_, response)| CheckResourceResponse {
+                available_resources: sum_resources(
+                    total.available_resources,
+                    response.available_resources,
+                ),
+                total_resources: sum_resources(total.total_resources, response.total_resources),
+            },
+        )))
+    }
+
+    async fn provision_instance(
+        &self,
+        request: Request<ProvisionInstanceRequest>,
+    ) -> Result<Response<ProvisionInstanceResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/ProvisionInstance")
+            .resource(&request.get_ref().machine_id);
+
+        entry
+            .record_with(
+                async move {
+                    let (data_center_id, mut client) = self
+                        .route(
+                            request.metadata(),
+                            Routed::Resource(ResourceKind::Machine),
+                            &request.get_ref().machine_id,
+                        )
+                        .await?;
+                    let response = client.provision_instance(request.into_inner()).await?;
+
+                    if let Some(instance) = &response.get_ref().instance {
+                        self.record(
+                            Routed::Resource(ResourceKind::Instance),
+                            &instance.instance_id,
+                            &data_center_id,
+                        );
+                    }
+
+                    Ok(response)
+                },
+                |response| {
+                    response
+                        .instance
+                        .iter()
+                        .map(|instance| instance.instance_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn create_image_metadata(
+        &self,
+        request: Request<CreateImageMetadataRequest>,
+    ) -> Result<Response<CreateImageMetadataResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/CreateImageMetadata")
+            .resource(&request.get_ref().project_id);
+
+        entry
+            .record_with(
+                async move {
+                    let (data_center_id, mut client) = self
+                        .place(
+                            request.metadata(),
+                            ServiceType::OperatingSystemImages,
+                            disk_mb(request.get_ref().file_size),
+                        )
+                        .await?;
+                    let response = client.create_image_metadata(request.into_inner()).await?;
+
+                    if let Some(image) = &response.get_ref().os_image_metadata {
+                        self.record(
+                            Routed::Resource(ResourceKind::Image),
+                            &image.image_id,
+                            &data_center_id,
+                        );
+
+                        if let Some(file_metadata) = &image.file_metadata {
+                            self.record(Routed::File, &file_metadata.file_path, &data_center_id);
+                        }
+                    }
+
+                    Ok(response)
+                },
+                |response| {
+                    response
+                        .os_image_metadata
+                        .iter()
+                        .map(|image| image.image_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn get_image_metadata(
+        &self,
+        request: Request<GetImageMetadataRequest>,
+    ) -> Result<Response<GetImageMetadataResponse>, Status> {
+        let route = self
+            .route(
+                request.metadata(),
+                Routed::Resource(ResourceKind::Image),
+                &request.get_ref().image_id,
+            )
+            .await;
+
+        match route {
+            Ok((_, mut client)) => client.get_image_metadata(request.into_inner()).await,
+            // Data centers answer with no image rather than an error for unknown images
+            Err(status) if status.code() == tonic::Code::NotFound => {
+                Ok(Response::new(GetImageMetadataResponse { image: None }))
+            }
+            Err(status) => Err(status),
+        }
+    }
+
+    async fn create_file_metadata(
+        &self,
+        request: Request<CreateFileMetadataRequest>,
+    ) -> Result<Response<CreateFileMetadataResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/CreateFileMetadata")
+            .resource(&request.get_ref().project_id)
+            .resource(&request.get_ref().file_path);
+
+        entry
+            .record(async move {
+                let (data_center_id, mut client) = self
+                    .place(
+                        request.metadata(),
+                        ServiceType::Storage,
+                        disk_mb(request.get_ref().file_size),
+                    )
+                    .await?;
+                let response = client.create_file_metadata(request.into_inner()).await?;
+
+                if let Some(metadata) = &response.get_ref().metadata {
+                    self.record(Routed::File, &metadata.file_path, &data_center_id);
+                }
+
+                Ok(response)
+            })
+            .await
+    }
+
+    async fn upload_file(
+        &self,
+        request: Request<Streaming<UploadFileRequest>>,
+    ) -> Result<Response<UploadFileResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/UploadFile");
+        let (metadata, _, mut stream) = request.into_parts();
+        let first = match stream.message().await {
+            Ok(Some(first)) => first,
+            Ok(None) => {
+                return entry
+                    .record(async {
+                        Err(Status::invalid_argument(
+                            "Uploads must contain at least one chunk",
+                        ))
+                    })
+                    .await
+            }
+            Err(status) => return entry.record(async { Err(status) }).await,
+        };
+
+        entry
+            .resource(&first.file_path)
+            .record(async move {
+                let (_, mut client) = self
+                    .route(&metadata, Routed::File, &first.file_path)
+                    .await?;
+
+                // Chunks are forwarded until the caller's upload fails, then held open so the
+                // call to the data center is dropped, aborting it, rather than ended, which
+                // would store a truncated file
+                let (chunks, forwarded) = mpsc::channel(1);
+                let (failed, upload_failed) = oneshot::channel();
+                tokio::spawn(
+                    async move {
+                        while let Some(chunk) = stream.next().await {
+                            match chunk {
+                                Ok(chunk) => {
+                                    if chunks.send(chunk).await.is_err() {
+                                        return;
+                                    }
+                                }
+                                Err(status) => {
+                                    let _ = failed.send(status);
+                                    chunks.closed().await;
+
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    .in_current_span(),
+                );
+
+                let upload = client
+                    .upload_file(tokio_stream::once(first).chain(ReceiverStream::new(forwarded)));
+
+                tokio::select! {
+                    biased;
+                    Ok(status) = upload_failed => Err(status),
+                    response = upload => response,
+                }
+            })
+            .await
+    }
+
+    async fn download_file(
+        &self,
+        request: Request<DownloadFileRequest>,
+    ) -> Result<Response<Self::DownloadFileStream>, Status> {
+        let (_, mut client) = self
+            .route(
+                request.metadata(),
+                Routed::File,
+                &request.get_ref().source_path,
+            )
+            .await?;
+
+        client.download_file(request.into_inner()).await
+    }
+
+    async fn get_file_metadata(
+        &self,
+        request: Request<GetFileMetadataRequest>,
+    ) -> Result<Response<GetFileMetadataResponse>, Status> {
+        let route = self
+            .route(
+                request.metadata(),
+                Routed::File,
+                &request.get_ref().file_path,
+            )
+            .await;
+
+        match route {
+            Ok((_, mut client)) => client.get_file_metadata(request.into_inner()).await,
+            // Data centers answer with no metadata rather than an error for unknown files
+            Err(status) if status.code() == tonic::Code::NotFound => {
+                Ok(Response::new(GetFileMetadataResponse { metadata: None }))
+            }
+            Err(status) => Err(status),
+        }
+    }
+
+    async fn list_image_metadata(
+        &self,
+        request: Request<ListImageMetadataRequest>,
+    ) -> Result<Response<ListImageMetadataResponse>, Status> {
+        let mut metadata = Vec::new();
+        let list = request.get_ref().clone();
+
+        for (data_center_id, response) in self
+            .fan_out(request.metadata(), move |mut client| {
+                let list = list.clone();
+                async move { client.list_image_metadata(list).await }
+            })
+            .await?
+        {
+            for image in response.metadata {
+                self.record(
+                    Routed::Resource(ResourceKind::Image),
+                    &image.image_id,
+                    &data_center_id,
+                );
+                metadata.push(image);
+            }
+        }
+
+        Ok(Response::new(ListImageMetadataResponse { metadata }))
+    }
+
+    async fn start_instance(
+        &self,
+        request: Request<StartInstanceRequest>,
+    ) -> Result<Response<StartInstanceResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/StartInstance")
+            .resource(&request.get_ref().instance_id);
+
+        entry
+            .record(async move {
+                let (_, mut client) = self
+                    .route(
+                        request.metadata(),
+                        Routed::Resource(ResourceKind::Instance),
+                        &request.get_ref().instance_id,
+                    )
+                    .await?;
+
+                client.start_instance(request.into_inner()).await
+            })
+            .await
+    }
+
+    async fn stop_instance(
+        &self,
+        request: Request<StopInstanceRequest>,
+    ) -> Result<Response<StopInstanceResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/StopInstance")
+            .resource(&request.get_ref().instance_id);
+
+        entry
+            .record(async move {
+                let (_, mut client) = self
+                    .route(
+                        request.metadata(),
+                        Routed::Resource(ResourceKind::Instance),
+                        &request.get_ref().instance_id,
+                    )
+                    .await?;
+
+                client.stop_instance(request.into_inner()).await
+            })
+            .await
+    }
+
+    async fn create_machine(
+        &self,
+        request: Request<CreateMachineRequest>,
+    ) -> Result<Response<CreateMachineResponse>, Status> {
+        let entry = self
+            .resolver
+            .audit()
+            .begin(&request, "/data_center.DataCenter/CreateMachine")
+            .resource(&request.get_ref().project_id)
+            .resource(&request.get_ref().image_id);
+
+        entry
+            .record_with(
+                async move {
+                    // Machines run from an image, so they are created in the data center holding it
+                    let (data_center_id, mut client) = self
+                        .route(
+                            request.metadata(),
+                            Routed::Resource(ResourceKind::Image),
+                            &request.get_ref().image_id,
+                        )
+                        .await?;
+                    let response = client.create_machine(request.into_inner()).await?;
+
+                    if let Some(machine) = &response.get_ref().machine {
+                        self.record(
+                            Routed::Resource(ResourceKind::Machine),
+                            &machine.machine_id,
+                            &data_center_id,
+                        );
+                    }
+
+                    Ok(response)
+                },
+                |response| {
+                    response
+                        .machine
+                        .iter()
+                        .map(|machine| machine.machine_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn list_machines(
+        &self,
+        request: Request<ListMachinesRequest>,
+    ) -> Result<Response<ListMachinesResponse>, Status> {
+        let mut machines = Vec::new();
+        let list = request.get_ref().clone();
+
+        for (data_center_id, response) in self
+            .fan_out(request.metadata(), move |mut client| {
+                let list = list.clone();
+                async move { client.list_machines(list).await }
+            })
+            .await?
+        {
+            for machine in response.machine {
+                self.record(
+                    Routed::Resource(ResourceKind::Machine),
+                    &machine.machine_id,
+                    &data_center_id,
+                );
+                machines.push(machine);
+            }
+        }
+
+        Ok(Response::new(ListMachinesResponse { machine: machines }))
+    }
+
+    async fn list_instances(
+        &self,
+        request: Request<ListInstancesRequest>,
+    ) -> Result<Response<ListInstancesResponse>, Status> {
+        let mut instances = Vec::new();
+        let list = request.get_ref().clone();
+
+        for (data_center_id, response) in self
+            .fan_out(request.metadata(), move |mut client| {
+                let list = list.clone();
+                async move { client.list_instances(list).await }
+            })
+            .await?
+        {
+            for instance in response.instance {
+                self.record(
+                    Routed::Resource(ResourceKind::Instance),
+                    &instance.instance_id,
+                    &data_center_id,
+                );
+                instances.push(instance);
+            }
+        }
+
+        Ok(Response::new(ListInstancesResponse {
+            instance: instances,
+        }))
+    }
+
+    async fn prove_identity(
+        &self,
+        request: Request<ProveIdentityRequest>,
+    ) -> Result<Response<ProveIdentityResponse>, Status> {
+        let Some(data_center) = self.requested(request.metadata()).await? else {
+            return Err(Status::invalid_argument(format!(
+                "Set {DATA_CENTER_ID_KEY} to the data center to prove the identity of"
+            )));
+        };
+        let mut client = self
+            .client(&data_center, request.metadata())
+            .map_err(|error| invalid_host(&data_center, error))?;
+
+        client.prove_identity(request.into_inner()).await
+    }
+}
@@ -0,0 +1,937 @@
+pub mod service;
+pub mod storage;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use auth::SignedToken;
+use rand::Rng;
+use tokio::{
+    sync::{oneshot, Notify},
+    task::JoinHandle,
+    time::Instant,
+};
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, ClientTlsConfig},
+    Code, Request, Status,
+};
+
+use crate::{
+    protos::resolver::{
+        raft_peer_client::RaftPeerClient, AppendEntriesRequest, AppendEntriesResponse, DataCenter,
+        InstallSnapshotRequest, InstallSnapshotResponse, ProposeRequest, RaftEntry, RaftHardState,
+        RaftSnapshot, RegistryCommand, RegistrySnapshot, RequestVoteRequest, RequestVoteResponse,
+    },
+    registry::Registry,
+};
+
+use self::storage::{RaftStorage, RecoveredState};
+
+/// Most entries sent to a follower in a single append
+const MAX_ENTRIES_PER_APPEND: usize = 256;
+
+type Proposal = oneshot::Sender<Result<Option<DataCenter>, Status>>;
+/// Client of another node, authenticated with the secret shared by the cluster
+type PeerClient = RaftPeerClient<InterceptedService<Channel, SignedToken>>;
+
+pub struct RaftConfig {
+    /// Id of this node
+    pub node_id: String,
+    /// Endpoints of the other nodes in the cluster keyed by node id
+    pub peers: BTreeMap<String, String>,
+    /// Secret shared by the nodes of the cluster, which only accept calls from each other
+    pub peer_secret: Vec<u8>,
+    /// Range the randomized election timeout is picked from
+    pub election_timeout: Range<Duration>,
+    /// Interval the leader sends heartbeats to followers at
+    pub heartbeat_interval: Duration,
+    /// How long a proposal waits to be committed before giving up
+    pub proposal_timeout: Duration,
+    /// Number of applied entries kept in the log before it is compacted into a snapshot
+    pub snapshot_threshold: u64,
+    /// Directory to persist the node's state in, kept in memory when not provided
+    pub data_dir: Option<PathBuf>,
+    /// Tls peers are dialed with, peers are dialed in plaintext when not provided
+    pub tls: Option<ClientTlsConfig>,
+    /// Whether registrations without a signature are refused when applied, which every node
+    /// of the cluster should agree on
+    pub signed_registrations_required: bool,
+}
+
+impl RaftConfig {
+    pub fn new(
+        node_id: String,
+        peers: BTreeMap<String, String>,
+        peer_secret: Vec<u8>,
+    ) -> RaftConfig {
+        RaftConfig {
+            node_id,
+            peers,
+            peer_secret,
+            election_timeout: Duration::from_millis(300)..Duration::from_millis(600),
+            heartbeat_interval: Duration::from_millis(100),
+            proposal_timeout: Duration::from_secs(5),
+            snapshot_threshold: 1024,
+            data_dir: None,
+            tls: None,
+            signed_registrations_required: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+struct RaftState {
+    role: Role,
+    term: u64,
+    voted_for: Option<String>,
+    leader_id: Option<String>,
+    election_deadline: Instant,
+    votes: BTreeSet<String>,
+    /// Entries following the snapshot
+    log: Vec<RaftEntry>,
+    snapshot: RaftSnapshot,
+    commit_index: u64,
+    last_applied: u64,
+    registry: Registry,
+    next_index: HashMap<String, u64>,
+    match_index: HashMap<String, u64>,
+    /// Peers with an append or snapshot in flight
+    replicating: BTreeSet<String>,
+    /// Proposals waiting for their entry to be applied, keyed by index along with their term
+    proposals: HashMap<u64, (u64, Proposal)>,
+    storage: RaftStorage,
+}
+
+impl RaftState {
+    fn last_log_index(&self) -> u64 {
+        self.log
+            .last()
+            .map(|entry| entry.index)
+            .unwrap_or(self.snapshot.last_included_index)
+    }
+
+    fn last_log_term(&self) -> u64 {
+        self.log
+            .last()
+            .map(|entry| entry.term)
+            .unwrap_or(self.snapshot.last_included_term)
+    }
+
+    fn entry(&self, index: u64) -> Option<&RaftEntry> {
+        if index <= self.snapshot.last_included_index {
+            return None;
+        }
+
+        self.log
+            .get((index - self.snapshot.last_included_index - 1) as usize)
+    }
+
+    /// Term of the entry at `index`, `None` when the entry was compacted away or doesn't exist
+    fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.last_included_index {
+            return Some(self.snapshot.last_included_term);
+        }
+
+        self.entry(index).map(|entry| entry.term)
+    }
+
+    fn save_hard_state(&mut self) -> io::Result<()> {
+        let hard_state = RaftHardState {
+            term: self.term,
+            voted_for: self.voted_for.clone().unwrap_or_default(),
+        };
+
+        self.storage.save_hard_state(&hard_state)
+    }
+
+    /// Drops every entry from `index` onwards
+    fn truncate(&mut self, index: u64) {
+        let retained = index.saturating_sub(self.snapshot.last_included_index + 1) as usize;
+        self.log.truncate(retained);
+    }
+
+    fn fail_proposals(&mut self, message: &str) {
+        for (_, (_, proposal)) in self.proposals.drain() {
+            let _ = proposal.send(Err(Status::unavailable(message)));
+        }
+    }
+}
+
+/// Node of a resolver cluster replicating the registry through the raft consensus protocol
+pub struct RaftNode {
+    config: RaftConfig,
+    state: Mutex<RaftState>,
+    peers: BTreeMap<String, PeerClient>,
+    replicate: Notify,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl RaftNode {
+    /// Starts the node, recovering its state from the configured data directory
+    pub fn start(config: RaftConfig) -> io::Result<Arc<RaftNode>> {
+        let (storage, recovered) = match &config.data_dir {
+            Some(data_dir) => RaftStorage::open(data_dir)?,
+            None => (RaftStorage::in_memory(), RecoveredState::default()),
+        };
+        let mut peers = BTreeMap::new();
+
+        for (node_id, endpoint) in config.peers.iter() {
+            let channel = grpc_tls::endpoint(endpoint, config.tls.as_ref())
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?
+                .connect_timeout(config.election_timeout.start)
+                .connect_lazy();
+            peers.insert(
+                node_id.clone(),
+                RaftPeerClient::with_interceptor(
+                    channel,
+                    SignedToken::new(&config.peer_secret, &config.node_id),
+                ),
+            );
+        }
+
+        let registry = restore_registry(&config, recovered.snapshot.registry.clone());
+        let snapshot_index = recovered.snapshot.last_included_index;
+        let state = RaftState {
+            role: Role::Follower,
+            term: recovered.hard_state.term,
+            voted_for: Some(recovered.hard_state.voted_for).filter(|vote| !vote.is_empty()),
+            leader_id: None,
+            election_deadline: Instant::now() + random_timeout(&config.election_timeout),
+            votes: BTreeSet::new(),
+            log: recovered.entries,
+            snapshot: recovered.snapshot,
+            commit_index: snapshot_index,
+            last_applied: snapshot_index,
+            registry,
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            replicating: BTreeSet::new(),
+            proposals: HashMap::new(),
+            storage,
+        };
+        let node = Arc::new(RaftNode {
+            config,
+            state: Mutex::new(state),
+            peers,
+            replicate: Notify::new(),
+            tasks: Mutex::new(Vec::new()),
+        });
+        let ticker = tokio::spawn(node.clone().tick());
+        let replicator = tokio::spawn(node.clone().replicate_on_proposal());
+        node.tasks
+            .lock()
+            .expect("Should acquire lock")
+            .extend([ticker, replicator]);
+
+        Ok(node)
+    }
+
+    /// Stops the node's background tasks, after which it no longer takes part in the cluster
+    pub fn stop(&self) {
+        for task in self.tasks.lock().expect("Should acquire lock").drain(..) {
+            task.abort();
+        }
+
+        let mut state = self.state.lock().expect("Should acquire lock");
+        state.role = Role::Follower;
+        state.leader_id = None;
+        state.fail_proposals("Node stopped");
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.node_id
+    }
+
+    pub fn role(&self) -> Role {
+        self.state.lock().expect("Should acquire lock").role
+    }
+
+    pub fn leader_id(&self) -> Option<String> {
+        self.state
+            .lock()
+            .expect("Should acquire lock")
+            .leader_id
+            .clone()
+    }
+
+    /// Reads the registry as of the last entry this node applied
+    pub fn read<T>(&self, read: impl FnOnce(&Registry) -> T) -> T {
+        read(&self.state.lock().expect("Should acquire lock").registry)
+    }
+
+    /// Replicates a command, returning the data center it affected once it is applied. Followers
+    /// forward the command to the leader
+    pub async fn propose(
+        &self,
+        command: RegistryCommand,
+        forwarded: bool,
+    ) -> Result<Option<DataCenter>, Status> {
+        let receiver = match self.append_proposal(&command, forwarded) {
+            Ok(Proposed::Appended(receiver)) => receiver,
+            Ok(Proposed::NoLeader) => {
+                return Err(Status::unavailable("No leader available to accept writes"))
+            }
+            Ok(Proposed::Forward(mut leader)) => {
+                let mut request = Request::new(ProposeRequest {
+                    command: Some(command),
+                    forwarded: true,
+                });
+                request.set_timeout(self.config.proposal_timeout);
+
+                return match leader.propose(request).await {
+                    Ok(response) => Ok(response.into_inner().data_center),
+                    // The leader may have been lost while the proposal was in flight, which
+                    // callers can retry like any other unavailable leader
+                    Err(status) if leader_unreachable(&status) => Err(Status::unavailable(
+                        format!("Leader unreachable: {}", status.message()),
+                    )),
+                    Err(status) => Err(status),
+                };
+            }
+            Err(error) => {
+                return Err(Status::internal(format!(
+                    "Failed to persist entry: {error}"
+                )))
+            }
+        };
+        self.replicate.notify_one();
+
+        match tokio::time::timeout(self.config.proposal_timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(Status::unavailable("Proposal was dropped")),
+            Err(_) => Err(Status::unavailable(
+                "Timed out waiting for the proposal to commit",
+            )),
+        }
+    }
+
+    /// Appends a proposed command to the leader's log, or hands it back to be forwarded to the
+    /// leader when this node is a follower
+    fn append_proposal(&self, command: &RegistryCommand, forwarded: bool) -> io::Result<Proposed> {
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        if state.role != Role::Leader {
+            let leader = state
+                .leader_id
+                .as_ref()
+                .filter(|leader_id| !forwarded && **leader_id != self.config.node_id)
+                .and_then(|leader_id| self.peers.get(leader_id).cloned());
+
+            return Ok(match leader {
+                Some(leader) => Proposed::Forward(Box::new(leader)),
+                None => Proposed::NoLeader,
+            });
+        }
+
+        let entry = RaftEntry {
+            index: state.last_log_index() + 1,
+            term: state.term,
+            command: Some(command.clone()),
+        };
+        state.storage.append(std::slice::from_ref(&entry))?;
+        let (sender, receiver) = oneshot::channel();
+        let term = state.term;
+        state.proposals.insert(entry.index, (term, sender));
+        state.log.push(entry);
+        self.advance_commit(&mut state);
+
+        Ok(Proposed::Appended(receiver))
+    }
+
+    pub fn handle_request_vote(&self, request: RequestVoteRequest) -> RequestVoteResponse {
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        if request.term > state.term {
+            self.become_follower(&mut state, request.term, None);
+        }
+
+        let log_is_current = request.last_log_term > state.last_log_term()
+            || (request.last_log_term == state.last_log_term()
+                && request.last_log_index >= state.last_log_index());
+        let can_vote = state
+            .voted_for
+            .as_ref()
+            .map(|vote| *vote == request.candidate_id)
+            .unwrap_or(true);
+        let vote_granted = request.term == state.term && can_vote && log_is_current;
+
+        if vote_granted {
+            state.voted_for = Some(request.candidate_id);
+            state.election_deadline = Instant::now() + self.election_timeout();
+
+            if let Err(error) = state.save_hard_state() {
+                tracing::error!(%error, "Failed to persist vote");
+                return RequestVoteResponse {
+                    term: state.term,
+                    vote_granted: false,
+                };
+            }
+        }
+
+        RequestVoteResponse {
+            term: state.term,
+            vote_granted,
+        }
+    }
+
+    pub fn handle_append_entries(
+        &self,
+        request: AppendEntriesRequest,
+    ) -> io::Result<AppendEntriesResponse> {
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        if request.term < state.term {
+            return Ok(AppendEntriesResponse {
+                term: state.term,
+                success: false,
+                last_log_index: state.last_log_index(),
+            });
+        }
+
+        self.become_follower(&mut state, request.term, Some(request.leader_id));
+        let matches_previous = request.prev_log_index < state.snapshot.last_included_index
+            || state.term_at(request.prev_log_index) == Some(request.prev_log_term);
+
+        if !matches_previous {
+            let last_log_index = state
+                .last_log_index()
+                .min(request.prev_log_index.saturating_sub(1));
+
+            return Ok(AppendEntriesResponse {
+                term: state.term,
+                success: false,
+                last_log_index,
+            });
+        }
+
+        let last_new_index = request.prev_log_index + request.entries.len() as u64;
+        let mut appended = Vec::new();
+        let mut truncated = false;
+
+        for entry in request.entries {
+            if entry.index <= state.snapshot.last_included_index {
+                continue;
+            }
+
+            match state.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    state.truncate(entry.index);
+                    truncated = true;
+                }
+                None => {}
+            }
+
+            appended.push(entry.clone());
+            state.log.push(entry);
+        }
+
+        if truncated {
+            let log = state.log.clone();
+            state.storage.rewrite_log(&log)?;
+        } else {
+            state.storage.append(&appended)?;
+        }
+
+        if request.leader_commit > state.commit_index {
+            state.commit_index = request.leader_commit.min(last_new_index);
+            self.apply_committed(&mut state);
+        }
+
+        Ok(AppendEntriesResponse {
+            term: state.term,
+            success: true,
+            last_log_index: state.last_log_index(),
+        })
+    }
+
+    pub fn handle_install_snapshot(
+        &self,
+        request: InstallSnapshotRequest,
+    ) -> io::Result<InstallSnapshotResponse> {
+        let mut state = self.state.lock().expect("Should acquire lock");
+
+        if request.term < state.term {
+            return Ok(InstallSnapshotResponse { term: state.term });
+        }
+
+        self.become_follower(&mut state, request.term, Some(request.leader_id));
+        let Some(snapshot) = request.snapshot else {
+            return Ok(InstallSnapshotResponse { term: state.term });
+        };
+
+        if snapshot.last_included_index <= state.last_applied {
+            return Ok(InstallSnapshotResponse { term: state.term });
+        }
+
+        if state.term_at(snapshot.last_included_index) == Some(snapshot.last_included_term) {
+            let compacted =
+                (snapshot.last_included_index - state.snapshot.last_included_index) as usize;
+            state.log.drain(..compacted);
+        } else {
+            state.log.clear();
+        }
+
+        // Replacing the registry ends every watch, letting watchers resume from the snapshot
+        state.registry = restore_registry(&self.config, snapshot.registry.clone());
+        state.commit_index = state.commit_index.max(snapshot.last_included_index);
+        state.last_applied = snapshot.last_included_index;
+        state.snapshot = snapshot;
+        let (snapshot, log) = (state.snapshot.clone(), state.log.clone());
+        state.storage.save_snapshot(&snapshot, &log)?;
+        self.apply_committed(&mut state);
+
+        Ok(InstallSnapshotResponse { term: state.term })
+    }
+
+    async fn tick(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.config.heartbeat_interval / 2);
+        let mut last_heartbeat = Instant::now();
+
+        loop {
+            interval.tick().await;
+            let (role, election_deadline) = {
+                let state = self.state.lock().expect("Should acquire lock");
+                (state.role, state.election_deadline)
+            };
+
+            match role {
+                Role::Leader if last_heartbeat.elapsed() >= self.config.heartbeat_interval => {
+                    last_heartbeat = Instant::now();
+                    self.clone().broadcast();
+                }
+                Role::Leader => {}
+                Role::Follower | Role::Candidate if Instant::now() >= election_deadline => {
+                    self.clone().start_election();
+                }
+                Role::Follower | Role::Candidate => {}
+            }
+        }
+    }
+
+    async fn replicate_on_proposal(self: Arc<Self>) {
+        loop {
+            self.replicate.notified().await;
+            self.clone().broadcast();
+        }
+    }
+
+    fn start_election(self: Arc<Self>) {
+        let request = {
+            let mut state = self.state.lock().expect("Should acquire lock");
+            state.role = Role::Candidate;
+            state.term += 1;
+            state.voted_for = Some(self.config.node_id.clone());
+            state.leader_id = None;
+            state.votes = BTreeSet::from([self.config.node_id.clone()]);
+            state.election_deadline = Instant::now() + self.election_timeout();
+
+            if let Err(error) = state.save_hard_state() {
+                tracing::error!(%error, "Failed to persist vote");
+                return;
+            }
+
+            if self.has_quorum(state.votes.len()) {
+                self.become_leader(&mut state);
+                return;
+            }
+
+            RequestVoteRequest {
+                term: state.term,
+                candidate_id: self.config.node_id.clone(),
+                last_log_index: state.last_log_index(),
+                last_log_term: state.last_log_term(),
+            }
+        };
+
+        for (node_id, peer) in self.peers.iter() {
+            let node = self.clone();
+            let node_id = node_id.clone();
+            let mut peer = peer.clone();
+            let request = request.clone();
+
+            tokio::spawn(async move {
+                let Ok(response) = peer.request_vote(node.peer_request(request.clone())).await
+                else {
+                    return;
+                };
+                let response = response.into_inner();
+                let mut state = node.state.lock().expect("Should acquire lock");
+
+                if response.term > state.term {
+                    node.become_follower(&mut state, response.term, None);
+                    return;
+                }
+
+                if state.role != Role::Candidate || state.term != request.term {
+                    return;
+                }
+
+                if response.vote_granted {
+                    state.votes.insert(node_id);
+
+                    if node.has_quorum(state.votes.len()) {
+                        node.become_leader(&mut state);
+                        drop(state);
+                        node.replicate.notify_one();
+                    }
+                }
+            });
+        }
+    }
+
+    fn become_leader(&self, state: &mut RaftState) {
+        state.role = Role::Leader;
+        state.leader_id = Some(self.config.node_id.clone());
+        let next_index = state.last_log_index() + 1;
+        state.next_index = self
+            .peers
+            .keys()
+            .map(|node_id| (node_id.clone(), next_index))
+            .collect();
+        state.match_index = self
+            .peers
+            .keys()
+            .map(|node_id| (node_id.clone(), 0))
+            .collect();
+        state.replicating.clear();
+
+        // Entries from earlier terms can only be committed through an entry from the current
+        // term, so a new leader appends an empty entry straight away
+        let entry = RaftEntry {
+            index: next_index,
+            term: state.term,
+            command: None,
+        };
+
+        // A leader that can't persist its entries can't commit any, so it steps down and leaves
+        // the election to a node that can, or to itself once its storage recovers
+        if let Err(error) = state.storage.append(std::slice::from_ref(&entry)) {
+            tracing::error!(%error, "Failed to persist entry, stepping down");
+            let term = state.term;
+            self.become_follower(state, term, None);
+
+            return;
+        }
+
+        state.log.push(entry);
+        self.advance_commit(state);
+    }
+
+    fn become_follower(&self, state: &mut RaftState, term: u64, leader_id: Option<String>) {
+        if term > state.term {
+            state.term = term;
+            state.voted_for = None;
+
+            if let Err(error) = state.save_hard_state() {
+                tracing::error!(%error, "Failed to persist term");
+            }
+        }
+
+        if state.role == Role::Leader {
+            state.fail_proposals("Leadership was lost before the proposal committed");
+        }
+
+        state.role = Role::Follower;
+        state.leader_id = leader_id;
+        state.election_deadline = Instant::now() + self.election_timeout();
+    }
+
+    /// Sends any entries each follower is missing, or an empty append as a heartbeat
+    fn broadcast(self: Arc<Self>) {
+        for node_id in self.peers.keys() {
+            {
+                let mut state = self.state.lock().expect("Should acquire lock");
+
+                if state.role != Role::Leader || !state.replicating.insert(node_id.clone()) {
+                    continue;
+                }
+            }
+
+            tokio::spawn(self.clone().replicate_to(node_id.clone()));
+        }
+    }
+
+    async fn replicate_to(self: Arc<Self>, node_id: String) {
+        let mut peer = self.peers[&node_id].clone();
+
+        loop {
+            let message = {
+                let mut state = self.state.lock().expect("Should acquire lock");
+
+                if state.role != Role::Leader {
+                    state.replicating.remove(&node_id);
+                    return;
+                }
+
+                self.next_message(&state, &node_id)
+            };
+            let term = message.term();
+            let result = match message {
+                PeerMessage::Append(request) => {
+                    let prev_log_index = request.prev_log_index;
+                    let sent = request.entries.len() as u64;
+                    peer.append_entries(self.peer_request(request))
+                        .await
+                        .map(|response| {
+                            PeerResponse::Append(prev_log_index, sent, response.into_inner())
+                        })
+                }
+                PeerMessage::Snapshot(request) => {
+                    let last_included_index = request
+                        .snapshot
+                        .as_ref()
+                        .map(|snapshot| snapshot.last_included_index)
+                        .unwrap_or_default();
+                    peer.install_snapshot(self.peer_request(request))
+                        .await
+                        .map(|response| {
+                            PeerResponse::Snapshot(last_included_index, response.into_inner())
+                        })
+                }
+            };
+            let mut state = self.state.lock().expect("Should acquire lock");
+            let Ok(response) = result else {
+                state.replicating.remove(&node_id);
+                return;
+            };
+
+            if response.term() > state.term {
+                self.become_follower(&mut state, response.term(), None);
+                state.replicating.remove(&node_id);
+                return;
+            }
+
+            if state.role != Role::Leader || state.term != term {
+                state.replicating.remove(&node_id);
+                return;
+            }
+
+            match response {
+                PeerResponse::Append(prev_log_index, sent, response) if response.success => {
+                    let match_index = prev_log_index + sent;
+                    state.match_index.insert(node_id.clone(), match_index);
+                    state.next_index.insert(node_id.clone(), match_index + 1);
+                    self.advance_commit(&mut state);
+                }
+                PeerResponse::Append(prev_log_index, _, response) => {
+                    let next_index = prev_log_index.min(response.last_log_index + 1).max(1);
+                    state.next_index.insert(node_id.clone(), next_index);
+                }
+                PeerResponse::Snapshot(last_included_index, _) => {
+                    state
+                        .match_index
+                        .insert(node_id.clone(), last_included_index);
+                    state
+                        .next_index
+                        .insert(node_id.clone(), last_included_index + 1);
+                }
+            }
+
+            let caught_up = state.next_index[&node_id] > state.last_log_index()
+                && state.match_index[&node_id] == state.last_log_index();
+
+            if caught_up {
+                state.replicating.remove(&node_id);
+                return;
+            }
+        }
+    }
+
+    fn next_message(&self, state: &RaftState, node_id: &str) -> PeerMessage {
+        let next_index = state.next_index[node_id];
+
+        if next_index <= state.snapshot.last_included_index {
+            return PeerMessage::Snapshot(InstallSnapshotRequest {
+                term: state.term,
+                leader_id: self.config.node_id.clone(),
+                snapshot: Some(state.snapshot.clone()),
+            });
+        }
+
+        let prev_log_index = next_index - 1;
+        let entries = state
+            .log
+            .iter()
+            .skip((next_index - state.snapshot.last_included_index - 1) as usize)
+            .take(MAX_ENTRIES_PER_APPEND)
+            .cloned()
+            .collect();
+
+        PeerMessage::Append(AppendEntriesRequest {
+            term: state.term,
+            leader_id: self.config.node_id.clone(),
+            prev_log_index,
+            prev_log_term: state.term_at(prev_log_index).unwrap_or_default(),
+            entries,
+            leader_commit: state.commit_index,
+        })
+    }
+
+    /// Commits the latest entry from the current term stored on a majority of the cluster
+    fn advance_commit(&self, state: &mut RaftState) {
+        let mut index = state.last_log_index();
+
+        while index > state.commit_index {
+            let replicas = 1 + state
+                .match_index
+                .values()
+                .filter(|match_index| **match_index >= index)
+                .count();
+
+            if state.term_at(index) == Some(state.term) && self.has_quorum(replicas) {
+                state.commit_index = index;
+                break;
+            }
+
+            index -= 1;
+        }
+
+        self.apply_committed(state);
+    }
+
+    fn apply_committed(&self, state: &mut RaftState) {
+        while state.last_applied < state.commit_index {
+            let index = state.last_applied + 1;
+            let Some(entry) = state.entry(index).cloned() else {
+                break;
+            };
+            let result = match entry.command {
+                Some(command) => state.registry.apply(command).map_err(Status::from),
+                None => Ok(None),
+            };
+            state.last_applied = index;
+
+            if let Some((term, proposal)) = state.proposals.remove(&index) {
+                let result = if term == entry.term {
+                    result
+                } else {
+                    Err(Status::unavailable(
+                        "Proposal was replaced by another leader",
+                    ))
+                };
+                let _ = proposal.send(result);
+            }
+        }
+
+        if state.last_applied - state.snapshot.last_included_index >= self.config.snapshot_threshold
+        {
+            self.compact(state);
+        }
+    }
+
+    /// Folds every applied entry into a new snapshot
+    fn compact(&self, state: &mut RaftState) {
+        let last_included_index = state.last_applied;
+        let Some(last_included_term) = state.term_at(last_included_index) else {
+            return;
+        };
+        state
+            .log
+            .drain(..(last_included_index - state.snapshot.last_included_index) as usize);
+        state.snapshot = RaftSnapshot {
+            last_included_index,
+            last_included_term,
+            registry: Some(state.registry.snapshot()),
+        };
+        let (snapshot, log) = (state.snapshot.clone(), state.log.clone());
+
+        if let Err(error) = state.storage.save_snapshot(&snapshot, &log) {
+            tracing::error!(%error, "Failed to persist snapshot");
+        }
+    }
+
+    fn has_quorum(&self, count: usize) -> bool {
+        count * 2 > self.peers.len() + 1
+    }
+
+    /// Wraps a message to a peer so that an unresponsive peer can't stall the node for longer
+    /// than an election timeout
+    fn peer_request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.set_timeout(self.config.election_timeout.start);
+
+        request
+    }
+
+    fn election_timeout(&self) -> Duration {
+        random_timeout(&self.config.election_timeout)
+    }
+}
+
+enum Proposed {
+    /// Proposal was appended to the leader's log and resolves once applied
+    Appended(oneshot::Receiver<Result<Option<DataCenter>, Status>>),
+    /// Proposal must be forwarded to the leader
+    Forward(Box<PeerClient>),
+    /// No leader is known to accept the proposal
+    NoLeader,
+}
+
+enum PeerMessage {
+    Append(AppendEntriesRequest),
+    Snapshot(InstallSnapshotRequest),
+}
+
+impl PeerMessage {
+    fn term(&self) -> u64 {
+        match self {
+            PeerMessage::Append(request) => request.term,
+            PeerMessage::Snapshot(request) => request.term,
+        }
+    }
+}
+
+enum PeerResponse {
+    /// Response to an append along with the previous log index and number of entries sent
+    Append(u64, u64, AppendEntriesResponse),
+    /// Response to a snapshot along with the last index the snapshot included
+    Snapshot(u64, InstallSnapshotResponse),
+}
+
+impl PeerResponse {
+    fn term(&self) -> u64 {
+        match self {
+            PeerResponse::Append(_, _, response) => response.term,
+            PeerResponse::Snapshot(_, response) => response.term,
+        }
+    }
+}
+
+/// Whether a forwarded proposal failed because the leader couldn't be reached rather than
+/// because the leader rejected it. Transport failures carry their cause as the status' source
+fn leader_unreachable(status: &Status) -> bool {
+    match status.code() {
+        Code::Cancelled | Code::DeadlineExceeded | Code::Unavailable => true,
+        _ => std::error::Error::source(status)
+            .is_some_and(|source| source.is::<tonic::transport::Error>()),
+    }
+}
+
+/// Registry holding the data centers of `snapshot`, checking registrations as configured
+fn restore_registry(config: &RaftConfig, snapshot: Option<RegistrySnapshot>) -> Registry {
+    let registry = Registry::restore(snapshot.unwrap_or_default());
+
+    if config.signed_registrations_required {
+        return registry.with_signed_registrations_required();
+    }
+
+    registry
+}
+
+fn random_timeout(range: &Range<Duration>) -> Duration {
+    rand::thread_rng().gen_range(range.clone())
+}
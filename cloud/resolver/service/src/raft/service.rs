@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use auth::{ApiKeys, AuthInterceptor, Authenticator};
+use tonic::{service::interceptor::InterceptedService, Request, Response, Status};
+
+use crate::protos::resolver::{
+    raft_peer_server::{RaftPeer, RaftPeerServer},
+    AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+    ProposeRequest, ProposeResponse, RequestVoteRequest, RequestVoteResponse,
+};
+
+use super::RaftNode;
+
+/// Serves the raft protocol for a node to the rest of its cluster
+pub struct RaftPeerService {
+    node: Arc<RaftNode>,
+}
+
+impl RaftPeerService {
+    pub fn new(node: Arc<RaftNode>) -> RaftPeerService {
+        RaftPeerService { node }
+    }
+}
+
+/// Server of the raft protocol for `node`, only accepting calls signed with the secret its
+/// cluster shares
+pub fn peer_server(
+    node: Arc<RaftNode>,
+) -> InterceptedService<RaftPeerServer<RaftPeerService>, AuthInterceptor> {
+    let authenticator =
+        Authenticator::new(Some(node.config.peer_secret.clone()), ApiKeys::default());
+
+    RaftPeerServer::with_interceptor(
+        RaftPeerService::new(node),
+        AuthInterceptor::new(Some(authenticator)),
+    )
+}
+
+#[tonic::async_trait]
+impl RaftPeer for RaftPeerService {
+    async fn request_vote(
+        &self,
+        request: Request<RequestVoteRequest>,
+    ) -> Result<Response<RequestVoteResponse>, Status> {
+        Ok(Response::new(
+            self.node.handle_request_vote(request.into_inner()),
+        ))
+    }
+
+    async fn append_entries(
+        &self,
+        request: Request<AppendEntriesRequest>,
+    ) -> Result<Response<AppendEntriesResponse>, Status> {
+        Ok(Response::new(
+            self.node
+                .handle_append_entries(request.into_inner())
+                .map_err(|error| Status::internal(format!("Failed to persist entries: {error}")))?,
+        ))
+    }
+
+    async fn install_snapshot(
+        &self,
+        request: Request<InstallSnapshotRequest>,
+    ) -> Result<Response<InstallSnapshotResponse>, Status> {
+        Ok(Response::new(
+            self.node
+                .handle_install_snapshot(request.into_inner())
+                .map_err(|error| {
+                    Status::internal(format!("Failed to persist snapshot: {error}"))
+                })?,
+        ))
+    }
+
+    async fn propose(
+        &self,
+        request: Request<ProposeRequest>,
+    ) -> Result<Response<ProposeResponse>, Status> {
+        let request = request.into_inner();
+        let command = request
+            .command
+            .ok_or_else(|| Status::invalid_argument("Command is required"))?;
+        let data_center = self.node.propose(command, request.forwarded).await?;
+
+        Ok(Response::new(ProposeResponse { data_center }))
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use prost::Message;
+
+use crate::{
+    protos::resolver::{RaftEntry, RaftHardState, RaftSnapshot},
+    store::{open_log, read_log, read_message, write_atomically},
+};
+
+const HARD_STATE_FILE: &str = "raft.state";
+const SNAPSHOT_FILE: &str = "raft.snapshot";
+const LOG_FILE: &str = "raft.log";
+
+/// State of a raft node recovered from disk
+#[derive(Default)]
+pub struct RecoveredState {
+    pub hard_state: RaftHardState,
+    pub snapshot: RaftSnapshot,
+    pub entries: Vec<RaftEntry>,
+}
+
+/// Durable storage for a raft node's hard state, snapshot and log. Storage without a directory
+/// keeps nothing, which is only useful for nodes that never restart
+pub struct RaftStorage {
+    directory: Option<PathBuf>,
+    log: Option<File>,
+}
+
+impl RaftStorage {
+    pub fn in_memory() -> RaftStorage {
+        RaftStorage {
+            directory: None,
+            log: None,
+        }
+    }
+
+    /// Opens the storage in `directory`, returning it with the state it holds
+    pub fn open(directory: &Path) -> io::Result<(RaftStorage, RecoveredState)> {
+        fs::create_dir_all(directory)?;
+        let hard_state =
+            read_message::<RaftHardState>(&directory.join(HARD_STATE_FILE))?.unwrap_or_default();
+        let snapshot =
+            read_message::<RaftSnapshot>(&directory.join(SNAPSHOT_FILE))?.unwrap_or_default();
+        let mut entries = Vec::new();
+
+        // Entries are only ever appended in index order, so any entry that doesn't directly
+        // follow the previous one was left behind by a log rewrite cut short by a crash
+        for entry in read_log::<RaftEntry>(&directory.join(LOG_FILE))? {
+            let expected_index = entries
+                .last()
+                .map(|last: &RaftEntry| last.index + 1)
+                .unwrap_or(entry.index);
+
+            if entry.index <= snapshot.last_included_index {
+                continue;
+            }
+
+            if entry.index != expected_index {
+                break;
+            }
+
+            entries.push(entry);
+        }
+
+        let storage = RaftStorage {
+            directory: Some(PathBuf::from(directory)),
+            log: Some(open_log(&directory.join(LOG_FILE))?),
+        };
+
+        Ok((
+            storage,
+            RecoveredState {
+                hard_state,
+                snapshot,
+                entries,
+            },
+        ))
+    }
+
+    pub fn save_hard_state(&mut self, hard_state: &RaftHardState) -> io::Result<()> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+
+        write_atomically(
+            &directory.join(HARD_STATE_FILE),
+            &hard_state.encode_to_vec(),
+        )
+    }
+
+    pub fn append(&mut self, entries: &[RaftEntry]) -> io::Result<()> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+        let mut contents = Vec::new();
+
+        for entry in entries {
+            entry.encode_length_delimited(&mut contents)?;
+        }
+
+        log.write_all(&contents)?;
+        log.sync_data()
+    }
+
+    /// Replaces the log with `entries`, used after the log was truncated or compacted
+    pub fn rewrite_log(&mut self, entries: &[RaftEntry]) -> io::Result<()> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+        log.set_len(0)?;
+
+        self.append(entries)
+    }
+
+    /// Saves the snapshot and replaces the log with the entries that follow it
+    pub fn save_snapshot(
+        &mut self,
+        snapshot: &RaftSnapshot,
+        entries: &[RaftEntry],
+    ) -> io::Result<()> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+        write_atomically(&directory.join(SNAPSHOT_FILE), &snapshot.encode_to_vec())?;
+
+        self.rewrite_log(entries)
+    }
+}
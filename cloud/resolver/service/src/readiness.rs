@@ -0,0 +1,46 @@
+use std::fmt;
+
+use health::ReadinessCheck;
+
+use crate::resolver::LocalDcnsResolver;
+
+/// Reason the resolver can't serve calls
+#[derive(Debug)]
+pub enum Unready {
+    /// Registry is replicated by a cluster that hasn't elected a leader
+    NoLeader,
+}
+
+impl fmt::Display for Unready {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unready::NoLeader => write!(formatter, "Resolver cluster should have a leader"),
+        }
+    }
+}
+
+impl std::error::Error for Unready {}
+
+/// Checks the registry of the resolver can serve reads and registrations
+#[derive(Clone)]
+pub struct ResolverReadiness {
+    resolver: LocalDcnsResolver,
+}
+
+impl ResolverReadiness {
+    pub fn new(resolver: LocalDcnsResolver) -> ResolverReadiness {
+        ResolverReadiness { resolver }
+    }
+}
+
+impl ReadinessCheck for ResolverReadiness {
+    type Error = Unready;
+
+    fn check(&self) -> Result<(), Unready> {
+        if self.resolver.registry_available() {
+            Ok(())
+        } else {
+            Err(Unready::NoLeader)
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, io,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use identity::{DeregistrationClaims, HeartbeatClaims, RegistrationClaims};
+use resource_name::ResourceNameError;
+use tokio::sync::broadcast;
+use tonic::Status;
+
+use crate::{
+    protos::{
+        data_center::Resources,
+        resolver::{
+            registry_change::Change, registry_command::Command, DataCenter, DataCenterChange,
+            DataCenterEvent, DataCenterHealth, DeregisterDataCenterRequest, HeartbeatRequest,
+            RegisterDataCenterRequest, RegistryCommand, RegistrySnapshot, WatchDataCentersResponse,
+        },
+    },
+    store::RegistryStore,
+};
+
+/// Number of changes kept for watchers resuming from an earlier revision
+const HISTORY_LEN: usize = 1024;
+/// Number of changes a watcher may fall behind before it has to resume
+const WATCH_CAPACITY: usize = 256;
+
+/// Reason a registration, deregistration or heartbeat was refused
+#[derive(Debug)]
+pub enum RegistrationRejection {
+    MissingHostName,
+    InvalidId(ResourceNameError),
+    Unsigned,
+    InvalidSignature(identity::IdentityError),
+    /// Deregistration of a data center registered with a key isn't signed with it
+    UnsignedDeregistration {
+        data_center_id: String,
+        fingerprint: String,
+    },
+    /// Heartbeat of a data center registered with a key isn't signed with it
+    UnsignedHeartbeat {
+        data_center_id: String,
+        fingerprint: String,
+    },
+    /// Id or host name is registered with another key
+    KeyBound {
+        data_center_id: String,
+        host_name: String,
+        fingerprint: String,
+    },
+}
+
+impl fmt::Display for RegistrationRejection {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistrationRejection::MissingHostName => {
+                formatter.write_str("Data centers must have a host name")
+            }
+            RegistrationRejection::InvalidId(error) => {
+                write!(formatter, "Invalid data center id: {error}")
+            }
+            RegistrationRejection::Unsigned => formatter
+                .write_str("Registrations should be signed with the key of the data center"),
+            RegistrationRejection::InvalidSignature(error) => {
+                write!(formatter, "Invalid signature: {error}")
+            }
+            RegistrationRejection::UnsignedDeregistration {
+                data_center_id,
+                fingerprint,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} is registered with key {fingerprint}, only a \
+                 deregistration signed with it is accepted"
+            ),
+            RegistrationRejection::UnsignedHeartbeat {
+                data_center_id,
+                fingerprint,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} is registered with key {fingerprint}, only a \
+                 heartbeat signed with it is accepted"
+            ),
+            RegistrationRejection::KeyBound {
+                data_center_id,
+                host_name,
+                fingerprint,
+            } => write!(
+                formatter,
+                "Data center {data_center_id} at {host_name} is registered with key {fingerprint}"
+            ),
+        }
+    }
+}
+
+impl From<RegistrationRejection> for Status {
+    fn from(rejection: RegistrationRejection) -> Self {
+        match rejection {
+            RegistrationRejection::MissingHostName | RegistrationRejection::InvalidId(_) => {
+                Status::invalid_argument(rejection.to_string())
+            }
+            RegistrationRejection::KeyBound { .. } => {
+                Status::permission_denied(rejection.to_string())
+            }
+            _ => Status::unauthenticated(rejection.to_string()),
+        }
+    }
+}
+
+/// Failure of a command applied to the registry
+#[derive(Debug)]
+pub enum RegistryError {
+    Rejected(RegistrationRejection),
+    Io(io::Error),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Rejected(rejection) => write!(formatter, "{rejection}"),
+            RegistryError::Io(error) => write!(formatter, "Failed to persist registry: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<RegistrationRejection> for RegistryError {
+    fn from(rejection: RegistrationRejection) -> Self {
+        RegistryError::Rejected(rejection)
+    }
+}
+
+impl From<io::Error> for RegistryError {
+    fn from(error: io::Error) -> Self {
+        RegistryError::Io(error)
+    }
+}
+
+impl From<RegistryError> for Status {
+    fn from(error: RegistryError) -> Self {
+        match error {
+            RegistryError::Rejected(rejection) => rejection.into(),
+            RegistryError::Io(_) => Status::internal(error.to_string()),
+        }
+    }
+}
+
+/// Registry of every data center on the network keyed by data center id
+pub struct Registry {
+    data_centers_by_id: BTreeMap<String, DataCenter>,
+    store: Option<RegistryStore>,
+    /// Whether data centers registering without signing their registration are refused
+    signed_registrations_required: bool,
+    /// Number of changes made to the registry since it was created
+    revision: u64,
+    /// Changes recently made to the registry, oldest first
+    history: VecDeque<WatchDataCentersResponse>,
+    changes: broadcast::Sender<WatchDataCentersResponse>,
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::restore(RegistrySnapshot::default())
+    }
+}
+
+impl Registry {
+    /// Opens a registry persisted in `directory`. Heartbeats are not persisted, so every data
+    /// center reloaded from disk is treated as last seen at `now_unix_ms`
+    pub fn open(directory: &Path, now_unix_ms: u64) -> io::Result<Registry> {
+        let (store, mut snapshot) = RegistryStore::open(directory)?;
+
+        for data_center in snapshot.data_centers.iter_mut() {
+            data_center.last_seen_unix_ms = now_unix_ms;
+        }
+
+        Ok(Registry {
+            store: Some(store),
+            ..Registry::restore(snapshot)
+        })
+    }
+
+    /// Creates an in memory registry holding the data centers of `snapshot`
+    pub fn restore(snapshot: RegistrySnapshot) -> Registry {
+        Registry {
+            data_centers_by_id: snapshot
+                .data_centers
+                .into_iter()
+                .map(|data_center| (data_center.data_center_id.clone(), data_center))
+                .collect(),
+            store: None,
+            signed_registrations_required: false,
+            revision: snapshot.revision,
+            history: VecDeque::new(),
+            changes: broadcast::channel(WATCH_CAPACITY).0,
+        }
+    }
+
+    /// Refuses data centers registering without signing their registration. Signatures are
+    /// verified whenever present either way
+    pub fn with_signed_registrations_required(mut self) -> Registry {
+        self.signed_registrations_required = true;
+
+        self
+    }
+
+    pub fn list(&self) -> Vec<DataCenter> {
+        self.data_centers_by_id.values().cloned().collect()
+    }
+
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot {
+            data_centers: self.list(),
+            revision: self.revision,
+        }
+    }
+
+    /// Changes bringing a watcher at `start_revision` up to date along with a receiver of the
+    /// changes that follow, or `None` when the changes made since `start_revision` are no
+    /// longer retained. Watching from revision zero starts with every data center registered
+    pub fn watch(
+        &self,
+        start_revision: u64,
+    ) -> Option<(
+        Vec<WatchDataCentersResponse>,
+        broadcast::Receiver<WatchDataCentersResponse>,
+    )> {
+        let receiver = self.changes.subscribe();
+
+        if start_revision == 0 {
+            let events = self
+                .data_centers_by_id
+                .values()
+                .map(|data_center| DataCenterEvent {
+                    change: DataCenterChange::Added as i32,
+                    data_center: Some(data_center.clone()),
+                })
+                .collect();
+            let response = WatchDataCentersResponse {
+                events,
+                revision: self.revision,
+                reset: true,
+            };
+
+            return Some((vec![response], receiver));
+        }
+
+        let oldest_retained = self
+            .history
+            .front()
+            .map(|change| change.revision)
+            .unwrap_or(self.revision + 1);
+
+        if start_revision > self.revision
+            || (start_revision < self.revision && start_revision + 1 < oldest_retained)
+        {
+            return None;
+        }
+
+        let missed = self
+            .history
+            .iter()
+            .filter(|change| change.revision > start_revision)
+            .cloned()
+            .collect();
+
+        Some((missed, receiver))
+    }
+
+    pub fn get(&self, data_center_id: &str) -> Option<DataCenter> {
+        self.data_centers_by_id.get(data_center_id).cloned()
+    }
+
+    /// Id of the data center registered for `host_name`
+    pub fn registered_id(&self, host_name: &str) -> Option<String> {
+        self.data_centers_by_id
+            .values()
+            .find(|data_center| data_center.host_name == host_name)
+            .map(|data_center| data_center.data_center_id.clone())
+    }
+
+    /// Applies a command, returning the data center it affected. Commands carry everything they
+    /// depend on so every replica applying the same commands ends up with the same registry.
+    /// Registrations are checked here rather than only where they are received, so commands
+    /// proposed to a cluster directly are held to the same rules
+    pub fn apply(&mut self, command: RegistryCommand) -> Result<Option<DataCenter>, RegistryError> {
+        let now_unix_ms = command.issued_at_unix_ms;
+
+        match command.command {
+            Some(Command::Register(request)) => {
+                self.check_registration(&request, now_unix_ms)?;
+
+                Ok(Some(self.register(request, now_unix_ms)?))
+            }
+            Some(Command::Deregister(request)) => {
+                self.check_deregistration(&request, now_unix_ms)?;
+
+                Ok(self.deregister(&request.data_center_id)?)
+            }
+            Some(Command::Heartbeat(request)) => {
+                self.check_heartbeat(&request, now_unix_ms)?;
+
+                Ok(self.heartbeat(
+                    &request.data_center_id,
+                    request.available_resources,
+                    now_unix_ms,
+                )?)
+            }
+            Some(Command::Sweep(sweep)) => {
+                self.sweep(
+                    now_unix_ms,
+                    Duration::from_millis(sweep.heartbeat_timeout_ms),
+                    Duration::from_millis(sweep.eviction_ttl_ms),
+                )?;
+
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that a registration names its data center, that the data center signed it no
+    /// longer than the allowed clock skew before `now_unix_ms` when it carries a key, its id or
+    /// host name is bound to one, or signatures are required, and that it doesn't take over the
+    /// id or host name of a data center registered with another key. Keys are bound to a data
+    /// center until it is deregistered or evicted
+    fn check_registration(
+        &self,
+        request: &RegisterDataCenterRequest,
+        now_unix_ms: u64,
+    ) -> Result<(), RegistrationRejection> {
+        if request.host_name.is_empty() {
+            return Err(RegistrationRejection::MissingHostName);
+        }
+
+        resource_name::validate_segment(&request.data_center_id)
+            .map_err(RegistrationRejection::InvalidId)?;
+
+        let mut bound = self.data_centers_by_id.values().filter(|data_center| {
+            (data_center.data_center_id == request.data_center_id
+                || data_center.host_name == request.host_name)
+                && !data_center.public_key.is_empty()
+        });
+
+        if !request.signature.is_empty() {
+            let capabilities = request.capabilities.as_ref();
+            RegistrationClaims::from_parts(
+                &request.data_center_id,
+                &request.host_name,
+                &request.region,
+                &request.zone,
+                &request.labels,
+                capabilities.map_or(&[], |capabilities| &capabilities.services),
+                capabilities.map_or(&[], |capabilities| &capabilities.architectures),
+                &request.public_key,
+                request.signed_at_unix_ms,
+            )
+            .verify(&request.signature, now_unix_ms)
+            .map_err(RegistrationRejection::InvalidSignature)?;
+        } else if self.signed_registrations_required
+            || !request.public_key.is_empty()
+            || bound.clone().next().is_some()
+        {
+            // Keys are public, so only a signature proves the registration comes from the
+            // holder of the key
+            return Err(RegistrationRejection::Unsigned);
+        }
+
+        match bound.find(|data_center| data_center.public_key != request.public_key) {
+            Some(data_center) => Err(RegistrationRejection::KeyBound {
+                data_center_id: data_center.data_center_id.clone(),
+                host_name: data_center.host_name.clone(),
+                fingerprint: identity::fingerprint(&data_center.public_key),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the deregistration of a data center registered with a key was signed with
+    /// that key no longer than the allowed clock skew before `now_unix_ms`, so nobody else can
+    /// drop the binding and take over the id
+    fn check_deregistration(
+        &self,
+        request: &DeregisterDataCenterRequest,
+        now_unix_ms: u64,
+    ) -> Result<(), RegistrationRejection> {
+        let Some(data_center) = self.bound(&request.data_center_id) else {
+            return Ok(());
+        };
+
+        if request.signature.is_empty() {
+            return Err(RegistrationRejection::UnsignedDeregistration {
+                data_center_id: data_center.data_center_id.clone(),
+                fingerprint: identity::fingerprint(&data_center.public_key),
+            });
+        }
+
+        DeregistrationClaims {
+            data_center_id: &request.data_center_id,
+            signed_at_unix_ms: request.signed_at_unix_ms,
+        }
+        .verify(&data_center.public_key, &request.signature, now_unix_ms)
+        .map_err(RegistrationRejection::InvalidSignature)
+    }
+
+    /// Checks that the heartbeat of a data center registered with a key was signed with that
+    /// key no longer than the allowed clock skew before `now_unix_ms`, so nobody else can keep
+    /// the data center alive once it has stopped
+    fn check_heartbeat(
+        &self,
+        request: &HeartbeatRequest,
+        now_unix_ms: u64,
+    ) -> Result<(), RegistrationRejection> {
+        let Some(data_center) = self.bound(&request.data_center_id) else {
+            return Ok(());
+        };
+
+        if request.signature.is_empty() {
+            return Err(RegistrationRejection::UnsignedHeartbeat {
+                data_center_id: data_center.data_center_id.clone(),
+                fingerprint: identity::fingerprint(&data_center.public_key),
+            });
+        }
+
+        HeartbeatClaims {
+            data_center_id: &request.data_center_id,
+            signed_at_unix_ms: request.signed_at_unix_ms,
+        }
+        .verify(&data_center.public_key, &request.signature, now_unix_ms)
+        .map_err(RegistrationRejection::InvalidSignature)
+    }
+
+    /// Record of the data center registered as `data_center_id` when it is bound to a key
+    fn bound(&self, data_center_id: &str) -> Option<&DataCenter> {
+        self.data_centers_by_id
+            .get(data_center_id)
+            .filter(|data_center| !data_center.public_key.is_empty())
+    }
+
+    /// Registers a data center without checking the registration, replacing any record with
+    /// the same id or host name. Data centers
+    /// registering without an id reuse the id of the record for their host name, ids are never
+    /// generated here so every replica registers the same id
+    pub fn register(
+        &mut self,
+        request: RegisterDataCenterRequest,
+        now_unix_ms: u64,
+    ) -> io::Result<DataCenter> {
+        let data_center_id = if request.data_center_id.is_empty() {
+            self.registered_id(&request.host_name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("No data center id registered for {}", request.host_name),
+                )
+            })?
+        } else {
+            request.data_center_id
+        };
+        let replaced: Vec<String> = self
+            .data_centers_by_id
+            .values()
+            .filter(|data_center| {
+                data_center.data_center_id != data_center_id
+                    && data_center.host_name == request.host_name
+            })
+            .map(|data_center| data_center.data_center_id.clone())
+            .collect();
+
+        for data_center_id in replaced {
+            self.deregister(&data_center_id)?;
+        }
+
+        let data_center = DataCenter {
+            data_center_id: data_center_id.clone(),
+            host_name: request.host_name,
+            health: DataCenterHealth::Healthy as i32,
+            available_resources: request.available_resources,
+            region: request.region,
+            zone: request.zone,
+            labels: request.labels,
+            capabilities: request.capabilities,
+            capacity: request.capacity,
+            last_seen_unix_ms: now_unix_ms,
+            public_key: request.public_key,
+        };
+        let change = match self
+            .data_centers_by_id
+            .insert(data_center_id, data_center.clone())
+        {
+            Some(_) => DataCenterChange::Updated,
+            None => DataCenterChange::Added,
+        };
+        self.record(change, data_center.clone())?;
+
+        Ok(data_center)
+    }
+
+    pub fn deregister(&mut self, data_center_id: &str) -> io::Result<Option<DataCenter>> {
+        let data_center = self.data_centers_by_id.remove(data_center_id);
+
+        if let Some(data_center) = &data_center {
+            self.record(DataCenterChange::Removed, data_center.clone())?;
+        }
+
+        Ok(data_center)
+    }
+
+    /// Records a heartbeat, returning `None` when the data center is not registered
+    pub fn heartbeat(
+        &mut self,
+        data_center_id: &str,
+        available_resources: Option<Resources>,
+        now_unix_ms: u64,
+    ) -> io::Result<Option<DataCenter>> {
+        let Some(data_center) = self.data_centers_by_id.get_mut(data_center_id) else {
+            return Ok(None);
+        };
+        let recovered = data_center.health() != DataCenterHealth::Healthy;
+        data_center.last_seen_unix_ms = now_unix_ms;
+        data_center.available_resources = available_resources;
+        data_center.set_health(DataCenterHealth::Healthy);
+        let data_center = data_center.clone();
+
+        if recovered {
+            self.record(DataCenterChange::Updated, data_center.clone())?;
+        }
+
+        Ok(Some(data_center))
+    }
+
+    /// Marks data centers that missed their heartbeats as unhealthy and evicts those that have
+    /// been silent for longer than the ttl
+    pub fn sweep(
+        &mut self,
+        now_unix_ms: u64,
+        heartbeat_timeout: Duration,
+        ttl: Duration,
+    ) -> io::Result<()> {
+        let silence = |data_center: &DataCenter| {
+            Duration::from_millis(now_unix_ms.saturating_sub(data_center.last_seen_unix_ms))
+        };
+        let evicted: Vec<String> = self
+            .data_centers_by_id
+            .values()
+            .filter(|data_center| silence(data_center) >= ttl)
+            .map(|data_center| data_center.data_center_id.clone())
+            .collect();
+
+        for data_center_id in evicted {
+            self.deregister(&data_center_id)?;
+        }
+
+        let mut silenced = Vec::new();
+
+        for data_center in self.data_centers_by_id.values_mut() {
+            if silence(data_center) >= heartbeat_timeout
+                && data_center.health() == DataCenterHealth::Healthy
+            {
+                data_center.set_health(DataCenterHealth::Unhealthy);
+                silenced.push(data_center.clone());
+            }
+        }
+
+        for data_center in silenced {
+            self.record(DataCenterChange::Updated, data_center)?;
+        }
+
+        Ok(())
+    }
+
+    /// Persists a change made to the registry and sends it to the registry's watchers
+    fn record(&mut self, change: DataCenterChange, data_center: DataCenter) -> io::Result<()> {
+        self.revision += 1;
+
+        if let Some(store) = self.store.as_mut() {
+            let persisted = match change {
+                DataCenterChange::Removed => Change::Remove(data_center.data_center_id.clone()),
+                _ => Change::Put(data_center.clone()),
+            };
+            store.append(persisted, self.revision)?;
+
+            if store.needs_compaction() {
+                store.compact(&RegistrySnapshot {
+                    data_centers: self.data_centers_by_id.values().cloned().collect(),
+                    revision: self.revision,
+                })?;
+            }
+        }
+
+        let response = WatchDataCentersResponse {
+            events: vec![DataCenterEvent {
+                change: change as i32,
+                data_center: Some(data_center),
+            }],
+            revision: self.revision,
+            reset: false,
+        };
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(response.clone());
+        // Sending only fails when nobody is watching
+        let _ = self.changes.send(response);
+
+        Ok(())
+    }
+}
+
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time should be after the unix epoch")
+        .as_millis() as u64
+}
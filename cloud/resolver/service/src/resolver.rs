@@ -0,0 +1,422 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use audit::AuditLog;
+use nanoid::nanoid;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    dialer::Dialer,
+    membership::MembershipView,
+    protos::resolver::{
+        dcns_resolver_server::DcnsResolver, registry_command::Command, DataCenter,
+        DeregisterDataCenterRequest, DeregisterDataCenterResponse, GetDataCenterRequest,
+        GetDataCenterResponse, HeartbeatRequest, HeartbeatResponse, ListDataCentersRequest,
+        ListDataCentersResponse, PlaceMachineRequest, PlaceMachineResponse,
+        RegisterDataCenterRequest, RegisterDataCenterResponse, RegistryCommand, SweepRegistry,
+        WatchDataCentersRequest, WatchDataCentersResponse,
+    },
+    raft::{RaftNode, Role},
+    registry::{unix_time_ms, Registry},
+    scheduler,
+};
+
+/// Time a data center has to report its live resources when placing a machine
+const CAPACITY_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Shortest time between sweeps of the registry, however short the heartbeat timeout
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Registry the resolver serves, either owned by this process or replicated across a cluster
+enum RegistryHandle {
+    Standalone(Mutex<Registry>),
+    Replicated(Arc<RaftNode>),
+}
+
+impl RegistryHandle {
+    fn read<T>(&self, read: impl FnOnce(&Registry) -> T) -> T {
+        match self {
+            RegistryHandle::Standalone(registry) => {
+                read(&registry.lock().expect("Should fetch lock"))
+            }
+            RegistryHandle::Replicated(node) => node.read(read),
+        }
+    }
+
+    async fn submit(&self, command: Command) -> Result<Option<DataCenter>, Status> {
+        let command = RegistryCommand {
+            issued_at_unix_ms: unix_time_ms(),
+            command: Some(command),
+        };
+
+        match self {
+            RegistryHandle::Standalone(registry) => registry
+                .lock()
+                .expect("Should fetch lock")
+                .apply(command)
+                .map_err(Status::from),
+            RegistryHandle::Replicated(node) => node.propose(command, false).await,
+        }
+    }
+
+    /// Whether this resolver is responsible for sweeping the registry
+    fn sweeps(&self) -> bool {
+        match self {
+            RegistryHandle::Standalone(_) => true,
+            RegistryHandle::Replicated(node) => node.role() == Role::Leader,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LocalDcnsResolver {
+    registry: Arc<RegistryHandle>,
+    membership: Option<MembershipView>,
+    /// How data centers are reached
+    dialer: Dialer,
+    audit: Arc<AuditLog>,
+}
+
+impl Default for LocalDcnsResolver {
+    fn default() -> LocalDcnsResolver {
+        LocalDcnsResolver::new(Registry::default())
+    }
+}
+
+impl LocalDcnsResolver {
+    pub fn new(registry: Registry) -> LocalDcnsResolver {
+        LocalDcnsResolver {
+            registry: Arc::new(RegistryHandle::Standalone(Mutex::new(registry))),
+            membership: None,
+            dialer: Dialer::default(),
+            audit: Arc::default(),
+        }
+    }
+
+    /// Creates a resolver serving the registry replicated by `node`
+    pub fn replicated(node: Arc<RaftNode>) -> LocalDcnsResolver {
+        LocalDcnsResolver {
+            registry: Arc::new(RegistryHandle::Replicated(node)),
+            membership: None,
+            dialer: Dialer::default(),
+            audit: Arc::default(),
+        }
+    }
+
+    /// Lists data centers from the gossip membership of the data centers instead of the
+    /// registry
+    pub fn with_membership(mut self, membership: MembershipView) -> LocalDcnsResolver {
+        self.membership = Some(membership);
+
+        self
+    }
+
+    /// Reaches data centers through `dialer` to check their resources and forward calls to
+    /// them
+    pub fn with_dialer(mut self, dialer: Dialer) -> LocalDcnsResolver {
+        self.dialer = dialer;
+
+        self
+    }
+
+    /// Records the registrations of data centers and the mutating calls proxied to them in
+    /// `audit`. Heartbeats only refresh the liveness of data centers, so they aren't recorded
+    pub fn with_audit_log(mut self, audit: Arc<AuditLog>) -> LocalDcnsResolver {
+        self.audit = audit;
+
+        self
+    }
+
+    pub fn dialer(&self) -> &Dialer {
+        &self.dialer
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Periodically marks data centers that missed their heartbeats as unhealthy and evicts
+    /// those silent for longer than `eviction_ttl`
+    pub fn spawn_sweeper(
+        &self,
+        heartbeat_timeout: Duration,
+        eviction_ttl: Duration,
+    ) -> JoinHandle<()> {
+        let registry = self.registry.clone();
+
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval((heartbeat_timeout / 3).max(MIN_SWEEP_INTERVAL));
+
+            loop {
+                interval.tick().await;
+
+                if !registry.sweeps() {
+                    continue;
+                }
+
+                let result = registry
+                    .submit(Command::Sweep(SweepRegistry {
+                        heartbeat_timeout_ms: heartbeat_timeout.as_millis() as u64,
+                        eviction_ttl_ms: eviction_ttl.as_millis() as u64,
+                    }))
+                    .await;
+
+                if let Err(status) = result {
+                    tracing::warn!(message = status.message(), "Failed to sweep registry");
+                }
+            }
+        })
+    }
+
+    /// Whether the registry can serve calls, which a replicated registry only can once its
+    /// cluster elected a leader
+    pub fn registry_available(&self) -> bool {
+        match self.registry.as_ref() {
+            RegistryHandle::Standalone(_) => true,
+            RegistryHandle::Replicated(node) => node.leader_id().is_some(),
+        }
+    }
+
+    /// Data centers held in the registry, whether or not data centers are listed from the
+    /// membership view
+    pub fn registered(&self) -> Vec<DataCenter> {
+        self.registry.read(|registry| registry.list())
+    }
+
+    /// Data centers on the network, taken from the membership view when one is configured
+    pub(crate) async fn data_centers(&self) -> Result<Vec<DataCenter>, Status> {
+        match &self.membership {
+            Some(membership) => membership.data_centers().await,
+            None => Ok(self.registered()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DcnsResolver for LocalDcnsResolver {
+    type WatchDataCentersStream = ReceiverStream<Result<WatchDataCentersResponse, Status>>;
+
+    async fn list_data_centers(
+        &self,
+        _request: Request<ListDataCentersRequest>,
+    ) -> Result<Response<ListDataCentersResponse>, Status> {
+        Ok(Response::new(ListDataCentersResponse {
+            data_center: self.data_centers().await?,
+        }))
+    }
+
+    async fn register_data_center(
+        &self,
+        request: Request<RegisterDataCenterRequest>,
+    ) -> Result<Response<RegisterDataCenterResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/resolver.DcnsResolver/RegisterDataCenter")
+            .resource(&request.get_ref().data_center_id);
+
+        entry
+            .record_with(
+                async move {
+                    let mut request = request.into_inner();
+
+                    // Generated once here rather than when applied, the registry then keeps it for
+                    // the host's later registrations. Signed registrations carry the id their
+                    // signature covers
+                    if request.data_center_id.is_empty() && request.signature.is_empty() {
+                        request.data_center_id = self
+                            .registry
+                            .read(|registry| registry.registered_id(&request.host_name))
+                            .unwrap_or_else(|| nanoid!());
+                    }
+
+                    let data_center = self.registry.submit(Command::Register(request)).await?;
+
+                    Ok(Response::new(RegisterDataCenterResponse { data_center }))
+                },
+                |response| {
+                    response
+                        .data_center
+                        .iter()
+                        .map(|data_center| data_center.data_center_id.clone())
+                        .collect()
+                },
+            )
+            .await
+    }
+
+    async fn deregister_data_center(
+        &self,
+        request: Request<DeregisterDataCenterRequest>,
+    ) -> Result<Response<DeregisterDataCenterResponse>, Status> {
+        let entry = self
+            .audit
+            .begin(&request, "/resolver.DcnsResolver/DeregisterDataCenter")
+            .resource(&request.get_ref().data_center_id);
+
+        entry
+            .record(async move {
+                let data_center_id = request.get_ref().data_center_id.clone();
+
+                if self
+                    .registry
+                    .submit(Command::Deregister(request.into_inner()))
+                    .await?
+                    .is_none()
+                {
+                    return Err(Status::not_found(format!(
+                        "No data center registered with id {data_center_id}"
+                    )));
+                }
+
+                Ok(Response::new(DeregisterDataCenterResponse {}))
+            })
+            .await
+    }
+
+    async fn get_data_center(
+        &self,
+        request: Request<GetDataCenterRequest>,
+    ) -> Result<Response<GetDataCenterResponse>, Status> {
+        let request = request.into_inner();
+        let Some(data_center) = self
+            .registry
+            .read(|registry| registry.get(&request.data_center_id))
+        else {
+            return Err(Status::not_found(format!(
+                "No data center registered with id {}",
+                request.data_center_id
+            )));
+        };
+
+        Ok(Response::new(GetDataCenterResponse {
+            data_center: Some(data_center),
+        }))
+    }
+
+    async fn heartbeat(
+        &self,
+        request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        let data_center_id = request.get_ref().data_center_id.clone();
+        let Some(data_center) = self
+            .registry
+            .submit(Command::Heartbeat(request.into_inner()))
+            .await?
+        else {
+            return Err(Status::not_found(format!(
+                "No data center registered with id {data_center_id}"
+            )));
+        };
+
+        Ok(Response::new(HeartbeatResponse {
+            data_center: Some(data_center),
+        }))
+    }
+
+    async fn place_machine(
+        &self,
+        request: Request<PlaceMachineRequest>,
+    ) -> Result<Response<PlaceMachineResponse>, Status> {
+        let request = request.into_inner();
+        let strategy = scheduler::strategy(request.strategy());
+        let Some(resources) = request.resources else {
+            return Err(Status::invalid_argument("Machines must request resources"));
+        };
+        let constraints = request.constraints.unwrap_or_default();
+        let eligible: Vec<DataCenter> = self
+            .data_centers()
+            .await?
+            .into_iter()
+            .filter(|data_center| scheduler::satisfies(data_center, &constraints))
+            .collect();
+
+        if eligible.is_empty() {
+            return Err(Status::failed_precondition(
+                "No healthy data center satisfies the placement constraints",
+            ));
+        }
+
+        let candidates = scheduler::check_candidates(
+            eligible,
+            &request.image_id,
+            CAPACITY_CHECK_TIMEOUT,
+            self.dialer(),
+        )
+        .await;
+        let Some(candidate) = scheduler::place(&candidates, &resources, strategy.as_ref()) else {
+            return Err(Status::resource_exhausted(format!(
+                "No data center has room for {} mb of ram, {} mb of disk and {} vcpus",
+                resources.ram_mb, resources.disk_mb, resources.vcpus
+            )));
+        };
+        let mut data_center = candidate.data_center;
+        data_center.available_resources = Some(candidate.available);
+        data_center.capacity = Some(candidate.capacity);
+
+        Ok(Response::new(PlaceMachineResponse {
+            data_center: Some(data_center),
+        }))
+    }
+
+    async fn watch_data_centers(
+        &self,
+        request: Request<WatchDataCentersRequest>,
+    ) -> Result<Response<Self::WatchDataCentersStream>, Status> {
+        if self.membership.is_some() {
+            return Err(Status::unimplemented(
+                "Watching requires the registry, this resolver lists data centers from the \
+                 gossip membership",
+            ));
+        }
+
+        let start_revision = request.into_inner().start_revision;
+        let Some((missed, mut changes)) = self
+            .registry
+            .read(|registry| registry.watch(start_revision))
+        else {
+            return Err(Status::out_of_range(format!(
+                "Changes since revision {start_revision} are no longer retained, watch again \
+                 from revision 0"
+            )));
+        };
+        let (sender, receiver) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            for response in missed {
+                if sender.send(Ok(response)).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                let change = tokio::select! {
+                    _ = sender.closed() => return,
+                    change = changes.recv() => change,
+                };
+                let result = match change {
+                    Ok(response) => Ok(response),
+                    Err(RecvError::Lagged(_)) => Err(Status::aborted(
+                        "Watcher fell behind, watch again from the last revision received",
+                    )),
+                    Err(RecvError::Closed) => Err(Status::aborted(
+                        "Registry was replaced, watch again from the last revision received",
+                    )),
+                };
+                let ended = result.is_err();
+
+                if sender.send(result).await.is_err() || ended {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
@@ -0,0 +1,244 @@
+use std::{cmp::Ordering, time::Duration};
+
+use rand::seq::SliceRandom;
+use tokio::task::JoinSet;
+use tonic::Request;
+
+use crate::{
+    dialer::Dialer,
+    protos::{
+        data_center::{CheckResourceRequest, GetImageMetadataRequest, Resources, ServiceType},
+        resolver::{DataCenter, DataCenterHealth, PlacementConstraints, PlacementStrategy},
+    },
+};
+
+/// Data center a machine could be placed in along with the resources it reported live
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub data_center: DataCenter,
+    /// Resources the data center had available when it was checked
+    pub available: Resources,
+    /// Total resources of the data center
+    pub capacity: Resources,
+}
+
+/// Strategy choosing the data center a machine is placed in
+pub trait Strategy: Send + Sync {
+    /// Chooses one of `candidates`, each of which has room for `resources`
+    fn choose<'a>(
+        &self,
+        candidates: &'a [Candidate],
+        resources: &Resources,
+    ) -> Option<&'a Candidate>;
+}
+
+/// Places machines in the data center left fullest by the placement
+pub struct BinPack;
+
+impl Strategy for BinPack {
+    fn choose<'a>(
+        &self,
+        candidates: &'a [Candidate],
+        resources: &Resources,
+    ) -> Option<&'a Candidate> {
+        candidates.iter().min_by(|left, right| {
+            free_share_after(left, resources)
+                .total_cmp(&free_share_after(right, resources))
+                .then_with(|| by_id(left, right))
+        })
+    }
+}
+
+/// Places machines in the data center left emptiest by the placement
+pub struct Spread;
+
+impl Strategy for Spread {
+    fn choose<'a>(
+        &self,
+        candidates: &'a [Candidate],
+        resources: &Resources,
+    ) -> Option<&'a Candidate> {
+        candidates.iter().min_by(|left, right| {
+            free_share_after(right, resources)
+                .total_cmp(&free_share_after(left, resources))
+                .then_with(|| by_id(left, right))
+        })
+    }
+}
+
+/// Places machines in any data center they fit in
+pub struct Random;
+
+impl Strategy for Random {
+    fn choose<'a>(
+        &self,
+        candidates: &'a [Candidate],
+        _resources: &Resources,
+    ) -> Option<&'a Candidate> {
+        candidates.choose(&mut rand::thread_rng())
+    }
+}
+
+pub fn strategy(strategy: PlacementStrategy) -> Box<dyn Strategy> {
+    match strategy {
+        PlacementStrategy::BinPack => Box::new(BinPack),
+        PlacementStrategy::Spread => Box::new(Spread),
+        PlacementStrategy::Random => Box::new(Random),
+    }
+}
+
+/// Whether machines may be placed in the data center under `constraints`, regardless of the
+/// room it has left
+pub fn satisfies(data_center: &DataCenter, constraints: &PlacementConstraints) -> bool {
+    data_center.health() == DataCenterHealth::Healthy
+        && offers(data_center, ServiceType::Compute)
+        && (constraints.region.is_empty() || constraints.region == data_center.region)
+        && (constraints.zone.is_empty() || constraints.zone == data_center.zone)
+        && constraints
+            .labels
+            .iter()
+            .all(|(key, value)| data_center.labels.get(key) == Some(value))
+        && !constraints
+            .anti_affinity
+            .contains(&data_center.data_center_id)
+}
+
+/// Whether the data center offers `service`, data centers that don't advertise their
+/// capabilities are assumed to offer every service
+pub fn offers(data_center: &DataCenter, service: ServiceType) -> bool {
+    data_center
+        .capabilities
+        .as_ref()
+        .map(|capabilities| capabilities.services.contains(&(service as i32)))
+        .unwrap_or(true)
+}
+
+/// Whether `available` resources have room for `required` resources
+pub fn fits(available: &Resources, required: &Resources) -> bool {
+    available.ram_mb >= required.ram_mb
+        && available.disk_mb >= required.disk_mb
+        && available.vcpus >= required.vcpus
+}
+
+/// Chooses where to place a machine requiring `resources` among the candidates with room for
+/// it
+pub fn place(
+    candidates: &[Candidate],
+    resources: &Resources,
+    strategy: &dyn Strategy,
+) -> Option<Candidate> {
+    let fitting: Vec<Candidate> = candidates
+        .iter()
+        .filter(|candidate| fits(&candidate.available, resources))
+        .cloned()
+        .collect();
+
+    strategy.choose(&fitting, resources).cloned()
+}
+
+/// Asks every data center for its live resources, keeping those that answer within `timeout`
+/// and hold the image `image_id` when one is given. Data centers are reached through `dialer`
+/// on the resolver's own behalf
+pub async fn check_candidates(
+    data_centers: Vec<DataCenter>,
+    image_id: &str,
+    timeout: Duration,
+    dialer: &Dialer,
+) -> Vec<Candidate> {
+    let mut checks = JoinSet::new();
+
+    for data_center in data_centers {
+        let image_id = String::from(image_id);
+        let dialer = dialer.clone();
+        checks.spawn(tokio::time::timeout(
+            timeout,
+            check_candidate(data_center, image_id, dialer),
+        ));
+    }
+
+    let mut candidates = Vec::new();
+
+    while let Some(result) = checks.join_next().await {
+        if let Ok(Ok(Some(candidate))) = result {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates.sort_by(by_id);
+
+    candidates
+}
+
+async fn check_candidate(
+    data_center: DataCenter,
+    image_id: String,
+    dialer: Dialer,
+) -> Option<Candidate> {
+    let channel = dialer
+        .endpoint(&data_center.host_name)
+        .ok()?
+        .connect()
+        .await
+        .ok()?;
+    let mut client = dialer.resolver_client(channel);
+    let resources = client
+        .check_resource(Request::new(CheckResourceRequest {}))
+        .await
+        .ok()?
+        .into_inner();
+
+    if !image_id.is_empty() {
+        client
+            .get_image_metadata(Request::new(GetImageMetadataRequest { image_id }))
+            .await
+            .ok()?
+            .into_inner()
+            .image?;
+    }
+
+    Some(Candidate {
+        data_center,
+        available: resources.available_resources?,
+        capacity: resources.total_resources.unwrap_or_default(),
+    })
+}
+
+/// Average share of the candidate's capacity left free once `resources` are placed in it
+fn free_share_after(candidate: &Candidate, resources: &Resources) -> f64 {
+    let dimensions = [
+        (
+            candidate.available.ram_mb,
+            candidate.capacity.ram_mb,
+            resources.ram_mb,
+        ),
+        (
+            candidate.available.disk_mb,
+            candidate.capacity.disk_mb,
+            resources.disk_mb,
+        ),
+        (
+            candidate.available.vcpus,
+            candidate.capacity.vcpus,
+            resources.vcpus,
+        ),
+    ];
+    let shares: Vec<f64> = dimensions
+        .into_iter()
+        .filter(|(_, capacity, _)| *capacity > 0)
+        .map(|(available, capacity, required)| {
+            available.saturating_sub(required) as f64 / capacity as f64
+        })
+        .collect();
+
+    if shares.is_empty() {
+        return 0.0;
+    }
+
+    shares.iter().sum::<f64>() / shares.len() as f64
+}
+
+fn by_id(left: &Candidate, right: &Candidate) -> Ordering {
+    left.data_center
+        .data_center_id
+        .cmp(&right.data_center.data_center_id)
+}
@@ -0,0 +1,151 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use prost::{bytes::Buf, Message};
+
+use crate::protos::resolver::{registry_change::Change, RegistryChange, RegistrySnapshot};
+
+const SNAPSHOT_FILE: &str = "registry.snapshot";
+const LOG_FILE: &str = "registry.log";
+/// Number of changes appended to the log before it is folded into a new snapshot
+const COMPACTION_THRESHOLD: usize = 1024;
+
+/// Durable storage for the registry made of a snapshot and a log of the changes made since the
+/// snapshot was taken
+pub struct RegistryStore {
+    directory: PathBuf,
+    log: File,
+    log_entries: usize,
+}
+
+impl RegistryStore {
+    /// Opens the store in `directory`, returning it along with the registry it holds
+    pub fn open(directory: &Path) -> io::Result<(RegistryStore, RegistrySnapshot)> {
+        fs::create_dir_all(directory)?;
+        let snapshot =
+            read_message::<RegistrySnapshot>(&directory.join(SNAPSHOT_FILE))?.unwrap_or_default();
+        let mut data_centers = snapshot.data_centers;
+        let mut revision = snapshot.revision;
+        let changes = read_log::<RegistryChange>(&directory.join(LOG_FILE))?;
+
+        for change in changes.iter() {
+            revision = revision.max(change.revision);
+
+            match &change.change {
+                Some(Change::Put(data_center)) => {
+                    data_centers
+                        .retain(|existing| existing.data_center_id != data_center.data_center_id);
+                    data_centers.push(data_center.clone());
+                }
+                Some(Change::Remove(data_center_id)) => {
+                    data_centers.retain(|existing| existing.data_center_id != *data_center_id)
+                }
+                None => {}
+            }
+        }
+
+        let mut store = RegistryStore {
+            directory: PathBuf::from(directory),
+            log: open_log(&directory.join(LOG_FILE))?,
+            log_entries: changes.len(),
+        };
+        let snapshot = RegistrySnapshot {
+            data_centers,
+            revision,
+        };
+        store.compact(&snapshot)?;
+
+        Ok((store, snapshot))
+    }
+
+    /// Appends a change that brought the registry to `revision` to the log and syncs it to disk
+    pub fn append(&mut self, change: Change, revision: u64) -> io::Result<()> {
+        let change = RegistryChange {
+            change: Some(change),
+            revision,
+        };
+        self.log
+            .write_all(&change.encode_length_delimited_to_vec())?;
+        self.log.sync_data()?;
+        self.log_entries += 1;
+
+        Ok(())
+    }
+
+    /// Whether the log has grown long enough that it should be folded into a new snapshot
+    pub fn needs_compaction(&self) -> bool {
+        self.log_entries >= COMPACTION_THRESHOLD
+    }
+
+    /// Replaces the snapshot with `snapshot` and truncates the log
+    pub fn compact(&mut self, snapshot: &RegistrySnapshot) -> io::Result<()> {
+        write_atomically(
+            &self.directory.join(SNAPSHOT_FILE),
+            &snapshot.encode_to_vec(),
+        )?;
+
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        self.log_entries = 0;
+
+        Ok(())
+    }
+}
+
+pub(crate) fn open_log(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Replaces the file at `path` with `contents` so readers see either the old or new contents
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut staged_path = path.as_os_str().to_owned();
+    staged_path.push(".tmp");
+    let mut staged = File::create(&staged_path)?;
+    staged.write_all(contents)?;
+    staged.sync_all()?;
+
+    fs::rename(staged_path, path)
+}
+
+/// Reads and decodes the message stored at `path`, returning `None` when there is no file
+pub(crate) fn read_message<M>(path: &Path) -> io::Result<Option<M>>
+where
+    M: Message + Default,
+{
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    M::decode(contents.as_slice())
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Reads every complete length delimited message in the file at `path`. A message cut short by
+/// a crash while it was being written is ignored
+pub(crate) fn read_log<M>(path: &Path) -> io::Result<Vec<M>>
+where
+    M: Message + Default,
+{
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut buffer = contents.as_slice();
+    let mut messages = Vec::new();
+
+    while buffer.has_remaining() {
+        match M::decode_length_delimited(&mut buffer) {
+            Ok(message) => messages.push(message),
+            Err(_) => break,
+        }
+    }
+
+    Ok(messages)
+}
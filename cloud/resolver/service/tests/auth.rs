@@ -0,0 +1,218 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use auth::{token, ApiKeys, AuthInterceptor, Authenticator, BearerToken};
+use data_center_service::{
+    data_center::LocalDataCenter,
+    protos::{
+        data_center::data_center_server::DataCenterServer as LocalDataCenterServer,
+        resolver::RegisterDataCenterRequest,
+    },
+    registration::Registration,
+};
+use identity::IdentityKey;
+use resolver_service::{
+    dialer::Dialer,
+    protos::{
+        data_center::{
+            data_center_client::DataCenterClient, data_center_server::DataCenterServer,
+            CreateFileMetadataRequest,
+        },
+        resolver::{
+            dcns_resolver_client::DcnsResolverClient, dcns_resolver_server::DcnsResolverServer,
+            ListDataCentersRequest,
+        },
+    },
+    proxy::{DataCenterProxy, DATA_CENTER_ID_KEY},
+    resolver::LocalDcnsResolver,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, Server},
+    Code, Request,
+};
+
+const SECRET: &[u8] = b"network secret";
+
+fn auth() -> AuthInterceptor {
+    AuthInterceptor::new(Some(Authenticator::new(
+        Some(SECRET.to_vec()),
+        ApiKeys::default(),
+    )))
+}
+
+fn token_for(subject: &str) -> String {
+    token::issue(SECRET, subject, Duration::from_secs(60))
+}
+
+async fn listen() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+
+    (listener, address)
+}
+
+/// Resolver authenticating its callers, presenting no token of its own to data centers
+async fn start_resolver() -> SocketAddr {
+    let (listener, address) = listen().await;
+    let resolver = LocalDcnsResolver::default().with_dialer(Dialer::default());
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(DataCenterServer::with_interceptor(
+                DataCenterProxy::new(resolver.clone()),
+                auth(),
+            ))
+            .add_service(DcnsResolverServer::with_interceptor(resolver, auth()))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve resolver");
+    });
+
+    address
+}
+
+/// Resolver letting anyone call it, presenting a token of its own to data centers
+async fn start_open_resolver() -> SocketAddr {
+    let (listener, address) = listen().await;
+    let resolver = LocalDcnsResolver::default().with_dialer(Dialer {
+        token: BearerToken::new(&token_for("resolver")).unwrap(),
+        ..Default::default()
+    });
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(DataCenterServer::new(DataCenterProxy::new(
+                resolver.clone(),
+            )))
+            .add_service(DcnsResolverServer::new(resolver))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve resolver");
+    });
+
+    address
+}
+
+async fn start_data_center(
+    resolver: SocketAddr,
+    token: BearerToken,
+) -> Result<Registration, String> {
+    let (listener, address) = listen().await;
+    let data_center =
+        Arc::new(LocalDataCenter::new(String::from("dc-1")).with_admins([String::from("alice")]));
+    let served = data_center.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(InterceptedService::new(
+                LocalDataCenterServer::from_arc(served),
+                auth(),
+            ))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+
+    Registration::start(
+        vec![resolver.to_string()],
+        RegisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            host_name: address.to_string(),
+            ..Default::default()
+        },
+        Duration::from_secs(60),
+        data_center,
+        Arc::new(IdentityKey::generate()),
+        None,
+        token,
+    )
+    .await
+    .map_err(|error| format!("{error:#}"))
+}
+
+async fn channel(address: SocketAddr) -> Channel {
+    Channel::from_shared(format!("http://{address}"))
+        .unwrap()
+        .connect()
+        .await
+        .expect("Should connect")
+}
+
+#[tokio::test]
+async fn calls_without_a_valid_token_are_rejected() {
+    let resolver = start_resolver().await;
+    let mut client = DcnsResolverClient::new(channel(resolver).await);
+
+    let status = client
+        .list_data_centers(Request::new(ListDataCentersRequest {}))
+        .await
+        .expect_err("Should reject call without token");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let forged = token::issue(b"other secret", "mallory", Duration::from_secs(60));
+    let mut client = DcnsResolverClient::with_interceptor(
+        channel(resolver).await,
+        BearerToken::new(&forged).unwrap(),
+    );
+    let status = client
+        .list_data_centers(Request::new(ListDataCentersRequest {}))
+        .await
+        .expect_err("Should reject forged token");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    assert!(start_data_center(resolver, BearerToken::default())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn proxied_calls_carry_the_token_of_the_caller() {
+    let resolver = start_resolver().await;
+    let _registration = start_data_center(resolver, BearerToken::new(&token_for("dc-1")).unwrap())
+        .await
+        .expect("Should register with token");
+    let mut client = DataCenterClient::with_interceptor(
+        channel(resolver).await,
+        BearerToken::new(&token_for("alice")).unwrap(),
+    );
+    let mut request = Request::new(CreateFileMetadataRequest {
+        file_path: String::from("auth-proxied"),
+        file_size: 16,
+        ..Default::default()
+    });
+    request
+        .metadata_mut()
+        .insert(DATA_CENTER_ID_KEY, "dc-1".parse().unwrap());
+
+    // The resolver has no token of its own, so the data center only accepts the call when
+    // the proxy forwards the token of the caller
+    client
+        .create_file_metadata(request)
+        .await
+        .expect("Should create file metadata on behalf of the caller");
+}
+
+#[tokio::test]
+async fn proxied_calls_of_anonymous_callers_stay_anonymous() {
+    let resolver = start_open_resolver().await;
+    let _registration = start_data_center(resolver, BearerToken::new(&token_for("dc-1")).unwrap())
+        .await
+        .expect("Should register with token");
+    let mut client = DataCenterClient::new(channel(resolver).await);
+    let mut request = Request::new(CreateFileMetadataRequest {
+        file_path: String::from("auth-anonymous"),
+        file_size: 16,
+        ..Default::default()
+    });
+    request
+        .metadata_mut()
+        .insert(DATA_CENTER_ID_KEY, "dc-1".parse().unwrap());
+
+    // The token of the resolver is only sent with the calls the resolver makes itself
+    let status = client
+        .create_file_metadata(request)
+        .await
+        .expect_err("Should refuse the call of an anonymous caller");
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
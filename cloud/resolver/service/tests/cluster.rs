@@ -0,0 +1,315 @@
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use auth::SignedToken;
+use resolver_client::{
+    client::ResolverClient,
+    protos::resolver::{ListDataCentersRequest, RegisterDataCenterRequest},
+};
+use resolver_service::{
+    protos::resolver::{
+        self as protos, dcns_resolver_server::DcnsResolverServer, raft_peer_client::RaftPeerClient,
+        registry_command::Command, ProposeRequest, RegistryCommand,
+    },
+    raft::{service::peer_server, RaftConfig, RaftNode, Role},
+    registry::unix_time_ms,
+    resolver::LocalDcnsResolver,
+};
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Code};
+
+const PEER_SECRET: &[u8] = b"cluster secret";
+
+struct ClusterNode {
+    node: Arc<RaftNode>,
+    shutdown: oneshot::Sender<()>,
+    server: JoinHandle<()>,
+}
+
+impl ClusterNode {
+    fn stop(self) {
+        self.node.stop();
+        let _ = self.shutdown.send(());
+        drop(self.server);
+    }
+}
+
+struct Cluster {
+    addresses: BTreeMap<String, SocketAddr>,
+    data_dirs: BTreeMap<String, PathBuf>,
+    nodes: BTreeMap<String, ClusterNode>,
+}
+
+impl Cluster {
+    async fn start(size: usize, data_dir: Option<PathBuf>) -> Cluster {
+        let mut listeners = BTreeMap::new();
+
+        for index in 0..size {
+            let listener = TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("Should bind listener");
+            listeners.insert(format!("node-{index}"), listener);
+        }
+
+        let addresses: BTreeMap<String, SocketAddr> = listeners
+            .iter()
+            .map(|(node_id, listener)| {
+                (
+                    node_id.clone(),
+                    listener.local_addr().expect("Should have address"),
+                )
+            })
+            .collect();
+        let data_dirs = addresses
+            .keys()
+            .filter_map(|node_id| {
+                data_dir
+                    .as_ref()
+                    .map(|data_dir| (node_id.clone(), data_dir.join(node_id)))
+            })
+            .collect();
+        let mut cluster = Cluster {
+            addresses,
+            data_dirs,
+            nodes: BTreeMap::new(),
+        };
+
+        for (node_id, listener) in listeners {
+            cluster.start_node(&node_id, listener);
+        }
+
+        cluster
+    }
+
+    fn start_node(&mut self, node_id: &str, listener: TcpListener) {
+        let peers = self
+            .addresses
+            .iter()
+            .filter(|(peer_id, _)| *peer_id != node_id)
+            .map(|(peer_id, address)| (peer_id.clone(), format!("http://{address}")))
+            .collect();
+        let mut config = RaftConfig::new(String::from(node_id), peers, PEER_SECRET.to_vec());
+        config.election_timeout = Duration::from_millis(150)..Duration::from_millis(300);
+        config.heartbeat_interval = Duration::from_millis(50);
+        config.snapshot_threshold = 8;
+        config.data_dir = self.data_dirs.get(node_id).cloned();
+        let node = RaftNode::start(config).expect("Should start node");
+        let resolver = LocalDcnsResolver::replicated(node.clone());
+        let peer_server = peer_server(node.clone());
+        let (shutdown, shutdown_signal) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            Server::builder()
+                .add_service(DcnsResolverServer::new(resolver))
+                .add_service(peer_server)
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = shutdown_signal.await;
+                })
+                .await
+                .expect("Should serve resolver");
+        });
+        self.nodes.insert(
+            String::from(node_id),
+            ClusterNode {
+                node,
+                shutdown,
+                server,
+            },
+        );
+    }
+
+    async fn restart_node(&mut self, node_id: &str) {
+        let listener = TcpListener::bind(self.addresses[node_id])
+            .await
+            .expect("Should bind listener");
+        self.start_node(node_id, listener);
+    }
+
+    fn stop_node(&mut self, node_id: &str) {
+        if let Some(node) = self.nodes.remove(node_id) {
+            node.stop();
+        }
+    }
+
+    fn endpoints(&self) -> Vec<String> {
+        self.addresses
+            .values()
+            .map(|address| format!("http://{address}"))
+            .collect()
+    }
+
+    async fn wait_for_leader(&self) -> String {
+        for _ in 0..100 {
+            let leader = self
+                .nodes
+                .iter()
+                .find(|(_, node)| node.node.role() == Role::Leader)
+                .map(|(node_id, _)| node_id.clone());
+
+            if let Some(leader) = leader {
+                return leader;
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("No leader was elected");
+    }
+
+    /// Waits until every running node has applied a registry holding exactly `expected`
+    async fn wait_for_registry(&self, expected: &[&str]) {
+        for _ in 0..100 {
+            let converged = self.nodes.values().all(|node| {
+                node.node.read(|registry| {
+                    registry
+                        .list()
+                        .iter()
+                        .map(|data_center| data_center.data_center_id.as_str())
+                        .eq(expected.iter().copied())
+                })
+            });
+
+            if converged {
+                return;
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("Nodes did not converge on {expected:?}");
+    }
+
+    fn stop(self) {
+        for node in self.nodes.into_values() {
+            node.stop();
+        }
+    }
+}
+
+async fn register(client: &mut ResolverClient, id: &str) {
+    let request = RegisterDataCenterRequest {
+        data_center_id: String::from(id),
+        host_name: format!("{id}.local:50052"),
+        ..Default::default()
+    };
+    client
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.register_data_center(request).await }
+        })
+        .await
+        .expect("Should register data center");
+}
+
+async fn list_ids(client: &mut ResolverClient) -> Vec<String> {
+    client
+        .call(|mut client| async move { client.list_data_centers(ListDataCentersRequest {}).await })
+        .await
+        .expect("Should list data centers")
+        .data_center
+        .into_iter()
+        .map(|data_center| data_center.data_center_id)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn registry_survives_losing_the_leader() {
+    let mut cluster = Cluster::start(3, None).await;
+    let mut client = ResolverClient::new(cluster.endpoints());
+    let leader = cluster.wait_for_leader().await;
+    register(&mut client, "dc-a").await;
+    register(&mut client, "dc-b").await;
+    cluster.wait_for_registry(&["dc-a", "dc-b"]).await;
+
+    cluster.stop_node(&leader);
+    register(&mut client, "dc-c").await;
+    cluster.wait_for_registry(&["dc-a", "dc-b", "dc-c"]).await;
+
+    assert_ne!(cluster.wait_for_leader().await, leader);
+    assert_eq!(list_ids(&mut client).await, vec!["dc-a", "dc-b", "dc-c"]);
+    cluster.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_node_catches_up() {
+    let data_dir = tempfile::tempdir().expect("Should create data dir");
+    let mut cluster = Cluster::start(3, Some(data_dir.path().to_path_buf())).await;
+    let mut client = ResolverClient::new(cluster.endpoints());
+    let leader = cluster.wait_for_leader().await;
+    register(&mut client, "dc-0").await;
+    cluster.wait_for_registry(&["dc-0"]).await;
+
+    let follower = cluster
+        .addresses
+        .keys()
+        .find(|node_id| **node_id != leader)
+        .cloned()
+        .expect("Should have a follower");
+    cluster.stop_node(&follower);
+    let mut expected = vec![String::from("dc-0")];
+
+    for index in 1..20 {
+        let id = format!("dc-{index:02}");
+        register(&mut client, &id).await;
+        expected.push(id);
+    }
+
+    expected.sort();
+    let expected: Vec<&str> = expected.iter().map(String::as_str).collect();
+    cluster.restart_node(&follower).await;
+    cluster.wait_for_registry(&expected).await;
+    cluster.stop();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn proposals_need_the_peer_secret_and_a_valid_registration() {
+    let cluster = Cluster::start(3, None).await;
+    let leader = cluster.wait_for_leader().await;
+    let endpoint = format!("http://{}", cluster.addresses[&leader]);
+    let propose = |host_name: &str| ProposeRequest {
+        command: Some(RegistryCommand {
+            issued_at_unix_ms: unix_time_ms(),
+            command: Some(Command::Register(protos::RegisterDataCenterRequest {
+                data_center_id: String::from("dc-a"),
+                host_name: String::from(host_name),
+                ..Default::default()
+            })),
+        }),
+        forwarded: false,
+    };
+
+    let mut unauthenticated = RaftPeerClient::connect(endpoint.clone())
+        .await
+        .expect("Should connect to leader");
+    let status = unauthenticated
+        .propose(propose("dc-a.local:50052"))
+        .await
+        .expect_err("Should reject proposal without token");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let channel = tonic::transport::Endpoint::from_shared(endpoint)
+        .expect("Should parse endpoint")
+        .connect()
+        .await
+        .expect("Should connect to leader");
+    let mut forged =
+        RaftPeerClient::with_interceptor(channel.clone(), SignedToken::new(b"other", "node-x"));
+    let status = forged
+        .propose(propose("dc-a.local:50052"))
+        .await
+        .expect_err("Should reject proposal signed with another secret");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut peer =
+        RaftPeerClient::with_interceptor(channel, SignedToken::new(PEER_SECRET, "node-x"));
+    let status = peer
+        .propose(propose(""))
+        .await
+        .expect_err("Should reject registration without host name when applied");
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    peer.propose(propose("dc-a.local:50052"))
+        .await
+        .expect("Should accept proposal from peer");
+    cluster.wait_for_registry(&["dc-a"]).await;
+    cluster.stop();
+}
@@ -0,0 +1,389 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use auth::BearerToken;
+use data_center_service::{
+    data_center::LocalDataCenter,
+    protos::{
+        data_center::data_center_server::DataCenterServer as LocalDataCenterServer,
+        resolver::RegisterDataCenterRequest as LocalRegisterDataCenterRequest,
+    },
+    registration::Registration,
+};
+use identity::{DeregistrationClaims, HeartbeatClaims, IdentityKey, RegistrationClaims};
+use resolver_client::{
+    client::ResolverClient,
+    verify::{verify_data_center, VerifyError},
+};
+use resolver_service::{
+    protos::{
+        data_center::data_center_server::DataCenterServer,
+        resolver::{
+            dcns_resolver_client::DcnsResolverClient, dcns_resolver_server::DcnsResolverServer,
+            DeregisterDataCenterRequest, GetDataCenterRequest, HeartbeatRequest,
+            RegisterDataCenterRequest,
+        },
+    },
+    proxy::DataCenterProxy,
+    registry::Registry,
+    resolver::LocalDcnsResolver,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    transport::{Channel, Server},
+    Code,
+};
+
+async fn listen() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+
+    (listener, address)
+}
+
+/// Resolver refusing unsigned registrations
+async fn start_resolver() -> SocketAddr {
+    start_resolver_with(Registry::default().with_signed_registrations_required()).await
+}
+
+async fn start_resolver_with(registry: Registry) -> SocketAddr {
+    let (listener, address) = listen().await;
+    serve_resolver(listener, registry);
+
+    address
+}
+
+fn serve_resolver(listener: TcpListener, registry: Registry) {
+    let resolver = LocalDcnsResolver::new(registry);
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(DataCenterServer::new(DataCenterProxy::new(
+                resolver.clone(),
+            )))
+            .add_service(DcnsResolverServer::new(resolver))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve resolver");
+    });
+}
+
+async fn start_data_center(
+    resolver: SocketAddr,
+    identity_key: Arc<IdentityKey>,
+) -> Result<Registration, String> {
+    let (listener, address) = listen().await;
+    let data_center = Arc::new(
+        LocalDataCenter::new(String::from("dc-1")).with_identity_key(identity_key.clone()),
+    );
+    let served = data_center.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(LocalDataCenterServer::from_arc(served))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+
+    Registration::start(
+        vec![resolver.to_string()],
+        LocalRegisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            host_name: address.to_string(),
+            ..Default::default()
+        },
+        Duration::from_secs(60),
+        data_center,
+        identity_key,
+        None,
+        BearerToken::default(),
+    )
+    .await
+    .map_err(|error| format!("{error:#}"))
+}
+
+async fn client(resolver: SocketAddr) -> DcnsResolverClient<Channel> {
+    DcnsResolverClient::connect(format!("http://{resolver}"))
+        .await
+        .expect("Should connect")
+}
+
+/// Registration of `host_name` as `data_center_id` signed with `identity_key`
+fn signed(
+    data_center_id: &str,
+    host_name: &str,
+    identity_key: &IdentityKey,
+) -> RegisterDataCenterRequest {
+    let mut request = RegisterDataCenterRequest {
+        data_center_id: String::from(data_center_id),
+        host_name: String::from(host_name),
+        public_key: identity_key.public_key(),
+        signed_at_unix_ms: identity::unix_time_ms(),
+        ..Default::default()
+    };
+    request.signature = identity_key.sign_registration(&RegistrationClaims {
+        data_center_id: &request.data_center_id,
+        host_name: &request.host_name,
+        public_key: &request.public_key,
+        signed_at_unix_ms: request.signed_at_unix_ms,
+        ..Default::default()
+    });
+
+    request
+}
+
+#[tokio::test]
+async fn registrations_are_signed_and_bound_to_their_key() {
+    let resolver = start_resolver().await;
+    let identity_key = Arc::new(IdentityKey::generate());
+    let _registration = start_data_center(resolver, identity_key.clone())
+        .await
+        .expect("Should register signed");
+    let mut client = client(resolver).await;
+
+    let data_center = client
+        .get_data_center(GetDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+        })
+        .await
+        .expect("Should get data center")
+        .into_inner()
+        .data_center
+        .unwrap();
+    assert_eq!(data_center.public_key, identity_key.public_key());
+
+    let status = client
+        .register_data_center(RegisterDataCenterRequest {
+            data_center_id: String::from("dc-2"),
+            host_name: String::from("unsigned:50052"),
+            ..Default::default()
+        })
+        .await
+        .expect_err("Should refuse unsigned registration");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut tampered = signed("dc-2", "honest:50052", &IdentityKey::generate());
+    tampered.host_name = String::from("attacker:50052");
+    let status = client
+        .register_data_center(tampered)
+        .await
+        .expect_err("Should refuse registration changed after signing");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    // Another key can take over neither the id nor the host of the data center
+    let attacker = IdentityKey::generate();
+    for request in [
+        signed("dc-1", "attacker:50052", &attacker),
+        signed("dc-3", &data_center.host_name, &attacker),
+    ] {
+        let status = client
+            .register_data_center(request)
+            .await
+            .expect_err("Should refuse registration with another key");
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    client
+        .register_data_center(signed("dc-1", &data_center.host_name, &identity_key))
+        .await
+        .expect("Should accept re-registration with the same key");
+}
+
+#[tokio::test]
+async fn unsigned_registrations_cannot_claim_a_key() {
+    let resolver = start_resolver_with(Registry::default()).await;
+    let mut client = client(resolver).await;
+    let identity_key = IdentityKey::generate();
+    client
+        .register_data_center(signed("dc-1", "honest:50052", &identity_key))
+        .await
+        .expect("Should register signed");
+
+    // Public keys are public, so copying one without signing proves nothing
+    for request in [
+        RegisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            host_name: String::from("attacker:50052"),
+            public_key: identity_key.public_key(),
+            ..Default::default()
+        },
+        RegisterDataCenterRequest {
+            data_center_id: String::from("dc-2"),
+            host_name: String::from("attacker:50052"),
+            public_key: identity_key.public_key(),
+            ..Default::default()
+        },
+        RegisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            host_name: String::from("attacker:50052"),
+            ..Default::default()
+        },
+        RegisterDataCenterRequest {
+            data_center_id: String::from("dc-3"),
+            host_name: String::from("honest:50052"),
+            ..Default::default()
+        },
+    ] {
+        let status = client
+            .register_data_center(request)
+            .await
+            .expect_err("Should refuse unsigned registration claiming a key");
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    client
+        .register_data_center(RegisterDataCenterRequest {
+            data_center_id: String::from("dc-4"),
+            host_name: String::from("unsigned:50052"),
+            ..Default::default()
+        })
+        .await
+        .expect("Should accept unsigned registration when signatures aren't required");
+}
+
+#[tokio::test]
+async fn data_centers_register_once_a_resolver_comes_up() {
+    let (listener, resolver) = listen().await;
+    drop(listener);
+    let registration = start_data_center(resolver, Arc::new(IdentityKey::generate()))
+        .await
+        .expect("Should start without a reachable resolver");
+    assert!(!registration.reachability().is_reachable());
+
+    serve_resolver(
+        TcpListener::bind(resolver)
+            .await
+            .expect("Should bind resolver"),
+        Registry::default().with_signed_registrations_required(),
+    );
+    let mut client = client(resolver).await;
+    let registered = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let response = client
+                .get_data_center(GetDataCenterRequest {
+                    data_center_id: String::from("dc-1"),
+                })
+                .await;
+
+            if response.is_ok() {
+                return;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+
+    assert!(registered.is_ok(), "Should register in the background");
+    assert!(registration.reachability().is_reachable());
+}
+
+#[tokio::test]
+async fn only_the_bound_key_deregisters_a_data_center() {
+    let resolver = start_resolver().await;
+    let identity_key = Arc::new(IdentityKey::generate());
+    let registration = start_data_center(resolver, identity_key)
+        .await
+        .expect("Should register signed");
+    let mut client = client(resolver).await;
+
+    let status = client
+        .deregister_data_center(DeregisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            ..Default::default()
+        })
+        .await
+        .expect_err("Should refuse unsigned deregistration");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let attacker = IdentityKey::generate();
+    let claims = DeregistrationClaims {
+        data_center_id: "dc-1",
+        signed_at_unix_ms: identity::unix_time_ms(),
+    };
+    let status = client
+        .deregister_data_center(DeregisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            signature: attacker.sign_deregistration(&claims),
+            signed_at_unix_ms: claims.signed_at_unix_ms,
+        })
+        .await
+        .expect_err("Should refuse deregistration signed with another key");
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    registration.stop().await.expect("Should deregister");
+    let status = client
+        .get_data_center(GetDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+        })
+        .await
+        .expect_err("Should be deregistered");
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn only_the_bound_key_keeps_a_data_center_alive() {
+    let resolver = start_resolver().await;
+    let identity_key = Arc::new(IdentityKey::generate());
+    let _registration = start_data_center(resolver, identity_key.clone())
+        .await
+        .expect("Should register signed");
+    let mut client = client(resolver).await;
+    let heartbeat = |identity_key: &IdentityKey| {
+        let claims = HeartbeatClaims {
+            data_center_id: "dc-1",
+            signed_at_unix_ms: identity::unix_time_ms(),
+        };
+
+        HeartbeatRequest {
+            data_center_id: String::from("dc-1"),
+            signature: identity_key.sign_heartbeat(&claims),
+            signed_at_unix_ms: claims.signed_at_unix_ms,
+            ..Default::default()
+        }
+    };
+
+    for request in [
+        HeartbeatRequest {
+            data_center_id: String::from("dc-1"),
+            ..Default::default()
+        },
+        heartbeat(&IdentityKey::generate()),
+    ] {
+        let status = client
+            .heartbeat(request)
+            .await
+            .expect_err("Should refuse heartbeat not signed with the bound key");
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    client
+        .heartbeat(heartbeat(&identity_key))
+        .await
+        .expect("Should accept heartbeat signed with the bound key");
+}
+
+#[tokio::test]
+async fn clients_verify_the_key_of_a_data_center() {
+    let resolver = start_resolver().await;
+    let identity_key = Arc::new(IdentityKey::generate());
+    let _registration = start_data_center(resolver, identity_key.clone())
+        .await
+        .expect("Should register signed");
+    let mut client = ResolverClient::new(vec![format!("http://{resolver}")]);
+
+    let data_center = verify_data_center(&mut client, "dc-1", Some(&identity_key.fingerprint()))
+        .await
+        .expect("Should verify pinned key");
+    assert_eq!(data_center.data_center_id, "dc-1");
+
+    let error = verify_data_center(
+        &mut client,
+        "dc-1",
+        Some(&IdentityKey::generate().fingerprint()),
+    )
+    .await
+    .expect_err("Should refuse another pinned key");
+    assert!(matches!(error, VerifyError::NotPinned { .. }));
+}
@@ -0,0 +1,122 @@
+use std::net::SocketAddr;
+
+use resolver_service::{
+    dialer::Dialer,
+    membership::MembershipView,
+    protos::{
+        membership::{
+            membership_server::{Membership, MembershipServer},
+            IndirectPingRequest, IndirectPingResponse, JoinRequest, JoinResponse,
+            ListMembersRequest, ListMembersResponse, Member, MemberState, PingRequest,
+            PingResponse,
+        },
+        resolver::{dcns_resolver_server::DcnsResolver, DataCenter, DataCenterHealth},
+    },
+    resolver::LocalDcnsResolver,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+/// Member of a gossip group that only answers membership listings
+struct FixedMembership {
+    members: Vec<Member>,
+}
+
+#[tonic::async_trait]
+impl Membership for FixedMembership {
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        Err(Status::unimplemented("Fixed membership doesn't gossip"))
+    }
+
+    async fn indirect_ping(
+        &self,
+        _request: Request<IndirectPingRequest>,
+    ) -> Result<Response<IndirectPingResponse>, Status> {
+        Err(Status::unimplemented("Fixed membership doesn't gossip"))
+    }
+
+    async fn join(&self, _request: Request<JoinRequest>) -> Result<Response<JoinResponse>, Status> {
+        Err(Status::unimplemented("Fixed membership doesn't gossip"))
+    }
+
+    async fn list_members(
+        &self,
+        _request: Request<ListMembersRequest>,
+    ) -> Result<Response<ListMembersResponse>, Status> {
+        Ok(Response::new(ListMembersResponse {
+            members: self.members.clone(),
+        }))
+    }
+}
+
+fn member(id: &str, state: MemberState) -> Member {
+    Member {
+        data_center: Some(DataCenter {
+            data_center_id: String::from(id),
+            host_name: format!("{id}.local:50052"),
+            ..Default::default()
+        }),
+        state: state as i32,
+        incarnation: 1,
+    }
+}
+
+async fn serve_membership(members: Vec<Member>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(MembershipServer::new(FixedMembership { members }))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve membership");
+    });
+
+    address
+}
+
+#[tokio::test]
+async fn lists_data_centers_from_membership() {
+    let address = serve_membership(vec![
+        member("dc-c", MemberState::Dead),
+        member("dc-b", MemberState::Suspect),
+        member("dc-a", MemberState::Alive),
+        member("dc-d", MemberState::Left),
+    ])
+    .await;
+    let unreachable = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener")
+        .local_addr()
+        .expect("Should have address");
+    let view = MembershipView::new(
+        vec![format!("http://{unreachable}"), format!("http://{address}")],
+        &Dialer::default(),
+    )
+    .expect("Should create view");
+    let resolver = LocalDcnsResolver::default().with_membership(view);
+
+    let data_centers: Vec<(String, DataCenterHealth)> = resolver
+        .list_data_centers(Request::new(Default::default()))
+        .await
+        .expect("Should list data centers")
+        .into_inner()
+        .data_center
+        .into_iter()
+        .map(|data_center| {
+            let health = data_center.health();
+            (data_center.data_center_id, health)
+        })
+        .collect();
+
+    assert_eq!(
+        data_centers,
+        vec![
+            (String::from("dc-a"), DataCenterHealth::Healthy),
+            (String::from("dc-b"), DataCenterHealth::Unhealthy),
+        ]
+    );
+}
@@ -0,0 +1,329 @@
+use std::{net::SocketAddr, path::Path, sync::Arc};
+
+use data_center_service::{
+    data_center::{LocalDataCenter, FILES_DIRECTORY},
+    protos::data_center::data_center_server::DataCenterServer as LocalDataCenterServer,
+};
+use resolver_service::{
+    protos::{
+        data_center::{
+            data_center_client::DataCenterClient, data_center_server::DataCenterServer, Chunk,
+            CreateFileMetadataRequest, CreateImageMetadataRequest, CreateMachineRequest,
+            DownloadFileRequest, GetFileMetadataRequest, GetImageMetadataRequest,
+            ListMachinesRequest, Resources, UploadFileRequest,
+        },
+        resolver::{dcns_resolver_server::DcnsResolver, RegisterDataCenterRequest},
+    },
+    proxy::{DataCenterProxy, DATA_CENTER_ID_KEY},
+    resolver::LocalDcnsResolver,
+};
+use resource_name::{ResourceKind, ResourceName};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    transport::{Channel, Server},
+    Code, Request,
+};
+
+async fn listen() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+
+    (listener, address)
+}
+
+async fn start_data_center(
+    resolver: &LocalDcnsResolver,
+    data_center_id: &str,
+    storage_root: &Path,
+) -> SocketAddr {
+    let (listener, address) = listen().await;
+    let data_center = Arc::new(
+        LocalDataCenter::new(String::from(data_center_id))
+            .with_storage_root(storage_root.to_path_buf()),
+    );
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(LocalDataCenterServer::from_arc(data_center))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+    resolver
+        .register_data_center(Request::new(RegisterDataCenterRequest {
+            data_center_id: String::from(data_center_id),
+            host_name: address.to_string(),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should register data center");
+
+    address
+}
+
+async fn start_proxy(resolver: &LocalDcnsResolver) -> DataCenterClient<Channel> {
+    let (listener, address) = listen().await;
+    let proxy = DataCenterProxy::new(resolver.clone());
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(DataCenterServer::new(proxy))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve proxy");
+    });
+
+    DataCenterClient::connect(format!("http://{address}"))
+        .await
+        .expect("Should connect to proxy")
+}
+
+fn to_data_center<T>(message: T, data_center_id: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(
+        DATA_CENTER_ID_KEY,
+        data_center_id.parse().expect("Should be valid metadata"),
+    );
+
+    request
+}
+
+fn upload_requests(file_path: &str, contents: &[u8]) -> Vec<UploadFileRequest> {
+    contents
+        .chunks(4096)
+        .enumerate()
+        .map(|(index, data)| UploadFileRequest {
+            file_path: String::from(file_path),
+            chunk: Some(Chunk {
+                start: (index * 4096) as u64,
+                end: (index * 4096 + data.len()) as u64,
+                data: data.to_vec(),
+            }),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn files_round_trip_through_the_proxy() {
+    let storage = tempfile::tempdir().expect("Should create storage dir");
+    let resolver = LocalDcnsResolver::default();
+    start_data_center(&resolver, "dc-a", storage.path()).await;
+    let data_center_b = start_data_center(&resolver, "dc-b", storage.path()).await;
+    let mut proxy = start_proxy(&resolver).await;
+    let file_path = String::from("file.bin");
+    let contents: Vec<u8> = (0..10_000).map(|index| (index % 251) as u8).collect();
+
+    proxy
+        .create_file_metadata(to_data_center(
+            CreateFileMetadataRequest {
+                file_path: file_path.clone(),
+                file_size: contents.len() as u64,
+                ..Default::default()
+            },
+            "dc-b",
+        ))
+        .await
+        .expect("Should create file metadata");
+    proxy
+        .upload_file(tokio_stream::iter(upload_requests(&file_path, &contents)))
+        .await
+        .expect("Should upload file");
+
+    let mut data_center_b = DataCenterClient::connect(format!("http://{data_center_b}"))
+        .await
+        .expect("Should connect to data center");
+    let stored = data_center_b
+        .get_file_metadata(GetFileMetadataRequest {
+            file_path: file_path.clone(),
+        })
+        .await
+        .expect("Should get file metadata")
+        .into_inner()
+        .metadata;
+    assert!(stored.is_some());
+
+    let mut stream = proxy
+        .download_file(DownloadFileRequest {
+            source_path: file_path.clone(),
+            ..Default::default()
+        })
+        .await
+        .expect("Should download file")
+        .into_inner();
+    let mut downloaded = vec![0; contents.len()];
+
+    while let Some(message) = stream.message().await.expect("Should receive chunk") {
+        let chunk = message.chunk.expect("Should have chunk");
+        downloaded[chunk.start as usize..chunk.end as usize].copy_from_slice(&chunk.data);
+    }
+
+    assert_eq!(downloaded, contents);
+}
+
+#[tokio::test]
+async fn machines_are_created_where_their_image_lives() {
+    let storage = tempfile::tempdir().expect("Should create storage dir");
+    let resolver = LocalDcnsResolver::default();
+    let data_center_a = start_data_center(&resolver, "dc-a", storage.path()).await;
+    start_data_center(&resolver, "dc-b", storage.path()).await;
+    let mut proxy = start_proxy(&resolver).await;
+    let image = proxy
+        .create_image_metadata(to_data_center(
+            CreateImageMetadataRequest {
+                file_size: 1024,
+                destination_file_path: String::from("image.qcow2"),
+                ..Default::default()
+            },
+            "dc-b",
+        ))
+        .await
+        .expect("Should create image")
+        .into_inner()
+        .os_image_metadata
+        .expect("Should have image");
+
+    // A proxy that hasn't seen the image finds the data center holding it from its name
+    let mut fresh_proxy = start_proxy(&resolver).await;
+    let found = fresh_proxy
+        .get_image_metadata(GetImageMetadataRequest {
+            image_id: image.image_id.clone(),
+        })
+        .await
+        .expect("Should get image")
+        .into_inner()
+        .image;
+    assert_eq!(found, Some(image.clone()));
+
+    let machine = fresh_proxy
+        .create_machine(CreateMachineRequest {
+            image_id: image.image_id.clone(),
+            resources: Some(Resources {
+                ram_mb: 256,
+                disk_mb: 256,
+                vcpus: 1,
+            }),
+            ..Default::default()
+        })
+        .await
+        .expect("Should create machine")
+        .into_inner()
+        .machine
+        .expect("Should have machine");
+
+    let listed = proxy
+        .list_machines(ListMachinesRequest::default())
+        .await
+        .expect("Should list machines")
+        .into_inner()
+        .machine;
+    assert_eq!(listed, vec![machine]);
+
+    let mut data_center_a = DataCenterClient::connect(format!("http://{data_center_a}"))
+        .await
+        .expect("Should connect to data center");
+    let on_a = data_center_a
+        .list_machines(ListMachinesRequest::default())
+        .await
+        .expect("Should list machines")
+        .into_inner()
+        .machine;
+    assert!(on_a.is_empty());
+}
+
+#[tokio::test]
+async fn files_are_placed_without_a_requested_data_center() {
+    let storage = tempfile::tempdir().expect("Should create storage dir");
+    let resolver = LocalDcnsResolver::default();
+    start_data_center(&resolver, "dc-a", storage.path()).await;
+    let mut proxy = start_proxy(&resolver).await;
+    let file_path = String::from("placed.bin");
+
+    proxy
+        .create_file_metadata(CreateFileMetadataRequest {
+            file_path: file_path.clone(),
+            file_size: 16,
+            ..Default::default()
+        })
+        .await
+        .expect("Should place file");
+    proxy
+        .upload_file(tokio_stream::iter(upload_requests(&file_path, &[7; 16])))
+        .await
+        .expect("Should upload file");
+
+    assert_eq!(
+        std::fs::read(storage.path().join(FILES_DIRECTORY).join(&file_path))
+            .expect("Should read uploaded file"),
+        vec![7; 16]
+    );
+}
+
+#[tokio::test]
+async fn resources_are_named_after_their_data_center() {
+    let storage = tempfile::tempdir().expect("Should create storage dir");
+    let resolver = LocalDcnsResolver::default();
+    let data_center_a = start_data_center(&resolver, "dc-a", storage.path()).await;
+    start_data_center(&resolver, "dc-b", storage.path()).await;
+    let mut proxy = start_proxy(&resolver).await;
+    let image = proxy
+        .create_image_metadata(to_data_center(
+            CreateImageMetadataRequest {
+                file_size: 1024,
+                destination_file_path: String::from("image.qcow2"),
+                ..Default::default()
+            },
+            "dc-a",
+        ))
+        .await
+        .expect("Should create image")
+        .into_inner()
+        .os_image_metadata
+        .expect("Should have image");
+    let name: ResourceName = image.image_id.parse().expect("Should be a resource name");
+    assert_eq!(name.data_center_id(), "dc-a");
+    assert_eq!(name.kind(), ResourceKind::Image);
+
+    // The data center holding the image accepts both its name and its bare id
+    let mut data_center_a = DataCenterClient::connect(format!("http://{data_center_a}"))
+        .await
+        .expect("Should connect to data center");
+    let by_id = data_center_a
+        .get_image_metadata(GetImageMetadataRequest {
+            image_id: String::from(name.id()),
+        })
+        .await
+        .expect("Should get image")
+        .into_inner()
+        .image;
+    assert_eq!(by_id, Some(image.clone()));
+
+    // The name wins over a header asking for another data center
+    let routed = proxy
+        .get_image_metadata(to_data_center(
+            GetImageMetadataRequest {
+                image_id: image.image_id.clone(),
+            },
+            "dc-b",
+        ))
+        .await
+        .expect("Should get image")
+        .into_inner()
+        .image;
+    assert_eq!(routed, Some(image));
+
+    // Names are checked before routing, whichever data center they name
+    for image_id in [
+        "dc/dc-a/machines/not-an-image",
+        "dc/dc-z/machines/not-an-image",
+        "not/a-name",
+    ] {
+        let status = proxy
+            .get_image_metadata(GetImageMetadataRequest {
+                image_id: String::from(image_id),
+            })
+            .await
+            .expect_err("Should reject the name");
+        assert_eq!(status.code(), Code::InvalidArgument, "{image_id}");
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use identity::{IdentityKey, RegistrationClaims};
+use resolver_service::{
+    protos::resolver::{
+        registry_command::Command, DataCenterHealth, RegisterDataCenterRequest, RegistryCommand,
+    },
+    registry::{RegistrationRejection, Registry, RegistryError},
+};
+
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+const EVICTION_TTL: Duration = Duration::from_secs(60);
+
+fn request(id: &str, host_name: &str) -> RegisterDataCenterRequest {
+    RegisterDataCenterRequest {
+        data_center_id: String::from(id),
+        host_name: String::from(host_name),
+        ..Default::default()
+    }
+}
+
+fn signed(id: &str, host_name: &str, identity_key: &IdentityKey) -> RegistryCommand {
+    let mut request = RegisterDataCenterRequest {
+        public_key: identity_key.public_key(),
+        signed_at_unix_ms: 1_000,
+        ..request(id, host_name)
+    };
+    request.signature = identity_key.sign_registration(&RegistrationClaims {
+        data_center_id: &request.data_center_id,
+        host_name: &request.host_name,
+        public_key: &request.public_key,
+        signed_at_unix_ms: request.signed_at_unix_ms,
+        ..Default::default()
+    });
+
+    RegistryCommand {
+        issued_at_unix_ms: 1_000,
+        command: Some(Command::Register(request)),
+    }
+}
+
+fn ids(registry: &Registry) -> Vec<String> {
+    registry
+        .list()
+        .into_iter()
+        .map(|data_center| data_center.data_center_id)
+        .collect()
+}
+
+#[test]
+fn reregistering_an_id_updates_its_record() {
+    let mut registry = Registry::default();
+    registry
+        .register(request("dc-1", "http://10.0.0.1:8080"), 1_000)
+        .expect("Should register");
+    registry
+        .register(request("dc-1", "http://10.0.0.2:8080"), 2_000)
+        .expect("Should register again");
+
+    assert_eq!(ids(&registry), vec!["dc-1"]);
+    let data_center = registry.get("dc-1").expect("Should be registered");
+    assert_eq!(data_center.host_name, "http://10.0.0.2:8080");
+    assert_eq!(data_center.last_seen_unix_ms, 2_000);
+}
+
+#[test]
+fn registering_a_host_under_a_new_id_replaces_its_record() {
+    let mut registry = Registry::default();
+    registry
+        .register(request("dc-1", "http://10.0.0.1:8080"), 1_000)
+        .expect("Should register");
+    registry
+        .register(request("dc-2", "http://10.0.0.1:8080"), 2_000)
+        .expect("Should register again");
+
+    assert_eq!(ids(&registry), vec!["dc-2"]);
+}
+
+#[test]
+fn registering_without_an_id_reuses_the_id_of_the_host() {
+    let mut registry = Registry::default();
+    registry
+        .register(request("dc-1", "http://10.0.0.1:8080"), 1_000)
+        .expect("Should register");
+    let data_center = registry
+        .register(request("", "http://10.0.0.1:8080"), 2_000)
+        .expect("Should register again");
+
+    assert_eq!(data_center.data_center_id, "dc-1");
+    assert_eq!(ids(&registry), vec!["dc-1"]);
+}
+
+#[test]
+fn silent_data_centers_turn_unhealthy_then_get_evicted() {
+    let mut registry = Registry::default();
+    registry
+        .register(request("dc-1", "http://10.0.0.1:8080"), 0)
+        .expect("Should register");
+    registry
+        .register(request("dc-2", "http://10.0.0.2:8080"), 0)
+        .expect("Should register");
+
+    registry
+        .sweep(10_000, HEARTBEAT_TIMEOUT, EVICTION_TTL)
+        .expect("Should sweep");
+    let health =
+        |registry: &Registry, id: &str| registry.get(id).expect("Should be registered").health();
+    assert_eq!(health(&registry, "dc-1"), DataCenterHealth::Healthy);
+
+    registry
+        .heartbeat("dc-2", None, 10_000)
+        .expect("Should record heartbeat")
+        .expect("Should be registered");
+    registry
+        .sweep(20_000, HEARTBEAT_TIMEOUT, EVICTION_TTL)
+        .expect("Should sweep");
+    assert_eq!(health(&registry, "dc-1"), DataCenterHealth::Unhealthy);
+    assert_eq!(health(&registry, "dc-2"), DataCenterHealth::Healthy);
+
+    registry
+        .sweep(60_000, HEARTBEAT_TIMEOUT, EVICTION_TTL)
+        .expect("Should sweep");
+    assert_eq!(ids(&registry), vec!["dc-2"]);
+    assert!(registry
+        .heartbeat("dc-1", None, 60_000)
+        .expect("Should record heartbeat")
+        .is_none());
+}
+
+#[test]
+fn heartbeats_bring_unhealthy_data_centers_back() {
+    let mut registry = Registry::default();
+    registry
+        .register(request("dc-1", "http://10.0.0.1:8080"), 0)
+        .expect("Should register");
+    registry
+        .sweep(20_000, HEARTBEAT_TIMEOUT, EVICTION_TTL)
+        .expect("Should sweep");
+
+    let data_center = registry
+        .heartbeat("dc-1", None, 25_000)
+        .expect("Should record heartbeat")
+        .expect("Should be registered");
+
+    assert_eq!(data_center.health(), DataCenterHealth::Healthy);
+    assert_eq!(data_center.last_seen_unix_ms, 25_000);
+}
+
+#[test]
+fn registrations_applied_after_a_key_was_bound_are_rejected() {
+    let mut registry = Registry::default();
+    let first = IdentityKey::generate();
+    let second = IdentityKey::generate();
+    // Both passed any check made where they were received, only the first applied may bind
+    let applied = [
+        signed("dc-1", "http://10.0.0.1:8080", &first),
+        signed("dc-1", "http://10.0.0.2:8080", &second),
+    ]
+    .map(|command| registry.apply(command));
+
+    assert!(applied[0].is_ok());
+    assert!(matches!(
+        applied[1],
+        Err(RegistryError::Rejected(
+            RegistrationRejection::KeyBound { .. }
+        ))
+    ));
+    let data_center = registry.get("dc-1").expect("Should be registered");
+    assert_eq!(data_center.public_key, first.public_key());
+    assert_eq!(data_center.host_name, "http://10.0.0.1:8080");
+}
@@ -0,0 +1,152 @@
+use std::{net::SocketAddr, path::Path};
+
+use resolver_service::{
+    protos::resolver::{
+        dcns_resolver_client::DcnsResolverClient, dcns_resolver_server::DcnsResolverServer,
+        DeregisterDataCenterRequest, ListDataCentersRequest, RegisterDataCenterRequest,
+    },
+    registry::{unix_time_ms, Registry},
+    resolver::LocalDcnsResolver,
+};
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    transport::{Channel, Server},
+    Request,
+};
+
+struct RunningResolver {
+    client: DcnsResolverClient<Channel>,
+    shutdown: oneshot::Sender<()>,
+    server: JoinHandle<()>,
+}
+
+impl RunningResolver {
+    async fn start(data_dir: &Path) -> RunningResolver {
+        let registry = Registry::open(data_dir, unix_time_ms()).expect("Should open registry");
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Should bind listener");
+        let address: SocketAddr = listener.local_addr().expect("Should have address");
+        let (shutdown, signal) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            Server::builder()
+                .add_service(DcnsResolverServer::new(LocalDcnsResolver::new(registry)))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    signal.await.ok();
+                })
+                .await
+                .expect("Should serve resolver");
+        });
+        let client = DcnsResolverClient::connect(format!("http://{address}"))
+            .await
+            .expect("Should connect to resolver");
+
+        RunningResolver {
+            client,
+            shutdown,
+            server,
+        }
+    }
+
+    async fn stop(self) {
+        drop(self.client);
+        self.shutdown.send(()).expect("Should signal shutdown");
+        self.server.await.expect("Should stop server");
+    }
+}
+
+async fn register(client: &mut DcnsResolverClient<Channel>, id: &str, host_name: &str) {
+    client
+        .register_data_center(Request::new(RegisterDataCenterRequest {
+            data_center_id: String::from(id),
+            host_name: String::from(host_name),
+            region: String::from("local"),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should register data center");
+}
+
+async fn list_ids(client: &mut DcnsResolverClient<Channel>) -> Vec<String> {
+    client
+        .list_data_centers(Request::new(ListDataCentersRequest {}))
+        .await
+        .expect("Should list data centers")
+        .into_inner()
+        .data_center
+        .into_iter()
+        .map(|data_center| data_center.data_center_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn registry_survives_restart() {
+    let data_dir = tempfile::tempdir().expect("Should create data dir");
+    let mut resolver = RunningResolver::start(data_dir.path()).await;
+    register(&mut resolver.client, "dc-a", "a.local:50052").await;
+    register(&mut resolver.client, "dc-b", "b.local:50052").await;
+    register(&mut resolver.client, "dc-c", "c.local:50052").await;
+    resolver
+        .client
+        .deregister_data_center(Request::new(DeregisterDataCenterRequest {
+            data_center_id: String::from("dc-b"),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should deregister data center");
+    resolver.stop().await;
+
+    let mut resolver = RunningResolver::start(data_dir.path()).await;
+    assert_eq!(list_ids(&mut resolver.client).await, vec!["dc-a", "dc-c"]);
+    let data_centers = resolver
+        .client
+        .list_data_centers(Request::new(ListDataCentersRequest {}))
+        .await
+        .expect("Should list data centers")
+        .into_inner()
+        .data_center;
+    assert!(data_centers
+        .iter()
+        .all(|data_center| data_center.region == "local"));
+    resolver.stop().await;
+}
+
+#[tokio::test]
+async fn registry_survives_restart_after_compaction() {
+    let data_dir = tempfile::tempdir().expect("Should create data dir");
+    let mut resolver = RunningResolver::start(data_dir.path()).await;
+
+    for generation in 0..1100 {
+        register(
+            &mut resolver.client,
+            &format!("dc-{}", generation % 3),
+            &format!("{}.local:50052", generation % 3),
+        )
+        .await;
+    }
+    resolver.stop().await;
+
+    let mut resolver = RunningResolver::start(data_dir.path()).await;
+    assert_eq!(
+        list_ids(&mut resolver.client).await,
+        vec!["dc-0", "dc-1", "dc-2"]
+    );
+    resolver.stop().await;
+}
+
+#[tokio::test]
+async fn registry_revision_survives_restart() {
+    let data_dir = tempfile::tempdir().expect("Should create data dir");
+    let mut resolver = RunningResolver::start(data_dir.path()).await;
+    register(&mut resolver.client, "dc-a", "a.local:50052").await;
+    register(&mut resolver.client, "dc-b", "b.local:50052").await;
+    resolver.stop().await;
+
+    let registry = Registry::open(data_dir.path(), unix_time_ms()).expect("Should open registry");
+    let (initial, _) = registry.watch(0).expect("Should watch from the start");
+    assert_eq!(initial[0].revision, 2);
+    assert!(registry.watch(2).is_some());
+    // Changes made before the restart are no longer retained
+    assert!(registry.watch(1).is_none());
+}
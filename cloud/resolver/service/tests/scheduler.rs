@@ -0,0 +1,210 @@
+use std::collections::BTreeSet;
+
+use resolver_service::{
+    protos::{
+        data_center::{Resources, ServiceType},
+        resolver::{
+            DataCenter, DataCenterCapabilities, DataCenterHealth, PlacementConstraints,
+            PlacementStrategy,
+        },
+    },
+    scheduler::{self, BinPack, Candidate, Random, Spread},
+};
+
+fn resources(ram_mb: u32, disk_mb: u32, vcpus: u32) -> Resources {
+    Resources {
+        ram_mb,
+        disk_mb,
+        vcpus,
+    }
+}
+
+/// Data center with 8 gb of ram, 100 gb of disk and 8 vcpus of which `used_share` is in use
+fn candidate(id: &str, used_share: f64) -> Candidate {
+    let capacity = resources(8192, 102_400, 8);
+    let available = resources(
+        (capacity.ram_mb as f64 * (1.0 - used_share)) as u32,
+        (capacity.disk_mb as f64 * (1.0 - used_share)) as u32,
+        (capacity.vcpus as f64 * (1.0 - used_share)) as u32,
+    );
+
+    Candidate {
+        data_center: DataCenter {
+            data_center_id: String::from(id),
+            host_name: format!("{id}.local:50052"),
+            region: String::from("eu"),
+            capabilities: Some(DataCenterCapabilities {
+                services: vec![ServiceType::Compute as i32],
+                architectures: vec![String::from("x86_64")],
+            }),
+            ..Default::default()
+        },
+        available,
+        capacity,
+    }
+}
+
+fn fleet() -> Vec<Candidate> {
+    vec![
+        candidate("dc-empty", 0.0),
+        candidate("dc-half", 0.5),
+        candidate("dc-busy", 0.75),
+        candidate("dc-full", 1.0),
+    ]
+}
+
+fn placed_id(
+    candidates: &[Candidate],
+    resources: &Resources,
+    strategy: &dyn scheduler::Strategy,
+) -> Option<String> {
+    scheduler::place(candidates, resources, strategy)
+        .map(|candidate| candidate.data_center.data_center_id)
+}
+
+#[test]
+fn bin_pack_fills_the_fullest_data_center_with_room() {
+    let small = resources(1024, 10_240, 1);
+    let large = resources(4096, 40_960, 4);
+
+    assert_eq!(
+        placed_id(&fleet(), &small, &BinPack).as_deref(),
+        Some("dc-busy")
+    );
+    assert_eq!(
+        placed_id(&fleet(), &large, &BinPack).as_deref(),
+        Some("dc-half")
+    );
+}
+
+#[test]
+fn spread_picks_the_emptiest_data_center() {
+    let small = resources(1024, 10_240, 1);
+    let mut candidates = fleet();
+
+    assert_eq!(
+        placed_id(&candidates, &small, &Spread).as_deref(),
+        Some("dc-empty")
+    );
+
+    candidates.retain(|candidate| candidate.data_center.data_center_id != "dc-empty");
+
+    assert_eq!(
+        placed_id(&candidates, &small, &Spread).as_deref(),
+        Some("dc-half")
+    );
+}
+
+#[test]
+fn spread_fills_a_fleet_evenly() {
+    let mut candidates: Vec<Candidate> = (0..4)
+        .map(|index| candidate(&format!("dc-{index}"), 0.0))
+        .collect();
+    let machine = resources(1024, 10_240, 1);
+
+    for _ in 0..8 {
+        let placed = scheduler::place(&candidates, &machine, &Spread)
+            .expect("Should place machine")
+            .data_center
+            .data_center_id;
+        let candidate = candidates
+            .iter_mut()
+            .find(|candidate| candidate.data_center.data_center_id == placed)
+            .expect("Should find placed data center");
+        candidate.available.ram_mb -= machine.ram_mb;
+        candidate.available.disk_mb -= machine.disk_mb;
+        candidate.available.vcpus -= machine.vcpus;
+    }
+
+    for candidate in candidates {
+        assert_eq!(candidate.available.vcpus, 6);
+    }
+}
+
+#[test]
+fn random_only_picks_data_centers_with_room() {
+    let machine = resources(4096, 40_960, 4);
+    let placed: BTreeSet<String> = (0..200)
+        .filter_map(|_| placed_id(&fleet(), &machine, &Random))
+        .collect();
+
+    assert_eq!(
+        placed,
+        BTreeSet::from([String::from("dc-empty"), String::from("dc-half")])
+    );
+}
+
+#[test]
+fn nothing_is_placed_when_no_data_center_has_room() {
+    let machine = resources(16_384, 10_240, 1);
+
+    for strategy in [
+        PlacementStrategy::BinPack,
+        PlacementStrategy::Spread,
+        PlacementStrategy::Random,
+    ] {
+        assert!(placed_id(&fleet(), &machine, scheduler::strategy(strategy).as_ref()).is_none());
+    }
+}
+
+#[test]
+fn constraints_filter_data_centers() {
+    let mut data_center = candidate("dc-a", 0.0).data_center;
+    data_center
+        .labels
+        .insert(String::from("tier"), String::from("gpu"));
+
+    assert!(scheduler::satisfies(
+        &data_center,
+        &PlacementConstraints::default()
+    ));
+    assert!(scheduler::satisfies(
+        &data_center,
+        &PlacementConstraints {
+            region: String::from("eu"),
+            labels: [(String::from("tier"), String::from("gpu"))].into(),
+            ..Default::default()
+        }
+    ));
+    assert!(!scheduler::satisfies(
+        &data_center,
+        &PlacementConstraints {
+            region: String::from("us"),
+            ..Default::default()
+        }
+    ));
+    assert!(!scheduler::satisfies(
+        &data_center,
+        &PlacementConstraints {
+            labels: [(String::from("tier"), String::from("cpu"))].into(),
+            ..Default::default()
+        }
+    ));
+    assert!(!scheduler::satisfies(
+        &data_center,
+        &PlacementConstraints {
+            anti_affinity: vec![String::from("dc-a")],
+            ..Default::default()
+        }
+    ));
+}
+
+#[test]
+fn unhealthy_and_storage_only_data_centers_are_excluded() {
+    let mut unhealthy = candidate("dc-a", 0.0).data_center;
+    unhealthy.set_health(DataCenterHealth::Unhealthy);
+    let mut storage_only = candidate("dc-b", 0.0).data_center;
+    storage_only.capabilities = Some(DataCenterCapabilities {
+        services: vec![ServiceType::Storage as i32],
+        architectures: Vec::new(),
+    });
+
+    assert!(!scheduler::satisfies(
+        &unhealthy,
+        &PlacementConstraints::default()
+    ));
+    assert!(!scheduler::satisfies(
+        &storage_only,
+        &PlacementConstraints::default()
+    ));
+}
@@ -0,0 +1,215 @@
+use std::{net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use auth::BearerToken;
+use data_center_service::{
+    data_center::LocalDataCenter,
+    protos::{
+        data_center::data_center_server::DataCenterServer as LocalDataCenterServer,
+        resolver::RegisterDataCenterRequest as LocalRegisterDataCenterRequest,
+    },
+    registration::Registration,
+};
+use dev_ca::CertificateAuthority;
+use grpc_tls::TlsFiles;
+use identity::IdentityKey;
+use resolver_client::{
+    client::ResolverClient, protos::resolver::ListDataCentersRequest as ClientListRequest,
+};
+use resolver_service::{
+    dialer::Dialer,
+    protos::{
+        data_center::{
+            data_center_client::DataCenterClient, data_center_server::DataCenterServer,
+            CreateFileMetadataRequest,
+        },
+        resolver::{
+            dcns_resolver_client::DcnsResolverClient, dcns_resolver_server::DcnsResolverServer,
+            ListDataCentersRequest,
+        },
+    },
+    proxy::DataCenterProxy,
+    resolver::LocalDcnsResolver,
+};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Request};
+
+/// Certificates of a resolver, a data center and a client issued by a development authority
+struct Certificates {
+    directory: TempDir,
+}
+
+impl Certificates {
+    fn issue() -> Certificates {
+        let directory = tempfile::tempdir().expect("Should create directory");
+        let authority = CertificateAuthority::init(directory.path()).expect("Should create ca");
+
+        for name in ["resolver", "dc-1", "client"] {
+            authority
+                .issue(name, &[])
+                .expect("Should issue certificate")
+                .write(directory.path(), name)
+                .expect("Should write certificate");
+        }
+
+        Certificates { directory }
+    }
+
+    fn files(&self, name: Option<&str>) -> TlsFiles {
+        let path = |file: String| Some(self.directory.path().join(file));
+
+        TlsFiles {
+            ca_certificate: path(String::from("ca.pem")),
+            certificate: name.and_then(|name| path(format!("{name}.pem"))),
+            key: name.and_then(|name| path(format!("{name}.key"))),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        self.directory.path()
+    }
+}
+
+async fn listen() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+
+    (listener, address)
+}
+
+async fn start_resolver(certificates: &Certificates) -> SocketAddr {
+    let (listener, address) = listen().await;
+    let tls = certificates.files(Some("resolver"));
+    let resolver = LocalDcnsResolver::default().with_dialer(Dialer {
+        tls: tls.client_config().expect("Should load client tls"),
+        ..Default::default()
+    });
+    let server_tls = tls
+        .server_config()
+        .expect("Should load server tls")
+        .expect("Should have server tls");
+    tokio::spawn(async move {
+        Server::builder()
+            .tls_config(server_tls)
+            .expect("Should configure tls")
+            .add_service(DataCenterServer::new(DataCenterProxy::new(
+                resolver.clone(),
+            )))
+            .add_service(DcnsResolverServer::new(resolver))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve resolver");
+    });
+
+    address
+}
+
+async fn start_data_center(certificates: &Certificates, resolver: SocketAddr) -> Registration {
+    let (listener, address) = listen().await;
+    let tls = certificates.files(Some("dc-1"));
+    let data_center = Arc::new(
+        LocalDataCenter::new(String::from("dc-1")).with_storage_root(certificates.path().into()),
+    );
+    let server_tls = tls
+        .server_config()
+        .expect("Should load server tls")
+        .expect("Should have server tls");
+    let served = data_center.clone();
+    tokio::spawn(async move {
+        Server::builder()
+            .tls_config(server_tls)
+            .expect("Should configure tls")
+            .add_service(LocalDataCenterServer::from_arc(served))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve data center");
+    });
+
+    Registration::start(
+        vec![resolver.to_string()],
+        LocalRegisterDataCenterRequest {
+            data_center_id: String::from("dc-1"),
+            host_name: address.to_string(),
+            ..Default::default()
+        },
+        Duration::from_secs(60),
+        data_center,
+        Arc::new(IdentityKey::generate()),
+        tls.client_config().expect("Should load client tls"),
+        BearerToken::default(),
+    )
+    .await
+    .expect("Should register over mutual tls")
+}
+
+#[tokio::test]
+async fn data_centers_and_clients_authenticate_with_mutual_tls() {
+    let certificates = Certificates::issue();
+    let resolver = start_resolver(&certificates).await;
+    let _registration = start_data_center(&certificates, resolver).await;
+    let client_tls = certificates
+        .files(Some("client"))
+        .client_config()
+        .expect("Should load client tls")
+        .expect("Should have client tls");
+
+    let data_centers = ResolverClient::new(vec![resolver.to_string()])
+        .with_tls(client_tls.clone())
+        .call(|mut client| async move { client.list_data_centers(ClientListRequest {}).await })
+        .await
+        .expect("Should list data centers")
+        .data_center;
+    assert_eq!(data_centers.len(), 1);
+    assert_eq!(data_centers[0].data_center_id, "dc-1");
+
+    let channel = grpc_tls::endpoint(&resolver.to_string(), Some(&client_tls))
+        .expect("Should create endpoint")
+        .connect()
+        .await
+        .expect("Should connect to resolver");
+    DataCenterClient::new(channel)
+        .create_file_metadata(CreateFileMetadataRequest {
+            file_path: String::from("placed"),
+            file_size: 16,
+            ..Default::default()
+        })
+        .await
+        .expect("Should place file on the data center over tls");
+}
+
+#[tokio::test]
+async fn clients_without_a_certificate_are_rejected() {
+    let certificates = Certificates::issue();
+    let resolver = start_resolver(&certificates).await;
+    let server_only = certificates
+        .files(None)
+        .client_config()
+        .expect("Should load client tls")
+        .expect("Should have client tls");
+
+    let authenticated = async {
+        let channel = grpc_tls::endpoint(&resolver.to_string(), Some(&server_only))
+            .expect("Should create endpoint")
+            .connect()
+            .await?;
+        DcnsResolverClient::new(channel)
+            .list_data_centers(Request::new(ListDataCentersRequest::default()))
+            .await?;
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    };
+    assert!(authenticated.await.is_err());
+
+    let plaintext = async {
+        let mut client = DcnsResolverClient::connect(format!("http://{resolver}")).await?;
+        client
+            .list_data_centers(Request::new(ListDataCentersRequest::default()))
+            .await?;
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    };
+    assert!(plaintext.await.is_err());
+}
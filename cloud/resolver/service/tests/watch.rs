@@ -0,0 +1,277 @@
+use std::{net::SocketAddr, time::Duration};
+
+use resolver_client::{client::ResolverClient, watch::NetworkView};
+use resolver_service::{
+    protos::resolver::{
+        dcns_resolver_client::DcnsResolverClient, dcns_resolver_server::DcnsResolverServer,
+        registry_command::Command, DataCenterChange, DataCenterHealth, DeregisterDataCenterRequest,
+        HeartbeatRequest, RegisterDataCenterRequest, RegistryCommand, SweepRegistry,
+        WatchDataCentersRequest, WatchDataCentersResponse,
+    },
+    registry::Registry,
+    resolver::LocalDcnsResolver,
+};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    transport::{Channel, Server},
+    Code, Request, Streaming,
+};
+
+async fn start_resolver() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Should bind listener");
+    let address = listener.local_addr().expect("Should have address");
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(DcnsResolverServer::new(LocalDcnsResolver::default()))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .expect("Should serve resolver");
+    });
+
+    address
+}
+
+async fn connect(address: SocketAddr) -> DcnsResolverClient<Channel> {
+    DcnsResolverClient::connect(format!("http://{address}"))
+        .await
+        .expect("Should connect to resolver")
+}
+
+async fn register(client: &mut DcnsResolverClient<Channel>, id: &str, region: &str) {
+    client
+        .register_data_center(Request::new(RegisterDataCenterRequest {
+            data_center_id: String::from(id),
+            host_name: format!("{id}.local:50052"),
+            region: String::from(region),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should register data center");
+}
+
+async fn deregister(client: &mut DcnsResolverClient<Channel>, id: &str) {
+    client
+        .deregister_data_center(Request::new(DeregisterDataCenterRequest {
+            data_center_id: String::from(id),
+            ..Default::default()
+        }))
+        .await
+        .expect("Should deregister data center");
+}
+
+async fn watch(
+    client: &mut DcnsResolverClient<Channel>,
+    start_revision: u64,
+) -> Streaming<WatchDataCentersResponse> {
+    client
+        .watch_data_centers(Request::new(WatchDataCentersRequest { start_revision }))
+        .await
+        .expect("Should watch data centers")
+        .into_inner()
+}
+
+async fn next(stream: &mut Streaming<WatchDataCentersResponse>) -> WatchDataCentersResponse {
+    tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .expect("Should receive a change in time")
+        .expect("Should receive a change")
+        .expect("Stream should stay open")
+}
+
+/// Change and data center id of every event in the response
+fn changes(response: &WatchDataCentersResponse) -> Vec<(DataCenterChange, String)> {
+    response
+        .events
+        .iter()
+        .map(|event| {
+            (
+                event.change(),
+                event
+                    .data_center
+                    .as_ref()
+                    .expect("Should have data center")
+                    .data_center_id
+                    .clone(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn watchers_receive_every_change_in_order() {
+    let address = start_resolver().await;
+    let mut client = connect(address).await;
+    register(&mut client, "dc-a", "east").await;
+
+    let mut stream = watch(&mut client, 0).await;
+    let initial = next(&mut stream).await;
+    assert!(initial.reset);
+    assert_eq!(initial.revision, 1);
+    assert_eq!(
+        changes(&initial),
+        vec![(DataCenterChange::Added, String::from("dc-a"))]
+    );
+
+    register(&mut client, "dc-b", "east").await;
+    register(&mut client, "dc-a", "west").await;
+    deregister(&mut client, "dc-b").await;
+
+    let added = next(&mut stream).await;
+    assert_eq!(added.revision, 2);
+    assert_eq!(
+        changes(&added),
+        vec![(DataCenterChange::Added, String::from("dc-b"))]
+    );
+    let updated = next(&mut stream).await;
+    assert_eq!(updated.revision, 3);
+    assert_eq!(
+        changes(&updated),
+        vec![(DataCenterChange::Updated, String::from("dc-a"))]
+    );
+    assert_eq!(
+        updated.events[0]
+            .data_center
+            .as_ref()
+            .expect("Should have data center")
+            .region,
+        "west"
+    );
+    let removed = next(&mut stream).await;
+    assert_eq!(removed.revision, 4);
+    assert!(!removed.reset);
+    assert_eq!(
+        changes(&removed),
+        vec![(DataCenterChange::Removed, String::from("dc-b"))]
+    );
+}
+
+#[tokio::test]
+async fn watchers_resume_after_the_last_revision_received() {
+    let address = start_resolver().await;
+    let mut client = connect(address).await;
+    register(&mut client, "dc-a", "east").await;
+    register(&mut client, "dc-b", "east").await;
+    deregister(&mut client, "dc-a").await;
+
+    let mut resumed = watch(&mut client, 1).await;
+    assert_eq!(
+        changes(&next(&mut resumed).await),
+        vec![(DataCenterChange::Added, String::from("dc-b"))]
+    );
+    let removed = next(&mut resumed).await;
+    assert_eq!(removed.revision, 3);
+    assert_eq!(
+        changes(&removed),
+        vec![(DataCenterChange::Removed, String::from("dc-a"))]
+    );
+
+    let status = client
+        .watch_data_centers(Request::new(WatchDataCentersRequest { start_revision: 10 }))
+        .await
+        .expect_err("Should refuse revisions the registry hasn't reached");
+    assert_eq!(status.code(), Code::OutOfRange);
+}
+
+#[test]
+fn heartbeats_only_report_health_changes() {
+    let mut registry = Registry::default();
+    let command = |issued_at_unix_ms, command| RegistryCommand {
+        issued_at_unix_ms,
+        command: Some(command),
+    };
+    registry
+        .apply(command(
+            0,
+            Command::Register(RegisterDataCenterRequest {
+                data_center_id: String::from("dc-a"),
+                host_name: String::from("a.local:50052"),
+                ..Default::default()
+            }),
+        ))
+        .expect("Should register data center");
+    let (_, mut changes) = registry
+        .watch(1)
+        .expect("Should retain the current revision");
+    let heartbeat = || {
+        Command::Heartbeat(HeartbeatRequest {
+            data_center_id: String::from("dc-a"),
+            ..Default::default()
+        })
+    };
+    let sweep = || {
+        Command::Sweep(SweepRegistry {
+            heartbeat_timeout_ms: 1_000,
+            eviction_ttl_ms: 60_000,
+        })
+    };
+
+    registry
+        .apply(command(500, heartbeat()))
+        .expect("Should apply heartbeat");
+    registry.apply(command(600, sweep())).expect("Should sweep");
+    assert!(changes.try_recv().is_err());
+
+    registry
+        .apply(command(2_000, sweep()))
+        .expect("Should sweep");
+    let silenced = changes.try_recv().expect("Should report the data center");
+    assert_eq!(silenced.revision, 2);
+    assert_eq!(
+        silenced.events[0]
+            .data_center
+            .as_ref()
+            .expect("Should have data center")
+            .health(),
+        DataCenterHealth::Unhealthy
+    );
+
+    registry
+        .apply(command(2_100, heartbeat()))
+        .expect("Should apply heartbeat");
+    registry
+        .apply(command(2_200, heartbeat()))
+        .expect("Should apply heartbeat");
+    let recovered = changes.try_recv().expect("Should report the data center");
+    assert_eq!(recovered.revision, 3);
+    assert_eq!(recovered.events[0].change(), DataCenterChange::Updated);
+    assert!(changes.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn network_view_follows_the_registry() {
+    let address = start_resolver().await;
+    let mut client = connect(address).await;
+    register(&mut client, "dc-a", "east").await;
+    let (views, mut updates) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut resolver = ResolverClient::new(vec![format!("http://{address}")]);
+        NetworkView::default()
+            .follow(&mut resolver, |view, _| {
+                let _ = views.send(view.clone());
+            })
+            .await
+    });
+
+    register(&mut client, "dc-b", "east").await;
+    deregister(&mut client, "dc-a").await;
+
+    let view = loop {
+        let view = tokio::time::timeout(Duration::from_secs(5), updates.recv())
+            .await
+            .expect("Should receive an update in time")
+            .expect("Should keep following");
+
+        if view.revision() == 3 {
+            break view;
+        }
+    };
+    let ids: Vec<String> = view
+        .data_centers()
+        .into_iter()
+        .map(|data_center| data_center.data_center_id)
+        .collect();
+    assert_eq!(ids, vec!["dc-b"]);
+}
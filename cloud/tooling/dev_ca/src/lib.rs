@@ -0,0 +1,191 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+
+const CA_COMMON_NAME: &str = "Decentralized Cloud Development CA";
+const CA_CERTIFICATE_FILE: &str = "ca.pem";
+const CA_KEY_FILE: &str = "ca.key";
+/// Names every leaf certificate is valid for, so services can be reached on loopback
+const LOOPBACK_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+#[derive(Debug)]
+pub enum DevCaError {
+    Io(PathBuf, io::Error),
+    Certificate(rcgen::Error),
+    /// A certificate authority already exists where a new one would be written
+    Exists(PathBuf),
+}
+
+impl fmt::Display for DevCaError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DevCaError::Io(path, error) => {
+                write!(formatter, "Failed to access {}: {error}", path.display())
+            }
+            DevCaError::Certificate(error) => {
+                write!(formatter, "Failed to generate certificate: {error}")
+            }
+            DevCaError::Exists(path) => write!(
+                formatter,
+                "A certificate authority already exists at {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DevCaError {}
+
+impl From<rcgen::Error> for DevCaError {
+    fn from(error: rcgen::Error) -> Self {
+        DevCaError::Certificate(error)
+    }
+}
+
+/// Pem encoded certificate and private key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PemPair {
+    pub certificate: String,
+    pub key: String,
+}
+
+impl PemPair {
+    /// Writes the pair as `<name>.pem` and `<name>.key` in `directory`, the key readable only by
+    /// the user, returning their paths
+    pub fn write(&self, directory: &Path, name: &str) -> Result<(PathBuf, PathBuf), DevCaError> {
+        fs::create_dir_all(directory)
+            .map_err(|error| DevCaError::Io(PathBuf::from(directory), error))?;
+        let certificate_path = directory.join(format!("{name}.pem"));
+        let key_path = directory.join(format!("{name}.key"));
+        write(&certificate_path, &self.certificate, 0o644)?;
+        write(&key_path, &self.key, 0o600)?;
+
+        Ok((certificate_path, key_path))
+    }
+}
+
+/// Certificate authority signing the certificates of services and clients in development. It
+/// is only meant for testing tls offline, as its key is kept unprotected on disk
+pub struct CertificateAuthority {
+    certificate: Certificate,
+}
+
+impl CertificateAuthority {
+    /// Generates a certificate authority with a new key
+    pub fn generate() -> Result<CertificateAuthority, DevCaError> {
+        Self::from_key(None)
+    }
+
+    /// Loads the certificate authority written by `init` in `directory`
+    pub fn load(directory: &Path) -> Result<CertificateAuthority, DevCaError> {
+        let key_path = directory.join(CA_KEY_FILE);
+        let key = fs::read_to_string(&key_path).map_err(|error| DevCaError::Io(key_path, error))?;
+
+        // The authority is rebuilt from the same parameters it was generated with, which with
+        // the same key signs certificates that verify against the certificate on disk
+        Self::from_key(Some(KeyPair::from_pem(&key)?))
+    }
+
+    /// Generates a certificate authority and writes it as `ca.pem` and `ca.key` in `directory`,
+    /// refusing to replace an existing one
+    pub fn init(directory: &Path) -> Result<CertificateAuthority, DevCaError> {
+        let certificate_path = directory.join(CA_CERTIFICATE_FILE);
+
+        if certificate_path.exists() {
+            return Err(DevCaError::Exists(certificate_path));
+        }
+
+        let authority = Self::generate()?;
+        authority.pem()?.write(directory, "ca")?;
+
+        Ok(authority)
+    }
+
+    fn from_key(key_pair: Option<KeyPair>) -> Result<CertificateAuthority, DevCaError> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name(CA_COMMON_NAME);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::CrlSign,
+            KeyUsagePurpose::DigitalSignature,
+        ];
+        params.key_pair = key_pair;
+
+        Ok(CertificateAuthority {
+            certificate: Certificate::from_params(params)?,
+        })
+    }
+
+    /// Certificate and key of the authority
+    pub fn pem(&self) -> Result<PemPair, DevCaError> {
+        Ok(PemPair {
+            certificate: self.certificate.serialize_pem()?,
+            key: self.certificate.serialize_private_key_pem(),
+        })
+    }
+
+    /// Issues a certificate for `name`, valid for `hosts` and loopback, that can both serve
+    /// tls and authenticate as a client for mutual tls
+    pub fn issue(&self, name: &str, hosts: &[String]) -> Result<PemPair, DevCaError> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name(name);
+        params.subject_alt_names = LOOPBACK_HOSTS
+            .iter()
+            .map(|host| String::from(*host))
+            .chain(hosts.iter().cloned())
+            .map(subject_alt_name)
+            .collect();
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        params.extended_key_usages = vec![
+            ExtendedKeyUsagePurpose::ServerAuth,
+            ExtendedKeyUsagePurpose::ClientAuth,
+        ];
+        params.use_authority_key_identifier_extension = true;
+        let certificate = Certificate::from_params(params)?;
+
+        Ok(PemPair {
+            certificate: certificate.serialize_pem_with_signer(&self.certificate)?,
+            key: certificate.serialize_private_key_pem(),
+        })
+    }
+}
+
+fn distinguished_name(common_name: &str) -> DistinguishedName {
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, common_name);
+
+    name
+}
+
+fn subject_alt_name(host: String) -> SanType {
+    match host.parse::<IpAddr>() {
+        Ok(address) => SanType::IpAddress(address),
+        Err(_) => SanType::DnsName(host),
+    }
+}
+
+fn write(path: &Path, contents: &str, mode: u32) -> Result<(), DevCaError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| DevCaError::Io(PathBuf::from(path), error))
+}
@@ -0,0 +1,58 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use dev_ca::{CertificateAuthority, DevCaError};
+
+#[derive(Debug, Parser)]
+#[command(name = "dev_ca")]
+#[command(about = "Local certificate authority to test tls in development", long_about = None)]
+struct Cli {
+    /// Directory the certificate authority and issued certificates are kept in
+    #[arg(long, global = true, default_value = "certs")]
+    dir: PathBuf,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Generates a certificate authority as ca.pem and ca.key
+    Init,
+    /// Issues a certificate and key for a service or client as <name>.pem and <name>.key
+    Issue {
+        /// Name of the certificate, used as its common name
+        name: String,
+        /// Host name or ip address the certificate is valid for besides loopback, may be
+        /// repeated
+        #[arg(long = "host")]
+        hosts: Vec<String>,
+    },
+}
+
+fn run(cli: Cli) -> Result<(), DevCaError> {
+    match cli.command {
+        Commands::Init => {
+            CertificateAuthority::init(&cli.dir)?;
+            println!("{}", cli.dir.join("ca.pem").display());
+        }
+        Commands::Issue { name, hosts } => {
+            let (certificate, key) = CertificateAuthority::load(&cli.dir)?
+                .issue(&name, &hosts)?
+                .write(&cli.dir, &name)?;
+            println!("{}", certificate.display());
+            println!("{}", key.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
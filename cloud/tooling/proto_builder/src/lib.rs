@@ -2,6 +2,10 @@ use std::path::PathBuf;
 
 const ROOT_PREFIX: &str = "/cloud/";
 const BUILD_DIRECTORY_VARIABLE: &str = "CARGO_MANIFEST_DIR";
+const OUTPUT_DIRECTORY_VARIABLE: &str = "OUT_DIR";
+/// File the descriptor set of every proto is written to in the output directory, included with
+/// `tonic::include_file_descriptor_set!("protos_descriptor")` to serve reflection
+const DESCRIPTOR_SET_FILE: &str = "protos_descriptor.bin";
 
 #[derive(Default, Debug)]
 struct TraversalInfo {
@@ -18,8 +22,12 @@ pub fn build_protos() {
     };
     let root_path = format!("{}{}", &build_directory[0..index], "/protos");
     let proto_paths = find_all_protos(PathBuf::from(&root_path));
+    let Ok(output_directory) = std::env::var(OUTPUT_DIRECTORY_VARIABLE) else {
+        panic!("No output directory provided for build");
+    };
 
     tonic_build::configure()
+        .file_descriptor_set_path(PathBuf::from(output_directory).join(DESCRIPTOR_SET_FILE))
         .compile(&proto_paths, &[root_path])
         .expect("Should compile protos");
 }
@@ -28,17 +36,15 @@ fn find_all_protos(root: PathBuf) -> Vec<PathBuf> {
     let mut info = TraversalInfo::default();
     info.dirs.push(root);
 
-    while !info.dirs.is_empty() {
-        let path = info.dirs.pop().expect("Should not be empty");
+    while let Some(path) = info.dirs.pop() {
         let Ok(directory) = std::fs::read_dir(path) else {
-                panic!("Failed to find proto directory");    
-            };
+            panic!("Failed to find proto directory");
+        };
 
         directory
             .filter_map(|entry| entry.ok())
             .filter(|entry| entry.path().is_dir() || !entry.path().ends_with(".proto"))
             .for_each(|entry| {
-                dbg!(&entry);
                 if entry.path().is_dir() {
                     info.dirs.push(entry.path());
                 } else {
@@ -0,0 +1,105 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use auth::{add_api_key, read_secret, token, AuthError};
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(name = "tokens")]
+#[command(about = "Issues the tokens and api keys services authenticate callers with", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Generates the secret tokens are signed with, shared by every service accepting them
+    Secret {
+        /// File the secret is written to
+        #[arg(long, default_value = "auth/secret")]
+        secret_file: PathBuf,
+    },
+    /// Signs a token for a caller with the secret
+    Issue {
+        /// Caller the token identifies
+        subject: String,
+        /// File holding the secret tokens are signed with
+        #[arg(long, default_value = "auth/secret")]
+        secret_file: PathBuf,
+        /// Hours the token is valid for
+        #[arg(long, default_value_t = 24)]
+        ttl_hours: u64,
+    },
+    /// Generates an api key for a caller, adding its digest to the api keys file
+    ApiKey {
+        /// Caller the api key identifies
+        subject: String,
+        /// File holding the digests of the accepted api keys
+        #[arg(long, default_value = "auth/api_keys")]
+        api_keys_file: PathBuf,
+    },
+}
+
+fn write_secret(path: &Path) -> Result<(), AuthError> {
+    let io_error = |error| AuthError::Io(PathBuf::from(path), error);
+
+    if let Some(directory) = path
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+    {
+        fs::create_dir_all(directory).map_err(io_error)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    // Never replaces a secret, which would invalidate every token signed with it
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", token::generate_secret()))
+        .map_err(io_error)
+}
+
+fn run(cli: Cli) -> Result<(), AuthError> {
+    match cli.command {
+        Commands::Secret { secret_file } => {
+            write_secret(&secret_file)?;
+            println!("{}", secret_file.display());
+        }
+        Commands::Issue {
+            subject,
+            secret_file,
+            ttl_hours,
+        } => {
+            let secret = read_secret(&secret_file)?;
+            println!(
+                "{}",
+                token::issue(&secret, &subject, Duration::from_secs(ttl_hours * 3600))
+            );
+        }
+        Commands::ApiKey {
+            subject,
+            api_keys_file,
+        } => println!("{}", add_api_key(&api_keys_file, &subject)?),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}